@@ -0,0 +1,18 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+fn main() {
+    println!("cargo:rerun-if-changed=include/datp.h");
+    if env::var_os("CARGO_FEATURE_CAPI_HEADER").is_none() {
+        return;
+    }
+
+    // build scripts may only write to OUT_DIR; installing the header next to the library is a
+    // separate step (see the README)
+    let include = PathBuf::from(env::var_os("OUT_DIR").expect("cargo sets OUT_DIR")).join("include");
+    fs::create_dir_all(&include).expect("cannot create the include directory");
+    fs::copy("include/datp.h", include.join("datp.h")).expect("cannot copy datp.h");
+    // build scripts of dependent crates see it as DEP_DATP_INCLUDE
+    println!("cargo:include={}", include.display());
+}
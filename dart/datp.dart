@@ -0,0 +1,180 @@
+// dart:ffi bindings for the `flutter` feature of datp (src/dart_api.rs).
+//
+// Kept in sync with the Rust side by the test_bindings_match_exports unit test. Copy this file
+// into a Flutter app and load the library built with
+// `cargo rustc --release --lib --features flutter --crate-type cdylib`. Needs package:ffi.
+
+import 'dart:ffi';
+
+import 'package:ffi/ffi.dart';
+
+final class DatpDartAccount extends Struct {
+  external Pointer<Utf8> kind; // "totp", "hotp" or "steam"
+  external Pointer<Utf8> issuer; // "" if there is none
+  external Pointer<Utf8> accountName;
+  external Pointer<Utf8> secret; // base32
+  external Pointer<Utf8> algorithm; // "SHA1", "SHA256" or "SHA512"
+  @Int64()
+  external int digits;
+  @Int64()
+  external int period; // seconds
+  @Int64()
+  external int counter; // HOTP moving factor, 0 for other kinds
+}
+
+/// An account parsed from an otpauth URI.
+class Account {
+  final String kind;
+  final String issuer;
+  final String accountName;
+  final String secret;
+  final String algorithm;
+  final int digits;
+  final int period;
+  final int counter;
+
+  const Account({
+    this.kind = 'totp',
+    this.issuer = '',
+    required this.accountName,
+    required this.secret,
+    this.algorithm = 'SHA1',
+    this.digits = 6,
+    this.period = 30,
+    this.counter = 0,
+  });
+}
+
+/// Thrown when a datp function fails, with the message of datp_dart_last_error.
+class DatpException implements Exception {
+  final String message;
+
+  const DatpException(this.message);
+
+  @override
+  String toString() => 'DatpException: $message';
+}
+
+class Datp {
+  final Pointer<Utf8> Function() _lastError;
+  final void Function(Pointer<Utf8>) _stringFree;
+  final Pointer<Utf8> Function(int) _generateSecret;
+  final Pointer<Utf8> Function(Pointer<Utf8>, int) _code;
+  final int Function(Pointer<Utf8>, int) _secondsRemaining;
+  final int Function(Pointer<Utf8>, Pointer<Utf8>, int, int) _verify;
+  final Pointer<DatpDartAccount> Function(Pointer<Utf8>) _parseUri;
+  final void Function(Pointer<DatpDartAccount>) _accountFree;
+  final Pointer<Utf8> Function(Pointer<DatpDartAccount>) _buildUri;
+  final Pointer<Utf8> Function(Pointer<Utf8>) _qrSvg;
+
+  Datp(DynamicLibrary library)
+      : _lastError = library.lookupFunction<Pointer<Utf8> Function(), Pointer<Utf8> Function()>('datp_dart_last_error'),
+        _stringFree = library.lookupFunction<Void Function(Pointer<Utf8>), void Function(Pointer<Utf8>)>('datp_dart_string_free'),
+        _generateSecret =
+            library.lookupFunction<Pointer<Utf8> Function(Int64), Pointer<Utf8> Function(int)>('datp_dart_generate_secret'),
+        _code = library.lookupFunction<Pointer<Utf8> Function(Pointer<Utf8>, Int64), Pointer<Utf8> Function(Pointer<Utf8>, int)>(
+            'datp_dart_code'),
+        _secondsRemaining =
+            library.lookupFunction<Int64 Function(Pointer<Utf8>, Int64), int Function(Pointer<Utf8>, int)>('datp_dart_seconds_remaining'),
+        _verify = library.lookupFunction<Int64 Function(Pointer<Utf8>, Pointer<Utf8>, Int64, Int64),
+            int Function(Pointer<Utf8>, Pointer<Utf8>, int, int)>('datp_dart_verify'),
+        _parseUri = library.lookupFunction<Pointer<DatpDartAccount> Function(Pointer<Utf8>),
+            Pointer<DatpDartAccount> Function(Pointer<Utf8>)>('datp_dart_parse_uri'),
+        _accountFree = library.lookupFunction<Void Function(Pointer<DatpDartAccount>), void Function(Pointer<DatpDartAccount>)>(
+            'datp_dart_account_free'),
+        _buildUri = library.lookupFunction<Pointer<Utf8> Function(Pointer<DatpDartAccount>),
+            Pointer<Utf8> Function(Pointer<DatpDartAccount>)>('datp_dart_build_uri'),
+        _qrSvg = library.lookupFunction<Pointer<Utf8> Function(Pointer<Utf8>), Pointer<Utf8> Function(Pointer<Utf8>)>(
+            'datp_dart_qr_svg');
+
+  static int _now() => DateTime.now().millisecondsSinceEpoch ~/ 1000;
+
+  DatpException _error() {
+    final message = _lastError();
+    return DatpException(message == nullptr ? 'unknown error' : message.toDartString());
+  }
+
+  /// Converts and frees a string returned by the library.
+  String _take(Pointer<Utf8> result) {
+    if (result == nullptr) throw _error();
+    try {
+      return result.toDartString();
+    } finally {
+      _stringFree(result);
+    }
+  }
+
+  R _withString<R>(String value, R Function(Pointer<Utf8>) body) {
+    final native = value.toNativeUtf8();
+    try {
+      return body(native);
+    } finally {
+      malloc.free(native);
+    }
+  }
+
+  /// A new random base32 secret of [bytes] bytes.
+  String generateSecret([int bytes = 20]) => _take(_generateSecret(bytes));
+
+  /// The code of the account in [uri] at [unixTime], now by default.
+  String code(String uri, {int? unixTime}) => _withString(uri, (uri) => _take(_code(uri, unixTime ?? _now())));
+
+  /// Seconds until the code changes, 0 for HOTP accounts.
+  int secondsRemaining(String uri, {int? unixTime}) {
+    final seconds = _withString(uri, (uri) => _secondsRemaining(uri, unixTime ?? _now()));
+    if (seconds < 0) throw _error();
+    return seconds;
+  }
+
+  /// Whether [code] is valid for the time-based account in [uri], [skew] (0 to 1000) steps either side.
+  bool verify(String uri, String code, {int? unixTime, int skew = 1}) {
+    final result = _withString(uri, (uri) => _withString(code, (code) => _verify(uri, code, unixTime ?? _now(), skew)));
+    if (result < 0) throw _error();
+    return result == 1;
+  }
+
+  Account parseUri(String uri) {
+    final native = _withString(uri, _parseUri);
+    if (native == nullptr) throw _error();
+    try {
+      final account = native.ref;
+      return Account(
+        kind: account.kind.toDartString(),
+        issuer: account.issuer.toDartString(),
+        accountName: account.accountName.toDartString(),
+        secret: account.secret.toDartString(),
+        algorithm: account.algorithm.toDartString(),
+        digits: account.digits,
+        period: account.period,
+        counter: account.counter,
+      );
+    } finally {
+      _accountFree(native);
+    }
+  }
+
+  String buildUri(Account account) {
+    final native = calloc<DatpDartAccount>();
+    final strings = [account.kind, account.issuer, account.accountName, account.secret, account.algorithm]
+        .map((value) => value.toNativeUtf8())
+        .toList();
+    try {
+      native.ref
+        ..kind = strings[0]
+        ..issuer = strings[1]
+        ..accountName = strings[2]
+        ..secret = strings[3]
+        ..algorithm = strings[4]
+        ..digits = account.digits
+        ..period = account.period
+        ..counter = account.counter;
+      return _take(_buildUri(native));
+    } finally {
+      strings.forEach(malloc.free);
+      calloc.free(native);
+    }
+  }
+
+  /// An SVG QR code provisioning the account in [uri].
+  String qrSvg(String uri) => _withString(uri, (uri) => _take(_qrSvg(uri)));
+}
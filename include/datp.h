@@ -0,0 +1,177 @@
+/*
+ * C interface of the datp library.
+ *
+ * Checked against src/c_api.rs by the test_header_matches_exports unit test, which compares
+ * every prototype, struct field and enum value here with the Rust definitions and fails on any
+ * difference. See c_api.rs for the detailed contract of each function.
+ *
+ * Strings are NUL-terminated UTF-8 unless the function name ends in _w (UTF-16). Strings
+ * returned by the library are freed with free_c_string, or datp_free_secret when they contain
+ * a secret. Functions that can fail record a message for datp_last_error.
+ */
+
+#ifndef DATP_H
+#define DATP_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define DATP_ABI_VERSION 1
+#define DATP_MIN_ENTROPY_BYTES 16
+
+typedef enum DatpStatus {
+    DATP_STATUS_OK = 0,
+    DATP_STATUS_NULL_POINTER = 1,
+    DATP_STATUS_QR_CODE = 2,
+    DATP_STATUS_INVALID_SECRET = 3,
+    DATP_STATUS_INVALID_ARGUMENT = 4,
+    DATP_STATUS_PANIC = 5,
+    DATP_STATUS_INVALID_ENCODING = 6,
+    DATP_STATUS_INVALID_INPUT = 7,
+} DatpStatus;
+
+typedef enum DatpSecretCheck {
+    DATP_SECRET_VALID = 0,
+    DATP_SECRET_EMPTY = 1,
+    DATP_SECRET_INVALID_CHARACTER = 2,
+    DATP_SECRET_INVALID_LENGTH = 3,
+} DatpSecretCheck;
+
+typedef enum DatpFormat {
+    DATP_FORMAT_MIGRATION = 0,
+    DATP_FORMAT_AEGIS = 1,
+    DATP_FORMAT_BITWARDEN = 2,
+    DATP_FORMAT_FREE_OTP_PLUS = 3,
+    DATP_FORMAT_TWO_FAS = 4,
+    DATP_FORMAT_CSV = 5,
+} DatpFormat;
+
+typedef struct TotpQrConfigC {
+    const char *dark_color;
+    const char *light_color;
+    unsigned int min_dimension;
+    uint8_t version;            /* 0..4 for QR versions 1..5 */
+    uint8_t ec_level;           /* 0 = L, 1 = M, 2 = Q, 3 = H */
+} TotpQrConfigC;
+
+typedef struct TotpQrConfigV2C {
+    const char *account_name;
+    const char *issuer;         /* may be NULL */
+    const char *dark_color;
+    const char *light_color;
+    unsigned int min_dimension;
+    uint8_t version;
+    uint8_t ec_level;
+} TotpQrConfigV2C;
+
+typedef struct DatpTotpParams {
+    uint8_t algorithm;          /* 0 = SHA1, 1 = SHA256, 2 = SHA512 */
+    unsigned int digits;        /* 1 to 9 */
+    uint64_t period;            /* seconds, 1 to 2^32 - 1 */
+    uint64_t t0;
+} DatpTotpParams;
+
+typedef struct DatpAccountC {
+    uint8_t kind;               /* 0 = TOTP, 1 = HOTP, 2 = Steam */
+    char *issuer;               /* NULL if there is none */
+    char *account_name;
+    char *secret;
+    uint8_t algorithm;
+    unsigned int digits;
+    uint64_t period;
+    uint64_t counter;
+} DatpAccountC;
+
+/* datp_qr_png is only exported by builds with the image feature */
+typedef struct DatpQrPngConfigC {
+    const char *account_name;
+    const char *issuer;         /* may be NULL */
+    unsigned int module_size;   /* pixels per module, at most 64 */
+    uint32_t dark_color;        /* 0xRRGGBB */
+    uint32_t light_color;
+    uint8_t ec_level;
+    uint8_t quiet_zone;         /* non-zero for a 4-module border */
+} DatpQrPngConfigC;
+
+typedef struct DatpTotp DatpTotp;
+typedef struct DatpWatch DatpWatch;
+
+typedef void (*DatpWatchCallback)(unsigned int code, uint64_t valid_for, void *user_data);
+
+/* errors and versions */
+const char *datp_last_error(void);
+const char *datp_version(void);
+unsigned int datp_abi_version(void);
+
+/* secrets */
+char *generate_totp_secret_c(unsigned int length);
+size_t generate_totp_secret_into_c(unsigned int length, char *buf, size_t buf_len);
+char *datp_secret_from_entropy(const uint8_t *bytes, size_t len);
+void free_c_string(char *s);
+void datp_free_secret(char *s);
+int datp_validate_secret(const char *secret);
+int datp_validate_secret_w(const uint16_t *secret);
+
+/* codes */
+unsigned int totp_raw_now_c(const char *secret, uint64_t step, uint64_t t0);
+unsigned int totp_raw_c(const char *secret, uint64_t step, uint64_t t0, uint64_t unix_time);
+DatpStatus totp_raw_now_status_c(const char *secret, uint64_t step, uint64_t t0, unsigned int *out_code);
+DatpStatus totp_raw_status_c(const char *secret, uint64_t step, uint64_t t0, uint64_t unix_time, unsigned int *out_code);
+DatpStatus totp_raw_status_w(const uint16_t *secret, uint64_t step, uint64_t t0, uint64_t unix_time, unsigned int *out_code);
+char *totp_string_c(const char *secret, unsigned int digits, uint64_t step, uint64_t t0, uint64_t unix_time);
+size_t totp_string_into_c(const char *secret, unsigned int digits, uint64_t step, uint64_t t0, uint64_t unix_time,
+                          char *buf, size_t buf_len);
+uint64_t datp_seconds_remaining(uint64_t step, uint64_t t0);
+uint64_t datp_seconds_remaining_at(uint64_t step, uint64_t t0, uint64_t unix_time);
+unsigned int hotp_raw_c(const char *secret, uint64_t counter);
+DatpStatus hotp_raw_status_c(const char *secret, uint64_t counter, unsigned int *out_code);
+DatpStatus datp_code_with(const char *secret, const DatpTotpParams *params, uint64_t unix_time, unsigned int *out_code);
+
+/* verification: 1 on a match, 0 without one, -1 on error */
+int totp_verify_c(const char *secret, const char *code, uint64_t step, uint64_t t0, uint64_t unix_time, uint64_t skew);
+int totp_verify_w(const uint16_t *secret, const uint16_t *code, uint64_t step, uint64_t t0, uint64_t unix_time,
+                  uint64_t skew);
+int hotp_verify_c(const char *secret, const char *code, uint64_t counter, uint64_t look_ahead, uint64_t *out_counter);
+int datp_verify_with(const char *secret, const DatpTotpParams *params, const char *code, uint64_t unix_time,
+                     uint64_t skew, int64_t *out_offset);
+
+/* generator handles */
+DatpTotp *datp_totp_new(const char *secret, const DatpTotpParams *params);
+DatpTotp *datp_totp_new_w(const uint16_t *secret, const DatpTotpParams *params);
+DatpStatus datp_totp_code(const DatpTotp *handle, uint64_t unix_time, unsigned int *out_code);
+int datp_totp_verify(const DatpTotp *handle, const char *code, uint64_t unix_time, uint64_t skew, int64_t *out_offset);
+void datp_totp_free(DatpTotp *handle);
+DatpWatch *datp_watch(const DatpTotp *handle, DatpWatchCallback callback, void *user_data);
+void datp_unwatch(DatpWatch *watch);
+
+/* provisioning */
+char *datp_build_uri(const char *account, const char *issuer, const char *secret, uint8_t algorithm,
+                     unsigned int digits, uint64_t period);
+char *datp_build_uri_w(const uint16_t *account, const uint16_t *issuer, const uint16_t *secret, uint8_t algorithm,
+                       unsigned int digits, uint64_t period);
+DatpStatus datp_import(int format, const char *data, const char *password, DatpAccountC **out_accounts,
+                       size_t *out_len);
+void datp_free_accounts(DatpAccountC *accounts, size_t len);
+DatpStatus datp_export(int format, const DatpAccountC *accounts, size_t len, const char *password, char **out);
+
+/* QR codes, only exported by builds with the qr feature (on by default) */
+char *totp_qr_svg_c(const char *secret, const TotpQrConfigC *config);
+DatpStatus totp_qr_svg_status_c(const char *secret, const TotpQrConfigC *config, char **out);
+size_t totp_qr_svg_into_c(const char *secret, const TotpQrConfigC *config, char *buf, size_t buf_len);
+char *totp_qr_svg_v2_c(const char *secret, const TotpQrConfigV2C *config);
+DatpStatus totp_qr_svg_v2_status_c(const char *secret, const TotpQrConfigV2C *config, char **out);
+size_t totp_qr_svg_v2_into_c(const char *secret, const TotpQrConfigV2C *config, char *buf, size_t buf_len);
+DatpStatus datp_qr_svg_with(const char *secret, const TotpQrConfigV2C *config, const DatpTotpParams *params,
+                            char **out);
+uint8_t *datp_qr_png(const char *secret, const DatpQrPngConfigC *config, const DatpTotpParams *params, size_t *out_len);
+void datp_free_bytes(uint8_t *bytes, size_t len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* DATP_H */
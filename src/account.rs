@@ -0,0 +1,83 @@
+use super::Algorithm;
+
+/// OTP type, the host part of an otpauth URI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum OtpKind {
+    /// Time-based (RFC 6238), uses `period`.
+    #[default]
+    Totp,
+    /// Counter-based (RFC 4226), uses `counter`.
+    Hotp,
+    /// Steam Guard: TOTP with SHA-1, 30 seconds, rendered as 5 characters (see `steam_raw`).
+    Steam,
+}
+
+impl OtpKind {
+    /// Name of the type, e.g. `"totp"`. Steam accounts are written to otpauth
+    /// URIs as `totp` with `encoder=steam`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OtpKind::Totp => "totp",
+            OtpKind::Hotp => "hotp",
+            OtpKind::Steam => "steam",
+        }
+    }
+}
+
+/// A provisioned OTP account, as described by an otpauth URI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Account {
+    pub kind: OtpKind,
+    pub issuer: Option<String>,
+    pub account_name: String,
+    pub secret: String,             // base32, uppercase, without padding
+    pub algorithm: Algorithm,
+    pub digits: u32,
+    pub period: u64,                // time step in seconds
+    pub counter: Option<u64>,       // moving factor of HOTP accounts
+    pub image: Option<String>,      // issuer logo URL, honored by FreeOTP+ and Aegis
+}
+
+impl Account {
+    /// Creates an account with the defaults used by most authenticator apps
+    /// (SHA-1, 6 digits, 30 second period).
+    ///
+    /// # Example
+    /// ```rust
+    /// use datp::Account;
+    ///
+    /// let account = Account::new("user@example.com", "JBSWY3DPEHPK3PXP");
+    /// assert_eq!(account.digits, 6);
+    /// ```
+    pub fn new(account_name: impl Into<String>, secret: impl Into<String>) -> Self {
+        Account {
+            kind: OtpKind::Totp,
+            issuer: None,
+            account_name: account_name.into(),
+            secret: secret.into(),
+            algorithm: Algorithm::Sha1,
+            digits: 6,
+            period: 30,
+            counter: None,
+            image: None,
+        }
+    }
+
+    /// Creates a counter-based (HOTP) account starting at `counter`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use datp::{Account, OtpKind};
+    ///
+    /// let account = Account::new_hotp("user@example.com", "JBSWY3DPEHPK3PXP", 0);
+    /// assert_eq!(account.kind, OtpKind::Hotp);
+    /// ```
+    pub fn new_hotp(account_name: impl Into<String>, secret: impl Into<String>, counter: u64) -> Self {
+        Account { kind: OtpKind::Hotp, counter: Some(counter), ..Account::new(account_name, secret) }
+    }
+
+    /// Creates a Steam Guard account.
+    pub fn new_steam(account_name: impl Into<String>, secret: impl Into<String>) -> Self {
+        Account { kind: OtpKind::Steam, digits: 5, issuer: Some("Steam".to_string()), ..Account::new(account_name, secret) }
+    }
+}
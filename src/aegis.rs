@@ -0,0 +1,253 @@
+// Aegis Authenticator backups: a JSON envelope whose `db` is either the
+// plain vault object or, when `header.slots` is set, the vault encrypted with
+// AES-256-GCM under a master key. Each password slot holds that master key,
+// itself encrypted with a key derived from the password through scrypt.
+
+use super::json::{self, Json};
+use super::{base64, crypto, uri, Account, Algorithm, DatpError, OtpKind};
+
+// parameters Aegis itself uses for new password slots
+const SCRYPT_N: u64 = 1 << 15;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+
+/// Reads an Aegis JSON backup, plain or password-encrypted.
+///
+/// # Arguments
+/// * `backup` - Contents of the `.json` backup file.
+/// * `password` - Backup password, only needed for encrypted backups.
+///
+/// # Returns
+/// `Result<Vec<Account>, DatpError>` - The TOTP, HOTP and Steam entries of the vault.
+/// Other entry types (mOTP, Yandex) are rejected with `DatpError::UnsupportedType`.
+pub fn import_aegis_json(backup: &str, password: Option<&str>) -> Result<Vec<Account>, DatpError> {
+    let root = json::parse(backup).ok_or(DatpError::InvalidJson)?;
+    let header = root.get("header").ok_or_else(|| invalid("missing 'header'"))?;
+
+    let vault = match (root.get("db"), header.get("slots")) {
+        (Some(db @ Json::Object(_)), _) => db.clone(),
+        (Some(Json::String(db)), Some(Json::Array(slots))) => {
+            let password = password.ok_or(DatpError::PasswordRequired)?;
+            let master_key = slots
+                .iter()
+                .filter(|slot| slot.get("type").and_then(Json::as_u64) == Some(1))
+                .find_map(|slot| decrypt_slot(slot, password).transpose())
+                .transpose()?
+                .ok_or(DatpError::WrongPassword)?;
+
+            let params = header.get("params").ok_or_else(|| invalid("missing 'header.params'"))?;
+            let nonce = hex_field(params, "nonce")?;
+            let tag = hex_field(params, "tag")?;
+            let ciphertext = base64::decode(db).ok_or_else(|| invalid("'db' is not valid base64"))?;
+            let plaintext =
+                crypto::aes256_gcm_decrypt(&master_key, &nonce, &ciphertext, &tag, &[]).ok_or(DatpError::WrongPassword)?;
+            let plaintext = String::from_utf8(plaintext).map_err(|_| DatpError::InvalidJson)?;
+            json::parse(&plaintext).ok_or(DatpError::InvalidJson)?
+        }
+        _ => return Err(invalid("unexpected 'db' and 'header.slots' combination")),
+    };
+
+    vault
+        .get("entries")
+        .and_then(Json::as_array)
+        .ok_or_else(|| invalid("missing 'entries' array"))?
+        .iter()
+        .map(parse_entry)
+        .collect()
+}
+
+/// Writes accounts as an Aegis JSON backup.
+///
+/// With a password the vault is encrypted the way Aegis does it (scrypt with
+/// N=2^15, r=8, p=1 and AES-256-GCM) and can be imported into Aegis with that
+/// password; without one a plain backup is written.
+pub fn export_aegis_json(accounts: &[Account], password: Option<&str>) -> Result<String, DatpError> {
+    export_aegis_with(accounts, password, SCRYPT_N, SCRYPT_R, SCRYPT_P)
+}
+
+fn export_aegis_with(accounts: &[Account], password: Option<&str>, n: u64, r: u32, p: u32) -> Result<String, DatpError> {
+    let entries = accounts.iter().map(entry_json).collect::<Result<Vec<_>, _>>()?;
+    let vault = Json::object(vec![("version", Json::Number(2.0)), ("entries", Json::Array(entries))]);
+
+    let Some(password) = password else {
+        return Ok(Json::object(vec![
+            ("version", Json::Number(1.0)),
+            ("header", Json::object(vec![("slots", Json::Null), ("params", Json::Null)])),
+            ("db", vault),
+        ])
+        .to_pretty_string());
+    };
+
+    let master_key = crypto::random_bytes(32);
+    let salt = crypto::random_bytes(32);
+    let key_nonce = crypto::random_bytes(12);
+    let db_nonce = crypto::random_bytes(12);
+
+    let derived = crypto::scrypt(password.as_bytes(), &salt, n, r, p, 32).ok_or_else(|| invalid("invalid scrypt parameters"))?;
+    let (encrypted_key, key_tag) = crypto::aes256_gcm_encrypt(&derived, &key_nonce, &master_key, &[]).expect("valid key and nonce sizes");
+    let (db, db_tag) = crypto::aes256_gcm_encrypt(&master_key, &db_nonce, vault.to_pretty_string().as_bytes(), &[])
+        .expect("valid key and nonce sizes");
+
+    let slot = Json::object(vec![
+        ("type", Json::Number(1.0)),
+        ("uuid", Json::string(crypto::random_uuid())),
+        ("key", Json::string(crypto::hex_encode(&encrypted_key))),
+        (
+            "key_params",
+            Json::object(vec![
+                ("nonce", Json::string(crypto::hex_encode(&key_nonce))),
+                ("tag", Json::string(crypto::hex_encode(&key_tag))),
+            ]),
+        ),
+        ("n", Json::Number(n as f64)),
+        ("r", Json::Number(r as f64)),
+        ("p", Json::Number(p as f64)),
+        ("salt", Json::string(crypto::hex_encode(&salt))),
+        ("repaired", Json::Bool(true)),
+        ("is_backup", Json::Bool(false)),
+    ]);
+    let header = Json::object(vec![
+        ("slots", Json::Array(vec![slot])),
+        (
+            "params",
+            Json::object(vec![
+                ("nonce", Json::string(crypto::hex_encode(&db_nonce))),
+                ("tag", Json::string(crypto::hex_encode(&db_tag))),
+            ]),
+        ),
+    ]);
+
+    Ok(Json::object(vec![("version", Json::Number(1.0)), ("header", header), ("db", Json::string(base64::encode(&db)))])
+        .to_pretty_string())
+}
+
+/// Returns the master key if `password` opens this slot, `None` if it does not.
+fn decrypt_slot(slot: &Json, password: &str) -> Result<Option<Vec<u8>>, DatpError> {
+    let salt = hex_field(slot, "salt")?;
+    let key = hex_field(slot, "key")?;
+    let key_params = slot.get("key_params").ok_or_else(|| invalid("slot without 'key_params'"))?;
+    let nonce = hex_field(key_params, "nonce")?;
+    let tag = hex_field(key_params, "tag")?;
+
+    let param = |name: &str| slot.get(name).and_then(Json::as_u64).ok_or_else(|| invalid("slot without scrypt parameters"));
+    let (n, r, p) = (param("n")?, param("r")?, param("p")?);
+    let derived = crypto::scrypt(password.as_bytes(), &salt, n, r as u32, p as u32, 32)
+        .ok_or_else(|| invalid("unsupported scrypt parameters"))?;
+
+    Ok(crypto::aes256_gcm_decrypt(&derived, &nonce, &key, &tag, &[]))
+}
+
+fn parse_entry(entry: &Json) -> Result<Account, DatpError> {
+    let info = entry.get("info").ok_or_else(|| invalid("entry without 'info'"))?;
+    let secret = info.str_field("secret").ok_or(DatpError::MissingSecret)?;
+    let mut account = Account::new(entry.str_field("name").unwrap_or(""), uri::normalize_secret(secret)?);
+    account.issuer = entry.str_field("issuer").filter(|issuer| !issuer.is_empty()).map(str::to_string);
+
+    if let Some(algo) = info.str_field("algo") {
+        account.algorithm = uri::parse_algorithm(algo)?;
+    }
+    if let Some(digits) = info.get("digits").and_then(Json::as_u64) {
+        account.digits = uri::parse_digits(&digits.to_string())?;
+    }
+    if let Some(period) = info.get("period").and_then(Json::as_u64) {
+        if period == 0 {
+            return Err(DatpError::InvalidPeriod(period.to_string()));
+        }
+        account.period = period;
+    }
+
+    match entry.str_field("type") {
+        Some("totp") => {}
+        Some("hotp") => {
+            account.kind = OtpKind::Hotp;
+            account.counter = Some(info.get("counter").and_then(Json::as_u64).unwrap_or(0));
+        }
+        Some("steam") => {
+            account.kind = OtpKind::Steam;
+            account.digits = 5;
+        }
+        other => return Err(DatpError::UnsupportedType(other.unwrap_or("").to_string())),
+    }
+    Ok(account)
+}
+
+fn entry_json(account: &Account) -> Result<Json, DatpError> {
+    let mut info = vec![
+        ("secret", Json::string(account.secret.as_str())),
+        ("algo", Json::string(account.algorithm.as_str())),
+        ("digits", Json::Number(account.digits as f64)),
+    ];
+    match account.kind {
+        OtpKind::Hotp => info.push(("counter", Json::Number(account.counter.unwrap_or(0) as f64))),
+        OtpKind::Totp | OtpKind::Steam => info.push(("period", Json::Number(account.period as f64))),
+    }
+    if account.kind == OtpKind::Steam && account.algorithm != Algorithm::Sha1 {
+        return Err(DatpError::UnknownAlgorithm(account.algorithm.as_str().to_string()));
+    }
+
+    Ok(Json::object(vec![
+        ("type", Json::string(account.kind.as_str())),
+        ("uuid", Json::string(crypto::random_uuid())),
+        ("name", Json::string(account.account_name.as_str())),
+        ("issuer", Json::string(account.issuer.as_deref().unwrap_or(""))),
+        ("note", Json::string("")),
+        ("favorite", Json::Bool(false)),
+        ("icon", Json::Null),
+        ("info", Json::object(info)),
+    ]))
+}
+
+fn hex_field(object: &Json, key: &str) -> Result<Vec<u8>, DatpError> {
+    object
+        .str_field(key)
+        .and_then(crypto::hex_decode)
+        .ok_or_else(|| invalid(&format!("missing or invalid hex field '{}'", key)))
+}
+
+fn invalid(reason: &str) -> DatpError {
+    DatpError::InvalidBackup(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // produced with the cryptography package, password "test", scrypt N=1024
+    const ENCRYPTED: &str = r#"{"version":1,"header":{"slots":[{"type":1,"uuid":"a1234567-89ab-cdef-0123-456789abcdef","key":"f1d8cbf8b77c4fc7558d9861ca86c701485a1f813c35652435a28e2df9480077","key_params":{"nonce":"000102030405060708090a0b","tag":"12db033ec99fcda7b25e98da9ef949a3"},"n":1024,"r":8,"p":1,"salt":"6465666768696a6b6c6d6e6f707172737475767778797a7b7c7d7e7f80818283","repaired":true,"is_backup":false}],"params":{"nonce":"32333435363738393a3b3c3d","tag":"9d0f6b50882afecbccd4306a4262c5bb"}},"db":"vXmZpbdP6IwDJdCmacHf2gPdb/PgKNTJ6zpQ45VH4gJGIvBrNZdo2uEuVv+GE5aEm9bdzfD8YQepb4RkeAm2GKvW951CDpoO6WN9Brkg+Kqrb170UvsYTHFSP1igQB4hZDSEul7R+zKqnBQZCPD41aLswforD1e9CQlt0y5u23PNWiFbNlfBSbuJxmj8kETZ6nHh8MZSgPAUfHfqVp6y/2jRiEYfB/HAk+FhI7LN1NUKoTH8oR8Vrqi7TkSoGRbCnpt11iWZY3DbKWEFsw1zXQMiHdlM6rnXEt3F3AOorZvF32SmHxHXkX/i1G5rM3R6RFjHg7rXpg0HO0TukZZZ+MJZEQhZHKq7aBdtSZQLrth4xyLVrSrhwNLAjvtEYjBjtoaa7XN1dXlTB+vf85Uziw/ku0NpdakgR5Vo91jP23bQfAyymY9VO97v0CW1pjucPRduVtC+jQ9pPbT8MAma4QeaMFZa8eLBsQzHijxvt8QCty19X2V80Ghm03GNWLParvWMbjjFv8+UNUN+ge59gHza8NBk/uty8imdTFeC489XtS06iOEGA+OZoCSYDkkMOP1rCTq+Wc2bhItTabkjazK0aJPEkjyVMG9qTLjN4PNW9Db3xFjNhUAK+yQqT22XfpVoi2YgnWnGKqmPRYllCvT3GgsfEMMLadgRNZws2faeLNUiAHHsYhDkbz5dc4Wusy4VbCbthRVR/ulPBfd296y/sEqalVRAS5Aac7VHRMubvaE3CA34pXS5t9Rjqk8iFrv1TzrB4jHzcaz3a8k4PV69Knxvv5md2TzmHRD+S45Gp58cNOzginqhqag+Pw5uGq1NIoRfTocf7x/Z3HjHSqmxrbjpG17kty9sssCHIldjn2dTOHcl4dIBrR84etTPvFrQupR0FbMd3CIIj4VxPIPtz1sUP8/UEN9jrcxvtTgnnYk2UHY6C+q72xqPbef6xfvsIEgOqg=="}"#;
+
+    fn expected() -> Vec<Account> {
+        let mut alice = Account::new("alice@example.com", "JBSWY3DPEHPK3PXP");
+        alice.issuer = Some("Example".to_string());
+        alice.algorithm = Algorithm::Sha256;
+        alice.digits = 8;
+        alice.period = 60;
+        let bob = Account::new_hotp("bob", "GEZDGNBVGY3TQOJQ", 3);
+        vec![alice, bob, Account::new_steam("gaben", "JBSWY3DPEHPK3PXP")]
+    }
+
+    #[test]
+    fn test_import_encrypted() {
+        assert_eq!(import_aegis_json(ENCRYPTED, Some("test")).unwrap(), expected());
+        assert_eq!(import_aegis_json(ENCRYPTED, Some("wrong")), Err(DatpError::WrongPassword));
+        assert_eq!(import_aegis_json(ENCRYPTED, None), Err(DatpError::PasswordRequired));
+    }
+
+    #[test]
+    fn test_plain_roundtrip() {
+        let backup = export_aegis_json(&expected(), None).unwrap();
+        assert_eq!(import_aegis_json(&backup, None).unwrap(), expected());
+    }
+
+    #[test]
+    fn test_encrypted_roundtrip() {
+        let backup = export_aegis_with(&expected(), Some("hunter2"), 1024, 8, 1).unwrap();
+        assert_eq!(import_aegis_json(&backup, Some("hunter2")).unwrap(), expected());
+    }
+
+    #[test]
+    fn test_import_unsupported_entry() {
+        let backup = r#"{"version":1,"header":{"slots":null,"params":null},"db":{"version":2,"entries":[
+            {"type":"motp","name":"x","issuer":"","info":{"secret":"JBSWY3DPEHPK3PXP","pin":"1234"}}]}}"#;
+        assert_eq!(import_aegis_json(backup, None), Err(DatpError::UnsupportedType("motp".to_string())));
+    }
+}
@@ -0,0 +1,243 @@
+// andOTP backups: a JSON array of entries with `secret`, `issuer`, `label`,
+// `digits`, `type` (TOTP, HOTP or STEAM), `algorithm`, `period` and `counter`.
+// Encrypted backups (`.json.aes`) are binary: the PBKDF2 iteration count as a
+// 4-byte big-endian number, a 12-byte salt and a 12-byte IV, followed by the
+// JSON encrypted with AES-256-GCM (tag appended) under
+// PBKDF2-HMAC-SHA1(password, salt, iterations, 32).
+
+use super::json::{self, Json};
+use super::{crypto, uri, Account, Algorithm, DatpError, OtpKind};
+
+const PBKDF2_ITERATIONS: u32 = 150_000;      // andOTP picks between 140000 and 160000
+const SALT_LEN: usize = 12;
+const IV_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+const HEADER_LEN: usize = 4 + SALT_LEN + IV_LEN;
+
+/// Reads an andOTP backup, plain (`.json`) or password-encrypted (`.json.aes`).
+///
+/// # Arguments
+/// * `backup` - Contents of the backup file; encrypted backups are binary.
+/// * `password` - Backup password, only needed for encrypted backups.
+///
+/// # Returns
+/// `Result<Vec<Account>, DatpError>` - The accounts in the backup.
+/// `DatpError::PasswordRequired` if the backup is encrypted and no password was given, and
+/// `DatpError::WrongPassword` if it does not decrypt.
+///
+/// # Example
+/// ```rust
+/// use datp::import_andotp;
+///
+/// let backup = br#"[{"secret": "JBSWY3DPEHPK3PXP", "issuer": "ACME", "label": "alice",
+///     "digits": 6, "type": "TOTP", "algorithm": "SHA1", "period": 30, "tags": []}]"#;
+/// let accounts = import_andotp(backup, None).unwrap();
+/// assert_eq!((accounts[0].issuer.as_deref(), accounts[0].account_name.as_str()), (Some("ACME"), "alice"));
+/// ```
+pub fn import_andotp(backup: &[u8], password: Option<&str>) -> Result<Vec<Account>, DatpError> {
+    // a plain backup is a JSON array; an encrypted one starts with a small iteration count
+    let plain = backup.iter().find(|byte| !byte.is_ascii_whitespace()) == Some(&b'[');
+    let decrypted;
+    let entries = if plain {
+        std::str::from_utf8(backup).map_err(|_| DatpError::InvalidJson)?
+    } else {
+        let password = password.ok_or(DatpError::PasswordRequired)?;
+        decrypted = decrypt(backup, password)?;
+        &decrypted
+    };
+
+    json::parse(entries)
+        .ok_or(DatpError::InvalidJson)?
+        .as_array()
+        .ok_or_else(|| invalid("expected an array of entries"))?
+        .iter()
+        .map(parse_entry)
+        .collect()
+}
+
+/// Writes accounts as an andOTP backup.
+///
+/// With a password the backup is encrypted the way andOTP does it (PBKDF2-HMAC-SHA1 with
+/// 150000 iterations and AES-256-GCM) and is binary, for a `.json.aes` file; without one a
+/// plain JSON backup is written.
+///
+/// # Returns
+/// `Result<Vec<u8>, DatpError>` - The backup file, or `DatpError::UnknownAlgorithm` for a
+/// Steam account with an algorithm other than SHA1, which andOTP can't represent.
+///
+/// # Example
+/// ```rust
+/// use datp::{export_andotp, import_andotp, Account};
+///
+/// let accounts = vec![Account::new("alice", "JBSWY3DPEHPK3PXP")];
+/// let backup = export_andotp(&accounts, None).unwrap();
+/// assert_eq!(import_andotp(&backup, None).unwrap(), accounts);
+/// ```
+pub fn export_andotp(accounts: &[Account], password: Option<&str>) -> Result<Vec<u8>, DatpError> {
+    export_andotp_with(accounts, password, PBKDF2_ITERATIONS)
+}
+
+fn export_andotp_with(accounts: &[Account], password: Option<&str>, iterations: u32) -> Result<Vec<u8>, DatpError> {
+    let entries = accounts.iter().map(entry_json).collect::<Result<Vec<_>, _>>()?;
+    let plaintext = Json::Array(entries).to_pretty_string();
+    let Some(password) = password else {
+        return Ok(plaintext.into_bytes());
+    };
+
+    let salt = crypto::random_bytes(SALT_LEN);
+    let iv = crypto::random_bytes(IV_LEN);
+    let key = crypto::pbkdf2_sha1(password.as_bytes(), &salt, iterations, 32);
+    let (ciphertext, tag) = crypto::aes256_gcm_encrypt(&key, &iv, plaintext.as_bytes(), &[]).expect("valid key and nonce sizes");
+
+    let mut backup = Vec::with_capacity(HEADER_LEN + ciphertext.len() + TAG_LEN);
+    backup.extend_from_slice(&iterations.to_be_bytes());
+    backup.extend_from_slice(&salt);
+    backup.extend_from_slice(&iv);
+    backup.extend_from_slice(&ciphertext);
+    backup.extend_from_slice(&tag);
+    Ok(backup)
+}
+
+fn decrypt(backup: &[u8], password: &str) -> Result<String, DatpError> {
+    if backup.len() < HEADER_LEN + TAG_LEN {
+        return Err(invalid("the encrypted backup is too short"));
+    }
+    let (header, data) = backup.split_at(HEADER_LEN);
+    let iterations = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+    if !(1..=crypto::MAX_PBKDF2_ITERATIONS).contains(&iterations) {
+        return Err(invalid("unexpected PBKDF2 iteration count"));
+    }
+    let (salt, iv) = header[4..].split_at(SALT_LEN);
+
+    let key = crypto::pbkdf2_sha1(password.as_bytes(), salt, iterations, 32);
+    let (ciphertext, tag) = data.split_at(data.len() - TAG_LEN);
+    let plaintext = crypto::aes256_gcm_decrypt(&key, iv, ciphertext, tag, &[]).ok_or(DatpError::WrongPassword)?;
+    String::from_utf8(plaintext).map_err(|_| DatpError::InvalidJson)
+}
+
+fn parse_entry(entry: &Json) -> Result<Account, DatpError> {
+    let secret = entry.str_field("secret").ok_or(DatpError::MissingSecret)?;
+    let label = entry.str_field("label").unwrap_or("");
+    let issuer = entry.str_field("issuer").filter(|issuer| !issuer.is_empty());
+
+    // backups from before andOTP had an issuer field keep it in the label
+    let (issuer, account_name) = match (issuer, label.split_once(':')) {
+        (None, Some((issuer, account_name))) => (Some(issuer.trim()), account_name.trim()),
+        _ => (issuer, label),
+    };
+    let mut account = Account::new(account_name, uri::normalize_secret(secret)?);
+    account.issuer = issuer.map(str::to_string);
+
+    if let Some(algorithm) = entry.str_field("algorithm") {
+        account.algorithm = uri::parse_algorithm(algorithm)?;
+    }
+    if let Some(digits) = entry.get("digits").and_then(Json::as_u64) {
+        account.digits = uri::parse_digits(&digits.to_string())?;
+    }
+    if let Some(period) = entry.get("period").and_then(Json::as_u64) {
+        if period == 0 {
+            return Err(DatpError::InvalidPeriod(period.to_string()));
+        }
+        account.period = period;
+    }
+    match entry.str_field("type").map(str::to_ascii_uppercase).as_deref() {
+        None | Some("TOTP") => {}
+        Some("HOTP") => {
+            account.kind = OtpKind::Hotp;
+            account.counter = Some(entry.get("counter").and_then(Json::as_u64).unwrap_or(0));
+        }
+        Some("STEAM") => {
+            account.kind = OtpKind::Steam;
+            account.digits = 5;
+        }
+        Some(other) => return Err(DatpError::UnsupportedType(other.to_string())),
+    }
+    Ok(account)
+}
+
+fn entry_json(account: &Account) -> Result<Json, DatpError> {
+    if account.kind == OtpKind::Steam && account.algorithm != Algorithm::Sha1 {
+        return Err(DatpError::UnknownAlgorithm(account.algorithm.as_str().to_string()));
+    }
+    let mut entry = vec![
+        ("secret", Json::string(account.secret.as_str())),
+        ("issuer", Json::string(account.issuer.as_deref().unwrap_or(""))),
+        ("label", Json::string(account.account_name.as_str())),
+        ("digits", Json::Number(account.digits as f64)),
+        ("type", Json::string(account.kind.as_str().to_ascii_uppercase())),
+        ("algorithm", Json::string(account.algorithm.as_str())),
+        ("thumbnail", Json::string("Default")),
+        ("last_used", Json::Number(0.0)),
+        ("used_frequency", Json::Number(0.0)),
+    ];
+    match account.kind {
+        OtpKind::Hotp => entry.push(("counter", Json::Number(account.counter.unwrap_or(0) as f64))),
+        OtpKind::Totp | OtpKind::Steam => entry.push(("period", Json::Number(account.period as f64))),
+    }
+    entry.push(("tags", Json::Array(Vec::new())));
+    Ok(Json::object(entry))
+}
+
+fn invalid(reason: &str) -> DatpError {
+    DatpError::InvalidBackup(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAIN: &str = r#"[
+        {"secret": "JBSWY3DPEHPK3PXP", "issuer": "GitHub", "label": "octocat", "digits": 6, "type": "TOTP",
+         "algorithm": "SHA1", "thumbnail": "Github", "last_used": 1590000000000, "used_frequency": 3, "period": 30,
+         "tags": ["work"]},
+        {"secret": "GEZDGNBVGY3TQOJQ", "issuer": "", "label": "ACME:door", "digits": 8, "type": "HOTP",
+         "algorithm": "SHA256", "counter": 7, "tags": []},
+        {"secret": "JBSWY3DPEHPK3PXP", "issuer": "Steam", "label": "gaben", "digits": 5, "type": "STEAM",
+         "algorithm": "SHA1", "period": 30, "tags": []}
+    ]"#;
+
+    #[test]
+    fn test_import_andotp() {
+        let accounts = import_andotp(PLAIN.as_bytes(), None).unwrap();
+        assert_eq!(accounts.len(), 3);
+        assert_eq!((accounts[0].issuer.as_deref(), accounts[0].account_name.as_str()), (Some("GitHub"), "octocat"));
+
+        let door = &accounts[1];
+        assert_eq!((door.issuer.as_deref(), door.account_name.as_str()), (Some("ACME"), "door"));
+        assert_eq!((door.kind, door.counter, door.digits, door.algorithm), (OtpKind::Hotp, Some(7), 8, Algorithm::Sha256));
+
+        assert_eq!((accounts[2].kind, accounts[2].digits), (OtpKind::Steam, 5));
+    }
+
+    #[test]
+    fn test_encrypted_round_trip() {
+        let accounts = import_andotp(PLAIN.as_bytes(), None).unwrap();
+        let backup = export_andotp_with(&accounts, Some("correct horse"), 1_000).unwrap();
+        assert_eq!(backup[..4], 1_000u32.to_be_bytes());
+        assert_eq!(import_andotp(&backup, Some("correct horse")).unwrap(), accounts);
+
+        assert_eq!(import_andotp(&backup, None), Err(DatpError::PasswordRequired));
+        assert_eq!(import_andotp(&backup, Some("wrong")), Err(DatpError::WrongPassword));
+        assert!(matches!(import_andotp(&backup[..HEADER_LEN], Some("correct horse")), Err(DatpError::InvalidBackup(_))));
+
+        let mut tampered = backup.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert_eq!(import_andotp(&tampered, Some("correct horse")), Err(DatpError::WrongPassword));
+        let mut expensive = backup;
+        expensive[..4].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(matches!(import_andotp(&expensive, Some("correct horse")), Err(DatpError::InvalidBackup(_))));
+    }
+
+    #[test]
+    fn test_import_andotp_errors() {
+        assert_eq!(import_andotp(b"[{\"label\": \"alice\"}]", None), Err(DatpError::MissingSecret));
+        assert_eq!(import_andotp(b"[{\"secret\": \"1nvalid\"}]", None), Err(DatpError::InvalidSecret));
+        assert!(matches!(import_andotp(b"[{\"secret\": \"JBSWY3DP\", \"type\": \"MOTP\"}]", None), Err(DatpError::UnsupportedType(_))));
+        assert!(matches!(import_andotp(b"[{}", None), Err(DatpError::InvalidJson)));
+        let zero = b"[{\"secret\": \"JBSWY3DP\", \"period\": 0}]";
+        assert_eq!(import_andotp(zero, None), Err(DatpError::InvalidPeriod("0".to_string())));
+
+        let steam = Account { algorithm: Algorithm::Sha256, ..Account::new_steam("bob", "JBSWY3DPEHPK3PXP") };
+        assert!(matches!(export_andotp(&[steam], None), Err(DatpError::UnknownAlgorithm(_))));
+    }
+}
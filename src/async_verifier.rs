@@ -0,0 +1,544 @@
+use std::collections::HashMap;
+use std::future::{self, Future};
+use std::sync::{Arc, Mutex};
+
+use super::{
+    hotp_verify_with, match_code, Account, AttemptStore, Clock, DriftStore, Lockout, OtpKind, SecretResolver,
+    SystemClock, Throttle, UsedCodeStore, VerificationObserver, VerificationOutcome, VerificationReport,
+};
+
+/// Looks up the account of a user for `AsyncTotpVerifier`, e.g. with a database query.
+///
+/// Implemented for every `SecretResolver`, closures included.
+pub trait AsyncSecretResolver {
+    fn resolve(&self, user: &str) -> impl Future<Output = Option<Account>> + Send;
+}
+
+impl<T: SecretResolver> AsyncSecretResolver for T {
+    fn resolve(&self, user: &str) -> impl Future<Output = Option<Account>> + Send {
+        future::ready(SecretResolver::resolve(self, user))
+    }
+}
+
+/// `UsedCodeStore` for stores reached over the network; see there for the contract.
+///
+/// Implemented for every `UsedCodeStore`, and by `()` for a verifier without one.
+pub trait AsyncUsedCodeStore {
+    fn mark_used(&self, user: &str, counter: u64, expires_at: u64) -> impl Future<Output = bool> + Send;
+}
+
+impl<T: UsedCodeStore> AsyncUsedCodeStore for T {
+    fn mark_used(&self, user: &str, counter: u64, expires_at: u64) -> impl Future<Output = bool> + Send {
+        future::ready(UsedCodeStore::mark_used(self, user, counter, expires_at))
+    }
+}
+
+impl AsyncUsedCodeStore for () {
+    fn mark_used(&self, _user: &str, _counter: u64, _expires_at: u64) -> impl Future<Output = bool> + Send {
+        future::ready(true)
+    }
+}
+
+/// The part of `AttemptStore` that `AsyncTotpVerifier` needs. `try_reserve` must check and
+/// record in one step, e.g. in a transaction.
+///
+/// Implemented for every `AttemptStore`, and by `()` for a verifier without one.
+pub trait AsyncAttemptStore {
+    fn try_reserve(&self, key: &str, at: u64, since: u64, max_failures: u32) -> impl Future<Output = bool> + Send;
+    fn clear(&self, key: &str) -> impl Future<Output = ()> + Send;
+}
+
+impl<T: AttemptStore> AsyncAttemptStore for T {
+    fn try_reserve(&self, key: &str, at: u64, since: u64, max_failures: u32) -> impl Future<Output = bool> + Send {
+        future::ready(AttemptStore::try_reserve(self, key, at, since, max_failures))
+    }
+
+    fn clear(&self, key: &str) -> impl Future<Output = ()> + Send {
+        AttemptStore::clear(self, key);
+        future::ready(())
+    }
+}
+
+impl AsyncAttemptStore for () {
+    fn try_reserve(&self, _key: &str, _at: u64, _since: u64, _max_failures: u32) -> impl Future<Output = bool> + Send {
+        future::ready(true)
+    }
+
+    fn clear(&self, _key: &str) -> impl Future<Output = ()> + Send {
+        future::ready(())
+    }
+}
+
+/// `DriftStore` for stores reached over the network.
+///
+/// Implemented for every `DriftStore`, and by `()` for a verifier without one.
+pub trait AsyncDriftStore {
+    fn drift(&self, user: &str) -> impl Future<Output = i64> + Send;
+    fn set_drift(&self, user: &str, steps: i64) -> impl Future<Output = ()> + Send;
+}
+
+impl<T: DriftStore> AsyncDriftStore for T {
+    fn drift(&self, user: &str) -> impl Future<Output = i64> + Send {
+        future::ready(DriftStore::drift(self, user))
+    }
+
+    fn set_drift(&self, user: &str, steps: i64) -> impl Future<Output = ()> + Send {
+        DriftStore::set_drift(self, user, steps);
+        future::ready(())
+    }
+}
+
+impl AsyncDriftStore for () {
+    fn drift(&self, _user: &str) -> impl Future<Output = i64> + Send {
+        future::ready(0)
+    }
+
+    fn set_drift(&self, _user: &str, _steps: i64) -> impl Future<Output = ()> + Send {
+        future::ready(())
+    }
+}
+
+/// Keeps the next expected counter of each HOTP user for `AsyncTotpVerifier::verify_hotp`.
+///
+/// Implemented for `Mutex<HashMap<String, u64>>`.
+pub trait AsyncCounterStore {
+    /// The next counter of `user`, or `None` to start from the account's `counter`.
+    fn counter(&self, user: &str) -> impl Future<Output = Option<u64>> + Send;
+    /// Moves the counter of `user` from `from` to `to` and returns true, or returns false if
+    /// another attempt moved it first. Must check and update in one step, e.g. with
+    /// `UPDATE .. WHERE counter = from`.
+    fn advance(&self, user: &str, from: u64, to: u64) -> impl Future<Output = bool> + Send;
+}
+
+impl AsyncCounterStore for Mutex<HashMap<String, u64>> {
+    fn counter(&self, user: &str) -> impl Future<Output = Option<u64>> + Send {
+        future::ready(self.lock().unwrap().get(user).copied())
+    }
+
+    fn advance(&self, user: &str, from: u64, to: u64) -> impl Future<Output = bool> + Send {
+        let mut counters = self.lock().unwrap();
+        let moved = counters.get(user).is_some_and(|counter| *counter != from);
+        if !moved {
+            counters.insert(user.to_string(), to);
+        }
+        future::ready(!moved)
+    }
+}
+
+impl<T: AsyncCounterStore + Send + Sync> AsyncCounterStore for Arc<T> {
+    fn counter(&self, user: &str) -> impl Future<Output = Option<u64>> + Send {
+        T::counter(self, user)
+    }
+
+    fn advance(&self, user: &str, from: u64, to: u64) -> impl Future<Output = bool> + Send {
+        T::advance(self, user, from, to)
+    }
+}
+
+/// `TotpVerifier` for async handlers: the resolver and the stores are awaited, so they can
+/// query a database without blocking the executor, and a complete verification is one
+/// `.await`.
+///
+/// The stores are type parameters rather than trait objects, as traits with async methods
+/// can't be; the builder methods swap the `()` placeholder for the store's type. The futures of
+/// `verify_report` and `verify_hotp_report` are `Send` for use on multi-threaded executors.
+///
+/// The clock, the lockout and the observer stay synchronous and run on the executor's thread.
+/// That is fine for the in-memory `Lockout`, but a `Lockout` over a `LockStore` that queries a
+/// database blocks the executor for each query; use an `AsyncAttemptStore` throttle for such
+/// limits instead.
+///
+/// # Example
+/// ```rust
+/// use std::collections::HashMap;
+/// use std::sync::Mutex;
+/// use datp::{Account, AsyncTotpVerifier, Throttle};
+///
+/// async fn login(user: &str, code: &str) -> bool {
+///     let verifier = AsyncTotpVerifier::new(|_: &str| Some(Account::new("alice", "JBSWY3DPEHPK3PXP")))
+///         .throttle(Mutex::new(HashMap::new()), Throttle::default());
+///     verifier.verify(user, code).await
+/// }
+/// ```
+pub struct AsyncTotpVerifier<R, U = (), A = (), D = (), K = ()> {
+    resolver: R,
+    clock: Box<dyn Clock + Send + Sync>,
+    window: u64,
+    used_codes: Option<U>,
+    throttle: Option<(A, Throttle)>,
+    drift: Option<D>,
+    counters: Option<K>,
+    lockout: Option<Arc<Lockout>>,
+    observer: Option<Box<dyn VerificationObserver + Send + Sync>>,
+}
+
+impl<R: AsyncSecretResolver> AsyncTotpVerifier<R> {
+    /// Creates a verifier using the system clock and a window of one step either way.
+    pub fn new(resolver: R) -> Self {
+        AsyncTotpVerifier {
+            resolver,
+            clock: Box::new(SystemClock),
+            window: 1,
+            used_codes: None,
+            throttle: None,
+            drift: None,
+            counters: None,
+            lockout: None,
+            observer: None,
+        }
+    }
+}
+
+impl<R, U, A, D, K> AsyncTotpVerifier<R, U, A, D, K> {
+    pub fn clock(mut self, clock: impl Clock + Send + Sync + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Steps accepted before and after the current one (or the user's learned drift), and the
+    /// counters looked ahead by `verify_hotp`.
+    pub fn window(mut self, steps: u64) -> Self {
+        self.window = steps;
+        self
+    }
+
+    pub fn replay_store<S: AsyncUsedCodeStore>(self, store: S) -> AsyncTotpVerifier<R, S, A, D, K> {
+        let AsyncTotpVerifier { resolver, clock, window, used_codes: _, throttle, drift, counters, lockout, observer } = self;
+        AsyncTotpVerifier { resolver, clock, window, used_codes: Some(store), throttle, drift, counters, lockout, observer }
+    }
+
+    pub fn throttle<S: AsyncAttemptStore>(self, store: S, throttle: Throttle) -> AsyncTotpVerifier<R, U, S, D, K> {
+        let AsyncTotpVerifier { resolver, clock, window, used_codes, throttle: _, drift, counters, lockout, observer } = self;
+        AsyncTotpVerifier { resolver, clock, window, used_codes, throttle: Some((store, throttle)), drift, counters, lockout, observer }
+    }
+
+    /// Centers the window of each user on the offset of their last accepted code, as
+    /// `TotpVerifier::drift_store` does.
+    pub fn drift_store<S: AsyncDriftStore>(self, store: S) -> AsyncTotpVerifier<R, U, A, S, K> {
+        let AsyncTotpVerifier { resolver, clock, window, used_codes, throttle, drift: _, counters, lockout, observer } = self;
+        AsyncTotpVerifier { resolver, clock, window, used_codes, throttle, drift: Some(store), counters, lockout, observer }
+    }
+
+    /// Stores the HOTP counters for `verify_hotp`.
+    pub fn counter_store<S: AsyncCounterStore>(self, store: S) -> AsyncTotpVerifier<R, U, A, D, S> {
+        let AsyncTotpVerifier { resolver, clock, window, used_codes, throttle, drift, counters: _, lockout, observer } = self;
+        AsyncTotpVerifier { resolver, clock, window, used_codes, throttle, drift, counters: Some(store), lockout, observer }
+    }
+
+    pub fn lockout(mut self, lockout: Arc<Lockout>) -> Self {
+        self.lockout = Some(lockout);
+        self
+    }
+
+    pub fn observer(mut self, observer: impl VerificationObserver + Send + Sync + 'static) -> Self {
+        self.observer = Some(Box::new(observer));
+        self
+    }
+}
+
+impl<R, U, A, D, K> AsyncTotpVerifier<R, U, A, D, K>
+where
+    R: AsyncSecretResolver + Sync,
+    U: AsyncUsedCodeStore + Sync,
+    A: AsyncAttemptStore + Sync,
+    D: AsyncDriftStore + Sync,
+    K: Sync,
+{
+    /// Checks `code` for `user` like `TotpVerifier::verify`.
+    pub async fn verify(&self, user: &str, code: &str) -> bool {
+        self.verify_report(user, code).await.is_accepted()
+    }
+
+    /// Checks `code` for `user` like `TotpVerifier::verify_report`: the attempt is reserved in
+    /// the throttle store and the lockout before the code is checked, and cleared again if it is
+    /// accepted.
+    pub async fn verify_report(&self, user: &str, code: &str) -> VerificationReport {
+        let now = self.clock.now();
+        self.attempt(user, now, async {
+            match self.resolver.resolve(user).await {
+                Some(account) if account.kind == OtpKind::Totp => self.check(user, &account, code, now).await,
+                _ => VerificationReport::new(VerificationOutcome::Rejected, self.window),
+            }
+        })
+        .await
+    }
+
+    /// Runs `check` unless the lockout or the throttle refuse the attempt, and records its outcome.
+    async fn attempt(&self, user: &str, now: u64, check: impl Future<Output = VerificationReport>) -> VerificationReport {
+        if self.lockout.as_ref().is_some_and(|lockout| lockout.is_locked(user, now)) {
+            self.notify(|observer| observer.on_throttled(user, now));
+            return VerificationReport::new(VerificationOutcome::Locked, self.window);
+        }
+        if let Some((store, throttle)) = &self.throttle
+            && !store.try_reserve(user, now, now.saturating_sub(throttle.window), throttle.max_failures).await
+        {
+            self.notify(|observer| observer.on_throttled(user, now));
+            return VerificationReport::new(VerificationOutcome::Throttled, self.window);
+        }
+        if self.lockout.as_ref().is_some_and(|lockout| !lockout.begin_attempt(user, now)) {
+            self.notify(|observer| observer.on_throttled(user, now));
+            return VerificationReport::new(VerificationOutcome::Locked, self.window);
+        }
+
+        let report = check.await;
+        match report.outcome {
+            VerificationOutcome::Accepted => {
+                if let Some((store, _)) = &self.throttle {
+                    store.clear(user).await;
+                }
+                if let Some(lockout) = &self.lockout {
+                    lockout.record_success(user);
+                }
+                self.notify(|observer| observer.on_success(user, now));
+                return report;
+            }
+            VerificationOutcome::Replayed => self.notify(|observer| observer.on_replay(user, now)),
+            VerificationOutcome::Rejected | VerificationOutcome::Throttled | VerificationOutcome::Locked => {
+                self.notify(|observer| observer.on_failure(user, now))
+            }
+        }
+        if let Some(lockout) = &self.lockout {
+            lockout.fail_attempt(user, now);
+        }
+        report
+    }
+
+    async fn check(&self, user: &str, account: &Account, code: &str, now: u64) -> VerificationReport {
+        let drift = match &self.drift {
+            Some(store) => store.drift(user).await,
+            None => 0,
+        };
+        let Some(found) = match_code(account, code, drift, now, self.window) else {
+            return VerificationReport::new(VerificationOutcome::Rejected, self.window);
+        };
+        if let Some(store) = &self.used_codes
+            && !store.mark_used(user, found.step, found.expires_at).await
+        {
+            return VerificationReport::matched(VerificationOutcome::Replayed, self.window, &found);
+        }
+        if let Some(store) = &self.drift {
+            store.set_drift(user, found.offset).await;
+        }
+        VerificationReport::matched(VerificationOutcome::Accepted, self.window, &found)
+    }
+
+    fn notify(&self, event: impl FnOnce(&dyn VerificationObserver)) {
+        if let Some(observer) = &self.observer {
+            event(observer.as_ref());
+        }
+    }
+}
+
+impl<R, U, A, D, K> AsyncTotpVerifier<R, U, A, D, K>
+where
+    R: AsyncSecretResolver + Sync,
+    U: AsyncUsedCodeStore + Sync,
+    A: AsyncAttemptStore + Sync,
+    D: AsyncDriftStore + Sync,
+    K: AsyncCounterStore + Sync,
+{
+    /// Checks the HOTP `code` of `user` against the `window` counters from the stored one, and
+    /// moves the stored counter past the match so the code can't be used again.
+    pub async fn verify_hotp(&self, user: &str, code: &str) -> bool {
+        self.verify_hotp_report(user, code).await.is_accepted()
+    }
+
+    /// `verify_hotp` with a report: `counter` is the counter of the matching code and
+    /// `matched_offset` how far past the stored counter it was. Throttling, the lockout and the
+    /// observer apply as in `verify_report`; the replay and drift stores are for TOTP only. A
+    /// code whose counter another attempt moved first is `Replayed`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// use std::sync::Mutex;
+    /// use datp::{hotp_raw, Account, AsyncTotpVerifier, OtpKind};
+    ///
+    /// async fn login(code: &str) -> bool {
+    ///     let mut account = Account::new("alice", "JBSWY3DPEHPK3PXP");
+    ///     account.kind = OtpKind::Hotp;
+    ///     account.counter = Some(0);
+    ///     let verifier = AsyncTotpVerifier::new(move |_: &str| Some(account.clone())).counter_store(Mutex::new(HashMap::new()));
+    ///     verifier.verify_hotp("alice", code).await
+    /// }
+    /// ```
+    pub async fn verify_hotp_report(&self, user: &str, code: &str) -> VerificationReport {
+        let now = self.clock.now();
+        self.attempt(user, now, async {
+            match (self.resolver.resolve(user).await, &self.counters) {
+                (Some(account), Some(counters)) if account.kind == OtpKind::Hotp => {
+                    self.check_hotp(user, &account, code, counters).await
+                }
+                _ => VerificationReport::new(VerificationOutcome::Rejected, self.window),
+            }
+        })
+        .await
+    }
+
+    async fn check_hotp(&self, user: &str, account: &Account, code: &str, counters: &K) -> VerificationReport {
+        let counter = match counters.counter(user).await {
+            Some(counter) => counter,
+            None => account.counter.unwrap_or(0),
+        };
+        let Some(matched) = hotp_verify_with(&account.secret, code, account.algorithm, account.digits, counter, self.window) else {
+            return VerificationReport::new(VerificationOutcome::Rejected, self.window);
+        };
+        let outcome = match counters.advance(user, counter, matched.saturating_add(1)).await {
+            true => VerificationOutcome::Accepted,
+            false => VerificationOutcome::Replayed,
+        };
+        VerificationReport {
+            matched_offset: Some((matched - counter) as i64),
+            counter: Some(matched),
+            ..VerificationReport::new(outcome, self.window)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hotp_raw, totp_raw};
+    use std::collections::{HashMap, HashSet};
+    use std::pin::pin;
+    use std::sync::Mutex;
+    use std::task::{Context, Poll, Waker};
+
+    const SECRET: &str = "JBSWY3DPEHPK3PXP";
+
+    fn code(unix_time: u64) -> String {
+        format!("{:06}", totp_raw(SECRET, 30, 0, unix_time).unwrap())
+    }
+
+    /// Polls `future` until it is ready; the stores below never wait on anything real.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut context = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return output;
+            }
+        }
+    }
+
+    /// A future that is pending once, like a query waiting for the database.
+    async fn round_trip() {
+        let mut pending = true;
+        future::poll_fn(|context| {
+            if std::mem::take(&mut pending) {
+                context.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            Poll::Ready(())
+        })
+        .await
+    }
+
+    /// Used codes "in a database", with a round trip per query.
+    #[derive(Default)]
+    struct RemoteCodes(Mutex<HashSet<(String, u64)>>);
+
+    impl AsyncUsedCodeStore for RemoteCodes {
+        async fn mark_used(&self, user: &str, counter: u64, _expires_at: u64) -> bool {
+            round_trip().await;
+            self.0.lock().unwrap().insert((user.to_string(), counter))
+        }
+    }
+
+    struct RemoteAccounts;
+
+    impl AsyncSecretResolver for RemoteAccounts {
+        async fn resolve(&self, user: &str) -> Option<Account> {
+            round_trip().await;
+            (user == "alice").then(|| Account::new("alice", SECRET))
+        }
+    }
+
+    fn assert_send<T: Send>(value: T) -> T {
+        value
+    }
+
+    #[test]
+    fn test_async_verifier() {
+        let verifier = AsyncTotpVerifier::new(RemoteAccounts)
+            .clock(|| 1_000_000)
+            .replay_store(RemoteCodes::default())
+            .throttle(Mutex::new(HashMap::new()), Throttle { max_failures: 2, window: 60 })
+            .drift_store(Mutex::new(HashMap::new()));
+
+        let report = block_on(assert_send(verifier.verify_report("alice", &code(1_000_030))));
+        assert_eq!((report.outcome, report.matched_offset), (VerificationOutcome::Accepted, Some(1)));
+        assert_eq!(block_on(verifier.verify_report("alice", &code(1_000_030))).outcome, VerificationOutcome::Replayed);
+        assert!(!block_on(verifier.verify("bob", &code(1_000_000))));
+
+        // the replay above is one failure, and a wrong code the second
+        assert!(!block_on(verifier.verify("alice", "000000")));
+        assert_eq!(block_on(verifier.verify_report("alice", &code(1_000_060))).outcome, VerificationOutcome::Throttled);
+    }
+
+    #[test]
+    fn test_async_verifier_with_sync_stores() {
+        // closures and the in-memory stores work as they are, and nothing is required but the
+        // resolver
+        let verifier = AsyncTotpVerifier::new(|user: &str| (user == "alice").then(|| Account::new("alice", SECRET))).clock(|| 1_000_000);
+        assert!(block_on(verifier.verify("alice", &code(1_000_000))));
+        assert!(block_on(verifier.verify("alice", &code(1_000_000))));
+        assert!(!block_on(verifier.verify("alice", &code(1_000_090))));
+        assert!(!block_on(verifier.verify("carol", &code(1_000_000))));
+    }
+
+    /// HOTP counters "in a database"; `stale` makes the next `advance` lose to another attempt.
+    #[derive(Default)]
+    struct RemoteCounters {
+        counters: Mutex<HashMap<String, u64>>,
+        stale: Mutex<bool>,
+    }
+
+    impl AsyncCounterStore for RemoteCounters {
+        async fn counter(&self, user: &str) -> Option<u64> {
+            round_trip().await;
+            AsyncCounterStore::counter(&self.counters, user).await
+        }
+
+        async fn advance(&self, user: &str, from: u64, to: u64) -> bool {
+            round_trip().await;
+            if std::mem::take(&mut *self.stale.lock().unwrap()) {
+                self.counters.lock().unwrap().insert(user.to_string(), from + 1);
+            }
+            self.counters.advance(user, from, to).await
+        }
+    }
+
+    #[test]
+    fn test_async_hotp() {
+        let hotp = |counter| format!("{:06}", hotp_raw(SECRET, counter).unwrap());
+        let counters = Arc::new(RemoteCounters::default());
+        let verifier = AsyncTotpVerifier::new(|user: &str| {
+            let mut account = Account::new(user, SECRET);
+            account.kind = if user == "alice" { OtpKind::Hotp } else { OtpKind::Totp };
+            account.counter = Some(5);
+            Some(account)
+        })
+        .clock(|| 1_000_000)
+        .window(3)
+        .throttle(Mutex::new(HashMap::new()), Throttle { max_failures: 10, window: 60 })
+        .counter_store(counters.clone());
+
+        // starts from the account's counter and looks ahead `window` counters
+        let report = block_on(assert_send(verifier.verify_hotp_report("alice", &hotp(7))));
+        assert_eq!((report.outcome, report.counter, report.matched_offset), (VerificationOutcome::Accepted, Some(7), Some(2)));
+        assert!(!block_on(verifier.verify_hotp("alice", &hotp(7))));
+        assert!(!block_on(verifier.verify_hotp("alice", &hotp(6))));
+        assert!(!block_on(verifier.verify_hotp("alice", &hotp(12))));
+        assert!(block_on(verifier.verify_hotp("alice", &hotp(8))));
+
+        // a TOTP account is not checked as HOTP, nor an HOTP account as TOTP
+        assert!(!block_on(verifier.verify_hotp("bob", &hotp(5))));
+        assert!(!block_on(verifier.verify("alice", &code(1_000_000))));
+
+        // another attempt moved the counter between the read and the update
+        *counters.stale.lock().unwrap() = true;
+        assert_eq!(block_on(verifier.verify_hotp_report("alice", &hotp(9))).outcome, VerificationOutcome::Replayed);
+        assert_eq!(counters.counters.lock().unwrap().get("alice"), Some(&10));
+    }
+}
@@ -0,0 +1,191 @@
+// Authy exports: the `authenticator_tokens` and `apps` arrays of Authy's
+// sync API, as saved by export tools. Authenticator tokens carry the base32
+// secret in `encrypted_seed`, AES-256-CBC encrypted under
+// PBKDF2-HMAC-SHA1(backup password, salt, key_derivation_iterations or 1000)
+// with `unique_iv` (hex) as IV, or zeros for older tokens. Authy apps (Twitch,
+// Authy's own login, ...) carry a hex `secret_seed` and show 7-digit codes
+// that change every 10 seconds.
+
+use base32::Alphabet;
+
+use super::json::{self, Json};
+use super::{base64, crypto, uri, Account, DatpError};
+
+const DEFAULT_ITERATIONS: u32 = 1_000;
+const APP_DIGITS: u32 = 7;
+const APP_PERIOD: u64 = 10;
+
+/// Reads an Authy export with `authenticator_tokens`, `apps` or both.
+///
+/// # Arguments
+/// * `backup` - The exported JSON.
+/// * `password` - Authy backup password, needed when there are authenticator tokens.
+///
+/// # Returns
+/// `Result<Vec<Account>, DatpError>` - The authenticator tokens followed by the apps.
+/// `DatpError::PasswordRequired` if tokens are encrypted and no password was given, and
+/// `DatpError::WrongPassword` if a seed does not decrypt to a base32 secret.
+///
+/// # Example
+/// ```rust
+/// use datp::import_authy;
+///
+/// let export = r#"{"apps": [{"_id": "5d8c3f", "name": "Twitch", "digits": 7,
+///     "secret_seed": "48656c6c6f21deadbeef"}]}"#;
+/// let accounts = import_authy(export, None).unwrap();
+/// assert_eq!(accounts[0].secret, "JBSWY3DPEHPK3PXP");
+/// assert_eq!((accounts[0].digits, accounts[0].period), (7, 10));
+/// ```
+pub fn import_authy(backup: &str, password: Option<&str>) -> Result<Vec<Account>, DatpError> {
+    let root = json::parse(backup).ok_or(DatpError::InvalidJson)?;
+    let tokens = array(&root, "authenticator_tokens")?;
+    let apps = array(&root, "apps")?;
+    if tokens.is_none() && apps.is_none() {
+        return Err(invalid("missing 'authenticator_tokens' and 'apps' arrays"));
+    }
+
+    let mut accounts = Vec::new();
+    for token in tokens.unwrap_or_default() {
+        accounts.push(parse_token(token, password)?);
+    }
+    for app in apps.unwrap_or_default() {
+        accounts.push(parse_app(app)?);
+    }
+    Ok(accounts)
+}
+
+fn array<'a>(root: &'a Json, key: &str) -> Result<Option<&'a [Json]>, DatpError> {
+    match root.get(key) {
+        None => Ok(None),
+        Some(value) => value.as_array().map(Some).ok_or_else(|| invalid(&format!("'{}' is not an array", key))),
+    }
+}
+
+fn parse_token(token: &Json, password: Option<&str>) -> Result<Account, DatpError> {
+    let encrypted = token.str_field("encrypted_seed").ok_or(DatpError::MissingSecret)?;
+    let password = password.ok_or(DatpError::PasswordRequired)?;
+    let secret = decrypt_seed(token, encrypted, password)?;
+
+    // `original_name` keeps the "Issuer:account" label of the scanned QR code
+    let label = token
+        .str_field("original_name")
+        .filter(|name| !name.is_empty())
+        .or_else(|| token.str_field("name"))
+        .unwrap_or("");
+    let (label_issuer, account_name) = match label.split_once(':') {
+        Some((issuer, account_name)) => (Some(issuer.trim()), account_name.trim()),
+        None => (None, label.trim()),
+    };
+    let mut account = Account::new(account_name, secret);
+    account.issuer = token
+        .str_field("issuer")
+        .or(label_issuer)
+        .filter(|issuer| !issuer.is_empty())
+        .map(str::to_string);
+    if let Some(digits) = token.get("digits").and_then(Json::as_u64) {
+        account.digits = uri::parse_digits(&digits.to_string())?;
+    }
+    Ok(account)
+}
+
+fn decrypt_seed(token: &Json, encrypted: &str, password: &str) -> Result<String, DatpError> {
+    let ciphertext = base64::decode(encrypted).ok_or_else(|| invalid("'encrypted_seed' is not valid base64"))?;
+    let salt = token.str_field("salt").ok_or_else(|| invalid("missing 'salt'"))?;
+    let iterations = match token.get("key_derivation_iterations") {
+        None | Some(Json::Null) => DEFAULT_ITERATIONS,
+        Some(value) => value
+            .as_u64()
+            .and_then(|iterations| u32::try_from(iterations).ok())
+            .filter(|iterations| (1..=crypto::MAX_PBKDF2_ITERATIONS).contains(iterations))
+            .ok_or_else(|| invalid("'key_derivation_iterations' is not a number between 1 and 10000000"))?,
+    };
+    let iv = match token.str_field("unique_iv").filter(|iv| !iv.is_empty()) {
+        Some(iv) => crypto::hex_decode(iv).filter(|iv| iv.len() == 16).ok_or_else(|| invalid("'unique_iv' is not 16 hex bytes"))?,
+        None => vec![0; 16],
+    };
+
+    let key = crypto::pbkdf2_sha1(password.as_bytes(), salt.as_bytes(), iterations, 32);
+    let seed = crypto::aes256_cbc_decrypt(&key, &iv, &ciphertext).ok_or(DatpError::WrongPassword)?;
+    // a wrong key can still end in valid padding, but hardly in a base32 string
+    let seed = String::from_utf8(seed).map_err(|_| DatpError::WrongPassword)?;
+    uri::normalize_secret(&seed).map_err(|_| DatpError::WrongPassword)
+}
+
+fn parse_app(app: &Json) -> Result<Account, DatpError> {
+    let seed = app.str_field("secret_seed").ok_or(DatpError::MissingSecret)?;
+    let seed = crypto::hex_decode(seed).filter(|seed| !seed.is_empty()).ok_or(DatpError::InvalidSecret)?;
+    let name = app.str_field("name").unwrap_or("");
+
+    let mut account = Account::new(name, base32::encode(Alphabet::Rfc4648 { padding: false }, &seed));
+    account.issuer = Some(name.to_string()).filter(|name| !name.is_empty());
+    account.digits = match app.get("digits").and_then(Json::as_u64) {
+        Some(digits) => uri::parse_digits(&digits.to_string())?,
+        None => APP_DIGITS,
+    };
+    account.period = APP_PERIOD;
+    Ok(account)
+}
+
+fn invalid(reason: &str) -> DatpError {
+    DatpError::InvalidBackup(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{totp_raw_with, Algorithm, OtpKind};
+
+    // seeds encrypted with openssl under password "correct horse"
+    const EXPORT: &str = r#"{
+        "authenticator_tokens": [
+            {"account_type": "github", "digits": 6, "encrypted_seed": "qIESqHesL2SH6njFPyYgA+sscJ8oymOposlaa+CXao0=",
+             "issuer": "GitHub", "key_derivation_iterations": 1000, "name": "GitHub: octocat",
+             "original_name": "GitHub:octocat", "salt": "Ax2kVb9PqL0sWmZ1", "unique_id": "1590000001",
+             "unique_iv": "a1b2c3d4e5f60718293a4b5c6d7e8f90"},
+            {"account_type": "unknown", "digits": 8, "encrypted_seed": "qN4oUNmVzn77+yDFx+GMotehdxy02hfBb5OGQ6Uw2ZRdM3zfJy6f6Wpk+oMDGyH3",
+             "issuer": null, "name": "backup server", "original_name": "", "salt": "8fHq2LzX0pNvR4tY",
+             "unique_id": "1590000002", "unique_iv": null}
+        ],
+        "apps": [
+            {"_id": "5d8c3f0a", "name": "Twitch", "digits": 7, "secret_seed": "3132333435363738393031323334353637383930"}
+        ]
+    }"#;
+
+    #[test]
+    fn test_import_authy() {
+        let accounts = import_authy(EXPORT, Some("correct horse")).unwrap();
+        assert_eq!(accounts.len(), 3);
+
+        let github = &accounts[0];
+        assert_eq!((github.issuer.as_deref(), github.account_name.as_str()), (Some("GitHub"), "octocat"));
+        assert_eq!(github.secret, "JBSWY3DPEHPK3PXP");
+
+        let server = &accounts[1];
+        assert_eq!((server.issuer.as_deref(), server.account_name.as_str()), (None, "backup server"));
+        assert_eq!(server.secret, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(server.digits, 8);
+
+        let twitch = &accounts[2];
+        assert_eq!(twitch.kind, OtpKind::Totp);
+        assert_eq!(twitch.issuer.as_deref(), Some("Twitch"));
+        assert_eq!((twitch.digits, twitch.period), (7, 10));
+        // the RFC 6238 secret, whose code at 59s is in step 5 with 10-second steps
+        assert_eq!(twitch.secret, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(totp_raw_with(&twitch.secret, Algorithm::Sha1, 7, 10, 0, 59), Some(8_254_676));
+    }
+
+    #[test]
+    fn test_import_authy_errors() {
+        assert_eq!(import_authy(EXPORT, None), Err(DatpError::PasswordRequired));
+        assert_eq!(import_authy(EXPORT, Some("wrong")), Err(DatpError::WrongPassword));
+        assert!(matches!(import_authy(r#"{"tokens": []}"#, None), Err(DatpError::InvalidBackup(_))));
+        assert_eq!(import_authy(r#"{"apps": [{"name": "x", "secret_seed": "zz"}]}"#, None), Err(DatpError::InvalidSecret));
+        assert_eq!(import_authy(r#"{"apps": []}"#, None), Ok(vec![]));
+
+        // rejected before any key derivation, however many tokens carry it
+        for iterations in ["0", "10000001", "4294967295", "4294967296"] {
+            let export = EXPORT.replace(r#""key_derivation_iterations": 1000"#, &format!(r#""key_derivation_iterations": {}"#, iterations));
+            assert!(matches!(import_authy(&export, Some("correct horse")), Err(DatpError::InvalidBackup(_))), "{}", iterations);
+        }
+    }
+}
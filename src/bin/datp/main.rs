@@ -0,0 +1,798 @@
+//! Command line front end of the library: `datp secret|code|verify|uri|qr|import|export|vault`.
+
+mod vault;
+
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::process::{Command, ExitCode, Stdio};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use datp::{
+    build_otpauth_uri, decode_migration_uri, encode_migration_uris, export_aegis_json, export_andotp,
+    export_bitwarden_json, export_csv, generate_totp_secret, hotp_raw_with, hotp_verify_with, import_2fas,
+    import_aegis_json, import_andotp, import_bitwarden_json, import_csv, import_freeotp_plus, qr_svg_for, seconds_remaining, totp_qr_png,
+    totp_qr_terminal, totp_raw_with, totp_verify_with, Account, Algorithm, DatpError, OtpKind, QrPngConfig,
+    QrStyle, Secret, TerminalQrStyle,
+};
+
+/// Accounts per `otpauth-migration://` URI written by `export --format migration`, as in
+/// Google Authenticator.
+const MIGRATION_ACCOUNTS_PER_URI: usize = 10;
+
+const IMPORT_FORMATS: &[&str] = &["uri", "migration", "aegis", "andotp", "bitwarden", "csv", "freeotp+", "2fas"];
+
+const USAGE: &str = "\
+usage: datp <command> [options]
+
+commands:
+  secret [--bytes N]          print a random base32 secret (default 20 bytes)
+  code [SECRET]               print the current code
+  verify [SECRET] --code CODE exit with 0 and print the step offset (HOTP: the counter) of
+                              a matching code, or exit with 1
+  uri [SECRET] --account NAME print the otpauth:// URI
+  qr [SECRET] --account NAME  print the QR code to the terminal, or save it with --output
+  qr URI                      the same for an otpauth:// URI
+  import [FILE] --format NAME convert a backup into otpauth:// URIs, one per line
+  export [FILE] --format NAME convert otpauth:// URIs, one per line, into a backup
+  vault init                  create an empty password-encrypted vault
+  vault list                  print the accounts in the vault
+  vault add [SECRET|URI]      add an account (SECRET needs --account)
+  vault remove ACCOUNT        remove an account
+  vault rename ACCOUNT --to NEW
+                              rename an account; NEW may be ISSUER:NAME
+
+ACCOUNT is the account name, or ISSUER:NAME when several accounts share a name. The
+vault is an encrypted Aegis backup at --vault, $DATP_VAULT or
+$XDG_DATA_HOME/datp/vault.json; its password is asked for on the terminal unless
+--password-env or --password-file is given.
+
+FILE defaults to stdin. Formats: uri, migration (otpauth-migration:// URIs), aegis,
+andotp, bitwarden, csv, and for import only freeotp+ and 2fas. Encrypted andotp
+backups are binary, so export them with --output.
+
+The secret is read from the SECRET argument (or --secret), --secret-file,
+--secret-env or --keyring, or from the first line of stdin when none is given or
+SECRET is -. Prefer the last three to keep secrets out of the shell history and ps.
+
+options:
+  --secret SECRET             same as the SECRET argument
+  --secret-file PATH          read the secret from the first line of PATH
+  --secret-env NAME           read the secret from the environment variable NAME
+  --keyring SERVICE/ACCOUNT   read the secret from the OS keyring (secret-tool on Linux,
+                              the login keychain on macOS)
+  --algorithm NAME            SHA1 (default), SHA256 or SHA512
+  --digits N                  digits per code, 1 to 9 (default 6)
+  --period SECONDS            time step (default 30)
+  --counter N                 HOTP counter; makes the account counter-based
+  --time UNIX                 code: generate the code for this time instead of now
+  --watch                     code: keep printing the current code with a countdown
+  --copy                      code: put the code on the clipboard instead of printing it
+  --clear-after SECONDS       code: with --copy, clear the clipboard again after SECONDS
+  --code CODE                 verify: the code to check
+  --window N                  verify: steps accepted before and after the current one
+                              (default 1), or with --counter the codes to look ahead
+  --account NAME              uri, qr: account name shown in the app
+  --issuer NAME               uri, qr: issuer shown in the app
+  --output PATH, --out PATH   qr, import, export: write to PATH instead of printing; qr
+                              writes a PNG for paths ending in .png and an SVG otherwise
+  --format NAME               import, export: format of the backup
+  --password-env NAME         import, export, vault: read the password of an encrypted
+                              backup or of the vault from the environment variable NAME;
+                              without it or --password-file, import and vault ask for
+                              the password on the terminal
+  --password-file PATH        import, export, vault: read the password from the first
+                              line of PATH
+  --vault PATH                vault: the vault file
+  --to NEW                    vault rename: the new name
+  --invert                    qr: invert the colours for dark terminals
+";
+
+/// Parsed command line; numeric options already hold their defaults.
+#[derive(Debug, PartialEq)]
+struct Options {
+    command: String,
+    action: Option<String>,         // init, list, add, remove or rename for `vault`
+    argument: Option<String>,       // SECRET, or FILE for import and export; "-" for stdin
+    secret_file: Option<String>,
+    secret_env: Option<String>,
+    keyring: Option<String>,        // "service/account"
+    algorithm: Algorithm,
+    digits: u32,
+    period: u64,
+    counter: Option<u64>,
+    time: Option<u64>,
+    code: Option<String>,
+    window: u64,
+    account: Option<String>,
+    issuer: Option<String>,
+    bytes: usize,                   // length of `secret` output
+    output: Option<String>,
+    format: Option<String>,
+    password_env: Option<String>,
+    password_file: Option<String>,
+    invert: bool,
+    watch: bool,
+    copy: bool,
+    clear_after: Option<u64>,
+    vault: Option<String>,
+    rename_to: Option<String>,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+        let command = args.next().ok_or("missing command")?;
+        if !["secret", "code", "verify", "uri", "qr", "import", "export", "vault"].contains(&command.as_str()) {
+            return Err(format!("unknown command '{}'", command));
+        }
+        let action = match command.as_str() {
+            "vault" => {
+                let action = args.next().ok_or("missing vault action")?;
+                if !["init", "list", "add", "remove", "rename"].contains(&action.as_str()) {
+                    return Err(format!("unknown vault action '{}'", action));
+                }
+                Some(action)
+            }
+            _ => None,
+        };
+        let mut options = Options {
+            command,
+            action,
+            argument: None,
+            secret_file: None,
+            secret_env: None,
+            keyring: None,
+            algorithm: Algorithm::Sha1,
+            digits: 6,
+            period: 30,
+            counter: None,
+            time: None,
+            code: None,
+            window: 1,
+            account: None,
+            issuer: None,
+            bytes: 20,
+            output: None,
+            format: None,
+            password_env: None,
+            password_file: None,
+            invert: false,
+            watch: false,
+            copy: false,
+            clear_after: None,
+            vault: None,
+            rename_to: None,
+        };
+
+        while let Some(arg) = args.next() {
+            let Some(flag) = arg.strip_prefix("--") else {
+                options.set_argument(arg)?;
+                continue;
+            };
+            let (name, inline) = match flag.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (flag, None),
+            };
+            let switch = match name {
+                "invert" => Some(&mut options.invert),
+                "watch" => Some(&mut options.watch),
+                "copy" => Some(&mut options.copy),
+                _ => None,
+            };
+            if let Some(switch) = switch {
+                *switch = true;
+                continue;
+            }
+            let value = inline.or_else(|| args.next()).ok_or_else(|| format!("--{} needs a value", name))?;
+            match name {
+                "secret" => options.set_argument(value)?,
+                "secret-file" => options.secret_file = Some(value),
+                "secret-env" => options.secret_env = Some(value),
+                "keyring" => options.keyring = Some(value),
+                "algorithm" => options.algorithm = value.parse::<Algorithm>().map_err(|e| e.to_string())?,
+                "digits" => options.digits = parse_number(name, &value, 1..=9)?,
+                "period" => options.period = parse_number(name, &value, 1..=u64::MAX)?,
+                "counter" => options.counter = Some(parse_number(name, &value, 0..=u64::MAX)?),
+                "time" => options.time = Some(parse_number(name, &value, 0..=u64::MAX)?),
+                "code" => options.code = Some(value),
+                "window" => options.window = parse_number(name, &value, 0..=1000)?,
+                "clear-after" => options.clear_after = Some(parse_number(name, &value, 1..=3600)?),
+                "account" => options.account = Some(value),
+                "issuer" => options.issuer = Some(value),
+                "bytes" => options.bytes = parse_number(name, &value, 1..=1024)?,
+                "output" | "out" => options.output = Some(value),
+                "format" => options.format = Some(value.to_ascii_lowercase()),
+                "password" => return Err("--password would show the password in ps; use --password-env or --password-file".to_string()),
+                "password-env" => options.password_env = Some(value),
+                "password-file" => options.password_file = Some(value),
+                "vault" => options.vault = Some(value),
+                "to" => options.rename_to = Some(value),
+                _ => return Err(format!("unknown option --{}", name)),
+            }
+        }
+        if options.clear_after.is_some() && !options.copy {
+            return Err("--clear-after needs --copy".to_string());
+        }
+        if options.copy && options.watch {
+            return Err("--copy does not work with --watch".to_string());
+        }
+        Ok(options)
+    }
+
+    fn set_argument(&mut self, argument: String) -> Result<(), String> {
+        match self.argument.replace(argument) {
+            Some(_) => Err("more than one SECRET or FILE argument".to_string()),
+            None => Ok(()),
+        }
+    }
+
+    /// Reads and normalizes the secret from the argument, the secret file, the environment
+    /// (through `env`, which is `env_var` outside of tests), the keyring or stdin.
+    fn read_secret(&self, env: impl Fn(&str) -> Option<String>) -> Result<String, String> {
+        let sources = [&self.argument, &self.secret_file, &self.secret_env, &self.keyring];
+        if sources.iter().filter(|source| source.is_some()).count() > 1 {
+            return Err("give only one of SECRET, --secret-file, --secret-env and --keyring".to_string());
+        }
+        let raw = if let Some(path) = &self.secret_file {
+            read_file(path)?
+        } else if let Some(name) = &self.secret_env {
+            env(name).ok_or_else(|| format!("the environment variable {} is not set", name))?
+        } else if let Some(entry) = &self.keyring {
+            keyring_lookup(entry)?
+        } else {
+            match self.argument.as_deref() {
+                Some(secret) if secret != "-" => secret.to_string(),
+                _ => read_file("-")?,
+            }
+        };
+        match raw.lines().next().unwrap_or("").parse::<Secret>() {
+            Ok(secret) => Ok(secret.to_string()),
+            Err(DatpError::MissingSecret) => Err("missing secret".to_string()),
+            Err(_) => Err("the secret is not valid base32".to_string()),
+        }
+    }
+
+    fn account(&self, secret: String) -> Result<Account, String> {
+        let account_name = self.account.clone().ok_or("--account is required")?;
+        Ok(Account {
+            kind: if self.counter.is_some() { OtpKind::Hotp } else { OtpKind::Totp },
+            issuer: self.issuer.clone(),
+            algorithm: self.algorithm,
+            digits: self.digits,
+            period: self.period,
+            counter: self.counter,
+            ..Account::new(account_name, secret)
+        })
+    }
+
+    /// The account of `qr`: an otpauth URI argument or the secret plus `--account`.
+    fn qr_account(&self) -> Result<Account, String> {
+        match self.argument.as_deref() {
+            Some(uri) if uri.get(..10).is_some_and(|scheme| scheme.eq_ignore_ascii_case("otpauth://")) => {
+                Account::from_uri(uri).map_err(|e| e.to_string())
+            }
+            _ => self.account(self.read_secret(env_var)?),
+        }
+    }
+
+    /// Reads the FILE argument, or stdin without one.
+    fn read_input(&self) -> Result<String, String> {
+        read_file(self.argument.as_deref().unwrap_or("-"))
+    }
+
+    /// Reads the FILE argument like `read_input`, for binary backups.
+    fn read_input_bytes(&self) -> Result<Vec<u8>, String> {
+        read_file_bytes(self.argument.as_deref().unwrap_or("-"))
+    }
+
+    /// The password from `--password-env` (looked up through `env`) or `--password-file`, or
+    /// `None` if neither is given.
+    fn read_password(&self, env: impl Fn(&str) -> Option<String>) -> Result<Option<String>, String> {
+        match (self.password_env.as_deref(), self.password_file.as_deref()) {
+            (Some(_), Some(_)) => Err("give either --password-env or --password-file".to_string()),
+            (Some(name), None) => env(name).map(Some).ok_or_else(|| format!("the environment variable {} is not set", name)),
+            (None, Some(path)) => Ok(read_file(path)?.lines().next().map(str::to_string)),
+            (None, None) => Ok(None),
+        }
+    }
+
+    fn import(&self) -> Result<Vec<Account>, String> {
+        let format = self.format.as_deref().ok_or("--format is required")?;
+        if !IMPORT_FORMATS.contains(&format) {
+            return Err(format!("unknown import format '{}'", format));
+        }
+        let input = self.read_input_bytes()?;
+        let password = self.read_password(env_var)?;
+        let accounts = match self.import_with(format, &input, password.as_deref()) {
+            // an encrypted backup without --password-env or --password-file
+            Err(DatpError::PasswordRequired) if password.is_none() => {
+                let password = vault::prompt_password("backup password: ")?;
+                self.import_with(format, &input, Some(&password))
+            }
+            accounts => accounts,
+        };
+        accounts.map_err(|e| e.to_string())
+    }
+
+    /// Reads `input` in one of the `IMPORT_FORMATS`.
+    fn import_with(&self, format: &str, input: &[u8], password: Option<&str>) -> Result<Vec<Account>, DatpError> {
+        if format == "andotp" {
+            return import_andotp(input, password);
+        }
+        let input = std::str::from_utf8(input).map_err(|_| DatpError::InvalidBackup("the file is not UTF-8 text".to_string()))?;
+        let lines = || input.lines().map(str::trim).filter(|line| !line.is_empty());
+        match format {
+            "uri" => lines().map(Account::from_uri).collect(),
+            "migration" => lines().map(decode_migration_uri).collect::<Result<Vec<_>, _>>().map(|batches| batches.concat()),
+            "aegis" => import_aegis_json(input, password),
+            "bitwarden" => import_bitwarden_json(input),
+            "csv" => import_csv(input),
+            "freeotp+" => import_freeotp_plus(input),
+            "2fas" => import_2fas(input, password).map(|services| services.into_iter().map(|service| service.account).collect()),
+            _ => unreachable!("import checks the format"),
+        }
+    }
+
+    /// Writes `accounts` as a backup; only encrypted andOTP backups are not text.
+    fn export(&self, accounts: &[Account]) -> Result<Vec<u8>, String> {
+        let format = self.format.as_deref().ok_or("--format is required")?;
+        let password = self.read_password(env_var)?;
+        let exported = match format {
+            "uri" => Ok(accounts.iter().map(build_otpauth_uri).collect::<Vec<_>>().join("\n")),
+            "migration" => encode_migration_uris(accounts, MIGRATION_ACCOUNTS_PER_URI).map(|uris| uris.join("\n")),
+            "aegis" => export_aegis_json(accounts, password.as_deref()),
+            "andotp" => return export_andotp(accounts, password.as_deref()).map_err(|e| e.to_string()),
+            "bitwarden" => Ok(export_bitwarden_json(accounts)),
+            "csv" => Ok(export_csv(accounts)),
+            "freeotp+" | "2fas" => return Err(format!("cannot export to {}, only import from it", format)),
+            _ => return Err(format!("unknown export format '{}'", format)),
+        };
+        exported.map(String::into_bytes).map_err(|e| e.to_string())
+    }
+}
+
+/// Looks up an environment variable for `--secret-env` and `--password-env`.
+fn env_var(name: &str) -> Option<String> {
+    env::var(name).ok()
+}
+
+/// Reads a whole file, or stdin for "-".
+fn read_file(path: &str) -> Result<String, String> {
+    if path == "-" {
+        let mut input = String::new();
+        io::stdin().read_to_string(&mut input).map_err(|e| format!("cannot read stdin: {}", e))?;
+        return Ok(input);
+    }
+    fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))
+}
+
+/// Reads a whole file as bytes, or stdin for "-".
+fn read_file_bytes(path: &str) -> Result<Vec<u8>, String> {
+    if path == "-" {
+        let mut input = Vec::new();
+        io::stdin().read_to_end(&mut input).map_err(|e| format!("cannot read stdin: {}", e))?;
+        return Ok(input);
+    }
+    fs::read(path).map_err(|e| format!("cannot read {}: {}", path, e))
+}
+
+/// Looks up the secret stored in the OS keyring under `service/account`, through the
+/// `secret-tool` command of libsecret or, on macOS, `security`.
+fn keyring_lookup(entry: &str) -> Result<String, String> {
+    let (service, account) = entry.split_once('/').ok_or("--keyring takes SERVICE/ACCOUNT")?;
+    let (program, args): (&str, &[&str]) = if cfg!(target_os = "macos") {
+        ("security", &["find-generic-password", "-s", service, "-a", account, "-w"])
+    } else if cfg!(windows) {
+        return Err("--keyring is not supported on Windows".to_string());
+    } else {
+        ("secret-tool", &["lookup", "service", service, "account", account])
+    };
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|e| format!("cannot run {}: {}", program, e))?;
+    if !output.status.success() || output.stdout.is_empty() {
+        return Err(format!("no keyring entry for {}", entry));
+    }
+    String::from_utf8(output.stdout).map_err(|_| "the keyring entry is not UTF-8".to_string())
+}
+
+/// Hidden command run by `clear_clipboard_later` in the background; the code comes on stdin so
+/// it does not show up in `ps`.
+const CLEAR_CLIPBOARD: &str = "__clear-clipboard";
+
+/// The commands that copy stdin to the clipboard and print the clipboard on this platform.
+fn clipboard_commands() -> (&'static [&'static str], &'static [&'static str]) {
+    if cfg!(target_os = "macos") {
+        (&["pbcopy"], &["pbpaste"])
+    } else if cfg!(windows) {
+        (&["clip"], &["powershell", "-NoProfile", "-Command", "Get-Clipboard"])
+    } else if env::var_os("WAYLAND_DISPLAY").is_some() {
+        (&["wl-copy"], &["wl-paste", "--no-newline"])
+    } else {
+        (&["xclip", "-selection", "clipboard"], &["xclip", "-selection", "clipboard", "-o"])
+    }
+}
+
+fn copy_to_clipboard(text: &str) -> Result<(), String> {
+    let (copy, _) = clipboard_commands();
+    let mut child = Command::new(copy[0])
+        .args(&copy[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("cannot run {}: {}", copy[0], e))?;
+    let written = child.stdin.take().expect("stdin is piped").write_all(text.as_bytes());
+    let status = child.wait().map_err(|e| format!("cannot run {}: {}", copy[0], e))?;
+    if written.is_err() || !status.success() {
+        return Err(format!("{} could not set the clipboard", copy[0]));
+    }
+    Ok(())
+}
+
+/// Starts a background copy of this program that clears the clipboard after `seconds`, unless
+/// something else was copied in the meantime.
+fn clear_clipboard_later(code: &str, seconds: u64) -> Result<(), String> {
+    let exe = env::current_exe().map_err(|e| format!("cannot find the datp executable: {}", e))?;
+    let mut child = Command::new(exe)
+        .args([CLEAR_CLIPBOARD, &seconds.to_string()])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("cannot start the clipboard timer: {}", e))?;
+    child.stdin.take().expect("stdin is piped").write_all(code.as_bytes()).map_err(|e| e.to_string())
+}
+
+/// Body of the hidden `__clear-clipboard SECONDS` command.
+fn clear_clipboard(seconds: Option<&String>) {
+    let mut code = String::new();
+    if io::stdin().read_to_string(&mut code).is_err() {
+        return;
+    }
+    thread::sleep(Duration::from_secs(seconds.and_then(|seconds| seconds.parse().ok()).unwrap_or(0)));
+    let (_, paste) = clipboard_commands();
+    let current = Command::new(paste[0]).args(&paste[1..]).stderr(Stdio::null()).output();
+    // without a way to read the clipboard, clear it anyway
+    let unchanged = current.map_or(true, |output| String::from_utf8_lossy(&output.stdout).trim_end() == code);
+    if unchanged {
+        let _ = copy_to_clipboard("");
+    }
+}
+
+/// Prints `output`, or writes it to `--output` and reports that instead. Binary output needs
+/// `--output`.
+fn write_output(options: &Options, output: impl Into<Vec<u8>>) -> Result<String, String> {
+    match options.output.as_deref() {
+        Some(path) => write_file(path, output.into()),
+        None => String::from_utf8(output.into()).map_err(|_| "the output is binary; write it with --output".to_string()),
+    }
+}
+
+fn write_file(path: &str, contents: impl AsRef<[u8]>) -> Result<String, String> {
+    fs::write(path, contents).map_err(|e| format!("cannot write {}: {}", path, e))?;
+    Ok(format!("wrote {}", path))
+}
+
+fn parse_number<T>(name: &str, value: &str, range: std::ops::RangeInclusive<T>) -> Result<T, String>
+where
+    T: std::str::FromStr + PartialOrd + std::fmt::Display,
+{
+    match value.parse::<T>() {
+        Ok(number) if range.contains(&number) => Ok(number),
+        _ => Err(format!("--{} must be a number from {} to {}", name, range.start(), range.end())),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).expect("system time is before 1970").as_secs()
+}
+
+/// Runs a command and returns what it prints to stdout.
+fn run(options: &Options) -> Result<String, String> {
+    match options.command.as_str() {
+        "secret" => Ok(generate_totp_secret(options.bytes)),
+        "code" if options.watch => {
+            if options.counter.is_some() || options.time.is_some() {
+                return Err("--watch works with the current time only, not --counter or --time".to_string());
+            }
+            watch(options, &options.read_secret(env_var)?)?;
+            Ok(String::new())
+        }
+        "code" => {
+            let secret = options.read_secret(env_var)?;
+            let code = match options.counter {
+                Some(counter) => hotp_raw_with(&secret, options.algorithm, options.digits, counter),
+                None => {
+                    let time = options.time.unwrap_or_else(now);
+                    totp_raw_with(&secret, options.algorithm, options.digits, options.period, 0, time)
+                }
+            };
+            let code = code.ok_or("cannot generate a code")?;
+            let code = format!("{:0width$}", code, width = options.digits as usize);
+            if !options.copy {
+                return Ok(code);
+            }
+            copy_to_clipboard(&code)?;
+            match options.clear_after {
+                Some(seconds) => {
+                    clear_clipboard_later(&code, seconds)?;
+                    Ok(format!("copied the code to the clipboard, clearing it in {} seconds", seconds))
+                }
+                None => Ok("copied the code to the clipboard".to_string()),
+            }
+        }
+        "verify" => {
+            let code = options.code.as_deref().ok_or("--code is required")?;
+            let secret = options.read_secret(env_var)?;
+            let (algorithm, digits) = (options.algorithm, options.digits);
+            let matched = match options.counter {
+                Some(counter) => hotp_verify_with(&secret, code, algorithm, digits, counter, options.window)
+                    .map(|counter| counter.to_string()),
+                None => {
+                    let time = options.time.unwrap_or_else(now);
+                    totp_verify_with(&secret, code, algorithm, digits, options.period, 0, time, options.window)
+                        .map(|offset| offset.to_string())
+                }
+            };
+            matched.ok_or_else(|| "the code does not match".to_string())
+        }
+        "uri" => {
+            let account = options.account(options.read_secret(env_var)?)?;
+            Ok(build_otpauth_uri(&account))
+        }
+        "qr" => {
+            let account = options.qr_account()?;
+            match options.output.as_deref() {
+                Some(path) if path.to_ascii_lowercase().ends_with(".png") => {
+                    write_file(path, totp_qr_png(&account, &QrPngConfig::default()).map_err(|e| e.to_string())?)
+                }
+                Some(path) => write_file(path, qr_svg_for(&account, &QrStyle::default()).map_err(|e| e.to_string())?),
+                None => totp_qr_terminal(&account, TerminalQrStyle::HalfBlock, options.invert)
+                    .map(|qr| qr.trim_end_matches('\n').to_string())
+                    .map_err(|e| e.to_string()),
+            }
+        }
+        "import" => {
+            let uris: Vec<String> = options.import()?.iter().map(build_otpauth_uri).collect();
+            write_output(options, uris.join("\n"))
+        }
+        "export" => {
+            let input = options.read_input()?;
+            let accounts = input
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(Account::from_uri)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| e.to_string())?;
+            write_output(options, options.export(&accounts)?)
+        }
+        "vault" => vault::run(options),
+        _ => unreachable!("commands are checked by Options::parse"),
+    }
+}
+
+/// Prints the current code until interrupted. On a terminal the line is redrawn every second
+/// with a countdown; otherwise each new code goes on its own line.
+///
+/// Every wait is computed from the clock again, so the output stays on the step boundaries
+/// instead of drifting like a fixed interval would.
+fn watch(options: &Options, secret: &str) -> Result<(), String> {
+    let terminal = io::stdout().is_terminal();
+    let mut stdout = io::stdout().lock();
+    let mut shown = None;
+    loop {
+        let time = now();
+        let step = time / options.period;
+        let left = seconds_remaining(options.period, 0, time).expect("the period is not 0");
+        let code = totp_raw_with(secret, options.algorithm, options.digits, options.period, 0, time)
+            .ok_or("cannot generate a code")?;
+        let code = format!("{:0width$}", code, width = options.digits as usize);
+
+        let written = if terminal {
+            let newline = if shown.is_some_and(|shown| shown != step) { "\n" } else { "" };
+            write!(stdout, "{}{}", newline, watch_line(&code, left, options.period)).and_then(|_| stdout.flush())
+        } else if shown != Some(step) {
+            writeln!(stdout, "{}", code).and_then(|_| stdout.flush())
+        } else {
+            Ok(())
+        };
+        if written.is_err() {
+            // the reader went away, e.g. `datp code --watch | head -3`
+            return Ok(());
+        }
+        shown = Some(step);
+
+        let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).expect("system time is before 1970");
+        let next = if terminal { elapsed.as_secs() + 1 } else { (elapsed.as_secs() / options.period + 1) * options.period };
+        thread::sleep(Duration::from_secs(next).saturating_sub(elapsed));
+    }
+}
+
+/// One status line of `code --watch`: the code, a bar of the time left and the seconds left.
+fn watch_line(code: &str, left: u64, period: u64) -> String {
+    const WIDTH: usize = 30;
+    let filled = (left as usize * WIDTH).div_ceil(period as usize).min(WIDTH);
+    format!("\r{}  [{}{}] {:>2}s ", code, "#".repeat(filled), "-".repeat(WIDTH - filled), left)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().is_some_and(|command| command == CLEAR_CLIPBOARD) {
+        clear_clipboard(args.get(1));
+        return ExitCode::SUCCESS;
+    }
+    if args.is_empty() || args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        print!("{}", USAGE);
+        return if args.is_empty() { ExitCode::from(2) } else { ExitCode::SUCCESS };
+    }
+    let options = match Options::parse(args.into_iter()) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("datp: {}\n\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match run(&options) {
+        Ok(output) if output.is_empty() => ExitCode::SUCCESS,
+        Ok(output) => {
+            // a closed pipe, as in `datp qr | head`, is not an error
+            let _ = writeln!(io::stdout(), "{}", output);
+            ExitCode::SUCCESS
+        }
+        Err(message) => {
+            eprintln!("datp: {}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Options, String> {
+        Options::parse(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn test_parse() {
+        let options = parse("code GEZDGNBV --digits=8 --algorithm sha-256 --time 59").unwrap();
+        assert_eq!(options.argument.as_deref(), Some("GEZDGNBV"));
+        assert_eq!((options.digits, options.algorithm, options.time), (8, Algorithm::Sha256, Some(59)));
+        assert_eq!(options.period, 30);
+
+        assert!(parse("").is_err());
+        assert!(parse("codes").is_err());
+        assert!(parse("code A B").is_err());
+        assert!(parse("code --digits 10").is_err());
+        assert!(parse("code --period 0").is_err());
+        assert!(parse("code --period").is_err());
+        assert!(parse("code --colour red").is_err());
+        assert!(parse("vault").is_err());
+        assert!(parse("vault open").is_err());
+        assert!(parse("code --clear-after 30").is_err());
+        assert!(parse("code --copy --watch").is_err());
+        assert_eq!(parse("code --copy --clear-after 45").unwrap().clear_after, Some(45));
+    }
+
+    #[test]
+    fn test_run() {
+        let code = parse("code gezd-gnbv-gy3t-qojq-gezd-gnbv-gy3t-qojq --digits 8 --time 59").unwrap();
+        assert_eq!(run(&code).unwrap(), "94287082");
+        let hotp = parse("code GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ --counter 0").unwrap();
+        assert_eq!(run(&hotp).unwrap(), "755224");
+
+        let uri = parse("uri JBSWY3DPEHPK3PXP --account alice --issuer ACME").unwrap();
+        assert_eq!(run(&uri).unwrap(), build_otpauth_uri(&uri.account("JBSWY3DPEHPK3PXP".to_string()).unwrap()));
+        assert!(run(&parse("uri JBSWY3DPEHPK3PXP").unwrap()).is_err());
+        assert!(run(&parse("code JBSWY3DPEHPK3PX1").unwrap()).is_err());
+
+        assert_eq!(run(&parse("secret --bytes 10").unwrap()).unwrap().len(), 16);
+        assert!(run(&parse("code JBSWY3DPEHPK3PXP --watch --time 59").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_import_export() {
+        let dir = env::temp_dir().join(format!("datp-cli-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+        let uris = "otpauth://totp/ACME:alice?secret=JBSWY3DPEHPK3PXP&issuer=ACME&algorithm=SHA1&digits=6&period=30\n\
+                    otpauth://hotp/bob?secret=GEZDGNBVGY3TQOJQ&algorithm=SHA256&digits=8&counter=7";
+        fs::write(path("uris.txt"), uris).unwrap();
+
+        for format in ["uri", "migration", "aegis", "andotp", "bitwarden", "csv"] {
+            let export = parse(&format!("export {} --format {} --output {}", path("uris.txt"), format, path(format))).unwrap();
+            run(&export).unwrap();
+            let imported = run(&parse(&format!("import {} --format {}", path(format), format)).unwrap()).unwrap();
+            let accounts: Vec<Account> = imported.lines().map(|uri| Account::from_uri(uri).unwrap()).collect();
+            assert_eq!(accounts.len(), 2, "{}", format);
+            assert_eq!((accounts[1].counter, accounts[1].digits), (Some(7), 8), "{}", format);
+        }
+        assert!(run(&parse(&format!("export {} --format 2fas", path("uris.txt"))).unwrap()).is_err());
+
+        // an encrypted andOTP backup, with the password from a file
+        fs::write(path("password"), "correct horse\n").unwrap();
+        let password = format!("--password-file {}", path("password"));
+        run(&parse(&format!("export {} --format andotp {} --output {}", path("uris.txt"), password, path("andotp.aes"))).unwrap()).unwrap();
+        assert_ne!(fs::read(path("andotp.aes")).unwrap().first(), Some(&b'['));
+        let imported = run(&parse(&format!("import {} --format andotp {}", path("andotp.aes"), password)).unwrap()).unwrap();
+        assert_eq!(imported, run(&parse(&format!("import {} --format uri", path("uris.txt"))).unwrap()).unwrap());
+        assert!(run(&parse(&format!("export {} --format andotp {}", path("uris.txt"), password)).unwrap()).is_err());
+        // andotp is binary, but the text formats still are not
+        assert!(run(&parse(&format!("import {} --format csv", path("andotp.aes"))).unwrap()).is_err());
+        assert!(run(&parse(&format!("import {}", path("uris.txt"))).unwrap()).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_qr() {
+        let dir = env::temp_dir().join(format!("datp-cli-qr-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+        let uri = "otpauth://totp/ACME:alice?secret=JBSWY3DPEHPK3PXP&issuer=ACME";
+
+        run(&parse(&format!("qr {} --out {}", uri, path("code.png"))).unwrap()).unwrap();
+        assert!(fs::read(path("code.png")).unwrap().starts_with(b"\x89PNG"));
+        run(&parse(&format!("qr JBSWY3DPEHPK3PXP --account alice --output {}", path("code.svg"))).unwrap()).unwrap();
+        assert!(fs::read_to_string(path("code.svg")).unwrap().contains("<svg"));
+
+        let terminal = run(&parse(&format!("qr {}", uri)).unwrap()).unwrap();
+        assert_eq!(terminal, run(&parse("qr JBSWY3DPEHPK3PXP --account alice --issuer ACME").unwrap()).unwrap());
+        assert!(run(&parse("qr otpauth://totp/alice").unwrap()).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn fake_env(name: &str) -> Option<String> {
+        match name {
+            "DATP_SECRET" => Some("jbsw y3dp ehpk 3pxp".to_string()),
+            "DATP_PASSWORD" => Some("hunter2".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_secret_sources() {
+        let options = parse("code --secret-env DATP_SECRET").unwrap();
+        assert_eq!(options.read_secret(fake_env).unwrap(), "JBSWY3DPEHPK3PXP");
+        assert!(parse("code --secret-env DATP_UNSET").unwrap().read_secret(fake_env).is_err());
+        assert!(parse("code JBSWY3DPEHPK3PXP --secret-env DATP_SECRET").unwrap().read_secret(fake_env).is_err());
+        assert!(parse("code --keyring datp").unwrap().read_secret(fake_env).is_err());
+    }
+
+    #[test]
+    fn test_password_sources() {
+        let options = parse("import --password-env DATP_PASSWORD").unwrap();
+        assert_eq!(options.read_password(fake_env).unwrap().as_deref(), Some("hunter2"));
+        assert_eq!(parse("import").unwrap().read_password(fake_env).unwrap(), None);
+        assert!(parse("import --password-env DATP_UNSET").unwrap().read_password(fake_env).is_err());
+        assert!(parse("import --password-env DATP_PASSWORD --password-file pw.txt").unwrap().read_password(fake_env).is_err());
+        // a password in argv shows up in ps
+        assert!(parse("import --password hunter2").is_err());
+    }
+
+    #[test]
+    fn test_verify() {
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        let verify = |args: &str| run(&parse(&format!("verify --secret {} {}", secret, args)).unwrap());
+        assert_eq!(verify("--code 94287082 --digits 8 --time 59").unwrap(), "0");
+        assert_eq!(verify("--code 94287082 --digits 8 --time 89").unwrap(), "-1");
+        assert!(verify("--code 94287082 --digits 8 --time 89 --window 0").is_err());
+        assert!(verify("--code 94287083 --digits 8 --time 59").is_err());
+        assert_eq!(verify("--code 359152 --counter 0 --window 5").unwrap(), "2");
+        assert!(verify("--time 59").is_err());
+        assert!(parse(&format!("verify {} --secret {}", secret, secret)).is_err());
+    }
+
+    #[test]
+    fn test_watch_line() {
+        assert_eq!(watch_line("123456", 30, 30), format!("\r123456  [{}] 30s ", "#".repeat(30)));
+        assert_eq!(watch_line("123456", 15, 30), format!("\r123456  [{}{}] 15s ", "#".repeat(15), "-".repeat(15)));
+        assert_eq!(watch_line("123456", 1, 60), format!("\r123456  [#{}]  1s ", "-".repeat(29)));
+    }
+}
@@ -0,0 +1,226 @@
+//! `datp vault`: accounts kept in a password-encrypted Aegis backup, so `datp` has one file to
+//! manage instead of a secret per invocation. The file can be imported into Aegis as is.
+
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use datp::{export_aegis_json, import_aegis_json, Account, DatpError};
+
+use super::Options;
+
+/// An opened vault; `save` writes the accounts back encrypted with the same password.
+struct Vault {
+    path: PathBuf,
+    password: String,
+    accounts: Vec<Account>,
+}
+
+impl Vault {
+    fn open(path: PathBuf, password: String) -> Result<Vault, String> {
+        let backup = fs::read_to_string(&path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        let accounts = import_aegis_json(&backup, Some(&password)).map_err(|e| match e {
+            DatpError::WrongPassword => "wrong vault password".to_string(),
+            e => format!("cannot open {}: {}", path.display(), e),
+        })?;
+        Ok(Vault { path, password, accounts })
+    }
+
+    /// Writes the vault through a temporary file, so an interrupted write can't truncate it.
+    fn save(&self) -> Result<(), String> {
+        let backup = export_aegis_json(&self.accounts, Some(&self.password)).map_err(|e| e.to_string())?;
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|e| format!("cannot create {}: {}", dir.display(), e))?;
+        }
+        let temp = self.path.with_extension("tmp");
+        write_private(&temp, backup.as_bytes()).map_err(|e| format!("cannot write {}: {}", temp.display(), e))?;
+        fs::rename(&temp, &self.path).map_err(|e| format!("cannot write {}: {}", self.path.display(), e))
+    }
+
+    /// Index of the account called `name`, either its account name or `issuer:account`.
+    fn find(&self, name: &str) -> Result<usize, String> {
+        let matches: Vec<usize> = (0..self.accounts.len()).filter(|&i| label(&self.accounts[i]) == name).collect();
+        let matches = if matches.is_empty() {
+            (0..self.accounts.len()).filter(|&i| self.accounts[i].account_name == name).collect()
+        } else {
+            matches
+        };
+        match matches[..] {
+            [index] => Ok(index),
+            [] => Err(format!("no account {} in the vault", name)),
+            _ => Err(format!("{} matches several accounts; use ISSUER:ACCOUNT", name)),
+        }
+    }
+}
+
+/// `issuer:account`, or the account name alone without an issuer.
+fn label(account: &Account) -> String {
+    match account.issuer.as_deref().filter(|issuer| !issuer.is_empty()) {
+        Some(issuer) => format!("{}:{}", issuer, account.account_name),
+        None => account.account_name.clone(),
+    }
+}
+
+/// Runs `datp vault <action>`.
+pub(crate) fn run(options: &Options) -> Result<String, String> {
+    let path = vault_path(options)?;
+    let action = options.action.as_deref().unwrap_or_default();
+    if action == "init" {
+        if path.exists() {
+            return Err(format!("{} already exists", path.display()));
+        }
+        let password = match options.read_password(super::env_var)? {
+            Some(password) => password,
+            None => {
+                let password = prompt_password("new vault password: ")?;
+                if prompt_password("repeat the password: ")? != password {
+                    return Err("the passwords do not match".to_string());
+                }
+                password
+            }
+        };
+        if password.is_empty() {
+            return Err("the vault password must not be empty".to_string());
+        }
+        Vault { path: path.clone(), password, accounts: Vec::new() }.save()?;
+        return Ok(format!("created {}", path.display()));
+    }
+
+    let password = match options.read_password(super::env_var)? {
+        Some(password) => password,
+        None => prompt_password("vault password: ")?,
+    };
+    let mut vault = Vault::open(path, password)?;
+    match action {
+        "list" => Ok(vault.accounts.iter().map(label).collect::<Vec<_>>().join("\n")),
+        "add" => {
+            let account = options.qr_account()?;
+            if vault.accounts.iter().any(|existing| label(existing) == label(&account)) {
+                return Err(format!("{} is already in the vault", label(&account)));
+            }
+            let added = label(&account);
+            vault.accounts.push(account);
+            vault.save()?;
+            Ok(format!("added {}", added))
+        }
+        "remove" => {
+            let name = options.argument.as_deref().ok_or("give the ACCOUNT to remove")?;
+            let removed = vault.accounts.remove(vault.find(name)?);
+            vault.save()?;
+            Ok(format!("removed {}", label(&removed)))
+        }
+        "rename" => {
+            let name = options.argument.as_deref().ok_or("give the ACCOUNT to rename")?;
+            let new_name = options.rename_to.as_deref().ok_or("--to is required")?;
+            let index = vault.find(name)?;
+            let account = &mut vault.accounts[index];
+            match new_name.split_once(':') {
+                Some((issuer, account_name)) => {
+                    account.issuer = Some(issuer.to_string()).filter(|issuer| !issuer.is_empty());
+                    account.account_name = account_name.to_string();
+                }
+                None => account.account_name = new_name.to_string(),
+            }
+            let renamed = label(account);
+            vault.save()?;
+            Ok(format!("renamed {} to {}", name, renamed))
+        }
+        _ => unreachable!("actions are checked by Options::parse"),
+    }
+}
+
+/// `--vault`, `$DATP_VAULT`, or `datp/vault.json` in the user's data directory.
+fn vault_path(options: &Options) -> Result<PathBuf, String> {
+    if let Some(path) = options.vault.as_deref() {
+        return Ok(PathBuf::from(path));
+    }
+    if let Some(path) = env::var_os("DATP_VAULT") {
+        return Ok(PathBuf::from(path));
+    }
+    let data_dir = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local").join("share")))
+    };
+    let data_dir = data_dir.ok_or("cannot find a data directory; use --vault or DATP_VAULT")?;
+    Ok(data_dir.join("datp").join("vault.json"))
+}
+
+/// Creates or truncates `path` so that only the current user can read it.
+fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents)
+}
+
+/// Asks for a password on the terminal. On Unix the input is hidden with `stty -echo`.
+pub(crate) fn prompt_password(prompt: &str) -> Result<String, String> {
+    let no_terminal = |_| "no terminal to ask for the password on; use --password-env or --password-file".to_string();
+    #[cfg(unix)]
+    let tty = fs::OpenOptions::new().read(true).write(true).open("/dev/tty").map_err(no_terminal)?;
+    #[cfg(not(unix))]
+    let tty = io::stdin();
+
+    let mut out = io::stderr();
+    let _ = write!(out, "{}", prompt).and_then(|_| out.flush());
+    #[cfg(unix)]
+    let stty = |arg: &str| {
+        let input = tty.try_clone().map(Stdio::from).unwrap_or(Stdio::null());
+        Command::new("stty").arg(arg).stdin(input).status().is_ok_and(|status| status.success())
+    };
+    #[cfg(not(unix))]
+    let stty = |_: &str| false;
+    let hidden = stty("-echo");
+
+    let mut line = String::new();
+    let read = BufReader::new(&tty).read_line(&mut line);
+    if hidden {
+        stty("echo");
+        let _ = writeln!(out);
+    }
+    read.map_err(no_terminal)?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::run as run_command;
+    use super::*;
+
+    fn vault(args: &str) -> Result<String, String> {
+        run_command(&Options::parse(args.split_whitespace().map(String::from))?)
+    }
+
+    #[test]
+    fn test_vault_commands() {
+        let dir = env::temp_dir().join(format!("datp-cli-vault-{}", std::process::id()));
+        let path = dir.join("vault.json");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("password"), "hunter2\n").unwrap();
+        fs::write(dir.join("wrong"), "wrong\n").unwrap();
+        let common = format!("--vault {} --password-file {}", path.display(), dir.join("password").display());
+
+        vault(&format!("vault init {}", common)).unwrap();
+        assert!(vault(&format!("vault init {}", common)).is_err());
+        vault(&format!("vault add JBSWY3DPEHPK3PXP --account alice --issuer ACME {}", common)).unwrap();
+        vault(&format!("vault add otpauth://totp/bob?secret=GEZDGNBVGY3TQOJQ {}", common)).unwrap();
+        assert!(vault(&format!("vault add JBSWY3DPEHPK3PXP --account alice --issuer ACME {}", common)).is_err());
+        assert_eq!(vault(&format!("vault list {}", common)).unwrap(), "ACME:alice\nbob");
+        assert!(vault(&format!("vault list --vault {} --password-file {}", path.display(), dir.join("wrong").display())).is_err());
+
+        vault(&format!("vault rename alice --to Corp:alice {}", common)).unwrap();
+        vault(&format!("vault remove bob {}", common)).unwrap();
+        assert!(vault(&format!("vault remove bob {}", common)).is_err());
+        assert_eq!(vault(&format!("vault list {}", common)).unwrap(), "Corp:alice");
+
+        let backup = fs::read_to_string(&path).unwrap();
+        assert_eq!(import_aegis_json(&backup, Some("hunter2")).unwrap()[0].secret, "JBSWY3DPEHPK3PXP");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
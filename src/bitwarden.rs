@@ -0,0 +1,145 @@
+// Bitwarden's unencrypted JSON export keeps the TOTP secret of a login in
+// `items[].login.totp`, as an otpauth URI, a bare base32 seed or `steam://<seed>`.
+
+use super::json::{self, Json};
+use super::{build_otpauth_uri, uri, Account, DatpError, OtpKind};
+
+/// Reads the TOTP entries of a Bitwarden JSON export.
+///
+/// Login items without a `totp` value are skipped. The item name becomes the
+/// issuer and the login username the account name when the TOTP value itself
+/// does not carry them.
+///
+/// # Example
+/// ```rust
+/// use datp::import_bitwarden_json;
+///
+/// let export = r#"{"encrypted": false, "items": [
+///     {"type": 1, "name": "GitHub", "login": {"username": "octocat", "totp": "JBSWY3DPEHPK3PXP"}}
+/// ]}"#;
+/// let accounts = import_bitwarden_json(export).unwrap();
+/// assert_eq!(accounts[0].issuer.as_deref(), Some("GitHub"));
+/// assert_eq!(accounts[0].account_name, "octocat");
+/// ```
+pub fn import_bitwarden_json(export: &str) -> Result<Vec<Account>, DatpError> {
+    let root = json::parse(export).ok_or(DatpError::InvalidJson)?;
+    if root.get("encrypted") == Some(&Json::Bool(true)) {
+        return Err(DatpError::InvalidBackup("encrypted Bitwarden exports are not supported".to_string()));
+    }
+    let items = root
+        .get("items")
+        .and_then(Json::as_array)
+        .ok_or_else(|| DatpError::InvalidBackup("missing 'items' array".to_string()))?;
+
+    let mut accounts = Vec::new();
+    for item in items {
+        let Some(login) = item.get("login") else { continue };
+        let Some(totp) = login.str_field("totp").map(str::trim).filter(|totp| !totp.is_empty()) else {
+            continue;
+        };
+        let name = item.str_field("name").unwrap_or("");
+        let username = login.str_field("username").unwrap_or("");
+        accounts.push(parse_totp_field(totp, name, username)?);
+    }
+    Ok(accounts)
+}
+
+/// Writes accounts as a Bitwarden JSON export with one login item per account.
+///
+/// Steam accounts use Bitwarden's `steam://` form, all others an otpauth URI.
+pub fn export_bitwarden_json(accounts: &[Account]) -> String {
+    let items = accounts
+        .iter()
+        .map(|account| {
+            let totp = match account.kind {
+                OtpKind::Steam => format!("steam://{}", account.secret),
+                _ => build_otpauth_uri(account),
+            };
+            let name = account.issuer.as_deref().unwrap_or(&account.account_name);
+            Json::object(vec![
+                ("id", Json::Null),
+                ("organizationId", Json::Null),
+                ("folderId", Json::Null),
+                ("type", Json::Number(1.0)),
+                ("reprompt", Json::Number(0.0)),
+                ("name", Json::string(name)),
+                ("notes", Json::Null),
+                ("favorite", Json::Bool(false)),
+                (
+                    "login",
+                    Json::object(vec![
+                        ("uris", Json::Array(Vec::new())),
+                        ("username", Json::string(account.account_name.as_str())),
+                        ("password", Json::Null),
+                        ("totp", Json::string(totp)),
+                    ]),
+                ),
+                ("collectionIds", Json::Null),
+            ])
+        })
+        .collect();
+
+    Json::object(vec![
+        ("encrypted", Json::Bool(false)),
+        ("folders", Json::Array(Vec::new())),
+        ("items", Json::Array(items)),
+    ])
+    .to_pretty_string()
+}
+
+fn parse_totp_field(totp: &str, name: &str, username: &str) -> Result<Account, DatpError> {
+    let mut account = if uri::strip_prefix_ignore_case(totp, "otpauth://").is_some() {
+        Account::from_uri(totp)?
+    } else if let Some(seed) = uri::strip_prefix_ignore_case(totp, "steam://") {
+        let mut account = Account::new_steam(String::new(), uri::normalize_secret(seed)?);
+        account.issuer = None;
+        account
+    } else {
+        Account::new(String::new(), uri::normalize_secret(totp)?)
+    };
+
+    if account.issuer.is_none() && !name.is_empty() {
+        account.issuer = Some(name.to_string());
+    }
+    if account.account_name.is_empty() {
+        account.account_name = if username.is_empty() { name.to_string() } else { username.to_string() };
+    }
+    Ok(account)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_bitwarden_variants() {
+        let export = r#"{
+            "encrypted": false,
+            "items": [
+                {"type": 1, "name": "GitHub", "login": {"username": "octocat", "totp": "otpauth://totp/GitHub:octocat?secret=JBSWY3DPEHPK3PXP&digits=8"}},
+                {"type": 1, "name": "Steam", "login": {"username": "gaben", "totp": "steam://JBSWY3DPEHPK3PXP"}},
+                {"type": 1, "name": "No TOTP", "login": {"username": "x", "totp": null}},
+                {"type": 2, "name": "A note", "secureNote": {"type": 0}}
+            ]
+        }"#;
+        let accounts = import_bitwarden_json(export).unwrap();
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[0].digits, 8);
+        assert_eq!(accounts[1], Account::new_steam("gaben", "JBSWY3DPEHPK3PXP"));
+    }
+
+    #[test]
+    fn test_bitwarden_roundtrip() {
+        let mut github = Account::new("octocat", "JBSWY3DPEHPK3PXP");
+        github.issuer = Some("GitHub".to_string());
+        let accounts = vec![github, Account::new_steam("gaben", "GEZDGNBVGY3TQOJQ")];
+        assert_eq!(import_bitwarden_json(&export_bitwarden_json(&accounts)).unwrap(), accounts);
+    }
+
+    #[test]
+    fn test_import_bitwarden_errors() {
+        assert_eq!(import_bitwarden_json("not json"), Err(DatpError::InvalidJson));
+        assert!(matches!(import_bitwarden_json(r#"{"encrypted": true}"#), Err(DatpError::InvalidBackup(_))));
+        assert!(matches!(import_bitwarden_json(r#"{"encrypted": false}"#), Err(DatpError::InvalidBackup(_))));
+    }
+}
@@ -0,0 +1,248 @@
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use super::csv::{header_error, parse_rows};
+use super::{build_otpauth_uri, generate_totp_secret, qr_svg_for, Account, DatpError, QrStyle};
+
+/// Renders an enrollment QR code for every account, lazily and in order.
+///
+/// Each account is rendered from its own otpauth URI, so HOTP, Steam and non-default
+/// parameters are kept (see [`qr_svg_for`]), and all codes share `style`. A URI that does not fit
+/// into a QR code yields an error for that account without stopping the iteration.
+///
+/// # Example
+/// ```rust
+/// use datp::{qr_svgs, Account, QrStyle};
+///
+/// let accounts = vec![Account::new("alice", "JBSWY3DPEHPK3PXP"), Account::new("bob", "GEZDGNBVGY3TQOJQ")];
+/// for (account, svg) in qr_svgs(&accounts, &QrStyle::default()) {
+///     std::fs::write(format!("{}.svg", account.account_name), svg.unwrap()).unwrap();
+/// #   std::fs::remove_file(format!("{}.svg", account.account_name)).unwrap();
+/// }
+/// ```
+pub fn qr_svgs<'a>(
+    accounts: &'a [Account],
+    style: &'a QrStyle,
+) -> impl Iterator<Item = (&'a Account, Result<String, DatpError>)> + 'a {
+    accounts.iter().map(move |account| (account, qr_svg_for(account, style)))
+}
+
+/// Like [`qr_svgs`], but renders on `threads` worker threads and returns all results in input order.
+///
+/// `threads == 0` uses the available parallelism of the machine.
+pub fn qr_svgs_parallel<'a>(
+    accounts: &'a [Account],
+    style: &QrStyle,
+    threads: usize,
+) -> Vec<(&'a Account, Result<String, DatpError>)> {
+    let threads = match threads {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    };
+    let render = |batch: &'a [Account]| -> Vec<_> { batch.iter().map(|account| (account, qr_svg_for(account, style))).collect() };
+    if threads == 1 || accounts.len() < 2 {
+        return render(accounts);
+    }
+
+    let chunk = accounts.len().div_ceil(threads);
+    thread::scope(|scope| {
+        let workers: Vec<_> = accounts
+            .chunks(chunk)
+            .map(|batch| scope.spawn(move || render(batch)))
+            .collect();
+        workers.into_iter().flat_map(|worker| worker.join().expect("QR worker panicked")).collect()
+    })
+}
+
+/// Options of `onboard` and `onboard_csv`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Onboarding<'a> {
+    pub issuer: &'a str,                // for rows without an issuer of their own
+    pub secret_bytes: usize,            // length of the generated secrets
+    pub style: QrStyle<'a>,             // style of all QR codes
+}
+
+impl Default for Onboarding<'_> {
+    fn default() -> Self {
+        Onboarding { issuer: "", secret_bytes: 20, style: QrStyle::default() }
+    }
+}
+
+/// A user provisioned by `onboard`. `account.secret` is the freshly generated secret; store it
+/// (e.g. with `export_csv`) before handing out the QR code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Onboarded {
+    pub account: Account,
+    pub uri: String,                    // otpauth URI of the account
+    pub qr_svg: String,                 // enrollment QR code of the URI
+}
+
+/// Provisions a batch of users in one call: generates a fresh secret for every
+/// `(employee, issuer)` row and returns the account, its otpauth URI and its QR code.
+///
+/// An empty issuer falls back to `options.issuer`. The QR codes are rendered in parallel (see
+/// [`qr_svgs_parallel`]). Nothing is returned if any row fails, so that no secret is generated
+/// for a batch that cannot be completed.
+///
+/// # Returns
+/// `Ok(Vec<Onboarded>)` - One entry per row, in order; `DatpError::InvalidLabel` for an empty
+/// employee or `DatpError::QrCode` when a URI does not fit into a QR code.
+///
+/// # Example
+/// ```rust
+/// use datp::{onboard, Onboarding};
+///
+/// let options = Onboarding { issuer: "ACME", ..Default::default() };
+/// let batch = onboard([("alice@acme.com", ""), ("bob@acme.com", "ACME VPN")], &options).unwrap();
+/// assert_eq!(batch[1].account.issuer.as_deref(), Some("ACME VPN"));
+/// assert!(batch[0].uri.contains(&batch[0].account.secret));
+/// ```
+pub fn onboard<'r>(rows: impl IntoIterator<Item = (&'r str, &'r str)>, options: &Onboarding) -> Result<Vec<Onboarded>, DatpError> {
+    let accounts = rows.into_iter().map(|(employee, issuer)| new_account(employee, issuer, options)).collect::<Result<Vec<_>, _>>()?;
+    provision(accounts, options).map_err(|(_, error)| error)
+}
+
+/// Like [`onboard`], with the rows read from CSV with an `employee` and an optional `issuer`
+/// column. The header row is required, other columns are ignored and blank rows are skipped.
+///
+/// # Returns
+/// `Ok(Vec<Onboarded>)` - One entry per row, in order; errors are reported as
+/// `DatpError::CsvRow` with the 1-based line number.
+///
+/// # Example
+/// ```rust
+/// use datp::{export_csv, onboard_csv, Onboarding};
+///
+/// let csv = "employee,department\nalice@acme.com,Sales\nbob@acme.com,IT\n";
+/// let batch = onboard_csv(csv, &Onboarding { issuer: "ACME", ..Default::default() }).unwrap();
+/// let accounts: Vec<_> = batch.iter().map(|user| user.account.clone()).collect();
+/// let secrets = export_csv(&accounts); // for the secret store
+/// assert_eq!(secrets.lines().count(), 3);
+/// ```
+pub fn onboard_csv(input: &str, options: &Onboarding) -> Result<Vec<Onboarded>, DatpError> {
+    let mut rows = parse_rows(input)?.into_iter();
+    let (_, header) = rows.next().ok_or_else(|| header_error("missing header row"))?;
+    let column = |name: &str| header.iter().position(|column| column.trim().eq_ignore_ascii_case(name));
+    let employee = column("employee").ok_or_else(|| header_error("header must contain an 'employee' column"))?;
+    let issuer = column("issuer");
+
+    let mut lines = Vec::new();
+    let mut accounts = Vec::new();
+    for (line, row) in rows {
+        if row.iter().all(|cell| cell.trim().is_empty()) {
+            continue;
+        }
+        let cell = |index: Option<usize>| index.and_then(|i| row.get(i)).map_or("", |cell| cell.trim());
+        let account = new_account(cell(Some(employee)), cell(issuer), options)
+            .map_err(|error| DatpError::CsvRow { line, error: Box::new(error) })?;
+        lines.push(line);
+        accounts.push(account);
+    }
+    provision(accounts, options).map_err(|(index, error)| DatpError::CsvRow { line: lines[index], error: Box::new(error) })
+}
+
+/// Writes the QR code of every user to `dir` as `<issuer>-<employee>.svg`, with characters that
+/// are not safe in file names replaced by `_` and a numeric suffix for duplicates.
+///
+/// # Returns
+/// `Ok(Vec<PathBuf>)` - The written files, in the order of `batch`.
+pub fn write_qr_files(batch: &[Onboarded], dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut names = HashSet::new();
+    let mut paths = Vec::with_capacity(batch.len());
+    for user in batch {
+        let account = &user.account;
+        let label = match account.issuer.as_deref() {
+            Some(issuer) => format!("{}-{}", issuer, account.account_name),
+            None => account.account_name.clone(),
+        };
+        let stem: String =
+            label.chars().map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '@' | '-' | '_') { c } else { '_' }).collect();
+        let mut name = format!("{}.svg", stem);
+        let mut suffix = 1;
+        while !names.insert(name.clone()) {
+            suffix += 1;
+            name = format!("{}-{}.svg", stem, suffix);
+        }
+        let path = dir.join(name);
+        std::fs::write(&path, &user.qr_svg)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+fn new_account(employee: &str, issuer: &str, options: &Onboarding) -> Result<Account, DatpError> {
+    let employee = employee.trim();
+    if employee.is_empty() {
+        return Err(DatpError::InvalidLabel);
+    }
+    let mut account = Account::new(employee, generate_totp_secret(options.secret_bytes));
+    let issuer = match issuer.trim() {
+        "" => options.issuer,
+        issuer => issuer,
+    };
+    account.issuer = (!issuer.is_empty()).then(|| issuer.to_string());
+    Ok(account)
+}
+
+/// Renders the QR codes of `accounts`; an error comes with the index of its account.
+fn provision(accounts: Vec<Account>, options: &Onboarding) -> Result<Vec<Onboarded>, (usize, DatpError)> {
+    let svgs = qr_svgs_parallel(&accounts, &options.style, 0)
+        .into_iter()
+        .enumerate()
+        .map(|(index, (_, svg))| svg.map_err(|error| (index, error)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(accounts.into_iter().zip(svgs).map(|(account, qr_svg)| Onboarded { uri: build_otpauth_uri(&account), account, qr_svg }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use qrcode::EcLevel;
+
+    #[test]
+    fn test_parallel_matches_serial() {
+        let mut accounts: Vec<Account> = (0..25).map(|i| Account::new(format!("user{i}"), "JBSWY3DPEHPK3PXP")).collect();
+        accounts.push(Account::new("x".repeat(4000), "JBSWY3DPEHPK3PXP"));
+        accounts.push(Account::new_hotp("badge", "GEZDGNBVGY3TQOJQ", 7));
+
+        let style = QrStyle { min_dimension: 100, ec_level: EcLevel::L, ..Default::default() };
+        let serial: Vec<_> = qr_svgs(&accounts, &style).collect();
+        assert_eq!(qr_svgs_parallel(&accounts, &style, 4), serial);
+        assert!(matches!(serial[25].1, Err(DatpError::QrCode(_))));
+        assert!(serial[26].1.as_ref().unwrap().contains("<title>QR code for badge</title>"));
+    }
+
+    #[test]
+    fn test_onboard_csv() {
+        let options = Onboarding { issuer: "ACME", style: QrStyle { min_dimension: 100, ..Default::default() }, ..Default::default() };
+        let csv = "Employee,Issuer\nalice@acme.com,\n\n\"bob, jr.\",ACME VPN\n";
+        let batch = onboard_csv(csv, &options).unwrap();
+        assert_eq!(batch.len(), 2);
+        assert_eq!(batch[0].account.issuer.as_deref(), Some("ACME"));
+        assert_eq!(batch[1].account.account_name, "bob, jr.");
+        assert_eq!(batch[1].account.issuer.as_deref(), Some("ACME VPN"));
+        assert_eq!(batch[0].account.secret.len(), 32);
+        assert_ne!(batch[0].account.secret, batch[1].account.secret);
+        assert_eq!(Account::from_uri(&batch[1].uri).unwrap(), batch[1].account);
+        assert!(batch[1].qr_svg.contains("<title>QR code for ACME VPN: bob, jr.</title>"));
+
+        let error = |line, error| Err(DatpError::CsvRow { line, error: Box::new(error) });
+        assert_eq!(onboard_csv("employee,issuer\nalice,\n ,Other\n", &options), error(3, DatpError::InvalidLabel));
+        assert!(matches!(onboard_csv(&format!("employee\nalice\n{}\n", "x".repeat(4000)), &options), Err(DatpError::CsvRow { line: 3, .. })));
+        assert!(matches!(onboard_csv("name\nalice\n", &options), Err(DatpError::CsvRow { line: 1, .. })));
+    }
+
+    #[test]
+    fn test_write_qr_files() {
+        let dir = std::env::temp_dir().join(format!("datp-onboard-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let batch = onboard([("alice/admin", "ACME"), ("alice admin", "ACME"), ("bob", "")], &Onboarding::default()).unwrap();
+        let paths = write_qr_files(&batch, &dir).unwrap();
+        let names: Vec<_> = paths.iter().map(|path| path.file_name().unwrap().to_str().unwrap()).collect();
+        assert_eq!(names, ["ACME-alice_admin.svg", "ACME-alice_admin-2.svg", "bob.svg"]);
+        assert_eq!(std::fs::read_to_string(&paths[2]).unwrap(), batch[2].qr_svg);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
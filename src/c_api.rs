@@ -1,64 +1,1348 @@
+#![allow(clippy::not_unsafe_ptr_arg_deref)]
+
+use std::cell::RefCell;
 use std::ffi::{CStr, CString};
-use std::os::raw::{c_char, c_uint};
+use std::os::raw::{c_char, c_int, c_uint, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
 use super::*; 
 
+/// Status code returned by the C functions that can fail.
+///
+/// On failure, `datp_last_error` returns a message describing it.
 #[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[doc(hidden)]
-pub struct TotpQrConfigC {
-    pub dark_color: *const c_char,
-    pub light_color: *const c_char,
-    pub min_dimension: c_uint,
-    pub version: u8,
-    pub ec_level: u8,
+pub enum DatpStatus {
+    Ok = 0,
+    NullPointer = 1,        // a required pointer argument was NULL
+    QrCode = 2,             // the URI does not fit into a QR code
+    InvalidSecret = 3,      // the secret is not valid base32
+    InvalidArgument = 4,    // a numeric argument is out of range, e.g. a step of 0
+    Panic = 5,              // an internal error; the library state is still consistent
+    InvalidEncoding = 6,    // a string argument is not valid UTF-8
+    InvalidInput = 7,       // an import or the accounts to export are malformed, or the password is wrong
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Records `message` as the last error of this thread and returns `status`.
+pub(crate) fn fail(status: DatpStatus, message: &str) -> DatpStatus {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    status
+}
+
+/// Runs the body of an exported function, converting a panic into `on_panic` so it never
+/// unwinds across the FFI boundary.
+pub(crate) fn guard<T>(on_panic: T, body: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or_else(|payload| {
+        let reason = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        fail(DatpStatus::Panic, &format!("internal error: {}", reason));
+        on_panic
+    })
+}
+
+/// Reads a required C string argument, failing with NullPointer when it is NULL and with
+/// InvalidEncoding when it is not UTF-8, rather than computing codes for a mangled secret.
+pub(crate) fn c_str<'a>(ptr: *const c_char, name: &str) -> Result<&'a str, DatpStatus> {
+    if ptr.is_null() {
+        return Err(fail(DatpStatus::NullPointer, &format!("{} is NULL", name)));
+    }
+    unsafe { CStr::from_ptr(ptr) }
+        .to_str()
+        .map_err(|e| fail(DatpStatus::InvalidEncoding, &format!("{} is not valid UTF-8: {}", name, e)))
+}
+
+/// A `_w` function argument converted to a NUL-terminated UTF-8 string for the function it
+/// wraps; NULL stays NULL so the wrapped function reports it. The copy is wiped when dropped,
+/// since it may hold a secret.
+struct WideArg(Option<Vec<u8>>);
+
+impl WideArg {
+    fn new(ptr: *const u16, name: &str) -> Result<WideArg, DatpStatus> {
+        if ptr.is_null() {
+            return Ok(WideArg(None));
+        }
+        let len = (0..).take_while(|&i| unsafe { *ptr.add(i) } != 0).count();
+        let units = unsafe { std::slice::from_raw_parts(ptr, len) };
+        // sized up front so that the buffer never reallocates and leaves a copy behind
+        let mut bytes = WideArg(Some(Vec::with_capacity(len * 3 + 1)));
+        let buf = bytes.0.as_mut().unwrap();
+        for c in char::decode_utf16(units.iter().copied()) {
+            let Ok(c) = c else { return Err(fail(DatpStatus::InvalidEncoding, &format!("{} is not valid UTF-16", name))) };
+            buf.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+        }
+        buf.push(0);
+        Ok(bytes)
+    }
+
+    fn ptr(&self) -> *const c_char {
+        self.0.as_ref().map_or(std::ptr::null(), |bytes| bytes.as_ptr() as *const c_char)
+    }
+}
+
+impl Drop for WideArg {
+    fn drop(&mut self) {
+        if let Some(bytes) = &mut self.0 {
+            crypto::wipe(bytes);
+        }
+    }
+}
+
+// message for the most recent failure on the calling thread, or NULL if nothing failed yet;
+// the pointer stays valid until the next failing call on the same thread and must not be freed
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_last_error() -> *const c_char {
+    guard(std::ptr::null(), || {
+        LAST_ERROR.with(|last| last.borrow().as_ref().map_or(std::ptr::null(), |message| message.as_ptr()))
+    })
+}
+
+/// Version of the C ABI returned by `datp_abi_version`.
+///
+/// Bump it whenever a `#[repr(C)]` struct or an exported function signature changes in a way
+/// that breaks existing callers; adding new functions does not need a bump.
+#[doc(hidden)]
+pub const DATP_ABI_VERSION: c_uint = 1;
+
+// crate version of the loaded library, e.g. "0.1.1"; a static string that must not be freed
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char
+}
+
+// DATP_ABI_VERSION of the loaded library; refuse to use it if this differs from the value the
+// caller was built against
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_abi_version() -> c_uint {
+    DATP_ABI_VERSION
 }
 
+// returns a new random base32 secret; free it with datp_free_secret
 #[unsafe(no_mangle)]
 #[doc(hidden)]
 pub extern "C" fn generate_totp_secret_c(length: c_uint) -> *mut c_char {
-    let secret = generate_totp_secret(length as usize);
-    CString::new(secret).unwrap().into_raw()
+    guard(std::ptr::null_mut(), || {
+        let secret = generate_totp_secret(length as usize);
+        CString::new(secret).unwrap().into_raw()
+    })
+}
+
+/// Fewest bytes `datp_secret_from_entropy` accepts: the 128 bits RFC 4226 requires of a shared secret.
+#[doc(hidden)]
+pub const DATP_MIN_ENTROPY_BYTES: usize = 16;
+
+// base32-encodes len bytes of caller-provided entropy, e.g. from a hardware RNG, as a secret;
+// len must be at least DATP_MIN_ENTROPY_BYTES. Returns NULL on failure (see datp_last_error),
+// free the secret with datp_free_secret
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_secret_from_entropy(bytes: *const u8, len: usize) -> *mut c_char {
+    guard(std::ptr::null_mut(), || {
+        if bytes.is_null() { fail(DatpStatus::NullPointer, "bytes is NULL"); return std::ptr::null_mut(); }
+        if len < DATP_MIN_ENTROPY_BYTES {
+            fail(DatpStatus::InvalidArgument, &format!("at least {} bytes of entropy are required", DATP_MIN_ENTROPY_BYTES));
+            return std::ptr::null_mut();
+        }
+        let entropy = unsafe { std::slice::from_raw_parts(bytes, len) };
+        CString::new(base32::encode(Alphabet::Rfc4648 { padding: false }, entropy)).unwrap().into_raw()
+    })
+}
+
+// like generate_totp_secret_c, written to a caller-provided buffer; see totp_qr_svg_into_c.
+// A buffer that is too small still consumes a fresh random secret, so size it with
+// (length * 8 + 4) / 5 + 1 bytes up front.
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn generate_totp_secret_into_c(length: c_uint, buf: *mut c_char, buf_len: usize) -> usize {
+    guard(0, || {
+        let secret = generate_totp_secret(length as usize);
+        let written = copy_into(&secret, buf, buf_len);
+        crypto::wipe(&mut secret.into_bytes());
+        written
+    })
 }
 
 #[unsafe(no_mangle)]
 #[allow(unused)]
 #[doc(hidden)]
 pub extern "C" fn free_c_string(s: *mut c_char) {
-    if s.is_null() { return; }
-    unsafe { CString::from_raw(s) };
+    guard((), || {
+        if s.is_null() { return; }
+        unsafe { CString::from_raw(s) };
+    })
+}
+
+// like free_c_string, but overwrites the string with zeros first; use it for secrets and for
+// anything containing one, such as otpauth URIs. NULL is ignored
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_free_secret(s: *mut c_char) {
+    guard((), || {
+        if s.is_null() { return; }
+        let secret = unsafe { CString::from_raw(s) };
+        crypto::wipe(&mut secret.into_bytes_with_nul());
+    })
+}
+
+/// Result of `datp_validate_secret`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[doc(hidden)]
+pub enum DatpSecretCheck {
+    Valid = 0,
+    Empty = 1,              // the empty string
+    InvalidCharacter = 2,   // a character outside A-Z and 2-7, lowercase, spaces and '=' included
+    InvalidLength = 3,      // a length no base32 encoder produces, e.g. a single character
+}
+
+// checks a secret the way the other functions decode it: uppercase A-Z and 2-7 only, without
+// spaces, dashes or '=' padding, so a Valid secret is accepted by all of them. Returns a
+// DatpSecretCheck value, or -1 if secret is NULL
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_validate_secret(secret: *const c_char) -> c_int {
+    guard(-1, || {
+        let Ok(secret_str) = c_str(secret, "secret") else { return -1 };
+        check_secret(secret_str) as c_int
+    })
 }
 
+// datp_validate_secret for a UTF-16 secret
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_validate_secret_w(secret: *const u16) -> c_int {
+    guard(-1, || {
+        let Ok(secret) = WideArg::new(secret, "secret") else { return -1 };
+        datp_validate_secret(secret.ptr())
+    })
+}
+
+fn check_secret(secret: &str) -> DatpSecretCheck {
+    if secret.is_empty() {
+        return DatpSecretCheck::Empty;
+    }
+    if !secret.chars().all(|c| matches!(c, 'A'..='Z' | '2'..='7')) {
+        return DatpSecretCheck::InvalidCharacter;
+    }
+    match secret.len() % 8 {
+        // the last character of a 1, 3 or 6 character group carries no complete byte
+        1 | 3 | 6 => DatpSecretCheck::InvalidLength,
+        _ => DatpSecretCheck::Valid,
+    }
+}
+
+// returns 0 on failure, which is also a valid code; use totp_raw_now_status_c to tell them apart
 #[unsafe(no_mangle)]
 #[doc(hidden)]
 pub extern "C" fn totp_raw_now_c(secret: *const c_char, step: u64, t0: u64) -> c_uint {
-    if secret.is_null() { return 0; }
-    let secret_str = unsafe { CStr::from_ptr(secret).to_string_lossy() };
-    totp_raw_now(secret_str.as_ref(), step, t0).unwrap_or(0)
+    guard(0, || {
+        let mut code = 0;
+        totp_raw_now_status_c(secret, step, t0, &mut code);
+        code
+    })
 }
 
+// returns 0 on failure, which is also a valid code; use totp_raw_status_c to tell them apart
 #[unsafe(no_mangle)]
 #[doc(hidden)]
 pub extern "C" fn totp_raw_c(secret: *const c_char, step: u64, t0: u64, unix_time: u64) -> c_uint {
-    if secret.is_null() { return 0; }
-    let secret_str = unsafe { CStr::from_ptr(secret).to_string_lossy() };
-    totp_raw(secret_str.as_ref(), step, t0, unix_time).unwrap_or(0)
+    guard(0, || {
+        let mut code = 0;
+        totp_raw_status_c(secret, step, t0, unix_time, &mut code);
+        code
+    })
+}
+
+// writes the current code to *out_code
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn totp_raw_now_status_c(secret: *const c_char, step: u64, t0: u64, out_code: *mut c_uint) -> DatpStatus {
+    guard(DatpStatus::Panic, || {
+        match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(now) => totp_raw_status_c(secret, step, t0, now.as_secs(), out_code),
+            Err(_) => fail(DatpStatus::InvalidArgument, "system clock is before the unix epoch"),
+        }
+    })
+}
+
+// writes the code for unix_time to *out_code
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn totp_raw_status_c(
+    secret: *const c_char,
+    step: u64,
+    t0: u64,
+    unix_time: u64,
+    out_code: *mut c_uint,
+) -> DatpStatus {
+    guard(DatpStatus::Panic, || {
+        let secret_str = match c_str(secret, "secret") {
+            Ok(secret) => secret,
+            Err(status) => return status,
+        };
+        if out_code.is_null() { return fail(DatpStatus::NullPointer, "out_code is NULL"); }
+        if step == 0 { return fail(DatpStatus::InvalidArgument, "step must not be 0"); }
+        if t0 > unix_time { return fail(DatpStatus::InvalidArgument, "unix_time is before t0"); }
+
+        match totp_raw(secret_str, step, t0, unix_time) {
+            Some(code) => {
+                unsafe { *out_code = code };
+                DatpStatus::Ok
+            }
+            None => fail(DatpStatus::InvalidSecret, "secret is not valid base32"),
+        }
+    })
+}
+
+// totp_raw_status_c for a UTF-16 secret
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn totp_raw_status_w(secret: *const u16, step: u64, t0: u64, unix_time: u64, out_code: *mut c_uint) -> DatpStatus {
+    guard(DatpStatus::Panic, || {
+        match WideArg::new(secret, "secret") {
+            Ok(secret) => totp_raw_status_c(secret.ptr(), step, t0, unix_time, out_code),
+            Err(status) => status,
+        }
+    })
+}
+
+// returns the SHA1 code for unix_time as a zero-padded string of `digits` (1..=9) characters,
+// to be freed with free_c_string, or NULL on failure (see datp_last_error)
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn totp_string_c(secret: *const c_char, digits: c_uint, step: u64, t0: u64, unix_time: u64) -> *mut c_char {
+    guard(std::ptr::null_mut(), || {
+        into_raw(totp_string(secret, digits, step, t0, unix_time))
+    })
+}
+
+// like totp_string_c, written to a caller-provided buffer; see totp_qr_svg_into_c
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn totp_string_into_c(
+    secret: *const c_char,
+    digits: c_uint,
+    step: u64,
+    t0: u64,
+    unix_time: u64,
+    buf: *mut c_char,
+    buf_len: usize,
+) -> usize {
+    guard(0, || {
+        write_into(totp_string(secret, digits, step, t0, unix_time), buf, buf_len)
+    })
+}
+
+fn totp_string(secret: *const c_char, digits: c_uint, step: u64, t0: u64, unix_time: u64) -> Result<String, DatpStatus> {
+    let secret_str = c_str(secret, "secret")?;
+    if !(1..=9).contains(&digits) { return Err(fail(DatpStatus::InvalidArgument, "digits must be between 1 and 9")); }
+    if step == 0 { return Err(fail(DatpStatus::InvalidArgument, "step must not be 0")); }
+    if t0 > unix_time { return Err(fail(DatpStatus::InvalidArgument, "unix_time is before t0")); }
+
+    match totp_raw_with(secret_str, Algorithm::Sha1, digits, step, t0, unix_time) {
+        Some(code) => Ok(format!("{:0width$}", code, width = digits as usize)),
+        None => Err(fail(DatpStatus::InvalidSecret, "secret is not valid base32")),
+    }
+}
+
+/// Hands a string result to C as an owned pointer, NULL on failure.
+pub(crate) fn into_raw(result: Result<String, DatpStatus>) -> *mut c_char {
+    match result {
+        Ok(s) => CString::new(s).unwrap().into_raw(),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Copies a string result into `buf` if `buf_len` bytes fit it and its NUL terminator.
+///
+/// Returns the size needed including the terminator, so callers can retry with a larger
+/// buffer, or 0 on failure. `buf` may be NULL when `buf_len` is 0 to query the size.
+fn write_into(result: Result<String, DatpStatus>, buf: *mut c_char, buf_len: usize) -> usize {
+    match result {
+        Ok(s) => copy_into(&s, buf, buf_len),
+        Err(_) => 0,
+    }
+}
+
+fn copy_into(s: &str, buf: *mut c_char, buf_len: usize) -> usize {
+    let required = s.len() + 1;
+    if required <= buf_len {
+        if buf.is_null() {
+            fail(DatpStatus::NullPointer, "buf is NULL");
+            return 0;
+        }
+        unsafe {
+            std::ptr::copy_nonoverlapping(s.as_ptr(), buf as *mut u8, s.len());
+            *buf.add(s.len()) = 0;
+        }
+    }
+    required
+}
+
+// seconds until the current code rolls over, from step down to 1; 0 on failure (see datp_last_error)
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_seconds_remaining(step: u64, t0: u64) -> u64 {
+    guard(0, || {
+        match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(now) => datp_seconds_remaining_at(step, t0, now.as_secs()),
+            Err(_) => { fail(DatpStatus::InvalidArgument, "system clock is before the unix epoch"); 0 }
+        }
+    })
+}
+
+// like datp_seconds_remaining, at unix_time instead of now
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_seconds_remaining_at(step: u64, t0: u64, unix_time: u64) -> u64 {
+    guard(0, || {
+        if step == 0 { fail(DatpStatus::InvalidArgument, "step must not be 0"); return 0; }
+        if t0 > unix_time { fail(DatpStatus::InvalidArgument, "unix_time is before t0"); return 0; }
+        seconds_remaining(step, t0, unix_time).unwrap_or(0)
+    })
+}
+
+// returns 0 on failure, which is also a valid code; use hotp_raw_status_c to tell them apart
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn hotp_raw_c(secret: *const c_char, counter: u64) -> c_uint {
+    guard(0, || {
+        let mut code = 0;
+        hotp_raw_status_c(secret, counter, &mut code);
+        code
+    })
+}
+
+// writes the 6-digit code for counter to *out_code
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn hotp_raw_status_c(secret: *const c_char, counter: u64, out_code: *mut c_uint) -> DatpStatus {
+    guard(DatpStatus::Panic, || {
+        let secret_str = match c_str(secret, "secret") {
+            Ok(secret) => secret,
+            Err(status) => return status,
+        };
+        if out_code.is_null() { return fail(DatpStatus::NullPointer, "out_code is NULL"); }
+
+        match hotp_raw(secret_str, counter) {
+            Some(code) => {
+                unsafe { *out_code = code };
+                DatpStatus::Ok
+            }
+            None => fail(DatpStatus::InvalidSecret, "secret is not valid base32"),
+        }
+    })
+}
+
+// checks a 6-digit SHA1 code given as a string (leading zeros included) against the steps
+// within skew of unix_time; returns 1 if it matches, 0 if not and -1 on error (see datp_last_error)
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn totp_verify_c(
+    secret: *const c_char,
+    code: *const c_char,
+    step: u64,
+    t0: u64,
+    unix_time: u64,
+    skew: u64,
+) -> c_int {
+    guard(-1, || {
+        let (secret_str, code_str) = match (c_str(secret, "secret"), c_str(code, "code")) {
+            (Ok(secret), Ok(code)) => (secret, code),
+            _ => return -1,
+        };
+        if step == 0 { fail(DatpStatus::InvalidArgument, "step must not be 0"); return -1; }
+        if t0 > unix_time { fail(DatpStatus::InvalidArgument, "unix_time is before t0"); return -1; }
+        if hotp_raw(secret_str, 0).is_none() { fail(DatpStatus::InvalidSecret, "secret is not valid base32"); return -1; }
+
+        totp_verify(secret_str, code_str, step, t0, unix_time, skew) as c_int
+    })
+}
+
+// totp_verify_c for a UTF-16 secret and code
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn totp_verify_w(secret: *const u16, code: *const u16, step: u64, t0: u64, unix_time: u64, skew: u64) -> c_int {
+    guard(-1, || {
+        let (Ok(secret), Ok(code)) = (WideArg::new(secret, "secret"), WideArg::new(code, "code")) else { return -1 };
+        totp_verify_c(secret.ptr(), code.ptr(), step, t0, unix_time, skew)
+    })
+}
+
+// checks a 6-digit SHA1 code against counter..=counter + look_ahead; on a match writes the
+// matching counter to *out_counter (may be NULL) and returns 1, otherwise like totp_verify_c
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn hotp_verify_c(
+    secret: *const c_char,
+    code: *const c_char,
+    counter: u64,
+    look_ahead: u64,
+    out_counter: *mut u64,
+) -> c_int {
+    guard(-1, || {
+        let (secret_str, code_str) = match (c_str(secret, "secret"), c_str(code, "code")) {
+            (Ok(secret), Ok(code)) => (secret, code),
+            _ => return -1,
+        };
+        if hotp_raw(secret_str, 0).is_none() { fail(DatpStatus::InvalidSecret, "secret is not valid base32"); return -1; }
+
+        match hotp_verify(secret_str, code_str, counter, look_ahead) {
+            Some(matched) => {
+                if !out_counter.is_null() { unsafe { *out_counter = matched }; }
+                1
+            }
+            None => 0,
+        }
+    })
+}
+
+/// Code parameters for `datp_totp_new` and the `datp_*_with` functions.
+#[repr(C)]
+#[doc(hidden)]
+pub struct DatpTotpParams {
+    pub algorithm: u8,          // 0 = SHA1, 1 = SHA256, 2 = SHA512
+    pub digits: c_uint,         // 1 to 9
+    pub period: u64,            // seconds, 1 to 2^32 - 1
+    pub t0: u64,                // usually 0
+}
+
+/// The parameters used when a `DatpTotpParams` pointer is NULL.
+const DEFAULT_PARAMS: DatpTotpParams = DatpTotpParams { algorithm: 0, digits: 6, period: 30, t0: 0 };
+
+/// TOTP generator behind the opaque `DatpTotp*` handle of the C API.
+///
+/// The secret is decoded once in `datp_totp_new`, so generating and verifying codes doesn't
+/// parse it again.
+#[derive(Clone)]
+#[doc(hidden)]
+pub struct DatpTotp {
+    key: Vec<u8>,
+    algorithm: Algorithm,
+    digits: u32,
+    period: u64,
+    t0: u64,
+}
+
+/// Longest period `datp_totp_new` accepts, so rollover times stay far from overflowing.
+const MAX_PERIOD: u64 = u32::MAX as u64;
+
+impl Drop for DatpTotp {
+    fn drop(&mut self) {
+        crypto::wipe(&mut self.key);
+    }
+}
+
+impl DatpTotp {
+    /// Decodes `secret` and checks `params`, which may be NULL for `DEFAULT_PARAMS`.
+    fn new(secret: &str, params: *const DatpTotpParams) -> Result<DatpTotp, DatpStatus> {
+        let params = if params.is_null() { &DEFAULT_PARAMS } else { unsafe { &*params } };
+        let algorithm = c_algorithm(params.algorithm)?;
+        if !(1..=9).contains(&params.digits) { return Err(fail(DatpStatus::InvalidArgument, "digits must be between 1 and 9")); }
+        if params.period == 0 { return Err(fail(DatpStatus::InvalidArgument, "period must not be 0")); }
+        if params.period > MAX_PERIOD { return Err(fail(DatpStatus::InvalidArgument, "period must be at most 2^32 - 1 seconds")); }
+        if params.t0.checked_add(params.period).is_none() { return Err(fail(DatpStatus::InvalidArgument, "t0 + period overflows")); }
+        let Some(key) = decode(Alphabet::Rfc4648 { padding: false }, secret) else {
+            return Err(fail(DatpStatus::InvalidSecret, "secret is not valid base32"));
+        };
+        Ok(DatpTotp { key, algorithm, digits: params.digits, period: params.period, t0: params.t0 })
+    }
+
+    fn counter(&self, unix_time: u64) -> Result<u64, DatpStatus> {
+        match unix_time.checked_sub(self.t0) {
+            Some(elapsed) => Ok(elapsed / self.period),
+            None => Err(fail(DatpStatus::InvalidArgument, "unix_time is before t0")),
+        }
+    }
+
+    /// Unix time at which the code of `counter` rolls over, or `None` if that is past `u64::MAX`.
+    fn next_rollover(&self, counter: u64) -> Option<u64> {
+        counter.checked_add(1)?.checked_mul(self.period)?.checked_add(self.t0)
+    }
+
+    fn code(&self, unix_time: u64) -> Result<u32, DatpStatus> {
+        let counter = self.counter(unix_time)?;
+        Ok(hotp_truncated_key(&self.key, self.algorithm, counter) % 10u32.pow(self.digits))
+    }
+
+    /// The offset in steps of the matching code, if any.
+    fn verify(&self, code: &str, unix_time: u64, skew: u64) -> Result<Option<i64>, DatpStatus> {
+        let current = self.counter(unix_time)?;
+        let (first, last) = (current.saturating_sub(skew), current.saturating_add(skew));
+        let matched = crate::verify::find_match(&self.key, code, self.algorithm, self.digits, first, last);
+        Ok(matched.map(|matched| matched as i64 - current as i64))
+    }
+}
+
+/// Stores a verification result for C: 1 and the offset on a match, 0 without one, -1 on error.
+fn verify_result(result: Result<Option<i64>, DatpStatus>, out_offset: *mut i64) -> c_int {
+    match result {
+        Ok(Some(offset)) => {
+            if !out_offset.is_null() { unsafe { *out_offset = offset }; }
+            1
+        }
+        Ok(None) => 0,
+        Err(_) => -1,
+    }
+}
+
+// creates a generator for secret; params may be NULL for SHA1, 6 digits every 30 seconds from 0.
+// Returns NULL on failure (see datp_last_error); free the handle with datp_totp_free
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_totp_new(secret: *const c_char, params: *const DatpTotpParams) -> *mut DatpTotp {
+    guard(std::ptr::null_mut(), || {
+        match c_str(secret, "secret").and_then(|secret| DatpTotp::new(secret, params)) {
+            Ok(totp) => Box::into_raw(Box::new(totp)),
+            Err(_) => std::ptr::null_mut(),
+        }
+    })
+}
+
+// datp_totp_new for a UTF-16 secret
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_totp_new_w(secret: *const u16, params: *const DatpTotpParams) -> *mut DatpTotp {
+    guard(std::ptr::null_mut(), || {
+        let Ok(secret) = WideArg::new(secret, "secret") else { return std::ptr::null_mut() };
+        datp_totp_new(secret.ptr(), params)
+    })
+}
+
+// writes the code for unix_time to *out_code and returns DatpStatus::Ok
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_totp_code(handle: *const DatpTotp, unix_time: u64, out_code: *mut c_uint) -> DatpStatus {
+    guard(DatpStatus::Panic, || {
+        if handle.is_null() { return fail(DatpStatus::NullPointer, "handle is NULL"); }
+        if out_code.is_null() { return fail(DatpStatus::NullPointer, "out_code is NULL"); }
+        match unsafe { &*handle }.code(unix_time) {
+            Ok(code) => {
+                unsafe { *out_code = code };
+                DatpStatus::Ok
+            }
+            Err(status) => status,
+        }
+    })
+}
+
+// checks code against the steps within skew of unix_time; on a match writes its offset in steps
+// to *out_offset (may be NULL) and returns 1, otherwise like totp_verify_c
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_totp_verify(
+    handle: *const DatpTotp,
+    code: *const c_char,
+    unix_time: u64,
+    skew: u64,
+    out_offset: *mut i64,
+) -> c_int {
+    guard(-1, || {
+        if handle.is_null() { fail(DatpStatus::NullPointer, "handle is NULL"); return -1; }
+        let result = c_str(code, "code").and_then(|code| unsafe { &*handle }.verify(code, unix_time, skew));
+        verify_result(result, out_offset)
+    })
+}
+
+// like totp_raw_status_c with the algorithm, digits and period of params (NULL for the defaults
+// of datp_totp_new)
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_code_with(
+    secret: *const c_char,
+    params: *const DatpTotpParams,
+    unix_time: u64,
+    out_code: *mut c_uint,
+) -> DatpStatus {
+    guard(DatpStatus::Panic, || {
+        if out_code.is_null() { return fail(DatpStatus::NullPointer, "out_code is NULL"); }
+        match c_str(secret, "secret").and_then(|secret| DatpTotp::new(secret, params)?.code(unix_time)) {
+            Ok(code) => {
+                unsafe { *out_code = code };
+                DatpStatus::Ok
+            }
+            Err(status) => status,
+        }
+    })
+}
+
+// like datp_totp_verify without a handle; params may be NULL for the defaults of datp_totp_new
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_verify_with(
+    secret: *const c_char,
+    params: *const DatpTotpParams,
+    code: *const c_char,
+    unix_time: u64,
+    skew: u64,
+    out_offset: *mut i64,
+) -> c_int {
+    guard(-1, || {
+        let result = c_str(secret, "secret").and_then(|secret| {
+            let totp = DatpTotp::new(secret, params)?;
+            totp.verify(c_str(code, "code")?, unix_time, skew)
+        });
+        verify_result(result, out_offset)
+    })
+}
+
+// builds the otpauth://totp URI for an account; issuer may be NULL, algorithm is 0 = SHA1,
+// 1 = SHA256 or 2 = SHA512. Returns NULL on failure (see datp_last_error), free with free_c_string
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_build_uri(
+    account: *const c_char,
+    issuer: *const c_char,
+    secret: *const c_char,
+    algorithm: u8,
+    digits: c_uint,
+    period: u64,
+) -> *mut c_char {
+    guard(std::ptr::null_mut(), || {
+        into_raw(build_uri(account, issuer, secret, algorithm, digits, period))
+    })
+}
+
+// datp_build_uri for UTF-16 strings; the URI is still returned as UTF-8
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_build_uri_w(
+    account: *const u16,
+    issuer: *const u16,
+    secret: *const u16,
+    algorithm: u8,
+    digits: c_uint,
+    period: u64,
+) -> *mut c_char {
+    guard(std::ptr::null_mut(), || {
+        let converted = (WideArg::new(account, "account"), WideArg::new(issuer, "issuer"), WideArg::new(secret, "secret"));
+        let (Ok(account), Ok(issuer), Ok(secret)) = converted else { return std::ptr::null_mut() };
+        datp_build_uri(account.ptr(), issuer.ptr(), secret.ptr(), algorithm, digits, period)
+    })
+}
+
+fn build_uri(
+    account: *const c_char,
+    issuer: *const c_char,
+    secret: *const c_char,
+    algorithm: u8,
+    digits: c_uint,
+    period: u64,
+) -> Result<String, DatpStatus> {
+    let account_name = c_str(account, "account")?;
+    let issuer = if issuer.is_null() { None } else { Some(c_str(issuer, "issuer")?.to_string()) };
+    let secret = c_str(secret, "secret")?;
+    let algorithm = c_algorithm(algorithm)?;
+    if !(1..=9).contains(&digits) { return Err(fail(DatpStatus::InvalidArgument, "digits must be between 1 and 9")); }
+    if period == 0 { return Err(fail(DatpStatus::InvalidArgument, "period must not be 0")); }
+    let secret = crate::uri::normalize_secret(secret).map_err(|e| fail(DatpStatus::InvalidSecret, &e.to_string()))?;
+
+    let account = Account { issuer, algorithm, digits, period, ..Account::new(account_name, secret) };
+    Ok(build_otpauth_uri(&account))
+}
+
+fn c_algorithm(algorithm: u8) -> Result<Algorithm, DatpStatus> {
+    match algorithm {
+        0 => Ok(Algorithm::Sha1),
+        1 => Ok(Algorithm::Sha256),
+        2 => Ok(Algorithm::Sha512),
+        _ => Err(fail(DatpStatus::InvalidArgument, "algorithm must be 0, 1 or 2")),
+    }
+}
+
+// frees a handle from datp_totp_new; NULL is ignored
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_totp_free(handle: *mut DatpTotp) {
+    guard((), || {
+        if !handle.is_null() {
+            unsafe { drop(Box::from_raw(handle)) };
+        }
+    })
 }
 
+/// Called by `datp_watch` with the new code, the seconds it stays valid and the caller's
+/// `user_data`.
+#[doc(hidden)]
+pub type DatpWatchCallback = extern "C" fn(code: c_uint, valid_for: u64, user_data: *mut c_void);
+
+/// Subscription created by `datp_watch`; dropping it stops and joins the timer thread.
+#[doc(hidden)]
+pub struct DatpWatch {
+    stopped: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for DatpWatch {
+    fn drop(&mut self) {
+        let (stopped, wake) = &*self.stopped;
+        *stopped.lock().unwrap() = true;
+        wake.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// `user_data` travels to the timer thread; the caller is responsible for it being usable there.
+struct UserData(*mut c_void);
+
+unsafe impl Send for UserData {}
+
+// calls callback with the current code right away and again every time it rolls over, on a
+// timer thread owned by the library, until datp_unwatch. The generator is copied, so handle may
+// be freed while the watch runs. Returns NULL on failure (see datp_last_error)
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_watch(
+    handle: *const DatpTotp,
+    callback: Option<DatpWatchCallback>,
+    user_data: *mut c_void,
+) -> *mut DatpWatch {
+    guard(std::ptr::null_mut(), || {
+        if handle.is_null() { fail(DatpStatus::NullPointer, "handle is NULL"); return std::ptr::null_mut(); }
+        let Some(callback) = callback else { fail(DatpStatus::NullPointer, "callback is NULL"); return std::ptr::null_mut(); };
+        let totp = unsafe { &*handle }.clone();
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        if let Some(counter) = now.checked_sub(totp.t0).map(|elapsed| elapsed / totp.period)
+            && totp.next_rollover(counter).is_none()
+        {
+            fail(DatpStatus::InvalidArgument, "the next rollover is past the end of time");
+            return std::ptr::null_mut();
+        }
+        let user_data = UserData(user_data);
+        let stopped = Arc::new((Mutex::new(false), Condvar::new()));
+
+        let signal = Arc::clone(&stopped);
+        let thread = thread::Builder::new().name("datp-watch".to_string()).spawn(move || {
+            let user_data = user_data;
+            let (stopped, wake) = &*signal;
+            let mut last = None;
+            loop {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+                // before t0 there's no code yet, so check again in a second
+                let mut wait = Duration::from_secs(1);
+                if let Ok(counter) = totp.counter(now.as_secs()) {
+                    let Some(next) = totp.next_rollover(counter) else { break };
+                    wait = Duration::from_secs(next).saturating_sub(now);
+                    if last != Some(counter) {
+                        last = Some(counter);
+                        let code = hotp_truncated_key(&totp.key, totp.algorithm, counter) % 10u32.pow(totp.digits);
+                        callback(code, wait.as_secs_f64().ceil() as u64, user_data.0);
+                    }
+                }
+                // wakes up early for datp_unwatch; spurious wakeups just go round the loop again
+                let state = stopped.lock().unwrap();
+                let (state, _) = wake.wait_timeout_while(state, wait, |stopped| !*stopped).unwrap();
+                if *state { break; }
+            }
+        });
+
+        match thread {
+            Ok(thread) => Box::into_raw(Box::new(DatpWatch { stopped, thread: Some(thread) })),
+            Err(e) => { fail(DatpStatus::Panic, &format!("cannot start the timer thread: {}", e)); std::ptr::null_mut() }
+        }
+    })
+}
+
+// stops a watch from datp_watch and waits for a running callback to return, so user_data can be
+// released afterwards; must not be called from the callback itself. NULL is ignored
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_unwatch(watch: *mut DatpWatch) {
+    guard((), || {
+        if !watch.is_null() {
+            unsafe { drop(Box::from_raw(watch)) };
+        }
+    })
+}
+
+/// An account exchanged with `datp_import` and `datp_export`.
+///
+/// Strings returned by `datp_import` are owned by the array and released by `datp_free_accounts`.
+#[repr(C)]
+#[doc(hidden)]
+pub struct DatpAccountC {
+    pub kind: u8,                   // 0 = TOTP, 1 = HOTP, 2 = Steam
+    pub issuer: *mut c_char,        // NULL if there is none
+    pub account_name: *mut c_char,
+    pub secret: *mut c_char,        // base32
+    pub algorithm: u8,              // 0 = SHA1, 1 = SHA256, 2 = SHA512
+    pub digits: c_uint,
+    pub period: u64,                // seconds
+    pub counter: u64,               // HOTP moving factor, 0 for other kinds
+}
+
+/// Formats understood by `datp_import` and `datp_export`, passed as an `int`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[doc(hidden)]
+pub enum DatpFormat {
+    Migration = 0,      // otpauth-migration:// URIs, one per line
+    Aegis = 1,          // Aegis JSON vault, encrypted if a password is given
+    Bitwarden = 2,      // Bitwarden JSON export
+    FreeOtpPlus = 3,    // FreeOTP+ JSON backup, import only
+    TwoFas = 4,         // 2FAS .2fas backup, import only
+    Csv = 5,            // CSV with a header row
+}
+
+/// Accounts per QR code when exporting `DatpFormat::Migration`, as Google Authenticator does.
+const MIGRATION_ACCOUNTS_PER_URI: usize = 10;
+
+fn c_format(format: c_int) -> Result<DatpFormat, DatpStatus> {
+    Ok(match format {
+        0 => DatpFormat::Migration,
+        1 => DatpFormat::Aegis,
+        2 => DatpFormat::Bitwarden,
+        3 => DatpFormat::FreeOtpPlus,
+        4 => DatpFormat::TwoFas,
+        5 => DatpFormat::Csv,
+        _ => return Err(fail(DatpStatus::InvalidArgument, "unknown format")),
+    })
+}
+
+fn invalid_input(error: DatpError) -> DatpStatus {
+    fail(DatpStatus::InvalidInput, &error.to_string())
+}
+
+fn account_to_c(account: Account) -> DatpAccountC {
+    let string = |s: String| CString::new(s.replace('\0', "")).unwrap().into_raw();
+    DatpAccountC {
+        kind: match account.kind {
+            OtpKind::Totp => 0,
+            OtpKind::Hotp => 1,
+            OtpKind::Steam => 2,
+        },
+        issuer: account.issuer.map_or(std::ptr::null_mut(), string),
+        account_name: string(account.account_name),
+        secret: string(account.secret),
+        algorithm: match account.algorithm {
+            Algorithm::Sha1 => 0,
+            Algorithm::Sha256 => 1,
+            Algorithm::Sha512 => 2,
+        },
+        digits: account.digits,
+        period: account.period,
+        counter: account.counter.unwrap_or(0),
+    }
+}
+
+fn account_from_c(account: &DatpAccountC) -> Result<Account, DatpStatus> {
+    let kind = match account.kind {
+        0 => OtpKind::Totp,
+        1 => OtpKind::Hotp,
+        2 => OtpKind::Steam,
+        _ => return Err(fail(DatpStatus::InvalidArgument, "kind must be 0, 1 or 2")),
+    };
+    // checked here, or datp_export would write backups that the importers reject
+    if !(1..=9).contains(&account.digits) { return Err(fail(DatpStatus::InvalidArgument, "digits must be between 1 and 9")); }
+    if kind != OtpKind::Hotp && account.period == 0 { return Err(fail(DatpStatus::InvalidArgument, "period must not be 0")); }
+    let secret = crate::uri::normalize_secret(c_str(account.secret, "secret")?).map_err(|e| fail(DatpStatus::InvalidSecret, &e.to_string()))?;
+    Ok(Account {
+        kind,
+        issuer: if account.issuer.is_null() { None } else { Some(c_str(account.issuer, "issuer")?.to_string()) },
+        account_name: c_str(account.account_name, "account_name")?.to_string(),
+        secret,
+        algorithm: c_algorithm(account.algorithm)?,
+        digits: account.digits,
+        period: account.period,
+        counter: (kind == OtpKind::Hotp).then_some(account.counter),
+        image: None,
+    })
+}
+
+// reads an export in one of the DatpFormat formats; password may be NULL for unencrypted
+// backups. On success writes an array of *out_len accounts to *out_accounts, to be released
+// with datp_free_accounts
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_import(
+    format: c_int,
+    data: *const c_char,
+    password: *const c_char,
+    out_accounts: *mut *mut DatpAccountC,
+    out_len: *mut usize,
+) -> DatpStatus {
+    guard(DatpStatus::Panic, || {
+        if out_accounts.is_null() { return fail(DatpStatus::NullPointer, "out_accounts is NULL"); }
+        if out_len.is_null() { return fail(DatpStatus::NullPointer, "out_len is NULL"); }
+        let import = || -> Result<Vec<Account>, DatpStatus> {
+            let format = c_format(format)?;
+            let data = c_str(data, "data")?;
+            let password = if password.is_null() { None } else { Some(c_str(password, "password")?) };
+            match format {
+                DatpFormat::Migration => {
+                    let mut accounts = Vec::new();
+                    for line in data.lines().map(str::trim).filter(|line| !line.is_empty()) {
+                        accounts.extend(decode_migration_uri(line).map_err(invalid_input)?);
+                    }
+                    Ok(accounts)
+                }
+                DatpFormat::Aegis => import_aegis_json(data, password).map_err(invalid_input),
+                DatpFormat::Bitwarden => import_bitwarden_json(data).map_err(invalid_input),
+                DatpFormat::FreeOtpPlus => import_freeotp_plus(data).map_err(invalid_input),
+                DatpFormat::TwoFas => {
+                    let services = import_2fas(data, password).map_err(invalid_input)?;
+                    Ok(services.into_iter().map(|service| service.account).collect())
+                }
+                DatpFormat::Csv => import_csv(data).map_err(invalid_input),
+            }
+        };
+
+        match import() {
+            Ok(accounts) => {
+                let accounts: Box<[DatpAccountC]> = accounts.into_iter().map(account_to_c).collect();
+                unsafe {
+                    *out_len = accounts.len();
+                    *out_accounts = Box::into_raw(accounts) as *mut DatpAccountC;
+                }
+                DatpStatus::Ok
+            }
+            Err(status) => status,
+        }
+    })
+}
+
+// releases an array from datp_import, wiping the secrets; NULL is ignored
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_free_accounts(accounts: *mut DatpAccountC, len: usize) {
+    guard((), || {
+        if accounts.is_null() { return; }
+        let accounts = unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(accounts, len)) };
+        for account in accounts.iter() {
+            free_c_string(account.issuer);
+            free_c_string(account.account_name);
+            datp_free_secret(account.secret);
+        }
+    })
+}
+
+// writes len accounts in one of the DatpFormat formats to *out (free it with datp_free_secret);
+// password may be NULL, and is only used by DatpFormat::Aegis. Accounts with digits outside 1-9,
+// a period of 0 (except HOTP) or a secret that is not base32 fail with InvalidArgument or
+// InvalidSecret
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_export(
+    format: c_int,
+    accounts: *const DatpAccountC,
+    len: usize,
+    password: *const c_char,
+    out: *mut *mut c_char,
+) -> DatpStatus {
+    guard(DatpStatus::Panic, || {
+        if out.is_null() { return fail(DatpStatus::NullPointer, "out is NULL"); }
+        if accounts.is_null() && len > 0 { return fail(DatpStatus::NullPointer, "accounts is NULL"); }
+        let export = || -> Result<String, DatpStatus> {
+            let format = c_format(format)?;
+            let password = if password.is_null() { None } else { Some(c_str(password, "password")?) };
+            let accounts = if len == 0 { &[][..] } else { unsafe { std::slice::from_raw_parts(accounts, len) } };
+            let accounts = accounts.iter().map(account_from_c).collect::<Result<Vec<_>, _>>()?;
+            match format {
+                DatpFormat::Migration => {
+                    let uris = encode_migration_uris(&accounts, MIGRATION_ACCOUNTS_PER_URI).map_err(invalid_input)?;
+                    Ok(uris.join("\n"))
+                }
+                DatpFormat::Aegis => export_aegis_json(&accounts, password).map_err(invalid_input),
+                DatpFormat::Bitwarden => Ok(export_bitwarden_json(&accounts)),
+                DatpFormat::Csv => Ok(export_csv(&accounts)),
+                DatpFormat::FreeOtpPlus | DatpFormat::TwoFas => {
+                    Err(fail(DatpStatus::InvalidArgument, "this format can only be imported"))
+                }
+            }
+        };
+
+        match export() {
+            Ok(data) => {
+                unsafe { *out = CString::new(data).unwrap().into_raw() };
+                DatpStatus::Ok
+            }
+            Err(status) => status,
+        }
+    })
+}
+
+#[cfg(feature = "qr")]
+#[repr(C)]
+#[doc(hidden)]
+pub struct TotpQrConfigC {
+    pub dark_color: *const c_char,
+    pub light_color: *const c_char,
+    pub min_dimension: c_uint,
+    pub version: u8,
+    pub ec_level: u8,
+}
+
+// TotpQrConfigC with the account the code provisions; issuer may be NULL
+#[cfg(feature = "qr")]
+#[repr(C)]
+#[doc(hidden)]
+pub struct TotpQrConfigV2C {
+    pub account_name: *const c_char,
+    pub issuer: *const c_char,
+    pub dark_color: *const c_char,
+    pub light_color: *const c_char,
+    pub min_dimension: c_uint,
+    pub version: u8,
+    pub ec_level: u8,
+}
+
+// returns NULL on failure; use totp_qr_svg_status_c to find out why
+#[cfg(feature = "qr")]
 #[unsafe(no_mangle)]
 #[doc(hidden)]
 pub extern "C" fn totp_qr_svg_c(secret: *const c_char, config: *const TotpQrConfigC) -> *mut c_char {
-    if secret.is_null() || config.is_null() { return std::ptr::null_mut(); }
+    guard(std::ptr::null_mut(), || {
+        into_raw(qr_svg_v1(secret, config))
+    })
+}
+
+// writes the SVG to *out (free it with free_c_string) and returns DatpStatus::Ok,
+// or leaves *out untouched and returns the error
+#[cfg(feature = "qr")]
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn totp_qr_svg_status_c(
+    secret: *const c_char,
+    config: *const TotpQrConfigC,
+    out: *mut *mut c_char,
+) -> DatpStatus {
+    guard(DatpStatus::Panic, || {
+        if out.is_null() { return fail(DatpStatus::NullPointer, "out is NULL"); }
+        match qr_svg_v1(secret, config) {
+            Ok(svg) => {
+                unsafe { *out = CString::new(svg).unwrap().into_raw() };
+                DatpStatus::Ok
+            }
+            Err(status) => status,
+        }
+    })
+}
+
+// writes the SVG and a NUL terminator to buf if it has room for them and returns the number of
+// bytes needed for both, so a return value above buf_len means "retry with a larger buffer";
+// returns 0 on failure (see datp_last_error). Pass buf = NULL and buf_len = 0 to query the size.
+#[cfg(feature = "qr")]
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn totp_qr_svg_into_c(
+    secret: *const c_char,
+    config: *const TotpQrConfigC,
+    buf: *mut c_char,
+    buf_len: usize,
+) -> usize {
+    guard(0, || {
+        write_into(qr_svg_v1(secret, config), buf, buf_len)
+    })
+}
+
+// returns NULL on failure; use totp_qr_svg_v2_status_c to find out why
+#[cfg(feature = "qr")]
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn totp_qr_svg_v2_c(secret: *const c_char, config: *const TotpQrConfigV2C) -> *mut c_char {
+    guard(std::ptr::null_mut(), || {
+        into_raw(qr_svg_v2(secret, config, std::ptr::null()))
+    })
+}
+
+// like totp_qr_svg_status_c, with the account name and issuer taken from the config
+#[cfg(feature = "qr")]
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn totp_qr_svg_v2_status_c(
+    secret: *const c_char,
+    config: *const TotpQrConfigV2C,
+    out: *mut *mut c_char,
+) -> DatpStatus {
+    guard(DatpStatus::Panic, || {
+        if out.is_null() { return fail(DatpStatus::NullPointer, "out is NULL"); }
+        match qr_svg_v2(secret, config, std::ptr::null()) {
+            Ok(svg) => {
+                unsafe { *out = CString::new(svg).unwrap().into_raw() };
+                DatpStatus::Ok
+            }
+            Err(status) => status,
+        }
+    })
+}
+
+// like totp_qr_svg_into_c, with the account name and issuer taken from the config
+#[cfg(feature = "qr")]
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn totp_qr_svg_v2_into_c(
+    secret: *const c_char,
+    config: *const TotpQrConfigV2C,
+    buf: *mut c_char,
+    buf_len: usize,
+) -> usize {
+    guard(0, || {
+        write_into(qr_svg_v2(secret, config, std::ptr::null()), buf, buf_len)
+    })
+}
+
+/// Options for `datp_qr_png`.
+#[cfg(feature = "image")]
+#[repr(C)]
+#[doc(hidden)]
+pub struct DatpQrPngConfigC {
+    pub account_name: *const c_char,
+    pub issuer: *const c_char,      // may be NULL
+    pub module_size: c_uint,        // pixels per module, at most 64
+    pub dark_color: u32,            // 0xRRGGBB
+    pub light_color: u32,           // 0xRRGGBB
+    pub ec_level: u8,               // 0 = L, 1 = M, 2 = Q, 3 = H
+    pub quiet_zone: u8,             // non-zero for a 4-module border
+}
+
+// renders the provisioning QR code as PNG file contents, with the algorithm, digits and period
+// of params (NULL for the defaults of datp_totp_new). Writes the size to *out_len and returns
+// the bytes, to be freed with datp_free_bytes, or NULL on failure (see datp_last_error).
+// Only exported by builds with the image feature
+#[cfg(feature = "image")]
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_qr_png(
+    secret: *const c_char,
+    config: *const DatpQrPngConfigC,
+    params: *const DatpTotpParams,
+    out_len: *mut usize,
+) -> *mut u8 {
+    guard(std::ptr::null_mut(), || {
+        if out_len.is_null() { fail(DatpStatus::NullPointer, "out_len is NULL"); return std::ptr::null_mut(); }
+        match qr_png(secret, config, params) {
+            Ok(png) => {
+                let png = png.into_boxed_slice();
+                unsafe { *out_len = png.len() };
+                Box::into_raw(png) as *mut u8
+            }
+            Err(_) => std::ptr::null_mut(),
+        }
+    })
+}
+
+#[cfg(feature = "image")]
+fn qr_png(secret: *const c_char, config: *const DatpQrPngConfigC, params: *const DatpTotpParams) -> Result<Vec<u8>, DatpStatus> {
+    if config.is_null() { return Err(fail(DatpStatus::NullPointer, "config is NULL")); }
+    let cfg = unsafe { &*config };
+    let secret_str = c_str(secret, "secret")?;
+    let totp = DatpTotp::new(secret_str, params)?;
+    if totp.t0 != 0 { return Err(fail(DatpStatus::InvalidArgument, "otpauth URIs have no t0")); }
+    let account_name = c_str(cfg.account_name, "account_name")?;
+    let issuer = if cfg.issuer.is_null() { None } else { Some(c_str(cfg.issuer, "issuer")?.to_string()) };
+    let secret = crate::uri::normalize_secret(secret_str).map_err(|e| fail(DatpStatus::InvalidSecret, &e.to_string()))?;
+    let account = Account {
+        issuer,
+        algorithm: totp.algorithm,
+        digits: totp.digits,
+        period: totp.period,
+        ..Account::new(account_name, secret)
+    };
+    let rgb = |color: u32| [(color >> 16) as u8, (color >> 8) as u8, color as u8];
+    let png_config = QrPngConfig {
+        module_size: cfg.module_size,
+        dark_color: rgb(cfg.dark_color),
+        light_color: rgb(cfg.light_color),
+        ec_level: match cfg.ec_level {
+            0 => EcLevel::L,
+            1 => EcLevel::M,
+            2 => EcLevel::Q,
+            3 => EcLevel::H,
+            _ => EcLevel::M,
+        },
+        quiet_zone: cfg.quiet_zone != 0,
+    };
+    totp_qr_png(&account, &png_config).map_err(|e| match e {
+        DatpError::InvalidModuleSize(_) => fail(DatpStatus::InvalidArgument, &e.to_string()),
+        _ => fail(DatpStatus::QrCode, &e.to_string()),
+    })
+}
+
+// frees bytes returned by datp_qr_png, wiping them first since they encode the secret;
+// NULL is ignored
+#[cfg(feature = "image")]
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_free_bytes(bytes: *mut u8, len: usize) {
+    guard((), || {
+        if bytes.is_null() { return; }
+        let mut bytes = unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(bytes, len)) };
+        crypto::wipe(&mut bytes);
+    })
+}
+
+#[cfg(feature = "qr")]
+fn qr_svg_v1(secret: *const c_char, config: *const TotpQrConfigC) -> Result<String, DatpStatus> {
+    if config.is_null() { return Err(fail(DatpStatus::NullPointer, "config is NULL")); }
+    let cfg = unsafe { &*config };
+    let v2 = TotpQrConfigV2C {
+        account_name: c"totp".as_ptr(),
+        issuer: c"totp".as_ptr(),
+        dark_color: cfg.dark_color,
+        light_color: cfg.light_color,
+        min_dimension: cfg.min_dimension,
+        version: cfg.version,
+        ec_level: cfg.ec_level,
+    };
+    qr_svg_v2(secret, &v2, std::ptr::null())
+}
+
+// like totp_qr_svg_v2_status_c, provisioning the algorithm, digits and period of params
+// (NULL for the defaults of datp_totp_new); a t0 other than 0 can't be put in an otpauth URI
+#[cfg(feature = "qr")]
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_qr_svg_with(
+    secret: *const c_char,
+    config: *const TotpQrConfigV2C,
+    params: *const DatpTotpParams,
+    out: *mut *mut c_char,
+) -> DatpStatus {
+    guard(DatpStatus::Panic, || {
+        if out.is_null() { return fail(DatpStatus::NullPointer, "out is NULL"); }
+        match qr_svg_v2(secret, config, params) {
+            Ok(svg) => {
+                unsafe { *out = CString::new(svg).unwrap().into_raw() };
+                DatpStatus::Ok
+            }
+            Err(status) => status,
+        }
+    })
+}
 
-    let secret_str = unsafe { CStr::from_ptr(secret).to_string_lossy() };
+#[cfg(feature = "qr")]
+fn qr_svg_v2(secret: *const c_char, config: *const TotpQrConfigV2C, params: *const DatpTotpParams) -> Result<String, DatpStatus> {
+    if config.is_null() { return Err(fail(DatpStatus::NullPointer, "config is NULL")); }
     let cfg = unsafe { &*config };
-    let dark = unsafe { CStr::from_ptr(cfg.dark_color).to_string_lossy() };
-    let light = unsafe { CStr::from_ptr(cfg.light_color).to_string_lossy() };
+    let secret_str = c_str(secret, "secret")?;
+    let totp = DatpTotp::new(secret_str, params)?;
+    if totp.t0 != 0 { return Err(fail(DatpStatus::InvalidArgument, "otpauth URIs have no t0")); }
+    let account_name = c_str(cfg.account_name, "account_name")?;
+    let issuer = if cfg.issuer.is_null() { "" } else { c_str(cfg.issuer, "issuer")? };
+    let dark = c_str(cfg.dark_color, "dark_color")?;
+    let light = c_str(cfg.light_color, "light_color")?;
 
     let qr_config = TotpQrConfig {
-        account_name: "totp",  // можно сделать отдельное поле для C api
-        issuer: "totp",
-        dark_color: &dark,
-        light_color: &light,
+        account_name,
+        issuer,
+        dark_color: dark,
+        light_color: light,
         min_dimension: cfg.min_dimension,
         version: match cfg.version {
             0 => Version::Normal(1),
@@ -75,8 +1359,549 @@ pub extern "C" fn totp_qr_svg_c(secret: *const c_char, config: *const TotpQrConf
             3 => EcLevel::H,
             _ => EcLevel::M,
         },
+        algorithm: totp.algorithm,
+        digits: totp.digits,
+        period: totp.period,
+        ..Default::default()
     };
 
-    let svg = totp_qr_svg(secret_str.as_ref(), &qr_config);
-    CString::new(svg).unwrap().into_raw()
+    totp_qr_svg(secret_str, &qr_config).map_err(|e| match e {
+        DatpError::InvalidColor(_) => fail(DatpStatus::InvalidArgument, &e.to_string()),
+        _ => fail(DatpStatus::QrCode, &e.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_and_last_error() {
+        let mut code = 0;
+        assert_eq!(totp_raw_status_c(c"GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ".as_ptr(), 30, 0, 59, &mut code), DatpStatus::Ok);
+        assert_eq!(code, 287082);
+
+        assert_eq!(totp_raw_status_c(c"not base32!".as_ptr(), 30, 0, 59, &mut code), DatpStatus::InvalidSecret);
+        let message = unsafe { CStr::from_ptr(datp_last_error()) };
+        assert_eq!(message.to_str().unwrap(), "secret is not valid base32");
+
+        assert_eq!(totp_raw_status_c(c"GEZDGNBVGY3TQOJQ".as_ptr(), 0, 0, 59, &mut code), DatpStatus::InvalidArgument);
+        assert_eq!(totp_raw_c(std::ptr::null(), 30, 0, 59), 0);
+        let message = unsafe { CStr::from_ptr(datp_last_error()) };
+        assert_eq!(message.to_str().unwrap(), "secret is NULL");
+    }
+
+    #[test]
+    fn test_verify() {
+        let secret = c"GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ".as_ptr();
+        assert_eq!(hotp_raw_c(secret, 2), 359152);
+        assert_eq!(totp_verify_c(secret, c"287082".as_ptr(), 30, 0, 89, 1), 1);
+        assert_eq!(totp_verify_c(secret, c"287082".as_ptr(), 30, 0, 89, 0), 0);
+        assert_eq!(totp_verify_c(secret, c"287082".as_ptr(), 0, 0, 89, 0), -1);
+
+        let mut matched = 0;
+        assert_eq!(hotp_verify_c(secret, c"359152".as_ptr(), 0, 3, &mut matched), 1);
+        assert_eq!(matched, 2);
+        assert_eq!(hotp_verify_c(c"!".as_ptr(), c"359152".as_ptr(), 0, 3, std::ptr::null_mut()), -1);
+    }
+
+    #[test]
+    fn test_totp_string() {
+        // 8 digit RFC 6238 vector at T = 1111111109 is 07081804
+        let code = totp_string_c(c"GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ".as_ptr(), 8, 30, 0, 1111111109);
+        assert_eq!(unsafe { CStr::from_ptr(code) }.to_str().unwrap(), "07081804");
+        free_c_string(code);
+        assert!(totp_string_c(c"GEZDGNBVGY3TQOJQ".as_ptr(), 10, 30, 0, 59).is_null());
+    }
+
+    #[test]
+    fn test_into_buffers() {
+        let secret = c"GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ".as_ptr();
+        let mut buf = [0 as c_char; 8];
+        assert_eq!(totp_string_into_c(secret, 8, 30, 0, 1111111109, buf.as_mut_ptr(), 8), 9);
+        assert_eq!(buf, [0; 8]);
+        let mut buf = [0 as c_char; 9];
+        assert_eq!(totp_string_into_c(secret, 8, 30, 0, 1111111109, buf.as_mut_ptr(), 9), 9);
+        assert_eq!(unsafe { CStr::from_ptr(buf.as_ptr()) }.to_str().unwrap(), "07081804");
+        assert_eq!(generate_totp_secret_into_c(10, buf.as_mut_ptr(), 0), 17);
+    }
+
+    #[cfg(feature = "qr")]
+    #[test]
+    fn test_qr_svg_into_buffer() {
+        let secret = c"GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ".as_ptr();
+        let config = TotpQrConfigC {
+            dark_color: c"#000000".as_ptr(),
+            light_color: c"#ffffff".as_ptr(),
+            min_dimension: 100,
+            version: 0,
+            ec_level: 1,
+        };
+        let required = totp_qr_svg_into_c(secret, &config, std::ptr::null_mut(), 0);
+        let mut svg = vec![0 as c_char; required];
+        assert_eq!(totp_qr_svg_into_c(secret, &config, svg.as_mut_ptr(), svg.len()), required);
+        let expected = totp_qr_svg_c(secret, &config);
+        assert_eq!(unsafe { CStr::from_ptr(svg.as_ptr()) }, unsafe { CStr::from_ptr(expected) });
+        free_c_string(expected);
+
+        assert_eq!(totp_qr_svg_into_c(std::ptr::null(), &config, svg.as_mut_ptr(), svg.len()), 0);
+
+        let config = TotpQrConfigC { dark_color: c"#000\"/><script/>".as_ptr(), light_color: config.light_color, min_dimension: 100, version: 0, ec_level: 1 };
+        let mut out = std::ptr::null_mut();
+        assert_eq!(totp_qr_svg_status_c(secret, &config, &mut out), DatpStatus::InvalidArgument);
+        assert!(out.is_null());
+    }
+
+    #[test]
+    fn test_totp_handle() {
+        // RFC 6238 SHA256 vector
+        let secret = c"GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZA".as_ptr();
+        let params = DatpTotpParams { algorithm: 1, digits: 8, period: 30, t0: 0 };
+        let totp = datp_totp_new(secret, &params);
+        assert!(!totp.is_null());
+
+        let mut code = 0;
+        assert_eq!(datp_totp_code(totp, 59, &mut code), DatpStatus::Ok);
+        assert_eq!(code, 46119246);
+        let mut offset = 0;
+        assert_eq!(datp_totp_verify(totp, c"46119246".as_ptr(), 89, 1, &mut offset), 1);
+        assert_eq!(offset, -1);
+        assert_eq!(datp_totp_verify(totp, c"46119246".as_ptr(), 89, 0, std::ptr::null_mut()), 0);
+        datp_totp_free(totp);
+
+        let params = DatpTotpParams { t0: 100, ..params };
+        let totp = datp_totp_new(secret, &params);
+        assert_eq!(datp_totp_code(totp, 59, &mut code), DatpStatus::InvalidArgument);
+        datp_totp_free(totp);
+
+        assert!(datp_totp_new(c"!".as_ptr(), std::ptr::null()).is_null());
+        assert_eq!(unsafe { CStr::from_ptr(datp_last_error()) }.to_str().unwrap(), "secret is not valid base32");
+        assert!(datp_totp_new(secret, &DatpTotpParams { period: 0, ..params }).is_null());
+        datp_totp_free(std::ptr::null_mut());
+    }
+
+    #[test]
+    fn test_params() {
+        // RFC 6238 SHA512 vector
+        let secret = c"GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNA".as_ptr();
+        let params = DatpTotpParams { algorithm: 2, digits: 8, period: 30, t0: 0 };
+        let mut code = 0;
+        assert_eq!(datp_code_with(secret, &params, 59, &mut code), DatpStatus::Ok);
+        assert_eq!(code, 90693936);
+        let mut offset = 0;
+        assert_eq!(datp_verify_with(secret, &params, c"90693936".as_ptr(), 60, 1, &mut offset), 1);
+        assert_eq!(offset, -1);
+        assert_eq!(datp_verify_with(secret, std::ptr::null(), c"90693936".as_ptr(), 60, 1, &mut offset), 0);
+        assert_eq!(datp_code_with(secret, &DatpTotpParams { digits: 10, ..params }, 59, &mut code), DatpStatus::InvalidArgument);
+    }
+
+    #[cfg(feature = "qr")]
+    #[test]
+    fn test_qr_svg_with_params() {
+        let secret = c"GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ".as_ptr();
+        let params = DatpTotpParams { algorithm: 2, digits: 8, period: 30, t0: 0 };
+        let config = TotpQrConfigV2C {
+            account_name: c"alice".as_ptr(),
+            issuer: std::ptr::null(),
+            dark_color: c"#000000".as_ptr(),
+            light_color: c"#ffffff".as_ptr(),
+            min_dimension: 100,
+            version: 0,
+            ec_level: 1,
+        };
+        let mut svg = std::ptr::null_mut();
+        assert_eq!(datp_qr_svg_with(secret, &config, &params, &mut svg), DatpStatus::Ok);
+        free_c_string(svg);
+        let params = DatpTotpParams { t0: 5, ..params };
+        assert_eq!(datp_qr_svg_with(secret, &config, &params, &mut svg), DatpStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn test_watch() {
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        extern "C" fn on_code(_code: c_uint, _valid_for: u64, user_data: *mut c_void) {
+            unsafe { &*(user_data as *const AtomicU64) }.fetch_add(1, Ordering::SeqCst);
+        }
+
+        let calls = AtomicU64::new(0);
+        let params = DatpTotpParams { period: 1, ..DEFAULT_PARAMS };
+        let totp = datp_totp_new(c"JBSWY3DPEHPK3PXP".as_ptr(), &params);
+        let watch = datp_watch(totp, Some(on_code), &calls as *const AtomicU64 as *mut c_void);
+        datp_totp_free(totp);
+        thread::sleep(Duration::from_millis(1500));
+        datp_unwatch(watch);
+        let seen = calls.load(Ordering::SeqCst);
+        assert!((2..=3).contains(&seen), "{} callbacks", seen);
+
+        thread::sleep(Duration::from_millis(1100));
+        assert_eq!(calls.load(Ordering::SeqCst), seen);
+        assert!(datp_watch(std::ptr::null(), Some(on_code), std::ptr::null_mut()).is_null());
+
+        for (params, message) in [
+            (DatpTotpParams { period: MAX_PERIOD + 1, ..DEFAULT_PARAMS }, "period must be at most 2^32 - 1 seconds"),
+            (DatpTotpParams { t0: u64::MAX - 10, ..DEFAULT_PARAMS }, "t0 + period overflows"),
+        ] {
+            assert!(datp_totp_new(c"JBSWY3DPEHPK3PXP".as_ptr(), &params).is_null());
+            assert_eq!(unsafe { CStr::from_ptr(datp_last_error()) }.to_str().unwrap(), message);
+        }
+        // a handle built around the checks of datp_totp_new still can't overflow the timer
+        let totp = DatpTotp { key: b"Hello!".to_vec(), algorithm: Algorithm::Sha1, digits: 6, period: u64::MAX, t0: 1 };
+        assert!(datp_watch(&totp, Some(on_code), std::ptr::null_mut()).is_null());
+        assert_eq!(unsafe { CStr::from_ptr(datp_last_error()) }.to_str().unwrap(), "the next rollover is past the end of time");
+    }
+
+    #[test]
+    fn test_import_export() {
+        let account = DatpAccountC {
+            kind: 1,
+            issuer: c"MyApp".as_ptr() as *mut c_char,
+            account_name: c"alice".as_ptr() as *mut c_char,
+            secret: c"JBSWY3DPEHPK3PXP".as_ptr() as *mut c_char,
+            algorithm: 1,
+            digits: 8,
+            period: 30,
+            counter: 7,
+        };
+        let mut uris = std::ptr::null_mut();
+        assert_eq!(datp_export(DatpFormat::Migration as c_int, &account, 1, std::ptr::null(), &mut uris), DatpStatus::Ok);
+
+        let (mut accounts, mut len) = (std::ptr::null_mut(), 0);
+        assert_eq!(datp_import(DatpFormat::Migration as c_int, uris, std::ptr::null(), &mut accounts, &mut len), DatpStatus::Ok);
+        datp_free_secret(uris);
+        assert_eq!(len, 1);
+        let imported = unsafe { &*accounts };
+        assert_eq!((imported.kind, imported.algorithm, imported.digits, imported.counter), (1, 1, 8, 7));
+        assert_eq!(unsafe { CStr::from_ptr(imported.issuer) }.to_str().unwrap(), "MyApp");
+        assert_eq!(unsafe { CStr::from_ptr(imported.secret) }.to_str().unwrap(), "JBSWY3DPEHPK3PXP");
+        datp_free_accounts(accounts, len);
+
+        let csv = c"label,secret\r\nbob,JBSWY3DPEHPK3PXP\r\n";
+        assert_eq!(datp_import(DatpFormat::Csv as c_int, csv.as_ptr(), std::ptr::null(), &mut accounts, &mut len), DatpStatus::Ok);
+        assert!(unsafe { &*accounts }.issuer.is_null());
+        datp_free_accounts(accounts, len);
+
+        assert_eq!(datp_import(DatpFormat::Csv as c_int, c"nope".as_ptr(), std::ptr::null(), &mut accounts, &mut len), DatpStatus::InvalidInput);
+        assert_eq!(datp_import(9, csv.as_ptr(), std::ptr::null(), &mut accounts, &mut len), DatpStatus::InvalidArgument);
+        let mut out = std::ptr::null_mut();
+        assert_eq!(datp_export(DatpFormat::TwoFas as c_int, &account, 1, std::ptr::null(), &mut out), DatpStatus::InvalidArgument);
+
+        // accounts the importers would reject are not exported
+        let totp = DatpAccountC { kind: 0, ..account };
+        for (invalid, status) in [
+            (DatpAccountC { digits: 0, ..totp }, DatpStatus::InvalidArgument),
+            (DatpAccountC { digits: 42, ..totp }, DatpStatus::InvalidArgument),
+            (DatpAccountC { period: 0, ..totp }, DatpStatus::InvalidArgument),
+            (DatpAccountC { secret: c"not base32!".as_ptr() as *mut c_char, ..totp }, DatpStatus::InvalidSecret),
+        ] {
+            assert_eq!(datp_export(DatpFormat::Csv as c_int, &invalid, 1, std::ptr::null(), &mut out), status);
+        }
+        let typed = DatpAccountC { secret: c"jbsw y3dp ehpk 3pxp".as_ptr() as *mut c_char, period: 0, ..account };
+        assert_eq!(datp_export(DatpFormat::Csv as c_int, &typed, 1, std::ptr::null(), &mut out), DatpStatus::Ok);
+        assert!(unsafe { CStr::from_ptr(out) }.to_str().unwrap().contains("JBSWY3DPEHPK3PXP"));
+        datp_free_secret(out);
+    }
+
+    #[test]
+    fn test_build_uri() {
+        let uri = datp_build_uri(c"user@example.com".as_ptr(), c"MyApp".as_ptr(), c"jbsw y3dp ehpk 3pxp".as_ptr(), 1, 8, 60);
+        assert_eq!(
+            unsafe { CStr::from_ptr(uri) }.to_str().unwrap(),
+            "otpauth://totp/MyApp:user%40example.com?secret=JBSWY3DPEHPK3PXP&issuer=MyApp&algorithm=SHA256&digits=8&period=60"
+        );
+        free_c_string(uri);
+
+        let uri = datp_build_uri(c"alice".as_ptr(), std::ptr::null(), c"JBSWY3DPEHPK3PXP".as_ptr(), 0, 6, 30);
+        assert_eq!(
+            unsafe { CStr::from_ptr(uri) }.to_str().unwrap(),
+            "otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&algorithm=SHA1&digits=6&period=30"
+        );
+        free_c_string(uri);
+
+        assert!(datp_build_uri(c"alice".as_ptr(), std::ptr::null(), c"1".as_ptr(), 0, 6, 30).is_null());
+        assert!(datp_build_uri(c"alice".as_ptr(), std::ptr::null(), c"JBSWY3DPEHPK3PXP".as_ptr(), 3, 6, 30).is_null());
+        assert_eq!(unsafe { CStr::from_ptr(datp_last_error()) }.to_str().unwrap(), "algorithm must be 0, 1 or 2");
+    }
+
+    #[test]
+    fn test_validate_secret() {
+        assert_eq!(datp_validate_secret(c"JBSWY3DPEHPK3PXP".as_ptr()), DatpSecretCheck::Valid as c_int);
+        assert_eq!(datp_validate_secret(c"".as_ptr()), DatpSecretCheck::Empty as c_int);
+        // the code functions decode strictly, so what they reject is not Valid either
+        let mut code = 0;
+        for typed in [c"jbsw y3dp-ehpk 3pxp", c"JBSWY3DPEHPK3PXP====", c" "] {
+            assert_eq!(datp_validate_secret(typed.as_ptr()), DatpSecretCheck::InvalidCharacter as c_int);
+            assert_eq!(totp_raw_status_c(typed.as_ptr(), 30, 0, 59, &mut code), DatpStatus::InvalidSecret);
+        }
+        assert_eq!(datp_validate_secret(c"JBSWY3DPEHPK3PX1".as_ptr()), DatpSecretCheck::InvalidCharacter as c_int);
+        assert_eq!(datp_validate_secret(c"JBSWY3DPE".as_ptr()), DatpSecretCheck::InvalidLength as c_int);
+        assert_eq!(datp_validate_secret(std::ptr::null()), -1);
+    }
+
+    #[test]
+    fn test_seconds_remaining() {
+        assert_eq!(datp_seconds_remaining_at(30, 10, 39), 1);
+        assert_eq!(datp_seconds_remaining_at(30, 10, 40), 30);
+        assert_eq!(datp_seconds_remaining_at(0, 0, 40), 0);
+        assert_eq!(datp_seconds_remaining_at(30, 50, 40), 0);
+        assert!((1..=30).contains(&datp_seconds_remaining(30, 0)));
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let mut code = 0;
+        let secret = c"JBSWY3DP\xffEHPK3PXP".as_ptr();
+        assert_eq!(totp_raw_status_c(secret, 30, 0, 59, &mut code), DatpStatus::InvalidEncoding);
+        let message = unsafe { CStr::from_ptr(datp_last_error()) };
+        assert!(message.to_str().unwrap().starts_with("secret is not valid UTF-8"));
+        assert_eq!(datp_validate_secret(secret), -1);
+    }
+
+    #[test]
+    fn test_wide_strings() {
+        let wide = |s: &str| s.encode_utf16().chain([0]).collect::<Vec<u16>>();
+        let secret = wide("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        let mut code = 0;
+        assert_eq!(totp_raw_status_w(secret.as_ptr(), 30, 0, 59, &mut code), DatpStatus::Ok);
+        assert_eq!(code, 287082);
+        assert_eq!(totp_verify_w(secret.as_ptr(), wide("287082").as_ptr(), 30, 0, 59, 0), 1);
+        assert_eq!(datp_validate_secret_w(secret.as_ptr()), DatpSecretCheck::Valid as c_int);
+
+        let totp = datp_totp_new_w(secret.as_ptr(), std::ptr::null());
+        assert_eq!(datp_totp_code(totp, 59, &mut code), DatpStatus::Ok);
+        assert_eq!(code, 287082);
+        datp_totp_free(totp);
+
+        let uri = datp_build_uri_w(wide("Zoë").as_ptr(), std::ptr::null(), secret.as_ptr(), 0, 6, 30);
+        assert!(unsafe { CStr::from_ptr(uri) }.to_str().unwrap().starts_with("otpauth://totp/Zo%C3%AB?"));
+        free_c_string(uri);
+
+        // unpaired surrogate
+        assert_eq!(totp_raw_status_w([0xd800, 0].as_ptr(), 30, 0, 59, &mut code), DatpStatus::InvalidEncoding);
+        assert_eq!(totp_raw_status_w(std::ptr::null(), 30, 0, 59, &mut code), DatpStatus::NullPointer);
+    }
+
+    #[test]
+    fn test_free_secret() {
+        let secret = generate_totp_secret_c(20);
+        assert_eq!(datp_validate_secret(secret), DatpSecretCheck::Valid as c_int);
+        datp_free_secret(secret);
+        datp_free_secret(std::ptr::null_mut());
+
+        let wide = WideArg::new([b'A' as u16, 0xe9, 0].as_ptr(), "secret").unwrap();
+        assert_eq!(unsafe { CStr::from_ptr(wide.ptr()) }.to_bytes(), "A\u{e9}".as_bytes());
+    }
+
+    /// The C spelling of a Rust type of the C API.
+    fn c_type(rust: &str) -> String {
+        let rust = rust.trim();
+        if let Some(inner) = rust.strip_prefix("*const ") {
+            return format!("const {} *", c_type(inner));
+        }
+        if let Some(inner) = rust.strip_prefix("*mut ") {
+            return format!("{} *", c_type(inner));
+        }
+        if let Some(inner) = rust.strip_prefix("Option<").and_then(|rest| rest.strip_suffix('>')) {
+            // nullable function pointers
+            return c_type(inner);
+        }
+        match rust {
+            "c_char" => "char",
+            "c_int" => "int",
+            "c_uint" => "unsigned int",
+            "c_void" => "void",
+            "u8" => "uint8_t",
+            "u16" => "uint16_t",
+            "u32" => "uint32_t",
+            "u64" => "uint64_t",
+            "i64" => "int64_t",
+            "usize" => "size_t",
+            name => name,
+        }
+        .to_string()
+    }
+
+    /// `name: Type, ...` as C parameters, `void` without any.
+    fn c_params(rust: &str) -> String {
+        let params: Vec<String> = rust
+            .split(',')
+            .map(str::trim)
+            .filter(|param| !param.is_empty())
+            .map(|param| {
+                let (name, ty) = param.split_once(':').unwrap();
+                format!("{} {}", c_type(ty), name.trim())
+            })
+            .collect();
+        if params.is_empty() { "void".to_string() } else { params.join(", ") }
+    }
+
+    /// Collapses whitespace and drops it around punctuation, so declarations compare however
+    /// they are wrapped.
+    fn normalize(declaration: &str) -> String {
+        let collapsed = declaration.split_whitespace().collect::<Vec<_>>().join(" ");
+        let mut out = String::new();
+        for c in collapsed.chars() {
+            if c == ' ' && out.ends_with(['*', '(', ')', ',', '{', ';']) {
+                continue;
+            }
+            if matches!(c, '*' | '(' | ')' | ',' | '{' | '}' | ';') && out.ends_with(' ') {
+                out.pop();
+            }
+            out.push(c);
+        }
+        out
+    }
+
+    /// The body of the `#[repr(C)]` struct or enum `name` in the Rust source, one item per
+    /// line with comments removed.
+    fn rust_items<'a>(source: &'a str, keyword: &str, name: &str) -> Vec<&'a str> {
+        let start = source.find(&format!("pub {} {} {{", keyword, name)).unwrap_or_else(|| panic!("{} not in c_api.rs", name));
+        let body = &source[start..];
+        body[body.find('{').unwrap() + 1..body.find('}').unwrap()]
+            .lines()
+            .map(|line| line.split("//").next().unwrap().trim().trim_end_matches(','))
+            .filter(|line| !line.is_empty())
+            .collect()
+    }
+
+    /// The items between the braces of `typedef <keyword> name { ... } name;` in the header.
+    fn header_items<'a>(header: &'a str, keyword: &str, name: &str) -> Vec<&'a str> {
+        let start = header.find(&format!("typedef {} {} {{", keyword, name)).unwrap_or_else(|| panic!("{} not in datp.h", name));
+        let body = &header[start..];
+        let end = body.find(&format!("}} {};", name)).unwrap();
+        body[body.find('{').unwrap() + 1..end].split([';', ',']).map(str::trim).filter(|item| !item.is_empty()).collect()
+    }
+
+    #[test]
+    fn test_header_matches_exports() {
+        // the header without comments and preprocessor lines, split into declarations
+        let header = include_str!("../include/datp.h");
+        let mut code = String::new();
+        for part in header.split("/*") {
+            code.push_str(part.split_once("*/").map_or(part, |(_, rest)| rest));
+        }
+        let code: String = code.lines().filter(|line| !line.trim_start().starts_with('#')).collect::<Vec<_>>().join("\n");
+        let declarations: Vec<String> = code.split(';').map(normalize).collect();
+        let declaration = |name: &str| {
+            declarations
+                .iter()
+                .find(|declaration| declaration.contains(&format!(" {}(", name)) || declaration.contains(&format!("*{}(", name)))
+                .unwrap_or_else(|| panic!("{} missing from datp.h", name))
+                .as_str()
+        };
+
+        let source = include_str!("c_api.rs");
+        let source = &source[..source.find("#[cfg(test)]").unwrap()];
+        let mut exports = 0;
+        for rest in source.split("pub extern \"C\" fn ").skip(1) {
+            let name = &rest[..rest.find('(').unwrap()];
+            let params = &rest[name.len() + 1..rest.find(')').unwrap()];
+            let after = &rest[rest.find(')').unwrap() + 1..rest.find('{').unwrap()];
+            let ret = after.trim().strip_prefix("->").map_or("void".to_string(), c_type);
+            let expected = normalize(&format!("{} {}({})", ret, name, c_params(params)));
+            assert_eq!(declaration(name), expected, "prototype of {}", name);
+            exports += 1;
+        }
+        // and nothing is declared that is not exported
+        let prototypes = declarations.iter().filter(|declaration| declaration.contains('(') && !declaration.starts_with("typedef"));
+        assert_eq!(prototypes.count(), exports);
+
+        let callback = source.split("pub type DatpWatchCallback = extern \"C\" fn(").nth(1).unwrap();
+        let callback = &callback[..callback.find(')').unwrap()];
+        let expected = normalize(&format!("typedef void (*DatpWatchCallback)({})", c_params(callback)));
+        assert!(declarations.contains(&expected), "{} missing from datp.h", expected);
+
+        for name in ["TotpQrConfigC", "TotpQrConfigV2C", "DatpTotpParams", "DatpAccountC", "DatpQrPngConfigC"] {
+            let fields: Vec<String> = rust_items(source, "struct", name)
+                .iter()
+                .map(|field| {
+                    let (field, ty) = field.trim_start_matches("pub ").split_once(':').unwrap();
+                    normalize(&format!("{} {}", c_type(ty), field.trim()))
+                })
+                .collect();
+            let declared: Vec<String> = header_items(&code, "struct", name).iter().map(|field| normalize(field)).collect();
+            assert_eq!(declared, fields, "fields of {}", name);
+        }
+
+        for name in ["DatpStatus", "DatpSecretCheck", "DatpFormat"] {
+            let variants = rust_items(source, "enum", name);
+            let declared = header_items(&code, "enum", name);
+            assert_eq!(declared.len(), variants.len(), "variants of {}", name);
+            for (declared, variant) in declared.iter().zip(&variants) {
+                let (constant, value) = declared.split_once(" = ").unwrap();
+                let (variant, expected) = variant.split_once(" = ").unwrap();
+                // FreeOtpPlus is DATP_FORMAT_FREE_OTP_PLUS
+                let mut snake = String::new();
+                for c in variant.chars() {
+                    if c.is_ascii_uppercase() && !snake.is_empty() {
+                        snake.push('_');
+                    }
+                    snake.push(c.to_ascii_uppercase());
+                }
+                assert!(constant.ends_with(&format!("_{}", snake)) && value == expected, "{} = {} in {}", constant, value, name);
+            }
+        }
+
+        for opaque in ["DatpTotp", "DatpWatch"] {
+            assert!(code.contains(&format!("typedef struct {0} {0};", opaque)), "{} missing from datp.h", opaque);
+        }
+        assert!(header.contains(&format!("#define DATP_ABI_VERSION {}\n", DATP_ABI_VERSION)));
+        assert!(header.contains(&format!("#define DATP_MIN_ENTROPY_BYTES {}\n", DATP_MIN_ENTROPY_BYTES)));
+    }
+
+    #[test]
+    fn test_secret_from_entropy() {
+        let secret = datp_secret_from_entropy(b"12345678901234567890".as_ptr(), 20);
+        assert_eq!(unsafe { CStr::from_ptr(secret) }.to_str().unwrap(), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        datp_free_secret(secret);
+
+        assert!(datp_secret_from_entropy(b"1234567890".as_ptr(), 10).is_null());
+        assert_eq!(unsafe { CStr::from_ptr(datp_last_error()) }.to_str().unwrap(), "at least 16 bytes of entropy are required");
+        assert!(datp_secret_from_entropy(std::ptr::null(), 20).is_null());
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_qr_png() {
+        let config = DatpQrPngConfigC {
+            account_name: c"alice".as_ptr(),
+            issuer: c"MyApp".as_ptr(),
+            module_size: 2,
+            dark_color: 0x000080,
+            light_color: 0xffffff,
+            ec_level: 1,
+            quiet_zone: 1,
+        };
+        let mut len = 0;
+        let png = datp_qr_png(c"JBSWY3DPEHPK3PXP".as_ptr(), &config, std::ptr::null(), &mut len);
+        let bytes = unsafe { std::slice::from_raw_parts(png, len) };
+        let account = Account { issuer: Some("MyApp".to_string()), ..Account::new("alice", "JBSWY3DPEHPK3PXP") };
+        let expected = QrPngConfig { module_size: 2, dark_color: [0, 0, 128], ..Default::default() };
+        assert_eq!(bytes, totp_qr_png(&account, &expected).unwrap());
+        datp_free_bytes(png, len);
+
+        assert!(datp_qr_png(c"JBSWY3DPEHPK3PXP".as_ptr(), std::ptr::null(), std::ptr::null(), &mut len).is_null());
+
+        // would need gigabytes for the bitmap, so it's refused before allocating
+        let config = DatpQrPngConfigC { module_size: u32::MAX, ..config };
+        assert!(datp_qr_png(c"JBSWY3DPEHPK3PXP".as_ptr(), &config, std::ptr::null(), &mut len).is_null());
+        assert_eq!(
+            unsafe { CStr::from_ptr(datp_last_error()) }.to_str().unwrap(),
+            "invalid module size '4294967295', expected at most 64 pixels"
+        );
+    }
+
+    #[test]
+    fn test_versions() {
+        assert_eq!(unsafe { CStr::from_ptr(datp_version()) }.to_str().unwrap(), env!("CARGO_PKG_VERSION"));
+        assert_eq!(datp_abi_version(), DATP_ABI_VERSION);
+    }
+
+    #[test]
+    fn test_guard_catches_panics() {
+        let status = guard(DatpStatus::Panic, || -> DatpStatus { panic!("boom") });
+        assert_eq!(status, DatpStatus::Panic);
+        let message = unsafe { CStr::from_ptr(datp_last_error()) };
+        assert_eq!(message.to_str().unwrap(), "internal error: boom");
+    }
 }
@@ -0,0 +1,181 @@
+/// Source of the current Unix time in seconds, for the parts of the crate that read the time
+/// themselves instead of taking it as an argument.
+///
+/// Implemented for `SystemClock` and for closures returning the time, which is handy for tests:
+///
+/// ```rust
+/// use datp::Clock;
+///
+/// let fixed = || 1_700_000_000;
+/// assert_eq!(fixed.now(), 1_700_000_000);
+/// ```
+pub trait Clock {
+    fn now(&self) -> u64;
+}
+
+impl<F: Fn() -> u64> Clock for F {
+    fn now(&self) -> u64 {
+        self()
+    }
+}
+
+/// The system clock; times before 1970 read as 0.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SystemClock;
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs())
+    }
+}
+
+/// Another clock corrected by `offset` seconds, e.g. the system clock adjusted by the offset
+/// that `ntp_offset` measured.
+///
+/// ```rust
+/// use datp::{Clock, OffsetClock};
+///
+/// let clock = OffsetClock { clock: || 1_000, offset: -30 };
+/// assert_eq!(clock.now(), 970);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OffsetClock<C> {
+    pub clock: C,
+    pub offset: i64,                 // seconds added to `clock`
+}
+
+impl<C: Clock> Clock for OffsetClock<C> {
+    fn now(&self) -> u64 {
+        self.clock.now().saturating_add_signed(self.offset)
+    }
+}
+
+/// A hardware tick counter (monotonic timer, RTC prescaler, ...) turned into Unix time from
+/// one known point: at `anchor_ticks` the time was `anchor_unix`. The anchor usually comes from
+/// the host when the device is provisioned or synchronized.
+///
+/// `ticks` reads the counter. Counters narrower than 64 bits must be extended by the caller so
+/// they don't wrap around.
+///
+/// ```rust
+/// use core::sync::atomic::{AtomicU64, Ordering};
+/// use datp::{Clock, MonotonicClock};
+///
+/// static TICKS: AtomicU64 = AtomicU64::new(5_000);
+/// let clock = MonotonicClock {
+///     ticks: || TICKS.load(Ordering::Relaxed),
+///     ticks_per_second: 1_000,
+///     anchor_ticks: 5_000,
+///     anchor_unix: 1_700_000_000,
+/// };
+/// TICKS.store(95_000, Ordering::Relaxed); // 90 s later
+/// assert_eq!(clock.now(), 1_700_000_090);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct MonotonicClock<T> {
+    pub ticks: T,
+    pub ticks_per_second: u64,
+    pub anchor_ticks: u64,           // counter value at `anchor_unix`
+    pub anchor_unix: u64,
+}
+
+impl<T: Fn() -> u64> Clock for MonotonicClock<T> {
+    /// Ticks before the anchor count as the anchor time; a `ticks_per_second` of 0 stays at
+    /// the anchor.
+    fn now(&self) -> u64 {
+        let elapsed = (self.ticks)().saturating_sub(self.anchor_ticks);
+        self.anchor_unix.saturating_add(elapsed.checked_div(self.ticks_per_second).unwrap_or(0))
+    }
+}
+
+/// A UTC calendar time as read from a real-time clock chip.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UtcDateTime {
+    pub year: u16,                   // e.g. 2024
+    pub month: u8,                   // 1 to 12
+    pub day: u8,                     // 1 to 31
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl UtcDateTime {
+    /// Seconds since 1970-01-01 UTC, or `None` for dates before 1970 or fields out of range.
+    ///
+    /// ```rust
+    /// use datp::UtcDateTime;
+    ///
+    /// let time = UtcDateTime { year: 2014, month: 1, day: 5, hour: 0, minute: 0, second: 0 };
+    /// assert_eq!(time.unix_time(), Some(1_388_880_000));
+    /// ```
+    pub fn unix_time(&self) -> Option<u64> {
+        let leap = self.year.is_multiple_of(4) && (!self.year.is_multiple_of(100) || self.year.is_multiple_of(400));
+        let month_days = match self.month {
+            2 if leap => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            1..=12 => 31,
+            _ => return None,
+        };
+        if self.year < 1970 || !(1..=month_days).contains(&self.day) || self.hour > 23 || self.minute > 59 || self.second > 59 {
+            return None;
+        }
+        // days from civil (Howard Hinnant), with March as the first month of the year
+        let (year, month) = (u64::from(self.year), u64::from(self.month));
+        let year = if month <= 2 { year - 1 } else { year };
+        let era = year / 400;
+        let year_of_era = year - era * 400;
+        let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + u64::from(self.day) - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146_097 + day_of_era - 719_468;
+        let seconds = u64::from(self.hour) * 3600 + u64::from(self.minute) * 60 + u64::from(self.second);
+        Some(days * 86_400 + seconds)
+    }
+}
+
+/// A calendar real-time clock (DS3231, PCF8523, the RTC peripheral of a microcontroller, ...)
+/// read through a closure returning the current UTC date and time. Invalid readings, such as
+/// a chip that lost power, read as 0 so that no valid code is generated from them.
+///
+/// ```rust
+/// use datp::{Clock, RtcClock, UtcDateTime};
+///
+/// let clock = RtcClock(|| UtcDateTime { year: 2024, month: 2, day: 29, hour: 12, minute: 0, second: 0 });
+/// assert_eq!(clock.now(), 1_709_208_000);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct RtcClock<F>(pub F);
+
+impl<F: Fn() -> UtcDateTime> Clock for RtcClock<F> {
+    fn now(&self) -> u64 {
+        (self.0)().unix_time().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utc_date_time() {
+        let time = |year, month, day, hour, minute, second| UtcDateTime { year, month, day, hour, minute, second };
+        assert_eq!(time(1970, 1, 1, 0, 0, 0).unix_time(), Some(0));
+        assert_eq!(time(2000, 3, 1, 0, 0, 1).unix_time(), Some(951_868_801));
+        assert_eq!(time(2038, 1, 19, 3, 14, 8).unix_time(), Some(2_147_483_648));
+        assert_eq!(time(2100, 12, 31, 23, 59, 59).unix_time(), Some(4_133_980_799));
+        assert_eq!(time(2100, 2, 29, 0, 0, 0).unix_time(), None);
+        assert_eq!(time(1969, 12, 31, 23, 59, 59).unix_time(), None);
+        assert_eq!(time(2024, 13, 1, 0, 0, 0).unix_time(), None);
+        assert_eq!(time(2024, 1, 1, 24, 0, 0).unix_time(), None);
+    }
+
+    #[test]
+    fn test_monotonic_clock() {
+        let clock = MonotonicClock { ticks: || 10, ticks_per_second: 0, anchor_ticks: 20, anchor_unix: 100 };
+        assert_eq!(clock.now(), 100);
+        let clock = MonotonicClock { ticks: || 32_768 * 61 + 5, ticks_per_second: 32_768, anchor_ticks: 0, anchor_unix: 100 };
+        assert_eq!(clock.now(), 161);
+    }
+}
@@ -0,0 +1,557 @@
+// Key derivation and authenticated encryption for the encrypted backup
+// formats of other authenticators: PBKDF2-HMAC-SHA256 and -SHA1 (RFC 8018),
+// scrypt (RFC 7914), AES-256-GCM (FIPS 197, SP 800-38D) and, for Authy,
+// AES-256-CBC with PKCS#7 padding.
+//
+// AES uses the classic S-box table, so it is not hardened against cache
+// timing attacks. That is acceptable for decrypting files at rest, but these
+// primitives should not be exposed to attacker-timed network input.
+
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+use alloc::{format, string::String, vec, vec::Vec};
+
+#[cfg(feature = "alloc")]
+use super::{hash, Algorithm};
+
+/// Compares two byte strings without short-circuiting on the first difference.
+pub(crate) fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    core::hint::black_box(a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y))) == 0
+}
+
+/// Compares two codes (or recovery codes, tokens, ...) in constant time, so the time taken
+/// does not reveal how many leading characters were right. Use it instead of `==` wherever a
+/// user-supplied code is checked against the expected one.
+///
+/// Only the contents are protected: codes of different lengths are rejected right away, which
+/// is fine as the length of a code is not a secret.
+///
+/// # Example
+/// ```rust
+/// use datp::ct_eq_codes;
+///
+/// assert!(ct_eq_codes("123456", "123456"));
+/// assert!(!ct_eq_codes("123456", "123457"));
+/// assert!(!ct_eq_codes("123456", "12345"));
+/// ```
+pub fn ct_eq_codes(a: impl AsRef<[u8]>, b: impl AsRef<[u8]>) -> bool {
+    ct_eq(a.as_ref(), b.as_ref())
+}
+
+/// Overwrites secret material with zeros in a way the compiler can't optimize out because
+/// the buffer is about to be freed.
+pub(crate) fn wipe(bytes: &mut [u8]) {
+    for byte in bytes.iter_mut() {
+        unsafe { core::ptr::write_volatile(byte, 0) };
+    }
+    core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+}
+
+#[cfg(feature = "alloc")]
+pub(crate) fn pbkdf2_sha256(password: &[u8], salt: &[u8], iterations: u32, len: usize) -> Vec<u8> {
+    pbkdf2(Algorithm::Sha256, password, salt, iterations, len)
+}
+
+/// Highest PBKDF2 iteration count accepted from a backup, so a crafted file can't keep the
+/// importer busy for hours.
+#[cfg(feature = "std")]
+pub(crate) const MAX_PBKDF2_ITERATIONS: u32 = 10_000_000;
+
+#[cfg(feature = "std")]
+pub(crate) fn pbkdf2_sha1(password: &[u8], salt: &[u8], iterations: u32, len: usize) -> Vec<u8> {
+    pbkdf2(Algorithm::Sha1, password, salt, iterations, len)
+}
+
+#[cfg(feature = "alloc")]
+fn pbkdf2(algorithm: Algorithm, password: &[u8], salt: &[u8], iterations: u32, len: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(len + 32);
+    let mut block = 1u32;
+    while out.len() < len {
+        let mut message = salt.to_vec();
+        message.extend_from_slice(&block.to_be_bytes());
+        let mut u = hash::hmac(algorithm, password, &message);
+        let mut t = u.clone();
+        for _ in 1..iterations {
+            u = hash::hmac(algorithm, password, &u);
+            t.iter_mut().zip(&*u).for_each(|(t, u)| *t ^= u);
+        }
+        out.extend_from_slice(&t);
+        block += 1;
+    }
+    out.truncate(len);
+    out
+}
+
+/// Upper bound on scrypt memory (`128 * n * r` bytes) accepted from a file.
+const SCRYPT_MAX_MEMORY: u64 = 256 * 1024 * 1024;
+
+#[cfg(feature = "alloc")]
+/// Derives `len` bytes with scrypt, or `None` if the parameters are invalid
+/// or would need more than 256 MiB of memory.
+pub(crate) fn scrypt(password: &[u8], salt: &[u8], n: u64, r: u32, p: u32, len: usize) -> Option<Vec<u8>> {
+    if n < 2 || !n.is_power_of_two() || r == 0 || p == 0 || p > 16 {
+        return None;
+    }
+    if n.checked_mul(r as u64)?.checked_mul(128)? > SCRYPT_MAX_MEMORY {
+        return None;
+    }
+
+    let block_len = 128 * r as usize;
+    let mut blocks = pbkdf2_sha256(password, salt, 1, block_len * p as usize);
+    for block in blocks.chunks_mut(block_len) {
+        romix(block, n as usize, r as usize);
+    }
+    Some(pbkdf2_sha256(password, &blocks, 1, len))
+}
+
+#[cfg(feature = "alloc")]
+fn romix(block: &mut [u8], n: usize, r: usize) {
+    let words = 32 * r;
+    let mut x: Vec<u32> = block
+        .chunks_exact(4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .collect();
+
+    let mut v = vec![0u32; words * n];
+    for i in 0..n {
+        v[i * words..(i + 1) * words].copy_from_slice(&x);
+        block_mix(&mut x, r);
+    }
+    for _ in 0..n {
+        let j = x[(2 * r - 1) * 16] as usize & (n - 1);
+        x.iter_mut().zip(&v[j * words..(j + 1) * words]).for_each(|(x, v)| *x ^= v);
+        block_mix(&mut x, r);
+    }
+
+    for (chunk, word) in block.chunks_exact_mut(4).zip(x) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+}
+
+#[cfg(feature = "alloc")]
+fn block_mix(b: &mut [u32], r: usize) {
+    let mut x = [0u32; 16];
+    x.copy_from_slice(&b[(2 * r - 1) * 16..]);
+    let mut y = vec![0u32; 32 * r];
+    for i in 0..2 * r {
+        x.iter_mut().zip(&b[i * 16..(i + 1) * 16]).for_each(|(x, b)| *x ^= b);
+        salsa20_8(&mut x);
+        y[i * 16..(i + 1) * 16].copy_from_slice(&x);
+    }
+    // even blocks first, then odd blocks
+    for i in 0..r {
+        b[i * 16..(i + 1) * 16].copy_from_slice(&y[2 * i * 16..(2 * i + 1) * 16]);
+        b[(r + i) * 16..(r + i + 1) * 16].copy_from_slice(&y[(2 * i + 1) * 16..(2 * i + 2) * 16]);
+    }
+}
+
+fn salsa20_8(b: &mut [u32; 16]) {
+    let mut x = *b;
+    let quarter = |x: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize| {
+        x[b] ^= x[a].wrapping_add(x[d]).rotate_left(7);
+        x[c] ^= x[b].wrapping_add(x[a]).rotate_left(9);
+        x[d] ^= x[c].wrapping_add(x[b]).rotate_left(13);
+        x[a] ^= x[d].wrapping_add(x[c]).rotate_left(18);
+    };
+    for _ in 0..4 {
+        quarter(&mut x, 0, 4, 8, 12);
+        quarter(&mut x, 5, 9, 13, 1);
+        quarter(&mut x, 10, 14, 2, 6);
+        quarter(&mut x, 15, 3, 7, 11);
+        quarter(&mut x, 0, 1, 2, 3);
+        quarter(&mut x, 5, 6, 7, 4);
+        quarter(&mut x, 10, 11, 8, 9);
+        quarter(&mut x, 15, 12, 13, 14);
+    }
+    b.iter_mut().zip(x).for_each(|(b, x)| *b = b.wrapping_add(x));
+}
+
+struct Aes256 {
+    round_keys: [[u8; 16]; 15],
+}
+
+impl Aes256 {
+    fn new(key: &[u8; 32]) -> Self {
+        let mut w = [[0u8; 4]; 60];
+        for (i, word) in key.chunks_exact(4).enumerate() {
+            w[i].copy_from_slice(word);
+        }
+        let mut rcon = 1u8;
+        for i in 8..60 {
+            let mut temp = w[i - 1];
+            if i % 8 == 0 {
+                temp = [SBOX[temp[1] as usize] ^ rcon, SBOX[temp[2] as usize], SBOX[temp[3] as usize], SBOX[temp[0] as usize]];
+                rcon = xtime(rcon);
+            } else if i % 8 == 4 {
+                temp = temp.map(|b| SBOX[b as usize]);
+            }
+            for j in 0..4 {
+                w[i][j] = w[i - 8][j] ^ temp[j];
+            }
+        }
+
+        let mut round_keys = [[0u8; 16]; 15];
+        for (round, key) in round_keys.iter_mut().enumerate() {
+            for col in 0..4 {
+                key[col * 4..col * 4 + 4].copy_from_slice(&w[round * 4 + col]);
+            }
+        }
+        Aes256 { round_keys }
+    }
+
+    fn encrypt_block(&self, block: &mut [u8; 16]) {
+        xor_into(block, &self.round_keys[0]);
+        for round in 1..15 {
+            for b in block.iter_mut() {
+                *b = SBOX[*b as usize];
+            }
+            // shift rows, the state is column-major
+            let state = *block;
+            for row in 1..4 {
+                for col in 0..4 {
+                    block[row + 4 * col] = state[row + 4 * ((col + row) % 4)];
+                }
+            }
+            if round != 14 {
+                for col in block.chunks_exact_mut(4) {
+                    let [a, b, c, d] = [col[0], col[1], col[2], col[3]];
+                    let all = a ^ b ^ c ^ d;
+                    col[0] ^= all ^ xtime(a ^ b);
+                    col[1] ^= all ^ xtime(b ^ c);
+                    col[2] ^= all ^ xtime(c ^ d);
+                    col[3] ^= all ^ xtime(d ^ a);
+                }
+            }
+            xor_into(block, &self.round_keys[round]);
+        }
+    }
+
+    /// The inverse cipher, running the rounds of `encrypt_block` backwards.
+    #[cfg(feature = "authy")]
+    fn decrypt_block(&self, block: &mut [u8; 16]) {
+        xor_into(block, &self.round_keys[14]);
+        for round in (0..14).rev() {
+            let state = *block;
+            for row in 1..4 {
+                for col in 0..4 {
+                    block[row + 4 * ((col + row) % 4)] = state[row + 4 * col];
+                }
+            }
+            for b in block.iter_mut() {
+                *b = INV_SBOX[*b as usize];
+            }
+            xor_into(block, &self.round_keys[round]);
+            if round != 0 {
+                // InvMixColumns is MixColumns after this preprocessing step
+                for col in block.chunks_exact_mut(4) {
+                    let u = xtime(xtime(col[0] ^ col[2]));
+                    let v = xtime(xtime(col[1] ^ col[3]));
+                    col[0] ^= u;
+                    col[1] ^= v;
+                    col[2] ^= u;
+                    col[3] ^= v;
+                    let [a, b, c, d] = [col[0], col[1], col[2], col[3]];
+                    let all = a ^ b ^ c ^ d;
+                    col[0] ^= all ^ xtime(a ^ b);
+                    col[1] ^= all ^ xtime(b ^ c);
+                    col[2] ^= all ^ xtime(c ^ d);
+                    col[3] ^= all ^ xtime(d ^ a);
+                }
+            }
+        }
+    }
+}
+
+fn xtime(b: u8) -> u8 {
+    (b << 1) ^ (((b >> 7) & 1) * 0x1b)
+}
+
+fn xor_into(block: &mut [u8; 16], other: &[u8; 16]) {
+    block.iter_mut().zip(other).for_each(|(b, o)| *b ^= o);
+}
+
+/// Multiplication in GF(2^128) with the GCM bit order, without secret-dependent branches.
+fn gf_mul(x: u128, y: u128) -> u128 {
+    let mut z = 0u128;
+    let mut v = y;
+    for i in 0..128 {
+        z ^= v & 0u128.wrapping_sub((x >> (127 - i)) & 1);
+        v = (v >> 1) ^ ((0xe1u128 << 120) & 0u128.wrapping_sub(v & 1));
+    }
+    z
+}
+
+fn ghash(h: u128, aad: &[u8], ciphertext: &[u8]) -> u128 {
+    let mut y = 0u128;
+    for data in [aad, ciphertext] {
+        for chunk in data.chunks(16) {
+            let mut block = [0u8; 16];
+            block[..chunk.len()].copy_from_slice(chunk);
+            y = gf_mul(y ^ u128::from_be_bytes(block), h);
+        }
+    }
+    let lengths = ((aad.len() as u128 * 8) << 64) | (ciphertext.len() as u128 * 8);
+    gf_mul(y ^ lengths, h)
+}
+
+#[cfg(feature = "alloc")]
+/// Runs AES-256-GCM in either direction and returns the tag over the ciphertext.
+fn gcm(key: &[u8], nonce: &[u8], input: &[u8], aad: &[u8], encrypt: bool) -> Option<(Vec<u8>, [u8; 16])> {
+    let key: &[u8; 32] = key.try_into().ok()?;
+    if nonce.len() != 12 {
+        return None;
+    }
+    let aes = Aes256::new(key);
+
+    let mut h = [0u8; 16];
+    aes.encrypt_block(&mut h);
+    let h = u128::from_be_bytes(h);
+
+    let mut counter = [0u8; 16];
+    counter[..12].copy_from_slice(nonce);
+    counter[15] = 1;
+    let mut tag_mask = counter;
+    aes.encrypt_block(&mut tag_mask);
+
+    let mut output = Vec::with_capacity(input.len());
+    for chunk in input.chunks(16) {
+        let next = u32::from_be_bytes([counter[12], counter[13], counter[14], counter[15]]).wrapping_add(1);
+        counter[12..].copy_from_slice(&next.to_be_bytes());
+        let mut keystream = counter;
+        aes.encrypt_block(&mut keystream);
+        output.extend(chunk.iter().zip(keystream).map(|(b, k)| b ^ k));
+    }
+
+    let ciphertext = if encrypt { &output[..] } else { input };
+    let tag = (ghash(h, aad, ciphertext) ^ u128::from_be_bytes(tag_mask)).to_be_bytes();
+    Some((output, tag))
+}
+
+#[cfg(feature = "alloc")]
+/// Encrypts with AES-256-GCM (32 byte key, 12 byte nonce), returning ciphertext and tag.
+pub(crate) fn aes256_gcm_encrypt(key: &[u8], nonce: &[u8], plaintext: &[u8], aad: &[u8]) -> Option<(Vec<u8>, [u8; 16])> {
+    gcm(key, nonce, plaintext, aad, true)
+}
+
+#[cfg(feature = "alloc")]
+/// Decrypts with AES-256-GCM, or returns `None` if the key is wrong or the data was tampered with.
+pub(crate) fn aes256_gcm_decrypt(key: &[u8], nonce: &[u8], ciphertext: &[u8], tag: &[u8], aad: &[u8]) -> Option<Vec<u8>> {
+    let (plaintext, expected) = gcm(key, nonce, ciphertext, aad, false)?;
+    if ct_eq(&expected, tag) { Some(plaintext) } else { None }
+}
+
+#[cfg(feature = "authy")]
+/// Decrypts AES-256-CBC with PKCS#7 padding, or returns `None` if the sizes or the padding are
+/// wrong, which usually means a wrong key.
+pub(crate) fn aes256_cbc_decrypt(key: &[u8], iv: &[u8], ciphertext: &[u8]) -> Option<Vec<u8>> {
+    let key: &[u8; 32] = key.try_into().ok()?;
+    let mut previous: [u8; 16] = iv.try_into().ok()?;
+    if ciphertext.is_empty() || !ciphertext.len().is_multiple_of(16) {
+        return None;
+    }
+    let aes = Aes256::new(key);
+
+    let mut output = Vec::with_capacity(ciphertext.len());
+    for chunk in ciphertext.chunks_exact(16) {
+        let mut block: [u8; 16] = chunk.try_into().expect("chunks are 16 bytes");
+        aes.decrypt_block(&mut block);
+        xor_into(&mut block, &previous);
+        output.extend_from_slice(&block);
+        previous.copy_from_slice(chunk);
+    }
+
+    let padding = *output.last()? as usize;
+    if !(1..=16).contains(&padding) || !output[output.len() - padding..].iter().all(|&b| b as usize == padding) {
+        return None;
+    }
+    output.truncate(output.len() - padding);
+    Some(output)
+}
+
+#[cfg(feature = "test-rng")]
+std::thread_local! {
+    static SEEDED_RNG: core::cell::RefCell<Option<rand::rngs::StdRng>> = const { core::cell::RefCell::new(None) };
+}
+
+/// Fills a buffer of `len` bytes from the thread-local CSPRNG, or from the generator of
+/// `seed_rng` if one is set on this thread.
+#[cfg(feature = "std")]
+pub(crate) fn random_bytes(len: usize) -> Vec<u8> {
+    use rand::Rng;
+
+    let mut bytes = vec![0u8; len];
+    #[cfg(feature = "test-rng")]
+    if SEEDED_RNG.with_borrow_mut(|rng| rng.as_mut().map(|rng| rng.fill(&mut bytes[..]))).is_some() {
+        return bytes;
+    }
+    rand::rng().fill(&mut bytes[..]);
+    bytes
+}
+
+/// Makes everything random on the current thread come from a generator seeded with `seed`:
+/// secrets from `generate_totp_secret`, salts and nonces of encrypted exports, UUIDs and
+/// migration batch ids. Integration tests and golden files then produce the same output on
+/// every run and platform (for the same version of datp).
+///
+/// Only for tests: with a known seed, the secrets are known too. `unseed_rng` switches back.
+///
+/// # Example
+/// ```rust
+/// use datp::{generate_totp_secret, seed_rng, unseed_rng};
+///
+/// seed_rng(42);
+/// let first = generate_totp_secret(20);
+/// seed_rng(42);
+/// assert_eq!(generate_totp_secret(20), first);
+/// unseed_rng();
+/// ```
+#[cfg(feature = "test-rng")]
+pub fn seed_rng(seed: u64) {
+    use rand::SeedableRng;
+
+    SEEDED_RNG.set(Some(rand::rngs::StdRng::seed_from_u64(seed)));
+}
+
+/// Switches the current thread back to the system CSPRNG after `seed_rng`.
+#[cfg(feature = "test-rng")]
+pub fn unseed_rng() {
+    SEEDED_RNG.set(None);
+}
+
+/// Generates a random version 4 UUID in its hyphenated form.
+#[cfg(feature = "std")]
+pub(crate) fn random_uuid() -> String {
+    let mut bytes = random_bytes(16);
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = hex_encode(&bytes);
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+#[cfg(feature = "alloc")]
+pub(crate) fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(feature = "alloc")]
+pub(crate) fn hex_decode(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
+
+const SBOX: [u8; 256] = [
+    0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab, 0x76,
+    0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0, 0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4, 0x72, 0xc0,
+    0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc, 0x34, 0xa5, 0xe5, 0xf1, 0x71, 0xd8, 0x31, 0x15,
+    0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a, 0x07, 0x12, 0x80, 0xe2, 0xeb, 0x27, 0xb2, 0x75,
+    0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0, 0x52, 0x3b, 0xd6, 0xb3, 0x29, 0xe3, 0x2f, 0x84,
+    0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b, 0x6a, 0xcb, 0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf,
+    0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85, 0x45, 0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8,
+    0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5, 0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2,
+    0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44, 0x17, 0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73,
+    0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a, 0x90, 0x88, 0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb,
+    0xe0, 0x32, 0x3a, 0x0a, 0x49, 0x06, 0x24, 0x5c, 0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79,
+    0xe7, 0xc8, 0x37, 0x6d, 0x8d, 0xd5, 0x4e, 0xa9, 0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08,
+    0xba, 0x78, 0x25, 0x2e, 0x1c, 0xa6, 0xb4, 0xc6, 0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a,
+    0x70, 0x3e, 0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e, 0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e,
+    0xe1, 0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94, 0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
+    0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68, 0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb, 0x16,
+];
+
+#[cfg(feature = "authy")]
+const INV_SBOX: [u8; 256] = {
+    let mut inverse = [0u8; 256];
+    let mut i = 0;
+    while i < 256 {
+        inverse[SBOX[i] as usize] = i as u8;
+        i += 1;
+    }
+    inverse
+};
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wipe() {
+        let mut secret = b"12345678901234567890".to_vec();
+        wipe(&mut secret);
+        assert_eq!(secret, [0; 20]);
+    }
+
+    #[test]
+    fn test_pbkdf2_sha256() {
+        assert_eq!(
+            hex_encode(&pbkdf2_sha256(b"password", b"salt", 4096, 40)),
+            "c5e478d59288c841aa530db6845c4c8d962893a001ce4e11a4963873aa98134af7ad98c1b458ce3f"
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_pbkdf2_sha1() {
+        // RFC 6070
+        assert_eq!(hex_encode(&pbkdf2_sha1(b"password", b"salt", 4096, 20)), "4b007901b765489abead49d926f721d065a429c1");
+    }
+
+    #[test]
+    fn test_scrypt_rfc7914() {
+        assert_eq!(
+            hex_encode(&scrypt(b"", b"", 16, 1, 1, 64).unwrap()),
+            "77d6576238657b203b19ca42c18a0497f16b4844e3074ae8dfdffa3fede21442\
+             fcd0069ded0948f8326a753a0fc81f17e8d3e0fb2e0d3628cf35e20c38d18906"
+        );
+        assert!(scrypt(b"", b"", 1000, 1, 1, 32).is_none());
+        assert!(scrypt(b"", b"", 1 << 30, 8, 1, 32).is_none());
+    }
+
+    #[test]
+    fn test_aes256_gcm() {
+        // SP 800-38D test case 16
+        let key = hex_decode("feffe9928665731c6d6a8f9467308308feffe9928665731c6d6a8f9467308308").unwrap();
+        let nonce = hex_decode("cafebabefacedbaddecaf888").unwrap();
+        let plaintext = hex_decode(
+            "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a721c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39",
+        )
+        .unwrap();
+        let aad = hex_decode("feedfacedeadbeeffeedfacedeadbeefabaddad2").unwrap();
+
+        let (ciphertext, tag) = aes256_gcm_encrypt(&key, &nonce, &plaintext, &aad).unwrap();
+        assert_eq!(
+            hex_encode(&ciphertext),
+            "522dc1f099567d07f47f37a32a84427d643a8cdcbfe5c0c97598a2bd2555d1aa8cb08e48590dbb3da7b08b1056828838c5f61e6393ba7a0abcc9f662"
+        );
+        assert_eq!(hex_encode(&tag), "76fc6ece0f4e1768cddf8853bb2d551b");
+        assert_eq!(aes256_gcm_decrypt(&key, &nonce, &ciphertext, &tag, &aad).unwrap(), plaintext);
+
+        let mut tampered = tag;
+        tampered[0] ^= 1;
+        assert!(aes256_gcm_decrypt(&key, &nonce, &ciphertext, &tampered, &aad).is_none());
+    }
+
+    #[cfg(feature = "authy")]
+    #[test]
+    fn test_aes256_cbc() {
+        // FIPS 197 appendix C.3
+        let key = hex_decode("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f").unwrap();
+        let aes = Aes256::new(key.as_slice().try_into().unwrap());
+        let mut block: [u8; 16] = hex_decode("8ea2b7ca516745bfeafc49904b496089").unwrap().try_into().unwrap();
+        aes.decrypt_block(&mut block);
+        assert_eq!(hex_encode(&block), "00112233445566778899aabbccddeeff");
+        aes.encrypt_block(&mut block);
+        assert_eq!(hex_encode(&block), "8ea2b7ca516745bfeafc49904b496089");
+
+        // produced with `openssl enc -aes-256-cbc`
+        let iv = hex_decode("0f0e0d0c0b0a09080706050403020100").unwrap();
+        let ciphertext =
+            hex_decode("8f3b2db1bf354178e7725b3dc6a6ba4a50dd5248b3f9a86a61c82a52772c644987dc7d701419a7902079731c3fdbc05b").unwrap();
+        assert_eq!(aes256_cbc_decrypt(&key, &iv, &ciphertext).unwrap(), b"Two blocks of plaintext, padded.");
+        assert!(aes256_cbc_decrypt(&[7; 32], &iv, &ciphertext).is_none());
+        assert!(aes256_cbc_decrypt(&key, &iv, &ciphertext[..40]).is_none());
+    }
+}
@@ -0,0 +1,206 @@
+// Generic CSV format for bulk provisioning, one account per row:
+//
+//     issuer,label,secret,algorithm,digits,period,type,counter
+//     ACME,alice@acme.com,JBSWY3DPEHPK3PXP,SHA1,6,30,totp,
+//     ACME,door-badge-17,GEZDGNBVGY3TQOJQ,SHA1,6,,hotp,0
+//
+// The header row is required; `label` and `secret` are the only mandatory
+// columns, the order is free and unknown columns are ignored. Empty cells fall
+// back to the defaults of `Account::new` (SHA1, 6 digits, 30 seconds, totp).
+// Fields follow RFC 4180 quoting.
+
+use super::{uri, Account, DatpError, OtpKind};
+
+const COLUMNS: [&str; 8] = ["issuer", "label", "secret", "algorithm", "digits", "period", "type", "counter"];
+
+/// Reads accounts from CSV in the documented schema (see the README for the column list).
+///
+/// Errors in a row are reported as `DatpError::CsvRow` with the 1-based line number.
+///
+/// # Example
+/// ```rust
+/// use datp::import_csv;
+///
+/// let csv = "issuer,label,secret\nACME,alice@acme.com,JBSWY3DPEHPK3PXP\n";
+/// let accounts = import_csv(csv).unwrap();
+/// assert_eq!(accounts[0].account_name, "alice@acme.com");
+/// ```
+pub fn import_csv(input: &str) -> Result<Vec<Account>, DatpError> {
+    let mut rows = parse_rows(input)?.into_iter();
+    let (_, header) = rows.next().ok_or_else(|| header_error("missing header row"))?;
+    let header: Vec<String> = header.iter().map(|name| name.trim().to_ascii_lowercase()).collect();
+    let column = |name: &str| header.iter().position(|column| column == name);
+    if column("label").is_none() || column("secret").is_none() {
+        return Err(header_error("header must contain 'label' and 'secret' columns"));
+    }
+
+    let mut accounts = Vec::new();
+    for (line, row) in rows {
+        if row.iter().all(|cell| cell.trim().is_empty()) {
+            continue;
+        }
+        let cell = |name: &str| column(name).and_then(|i| row.get(i)).map(|cell| cell.trim()).filter(|cell| !cell.is_empty());
+        let account = parse_account(cell).map_err(|error| DatpError::CsvRow { line, error: Box::new(error) })?;
+        accounts.push(account);
+    }
+    Ok(accounts)
+}
+
+/// Writes accounts as CSV in the documented schema, header row included.
+pub fn export_csv(accounts: &[Account]) -> String {
+    let mut out = COLUMNS.join(",");
+    out.push_str("\r\n");
+    for account in accounts {
+        let (period, counter) = match account.kind {
+            OtpKind::Hotp => (String::new(), account.counter.unwrap_or(0).to_string()),
+            OtpKind::Totp | OtpKind::Steam => (account.period.to_string(), String::new()),
+        };
+        let cells = [
+            account.issuer.clone().unwrap_or_default(),
+            account.account_name.clone(),
+            account.secret.clone(),
+            account.algorithm.as_str().to_string(),
+            account.digits.to_string(),
+            period,
+            account.kind.as_str().to_string(),
+            counter,
+        ];
+        let cells: Vec<String> = cells.iter().map(|cell| quote(cell)).collect();
+        out.push_str(&cells.join(","));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+fn parse_account<'a>(cell: impl Fn(&str) -> Option<&'a str>) -> Result<Account, DatpError> {
+    let label = cell("label").ok_or(DatpError::InvalidLabel)?;
+    let secret = uri::normalize_secret(cell("secret").ok_or(DatpError::MissingSecret)?)?;
+    let mut account = Account::new(label, secret);
+    account.issuer = cell("issuer").map(str::to_string);
+
+    if let Some(algorithm) = cell("algorithm") {
+        account.algorithm = uri::parse_algorithm(algorithm)?;
+    }
+    if let Some(digits) = cell("digits") {
+        account.digits = uri::parse_digits(digits)?;
+    }
+    if let Some(period) = cell("period") {
+        account.period = match period.parse::<u64>() {
+            Ok(period) if period > 0 => period,
+            _ => return Err(DatpError::InvalidPeriod(period.to_string())),
+        };
+    }
+    match cell("type").map(str::to_ascii_lowercase).as_deref() {
+        None | Some("totp") => {}
+        Some("hotp") => account.kind = OtpKind::Hotp,
+        Some("steam") => {
+            account.kind = OtpKind::Steam;
+            account.digits = 5;
+        }
+        Some(other) => return Err(DatpError::UnsupportedType(other.to_string())),
+    }
+    if let Some(counter) = cell("counter") {
+        account.counter = Some(counter.parse().map_err(|_| DatpError::InvalidCounter(counter.to_string()))?);
+    }
+    if account.kind == OtpKind::Hotp && account.counter.is_none() {
+        return Err(DatpError::MissingCounter);
+    }
+    Ok(account)
+}
+
+pub(crate) fn header_error(reason: &str) -> DatpError {
+    DatpError::CsvRow { line: 1, error: Box::new(DatpError::InvalidBackup(reason.to_string())) }
+}
+
+fn quote(cell: &str) -> String {
+    if cell.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+/// Splits RFC 4180 CSV into rows of fields, each with its starting line number.
+pub(crate) fn parse_rows(input: &str) -> Result<Vec<(usize, Vec<String>)>, DatpError> {
+    let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut line = 1;
+    let mut row_line = 1;
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, in_quotes) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', true) => in_quotes = false,
+            ('"', false) if field.is_empty() => in_quotes = true,
+            (',', false) => row.push(std::mem::take(&mut field)),
+            ('\r', false) if chars.peek() == Some(&'\n') => {}
+            ('\n', false) => {
+                row.push(std::mem::take(&mut field));
+                rows.push((row_line, std::mem::take(&mut row)));
+                line += 1;
+                row_line = line;
+            }
+            (c, _) => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+        }
+    }
+    if in_quotes {
+        return Err(DatpError::CsvRow { line: row_line, error: Box::new(DatpError::InvalidBackup("unterminated quoted field".to_string())) });
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push((row_line, row));
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Algorithm;
+
+    #[test]
+    fn test_import_csv() {
+        let csv = "Label,Secret,Issuer,Digits,Type,Counter,Notes\r\n\
+                   alice@acme.com,JBSWY3DPEHPK3PXP,\"ACME, Inc.\",8,,,first\r\n\
+                   \r\n\
+                   badge-17,GEZDGNBVGY3TQOJQ,ACME,,hotp,3,\"multi\nline\"\r\n";
+        let accounts = import_csv(csv).unwrap();
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[0].issuer.as_deref(), Some("ACME, Inc."));
+        assert_eq!(accounts[0].digits, 8);
+        assert_eq!(accounts[1].kind, OtpKind::Hotp);
+        assert_eq!(accounts[1].counter, Some(3));
+    }
+
+    #[test]
+    fn test_csv_roundtrip() {
+        let mut alice = Account::new("alice \"the admin\"", "JBSWY3DPEHPK3PXP");
+        alice.issuer = Some("ACME, Inc.".to_string());
+        alice.algorithm = Algorithm::Sha512;
+        let accounts = vec![alice, Account::new_hotp("badge", "GEZDGNBVGY3TQOJQ", 5), Account::new_steam("gaben", "JBSWY3DPEHPK3PXP")];
+        assert_eq!(import_csv(&export_csv(&accounts)).unwrap(), accounts);
+    }
+
+    #[test]
+    fn test_import_csv_errors() {
+        let csv = "issuer,label,secret,digits\nA,a,JBSWY3DPEHPK3PXP,6\nB,b,JBSWY3DPEHPK3PXP,12\n";
+        assert_eq!(
+            import_csv(csv),
+            Err(DatpError::CsvRow { line: 3, error: Box::new(DatpError::InvalidDigits("12".to_string())) })
+        );
+        assert!(matches!(import_csv("issuer,secret\n"), Err(DatpError::CsvRow { line: 1, .. })));
+        assert!(matches!(import_csv("label,secret\n\"a,JBSWY3DPEHPK3PXP\n"), Err(DatpError::CsvRow { line: 2, .. })));
+    }
+}
@@ -0,0 +1,266 @@
+#![allow(clippy::not_unsafe_ptr_arg_deref)]
+
+// FFI surface shaped for Dart (`dart:ffi`), used by dart/datp.dart.
+//
+// Everything crosses the boundary as UTF-8 strings, 64-bit integers and one plain struct.
+// Accounts are passed as otpauth URIs and kinds and algorithms as their names, so Dart never
+// has to mirror a Rust enum. Unix times are signed because Dart has no unsigned integers.
+// Strings returned here are freed with datp_dart_string_free, which wipes them first.
+
+use std::ffi::CString;
+use std::os::raw::c_char;
+
+use super::c_api::{c_str, fail, guard, into_raw, DatpStatus};
+use super::*;
+
+const MAX_SKEW: u64 = 1_000;           // as the CLI's --window; each step is an HMAC on the isolate
+
+/// An account as seen from Dart; every string is set, an account without issuer has `""`.
+#[repr(C)]
+#[doc(hidden)]
+pub struct DatpDartAccount {
+    pub kind: *mut c_char,              // "totp", "hotp" or "steam"
+    pub issuer: *mut c_char,
+    pub account_name: *mut c_char,
+    pub secret: *mut c_char,            // base32
+    pub algorithm: *mut c_char,         // "SHA1", "SHA256" or "SHA512"
+    pub digits: i64,
+    pub period: i64,                    // seconds
+    pub counter: i64,                   // HOTP moving factor, 0 for other kinds
+}
+
+fn account(uri: *const c_char) -> Result<Account, DatpStatus> {
+    Account::from_uri(c_str(uri, "uri")?).map_err(|e| fail(DatpStatus::InvalidInput, &e.to_string()))
+}
+
+fn unix_time(unix_time: i64) -> Result<u64, DatpStatus> {
+    u64::try_from(unix_time).map_err(|_| fail(DatpStatus::InvalidArgument, "unix_time must not be negative"))
+}
+
+/// The code of `account` at `unix_time`; HOTP accounts use their counter.
+fn code(account: &Account, unix_time: u64) -> Result<String, DatpStatus> {
+    let secret = &account.secret;
+    let code = match account.kind {
+        OtpKind::Totp => totp_raw_with(secret, account.algorithm, account.digits, account.period, 0, unix_time)
+            .map(|code| format!("{:0width$}", code, width = account.digits as usize)),
+        OtpKind::Hotp => hotp_raw_with(secret, account.algorithm, account.digits, account.counter.unwrap_or(0))
+            .map(|code| format!("{:0width$}", code, width = account.digits as usize)),
+        OtpKind::Steam => steam_raw(secret, unix_time),
+    };
+    code.ok_or_else(|| fail(DatpStatus::InvalidSecret, "secret is not valid base32"))
+}
+
+fn string(s: String) -> *mut c_char {
+    CString::new(s.replace('\0', "")).unwrap().into_raw()
+}
+
+// message of the last failure on the calling thread, see datp_last_error
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_dart_last_error() -> *const c_char {
+    c_api::datp_last_error()
+}
+
+// wipes and frees a string returned by a datp_dart_ function; NULL is ignored
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_dart_string_free(s: *mut c_char) {
+    c_api::datp_free_secret(s)
+}
+
+// a new random base32 secret of bytes bytes, NULL on failure
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_dart_generate_secret(bytes: i64) -> *mut c_char {
+    guard(std::ptr::null_mut(), || {
+        into_raw(match usize::try_from(bytes) {
+            Ok(bytes) if bytes > 0 => Ok(generate_totp_secret(bytes)),
+            _ => Err(fail(DatpStatus::InvalidArgument, "bytes must be positive")),
+        })
+    })
+}
+
+// the current code of the account in an otpauth URI at unix_time; HOTP accounts use the counter
+// of the URI. NULL on failure
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_dart_code(uri: *const c_char, unix_time: i64) -> *mut c_char {
+    guard(std::ptr::null_mut(), || into_raw(account(uri).and_then(|account| code(&account, self::unix_time(unix_time)?))))
+}
+
+// seconds until the code of a time-based account changes, 0 for HOTP accounts, -1 on failure
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_dart_seconds_remaining(uri: *const c_char, unix_time: i64) -> i64 {
+    guard(-1, || {
+        let remaining = || -> Result<u64, DatpStatus> {
+            let account = account(uri)?;
+            let unix_time = self::unix_time(unix_time)?;
+            Ok(match account.kind {
+                OtpKind::Totp | OtpKind::Steam => seconds_remaining(account.period, 0, unix_time).unwrap_or(0),
+                OtpKind::Hotp => 0,
+            })
+        };
+        remaining().map_or(-1, |seconds| seconds as i64)
+    })
+}
+
+// checks code against a time-based account, accepting skew (0 to 1000) steps either side: 1 if
+// it matches, 0 if it doesn't, -1 on failure (including HOTP accounts, whose counter the app keeps
+// itself)
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_dart_verify(uri: *const c_char, code: *const c_char, unix_time: i64, skew: i64) -> i64 {
+    guard(-1, || {
+        let verify = || -> Result<bool, DatpStatus> {
+            let account = account(uri)?;
+            let given = c_str(code, "code")?;
+            let unix_time = self::unix_time(unix_time)?;
+            if account.kind == OtpKind::Hotp {
+                return Err(fail(DatpStatus::InvalidArgument, "HOTP accounts cannot be verified by time"));
+            }
+            let skew = u64::try_from(skew).ok().filter(|skew| *skew <= MAX_SKEW);
+            let skew = skew.ok_or_else(|| fail(DatpStatus::InvalidArgument, "skew must be between 0 and 1000"))?;
+            let current = unix_time / account.period;
+            let mut matched = false;
+            for step in current.saturating_sub(skew)..=current.saturating_add(skew) {
+                let Some(time) = step.checked_mul(account.period) else { break };
+                matched |= ct_eq_codes(self::code(&account, time)?, given);
+            }
+            Ok(matched)
+        };
+        verify().map_or(-1, i64::from)
+    })
+}
+
+// parses an otpauth URI into a new account, NULL on failure; free it with datp_dart_account_free
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_dart_parse_uri(uri: *const c_char) -> *mut DatpDartAccount {
+    guard(std::ptr::null_mut(), || {
+        let Ok(account) = account(uri) else { return std::ptr::null_mut() };
+        Box::into_raw(Box::new(DatpDartAccount {
+            kind: string(account.kind.as_str().to_string()),
+            issuer: string(account.issuer.unwrap_or_default()),
+            account_name: string(account.account_name),
+            secret: string(account.secret),
+            algorithm: string(account.algorithm.to_string()),
+            digits: account.digits as i64,
+            period: account.period as i64,
+            counter: account.counter.unwrap_or(0) as i64,
+        }))
+    })
+}
+
+// frees an account from datp_dart_parse_uri, wiping its strings; NULL is ignored
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_dart_account_free(account: *mut DatpDartAccount) {
+    guard((), || {
+        if account.is_null() { return; }
+        let account = unsafe { Box::from_raw(account) };
+        for s in [account.kind, account.issuer, account.account_name, account.secret, account.algorithm] {
+            c_api::datp_free_secret(s);
+        }
+    })
+}
+
+// the otpauth URI of an account filled in by Dart, NULL on failure
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_dart_build_uri(account: *const DatpDartAccount) -> *mut c_char {
+    guard(std::ptr::null_mut(), || {
+        let build = || -> Result<String, DatpStatus> {
+            if account.is_null() { return Err(fail(DatpStatus::NullPointer, "account is NULL")); }
+            let account = unsafe { &*account };
+            let invalid = |e: DatpError| fail(DatpStatus::InvalidArgument, &e.to_string());
+            let kind = match c_str(account.kind, "kind")? {
+                "totp" => OtpKind::Totp,
+                "hotp" => OtpKind::Hotp,
+                "steam" => OtpKind::Steam,
+                other => return Err(invalid(DatpError::UnsupportedType(other.to_string()))),
+            };
+            let issuer = c_str(account.issuer, "issuer")?;
+            let secret = crate::uri::normalize_secret(c_str(account.secret, "secret")?)
+                .map_err(|e| fail(DatpStatus::InvalidSecret, &e.to_string()))?;
+            let digits = u32::try_from(account.digits).ok().filter(|digits| (1..=9).contains(digits));
+            let digits = digits.ok_or_else(|| invalid(DatpError::InvalidDigits(account.digits.to_string())))?;
+            let period = u64::try_from(account.period).ok().filter(|&period| period > 0);
+            let period = period.ok_or_else(|| invalid(DatpError::InvalidPeriod(account.period.to_string())))?;
+            let counter = u64::try_from(account.counter).map_err(|_| invalid(DatpError::InvalidCounter(account.counter.to_string())))?;
+            let account = Account {
+                kind,
+                issuer: (!issuer.is_empty()).then(|| issuer.to_string()),
+                algorithm: c_str(account.algorithm, "algorithm")?.parse().map_err(invalid)?,
+                digits,
+                period,
+                counter: (kind == OtpKind::Hotp).then_some(counter),
+                ..Account::new(c_str(account.account_name, "account_name")?, secret)
+            };
+            Ok(build_otpauth_uri(&account))
+        };
+        into_raw(build())
+    })
+}
+
+// an SVG QR code provisioning the account of an otpauth URI, NULL on failure
+#[unsafe(no_mangle)]
+#[doc(hidden)]
+pub extern "C" fn datp_dart_qr_svg(uri: *const c_char) -> *mut c_char {
+    guard(std::ptr::null_mut(), || {
+        let svg = c_str(uri, "uri").and_then(|uri| qr_svg_for_uri(uri, &QrStyle::default()).map_err(|e| fail(DatpStatus::QrCode, &e.to_string())));
+        into_raw(svg)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    fn take(s: *mut c_char) -> String {
+        assert!(!s.is_null(), "{:?}", unsafe { CStr::from_ptr(datp_dart_last_error()) });
+        let owned = unsafe { CStr::from_ptr(s) }.to_str().unwrap().to_string();
+        datp_dart_string_free(s);
+        owned
+    }
+
+    #[test]
+    fn test_bindings_match_exports() {
+        let bindings = include_str!("../dart/datp.dart");
+        let source = include_str!("dart_api.rs");
+        let source = &source[..source.find("#[cfg(test)]").unwrap()];
+        for name in source.split("pub extern \"C\" fn ").skip(1).map(|rest| &rest[..rest.find('(').unwrap()]) {
+            assert!(bindings.contains(&format!("'{}'", name)), "{} missing from dart/datp.dart", name);
+        }
+    }
+
+    #[test]
+    fn test_dart_api() {
+        let uri = c"otpauth://totp/ACME:alice?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&digits=8";
+        assert_eq!(take(datp_dart_code(uri.as_ptr(), 59)), "94287082");
+        assert_eq!(datp_dart_seconds_remaining(uri.as_ptr(), 59), 1);
+        assert_eq!(datp_dart_verify(uri.as_ptr(), c"94287082".as_ptr(), 89, 1), 1);
+        assert_eq!(datp_dart_verify(uri.as_ptr(), c"94287082".as_ptr(), 89, 0), 0);
+        assert_eq!(datp_dart_verify(uri.as_ptr(), c"94287082".as_ptr(), -1, 0), -1);
+        assert_eq!(datp_dart_verify(uri.as_ptr(), c"94287082".as_ptr(), 89, 1_000), 1);
+        for skew in [-1, 1_001, i64::MAX] {
+            assert_eq!(datp_dart_verify(uri.as_ptr(), c"94287082".as_ptr(), 89, skew), -1);
+        }
+        assert!(datp_dart_code(c"otpauth://totp/alice".as_ptr(), 59).is_null());
+
+        let account = datp_dart_parse_uri(uri.as_ptr());
+        let fields = unsafe { &*account };
+        let field = |s: *mut c_char| unsafe { CStr::from_ptr(s) }.to_str().unwrap();
+        assert_eq!((field(fields.kind), field(fields.issuer), field(fields.algorithm)), ("totp", "ACME", "SHA1"));
+        assert_eq!((fields.digits, fields.period, fields.counter), (8, 30, 0));
+        assert_eq!(Account::from_uri(&take(datp_dart_build_uri(account))), Account::from_uri(uri.to_str().unwrap()));
+        datp_dart_account_free(account);
+
+        let hotp = c"otpauth://hotp/badge?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&counter=1";
+        assert_eq!(take(datp_dart_code(hotp.as_ptr(), 0)), "287082");
+        assert_eq!(datp_dart_seconds_remaining(hotp.as_ptr(), 0), 0);
+        assert_eq!(datp_dart_verify(hotp.as_ptr(), c"287082".as_ptr(), 0, 1), -1);
+        assert_eq!(take(datp_dart_generate_secret(20)).len(), 32);
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use super::{build_otpauth_uri, generate_totp_secret, totp_verify_with, Account};
+
+/// Bytes of the secrets created by `Enrollment::start`, as recommended by RFC 4226.
+const SECRET_BYTES: usize = 20;
+
+/// Where an enrollment is in its flow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnrollmentState {
+    /// The secret was shown to the user but no code was confirmed yet.
+    Pending,
+    /// The user proved that their app generates the right codes; the account can be used.
+    Active,
+}
+
+/// How enrollments are confirmed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EnrollmentPolicy {
+    pub expires_after: u64,          // seconds a pending enrollment stays valid
+    pub consecutive_codes: bool,     // require the codes of two consecutive steps
+    pub skew: u64,                   // steps of clock drift accepted in each direction
+}
+
+impl Default for EnrollmentPolicy {
+    fn default() -> Self {
+        EnrollmentPolicy { expires_after: 600, consecutive_codes: false, skew: 1 }
+    }
+}
+
+/// Outcome of `Enrollment::confirm`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Confirmation {
+    /// The enrollment is now active.
+    Activated,
+    /// The code was right; with `consecutive_codes` the code of the next step is needed as well.
+    NextCodeRequired,
+    /// The code does not match; the enrollment stays pending.
+    WrongCode,
+    /// The enrollment was not confirmed within `expires_after`; start a new one.
+    Expired,
+    /// The enrollment was activated before.
+    AlreadyActive,
+}
+
+/// A TOTP account being set up: a fresh secret is shown to the user as a URI or QR code and
+/// only becomes active once the user confirms a code from their app.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Enrollment {
+    pub account: Account,
+    pub state: EnrollmentState,
+    pub created_at: u64,             // Unix time of `start`
+    first_step: Option<u64>,         // step of the first code when two are required
+}
+
+impl Enrollment {
+    /// Starts an enrollment with a new random secret.
+    ///
+    /// # Example
+    /// ```rust
+    /// use datp::{totp_raw, Confirmation, Enrollment, EnrollmentPolicy, EnrollmentState};
+    ///
+    /// let policy = EnrollmentPolicy::default();
+    /// let mut enrollment = Enrollment::start("alice@example.com", Some("MyApp"), 1_000_000);
+    /// println!("{}", enrollment.uri());
+    ///
+    /// let code = format!("{:06}", totp_raw(&enrollment.account.secret, 30, 0, 1_000_010).unwrap());
+    /// assert_eq!(enrollment.confirm(&code, 1_000_010, &policy), Confirmation::Activated);
+    /// assert_eq!(enrollment.state, EnrollmentState::Active);
+    /// ```
+    pub fn start(account_name: impl Into<String>, issuer: Option<&str>, unix_time: u64) -> Self {
+        let mut account = Account::new(account_name, generate_totp_secret(SECRET_BYTES));
+        account.issuer = issuer.map(str::to_string);
+        Enrollment { account, state: EnrollmentState::Pending, created_at: unix_time, first_step: None }
+    }
+
+    /// The otpauth URI to show or encode as a QR code, e.g. with `qr_svg_for`.
+    pub fn uri(&self) -> String {
+        build_otpauth_uri(&self.account)
+    }
+
+    /// Whether the enrollment is still pending after `policy.expires_after`.
+    pub fn is_expired(&self, unix_time: u64, policy: &EnrollmentPolicy) -> bool {
+        self.state == EnrollmentState::Pending && unix_time.saturating_sub(self.created_at) > policy.expires_after
+    }
+
+    /// Checks a code from the user's app and activates the enrollment once it is confirmed.
+    ///
+    /// With `policy.consecutive_codes`, the first right code returns `NextCodeRequired` and
+    /// the enrollment is activated by the code of the following step. That catches apps whose
+    /// clock is badly off, which otherwise only shows up at the next login.
+    pub fn confirm(&mut self, code: &str, unix_time: u64, policy: &EnrollmentPolicy) -> Confirmation {
+        if self.state == EnrollmentState::Active {
+            return Confirmation::AlreadyActive;
+        }
+        if self.is_expired(unix_time, policy) {
+            return Confirmation::Expired;
+        }
+        let account = &self.account;
+        let offset =
+            totp_verify_with(&account.secret, code, account.algorithm, account.digits, account.period, 0, unix_time, policy.skew);
+        let Some(offset) = offset else {
+            return Confirmation::WrongCode;
+        };
+        let step = (unix_time / account.period).saturating_add_signed(offset);
+        match self.first_step {
+            _ if !policy.consecutive_codes => {}
+            Some(first) if step == first + 1 => {}
+            _ => {
+                self.first_step = Some(step);
+                return Confirmation::NextCodeRequired;
+            }
+        }
+        self.state = EnrollmentState::Active;
+        self.first_step = None;
+        Confirmation::Activated
+    }
+}
+
+/// Storage of enrollments by user key, for `Enrollments`.
+///
+/// Implemented for `Mutex<HashMap<String, Enrollment>>`, which is enough for a single process.
+pub trait EnrollmentStore {
+    fn load(&self, user: &str) -> Option<Enrollment>;
+    fn save(&self, user: &str, enrollment: &Enrollment);
+    fn remove(&self, user: &str);
+}
+
+impl EnrollmentStore for Mutex<HashMap<String, Enrollment>> {
+    fn load(&self, user: &str) -> Option<Enrollment> {
+        self.lock().unwrap_or_else(|e| e.into_inner()).get(user).cloned()
+    }
+
+    fn save(&self, user: &str, enrollment: &Enrollment) {
+        self.lock().unwrap_or_else(|e| e.into_inner()).insert(user.to_string(), enrollment.clone());
+    }
+
+    fn remove(&self, user: &str) {
+        self.lock().unwrap_or_else(|e| e.into_inner()).remove(user);
+    }
+}
+
+/// The enrollment flow on top of an `EnrollmentStore`: `begin`, show the URI, `confirm`.
+///
+/// # Example
+/// ```rust
+/// use std::collections::HashMap;
+/// use std::sync::Mutex;
+/// use datp::{totp_raw, Confirmation, EnrollmentPolicy, Enrollments};
+///
+/// let enrollments = Enrollments::new(Mutex::new(HashMap::new()), EnrollmentPolicy::default());
+/// let enrollment = enrollments.begin("alice", "alice@example.com", Some("MyApp"), 1_000_000);
+/// let code = format!("{:06}", totp_raw(&enrollment.account.secret, 30, 0, 1_000_030).unwrap());
+/// assert_eq!(enrollments.confirm("alice", &code, 1_000_030), Confirmation::Activated);
+/// assert!(enrollments.active_account("alice").is_some());
+/// ```
+pub struct Enrollments<S: EnrollmentStore> {
+    store: S,
+    policy: EnrollmentPolicy,
+}
+
+impl<S: EnrollmentStore> Enrollments<S> {
+    pub fn new(store: S, policy: EnrollmentPolicy) -> Self {
+        Enrollments { store, policy }
+    }
+
+    /// Starts a new enrollment for `user`, replacing a pending or active one.
+    pub fn begin(&self, user: &str, account_name: &str, issuer: Option<&str>, unix_time: u64) -> Enrollment {
+        let enrollment = Enrollment::start(account_name, issuer, unix_time);
+        self.store.save(user, &enrollment);
+        enrollment
+    }
+
+    /// Confirms the pending enrollment of `user`. Expired enrollments are removed, and a user
+    /// without an enrollment gets `Expired` as well.
+    pub fn confirm(&self, user: &str, code: &str, unix_time: u64) -> Confirmation {
+        let Some(mut enrollment) = self.store.load(user) else {
+            return Confirmation::Expired;
+        };
+        let confirmation = enrollment.confirm(code, unix_time, &self.policy);
+        match confirmation {
+            Confirmation::Expired => self.store.remove(user),
+            Confirmation::Activated | Confirmation::NextCodeRequired => self.store.save(user, &enrollment),
+            Confirmation::WrongCode | Confirmation::AlreadyActive => {}
+        }
+        confirmation
+    }
+
+    /// The account of `user` once the enrollment is active.
+    pub fn active_account(&self, user: &str) -> Option<Account> {
+        self.store.load(user).filter(|enrollment| enrollment.state == EnrollmentState::Active).map(|e| e.account)
+    }
+
+    /// The underlying store.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::totp_raw;
+
+    fn code(enrollment: &Enrollment, unix_time: u64) -> String {
+        format!("{:06}", totp_raw(&enrollment.account.secret, 30, 0, unix_time).unwrap())
+    }
+
+    #[test]
+    fn test_confirm() {
+        let policy = EnrollmentPolicy::default();
+        let mut enrollment = Enrollment::start("alice", Some("ACME"), 1_000);
+        assert!(enrollment.uri().starts_with("otpauth://totp/ACME:alice?secret="));
+        assert_eq!(enrollment.confirm("000000x", 1_010, &policy), Confirmation::WrongCode);
+        assert_eq!(enrollment.confirm(&code(&enrollment, 1_610), 1_610, &policy), Confirmation::Expired);
+        assert_eq!(enrollment.confirm(&code(&enrollment, 1_010), 1_010, &policy), Confirmation::Activated);
+        assert_eq!(enrollment.confirm(&code(&enrollment, 1_010), 1_010, &policy), Confirmation::AlreadyActive);
+        assert!(!enrollment.is_expired(5_000, &policy));
+    }
+
+    #[test]
+    fn test_consecutive_codes() {
+        let policy = EnrollmentPolicy { consecutive_codes: true, ..Default::default() };
+        let mut enrollment = Enrollment::start("alice", None, 1_000);
+        assert_eq!(enrollment.confirm(&code(&enrollment, 1_020), 1_020, &policy), Confirmation::NextCodeRequired);
+        // the same code again is not enough
+        assert_eq!(enrollment.confirm(&code(&enrollment, 1_020), 1_025, &policy), Confirmation::NextCodeRequired);
+        // the next step's code, typed before the step begins
+        assert_eq!(enrollment.confirm(&code(&enrollment, 1_050), 1_028, &policy), Confirmation::Activated);
+    }
+
+    #[test]
+    fn test_enrollments_store() {
+        let enrollments = Enrollments::new(Mutex::new(HashMap::new()), EnrollmentPolicy::default());
+        assert_eq!(enrollments.confirm("nobody", "123456", 0), Confirmation::Expired);
+
+        let expired = enrollments.begin("bob", "bob", None, 0);
+        assert_eq!(enrollments.confirm("bob", &code(&expired, 700), 700), Confirmation::Expired);
+        assert!(enrollments.store().load("bob").is_none());
+
+        let enrollment = enrollments.begin("bob", "bob", None, 1_000);
+        assert!(enrollments.active_account("bob").is_none());
+        assert_eq!(enrollments.confirm("bob", &code(&enrollment, 1_040), 1_040), Confirmation::Activated);
+        assert_eq!(enrollments.active_account("bob"), Some(enrollment.account));
+    }
+}
@@ -0,0 +1,108 @@
+use std::fmt::Write;
+
+use qrcode::Color;
+
+use super::svg::{self, SvgLayout};
+use super::{encode_qr, totp_url, DatpError, TotpQrConfig};
+
+/// Generates a TOTP QR code as an Encapsulated PostScript document.
+///
+/// Uses the same configuration as `totp_qr_svg`, with sizes in PostScript points instead of
+/// pixels. Colors must be `#rrggbb` or `#rgb`; `module_shape` and `gradient` are not supported
+/// and the code is always drawn with square modules in `dark_color`.
+///
+/// # Arguments
+/// * `secret_base32` - Base32-encoded TOTP secret.
+/// * `config` - TotpQrConfig struct with customization options.
+///
+/// # Returns
+/// `Ok(String)` - The EPS document, `DatpError::InvalidColor` for colors that are not hex, or
+/// `DatpError::QrCode` when the URI does not fit into a QR code.
+pub fn totp_qr_eps(secret_base32: &str, config: &TotpQrConfig) -> Result<String, DatpError> {
+    let dark = parse_hex_color(config.dark_color)?;
+    let light = parse_hex_color(config.light_color)?;
+    let style = config.style();
+    let code = encode_qr(&totp_url(secret_base32, config), &style)?;
+    let layout = SvgLayout::new(&code, &style);
+    let SvgLayout { size, unit, origin } = layout;
+    let (page_width, page_height) = config.exact_size.unwrap_or((size, size));
+    let width = code.width();
+    let colors = code.to_colors();
+
+    let mut eps = String::new();
+    eps.push_str("%!PS-Adobe-3.0 EPSF-3.0\n");
+    writeln!(eps, "%%BoundingBox: 0 0 {page_width} {page_height}").unwrap();
+    writeln!(eps, "%%Title: {}", svg::account_title(config.issuer, config.account_name).replace(['\r', '\n'], " ")).unwrap();
+    eps.push_str("%%Creator: datp\n%%EndComments\n");
+    writeln!(eps, "{} setrgbcolor 0 0 {page_width} {page_height} rectfill", rgb(light)).unwrap();
+    if config.exact_size.is_some() {
+        // same centering and scaling as the SVG viewBox
+        let (vx, vy, vw, _) = layout.view_box(page_width, page_height);
+        let scale = page_width as f64 / vw;
+        writeln!(eps, "{} {} translate {scale} {scale} scale", -vx * scale, -vy * scale).unwrap();
+    }
+    writeln!(eps, "{} setrgbcolor", rgb(dark)).unwrap();
+
+    // PostScript's origin is the bottom left corner
+    for y in 0..width {
+        let bottom = size - origin - (y as u32 + 1) * unit;
+        let mut x = 0;
+        while x < width {
+            let run = colors[y * width + x..(y + 1) * width].iter().take_while(|&&c| c == Color::Dark).count();
+            if run > 0 {
+                writeln!(eps, "{} {bottom} {} {unit} rectfill", origin + x as u32 * unit, run as u32 * unit).unwrap();
+            }
+            x += run.max(1);
+        }
+    }
+    eps.push_str("showpage\n%%EOF\n");
+    Ok(eps)
+}
+
+pub(crate) fn parse_hex_color(color: &str) -> Result<[u8; 3], DatpError> {
+    let invalid = || DatpError::InvalidColor(color.to_string());
+    let hex = color.strip_prefix('#').ok_or_else(invalid)?;
+    let digits: Vec<u8> = hex.chars().map(|c| c.to_digit(16).map(|d| d as u8)).collect::<Option<_>>().ok_or_else(invalid)?;
+    match digits[..] {
+        [r, g, b] => Ok([r * 17, g * 17, b * 17]),
+        [r1, r2, g1, g2, b1, b2] => Ok([r1 * 16 + r2, g1 * 16 + g2, b1 * 16 + b2]),
+        _ => Err(invalid()),
+    }
+}
+
+pub(crate) fn rgb(color: [u8; 3]) -> String {
+    let channel = |c: u8| {
+        let value = format!("{:.3}", c as f64 / 255.0);
+        value.trim_end_matches('0').trim_end_matches('.').to_string()
+    };
+    format!("{} {} {}", channel(color[0]), channel(color[1]), channel(color[2]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use qrcode::Version;
+
+    #[test]
+    fn test_totp_qr_eps() {
+        let config = TotpQrConfig {
+            account_name: "user@example.com",
+            issuer: "MyApp",
+            dark_color: "#000080",
+            light_color: "#fff",
+            min_dimension: 228,
+            version: Version::Normal(8),
+            ..Default::default()
+        };
+        let eps = totp_qr_eps("JBSWY3DPEHPK3PXP", &config).unwrap();
+        // version 8 is 49 modules wide, plus the quiet zone, 4pt each
+        assert!(eps.starts_with("%!PS-Adobe-3.0 EPSF-3.0\n%%BoundingBox: 0 0 228 228\n%%Title: QR code for MyApp: user@example.com\n"));
+        assert!(eps.contains("\n1 1 1 setrgbcolor 0 0 228 228 rectfill\n0 0 0.502 setrgbcolor\n"));
+        // top edge of the top left finder pattern
+        assert!(eps.contains("setrgbcolor\n16 208 28 4 rectfill\n"));
+        assert!(eps.ends_with("showpage\n%%EOF\n"));
+
+        let config = TotpQrConfig { dark_color: "navy", ..config };
+        assert_eq!(totp_qr_eps("JBSWY3DPEHPK3PXP", &config), Err(DatpError::InvalidColor("navy".to_string())));
+    }
+}
@@ -0,0 +1,89 @@
+use std::fmt;
+
+/// Errors returned by the fallible parts of the crate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DatpError {
+    /// The URI does not use the expected `otpauth://` or `otpauth-migration://` scheme.
+    InvalidScheme,
+    /// The OTP type in the URI host is not supported.
+    UnsupportedType(String),
+    /// The label is missing or has no account name.
+    InvalidLabel,
+    /// A percent-escape sequence is malformed or decodes to invalid UTF-8.
+    InvalidPercentEncoding,
+    /// The `secret` parameter is missing.
+    MissingSecret,
+    /// The secret is not valid base32.
+    InvalidSecret,
+    /// The `algorithm` parameter names an unknown hash function.
+    UnknownAlgorithm(String),
+    /// The `digits` parameter is not a number between 1 and 9.
+    InvalidDigits(String),
+    /// The `period` parameter is not a positive number.
+    InvalidPeriod(String),
+    /// The `counter` parameter is not a number.
+    InvalidCounter(String),
+    /// An HOTP URI has no `counter` parameter.
+    MissingCounter,
+    /// The issuer label prefix and the `issuer` parameter disagree (strict mode).
+    ConflictingIssuer { label: String, parameter: String },
+    /// A parameter appears more than once (strict mode).
+    DuplicateParameter(String),
+    /// A parameter is not part of the otpauth format (strict mode).
+    UnknownParameter(String),
+    /// An otpauth-migration payload is missing, not valid base64, or not a valid protobuf message.
+    InvalidMigrationPayload,
+    /// A backup or export file is not valid JSON.
+    InvalidJson,
+    /// A backup or export file does not have the expected structure.
+    InvalidBackup(String),
+    /// An encrypted backup was given without a password.
+    PasswordRequired,
+    /// The password does not open the encrypted backup, or the backup was tampered with.
+    WrongPassword,
+    /// A row of a CSV import is invalid; `line` is 1-based and counts the header.
+    CsvRow { line: usize, error: Box<DatpError> },
+    /// The data cannot be encoded as a QR code, usually because it exceeds the capacity.
+    QrCode(String),
+    /// A color is not in `#rrggbb` or `#rgb` form.
+    InvalidColor(String),
+    /// The pixels per module of a PNG QR code are above `MAX_PNG_MODULE_SIZE`.
+    InvalidModuleSize(String),
+    /// A background thread could not be started, or panicked.
+    Thread(String),
+}
+
+impl fmt::Display for DatpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DatpError::InvalidScheme => write!(f, "unsupported URI scheme"),
+            DatpError::UnsupportedType(kind) => write!(f, "unsupported OTP type '{}'", kind),
+            DatpError::InvalidLabel => write!(f, "URI label must contain an account name"),
+            DatpError::InvalidPercentEncoding => write!(f, "malformed percent-encoding"),
+            DatpError::MissingSecret => write!(f, "missing 'secret' parameter"),
+            DatpError::InvalidSecret => write!(f, "secret is not valid base32"),
+            DatpError::UnknownAlgorithm(value) => write!(f, "unknown algorithm '{}'", value),
+            DatpError::InvalidDigits(value) => write!(f, "invalid digits '{}', expected 1 to 9", value),
+            DatpError::InvalidPeriod(value) => write!(f, "invalid period '{}', expected a positive number of seconds", value),
+            DatpError::InvalidCounter(value) => write!(f, "invalid counter '{}'", value),
+            DatpError::MissingCounter => write!(f, "missing 'counter' parameter for hotp"),
+            DatpError::ConflictingIssuer { label, parameter } => {
+                write!(f, "issuer '{}' in the label does not match issuer parameter '{}'", label, parameter)
+            }
+            DatpError::DuplicateParameter(key) => write!(f, "parameter '{}' appears more than once", key),
+            DatpError::UnknownParameter(key) => write!(f, "unknown parameter '{}'", key),
+            DatpError::InvalidMigrationPayload => write!(f, "invalid otpauth-migration payload"),
+            DatpError::InvalidJson => write!(f, "backup is not valid JSON"),
+            DatpError::InvalidBackup(reason) => write!(f, "invalid backup: {}", reason),
+            DatpError::PasswordRequired => write!(f, "backup is encrypted, a password is required"),
+            DatpError::WrongPassword => write!(f, "wrong password or corrupted backup"),
+            DatpError::CsvRow { line, error } => write!(f, "line {}: {}", line, error),
+            DatpError::QrCode(reason) => write!(f, "cannot create QR code: {}", reason),
+            DatpError::InvalidColor(color) => write!(f, "invalid color: {}", color),
+            DatpError::InvalidModuleSize(value) => write!(f, "invalid module size '{}', expected at most 64 pixels", value),
+            DatpError::Thread(reason) => write!(f, "thread failed: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for DatpError {}
@@ -0,0 +1,119 @@
+// FreeOTP+ JSON backups: `tokens` entries with the secret stored as an array
+// of Java (signed) bytes, or as a base32 string by some versions.
+
+use base32::Alphabet;
+
+use super::json::{self, Json};
+use super::{uri, Account, DatpError, OtpKind};
+
+/// Reads a FreeOTP+ JSON backup.
+///
+/// # Example
+/// ```rust
+/// use datp::import_freeotp_plus;
+///
+/// let backup = r#"{"tokens": [{"type": "TOTP", "issuerExt": "GitHub", "label": "octocat",
+///     "algo": "SHA1", "digits": 6, "period": 30, "secret": [72, 101, 108, 108, 111, 33, -34, -83, -66, -17]}]}"#;
+/// let accounts = import_freeotp_plus(backup).unwrap();
+/// assert_eq!(accounts[0].secret, "JBSWY3DPEHPK3PXP");
+/// ```
+pub fn import_freeotp_plus(backup: &str) -> Result<Vec<Account>, DatpError> {
+    let root = json::parse(backup).ok_or(DatpError::InvalidJson)?;
+    root.get("tokens")
+        .and_then(Json::as_array)
+        .ok_or_else(|| DatpError::InvalidBackup("missing 'tokens' array".to_string()))?
+        .iter()
+        .map(parse_token)
+        .collect()
+}
+
+fn parse_token(token: &Json) -> Result<Account, DatpError> {
+    let secret = match token.get("secret") {
+        Some(Json::String(secret)) => uri::normalize_secret(secret)?,
+        Some(Json::Array(bytes)) => {
+            let bytes = bytes
+                .iter()
+                .map(|byte| match byte {
+                    // Java serializes bytes as signed values
+                    Json::Number(value) if value.fract() == 0.0 && (-128.0..=255.0).contains(value) => Some(*value as i16 as u8),
+                    _ => None,
+                })
+                .collect::<Option<Vec<u8>>>()
+                .ok_or(DatpError::InvalidSecret)?;
+            if bytes.is_empty() {
+                return Err(DatpError::MissingSecret);
+            }
+            base32::encode(Alphabet::Rfc4648 { padding: false }, &bytes)
+        }
+        _ => return Err(DatpError::MissingSecret),
+    };
+
+    let label = token.str_field("label").unwrap_or("");
+    let mut account = Account::new(label, secret);
+    account.issuer = ["issuerExt", "issuerInt", "issuerAlt"]
+        .iter()
+        .find_map(|key| token.str_field(key).filter(|issuer| !issuer.is_empty()))
+        .map(str::to_string);
+    account.image = token.str_field("image").filter(|image| !image.is_empty()).map(str::to_string);
+
+    if let Some(algo) = token.str_field("algo") {
+        account.algorithm = uri::parse_algorithm(algo)?;
+    }
+    if let Some(digits) = token.get("digits").and_then(Json::as_u64) {
+        account.digits = uri::parse_digits(&digits.to_string())?;
+    }
+    if let Some(period) = token.get("period").and_then(Json::as_u64) {
+        if period == 0 {
+            return Err(DatpError::InvalidPeriod(period.to_string()));
+        }
+        account.period = period;
+    }
+    match token.str_field("type").map(str::to_ascii_uppercase).as_deref() {
+        None | Some("TOTP") => {}
+        Some("HOTP") => {
+            account.kind = OtpKind::Hotp;
+            account.counter = Some(token.get("counter").and_then(Json::as_u64).unwrap_or(0));
+        }
+        Some(other) => return Err(DatpError::UnsupportedType(other.to_string())),
+    }
+    Ok(account)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Algorithm;
+
+    #[test]
+    fn test_import_freeotp_plus() {
+        let backup = r#"{
+            "tokenOrder": ["GitHub:octocat", "bob"],
+            "tokens": [
+                {"algo": "SHA256", "counter": 0, "digits": 8, "issuerExt": "GitHub", "label": "octocat",
+                 "period": 60, "secret": [49, 50, 51, 52, 53, 54, 55, 56, 57, 48], "type": "TOTP"},
+                {"algo": "SHA1", "counter": 9, "digits": 6, "issuerExt": "", "label": "bob",
+                 "secret": "gezd gnbv gy3t qojq", "type": "HOTP", "image": "content://logo.png"}
+            ]
+        }"#;
+        let accounts = import_freeotp_plus(backup).unwrap();
+        assert_eq!(accounts[0].issuer.as_deref(), Some("GitHub"));
+        assert_eq!(accounts[0].secret, "GEZDGNBVGY3TQOJQ");
+        assert_eq!(accounts[0].algorithm, Algorithm::Sha256);
+        assert_eq!((accounts[0].digits, accounts[0].period), (8, 60));
+
+        assert_eq!(accounts[1].kind, OtpKind::Hotp);
+        assert_eq!(accounts[1].counter, Some(9));
+        assert_eq!(accounts[1].issuer, None);
+        assert_eq!(accounts[1].secret, "GEZDGNBVGY3TQOJQ");
+        assert_eq!(accounts[1].image.as_deref(), Some("content://logo.png"));
+    }
+
+    #[test]
+    fn test_import_freeotp_plus_errors() {
+        assert_eq!(import_freeotp_plus(r#"{"tokens": [{"secret": [1000]}]}"#), Err(DatpError::InvalidSecret));
+        assert_eq!(import_freeotp_plus(r#"{"tokens": [{"label": "x"}]}"#), Err(DatpError::MissingSecret));
+        assert!(matches!(import_freeotp_plus("{}"), Err(DatpError::InvalidBackup(_))));
+        let zero = r#"{"tokens": [{"secret": "JBSWY3DPEHPK3PXP", "period": 0}]}"#;
+        assert_eq!(import_freeotp_plus(zero), Err(DatpError::InvalidPeriod("0".to_string())));
+    }
+}
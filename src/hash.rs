@@ -0,0 +1,398 @@
+// HMAC over the hash functions allowed by the otpauth spec.
+// SHA-1 goes through the `hmac` crate, SHA-256/SHA-512 are implemented here
+// following FIPS 180-4.
+
+use core::ops::{Deref, DerefMut};
+
+use hmac::{Hmac, KeyInit, Mac};
+use sha1::Sha1;
+
+use super::{crypto, Algorithm};
+
+type HmacSha1 = Hmac<Sha1>;
+
+/// A MAC or hash of up to 64 bytes, kept on the stack and wiped when dropped.
+#[derive(Clone)]
+pub(crate) struct Digest {
+    bytes: [u8; 64],
+    len: usize,
+}
+
+impl Digest {
+    fn new(bytes: &[u8]) -> Self {
+        let mut digest = Digest { bytes: [0; 64], len: bytes.len() };
+        digest.bytes[..bytes.len()].copy_from_slice(bytes);
+        digest
+    }
+}
+
+impl Deref for Digest {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+impl DerefMut for Digest {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.bytes[..self.len]
+    }
+}
+
+impl Drop for Digest {
+    fn drop(&mut self) {
+        crypto::wipe(&mut self.bytes);
+    }
+}
+
+/// Computes `HMAC(algorithm, key, message)` without allocating.
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+pub(crate) fn hmac(algorithm: Algorithm, key: &[u8], message: &[u8]) -> Digest {
+    HmacKey::new(algorithm, key).mac(message)
+}
+
+/// HMAC keyed once for many messages, such as the counters of a verification window: the
+/// padded key is absorbed up front and every `mac` continues from a copy of that state.
+#[derive(Clone)]
+#[allow(clippy::large_enum_variant)] // kept on the stack on purpose
+pub(crate) enum HmacKey {
+    Sha1(HmacSha1),
+    Sha2 { inner: Sha2, outer: Sha2 },
+}
+
+impl HmacKey {
+    pub(crate) fn new(algorithm: Algorithm, key: &[u8]) -> Self {
+        match algorithm {
+            Algorithm::Sha1 => HmacKey::Sha1(HmacSha1::new_from_slice(key).expect("HMAC accepts keys of any length")),
+            Algorithm::Sha256 => hmac_sha2(Sha2::sha256, key),
+            Algorithm::Sha512 => hmac_sha2(Sha2::sha512, key),
+        }
+    }
+
+    pub(crate) fn mac(&self, message: &[u8]) -> Digest {
+        match self {
+            HmacKey::Sha1(mac) => {
+                let mut mac = mac.clone();
+                mac.update(message);
+                Digest::new(&mac.finalize().into_bytes())
+            }
+            HmacKey::Sha2 { inner, outer } => {
+                let mut inner = inner.clone();
+                inner.update(message);
+                let inner_hash = inner.finish();
+                let mut outer = outer.clone();
+                outer.update(&inner_hash);
+                outer.finish()
+            }
+        }
+    }
+}
+
+fn hmac_sha2(new: fn() -> Sha2, key: &[u8]) -> HmacKey {
+    let block_size = new().block_size();
+    let hashed_key;
+    let key = if key.len() > block_size {
+        hashed_key = hash(new, key);
+        &hashed_key[..]
+    } else {
+        key
+    };
+    let mut block = [0u8; 128];
+    block[..key.len()].copy_from_slice(key);
+
+    let mut padded_key = [0u8; 128];
+
+    let mut inner = new();
+    padded_key.iter_mut().zip(block).for_each(|(padded, byte)| *padded = byte ^ 0x36);
+    inner.update(&padded_key[..block_size]);
+
+    let mut outer = new();
+    padded_key.iter_mut().zip(block).for_each(|(padded, byte)| *padded = byte ^ 0x5c);
+    outer.update(&padded_key[..block_size]);
+
+    crypto::wipe(&mut block);
+    crypto::wipe(&mut padded_key);
+    HmacKey::Sha2 { inner, outer }
+}
+
+fn hash(new: fn() -> Sha2, data: &[u8]) -> Digest {
+    let mut hasher = new();
+    hasher.update(data);
+    hasher.finish()
+}
+
+#[derive(Clone, Copy)]
+enum State {
+    Sha256([u32; 8]),
+    Sha512([u64; 8]),
+}
+
+/// Incremental SHA-256 or SHA-512 over a fixed block buffer.
+#[derive(Clone)]
+pub(crate) struct Sha2 {
+    state: State,
+    block: [u8; 128],
+    filled: usize,   // bytes of `block` waiting for compression
+    length: u128,    // total bytes hashed
+}
+
+impl Sha2 {
+    fn sha256() -> Self {
+        Sha2 { state: State::Sha256(SHA256_H), block: [0; 128], filled: 0, length: 0 }
+    }
+
+    fn sha512() -> Self {
+        Sha2 { state: State::Sha512(SHA512_H), block: [0; 128], filled: 0, length: 0 }
+    }
+
+    fn block_size(&self) -> usize {
+        match self.state {
+            State::Sha256(_) => 64,
+            State::Sha512(_) => 128,
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        let block_size = self.block_size();
+        self.length += data.len() as u128;
+        while !data.is_empty() {
+            let take = (block_size - self.filled).min(data.len());
+            self.block[self.filled..self.filled + take].copy_from_slice(&data[..take]);
+            self.filled += take;
+            data = &data[take..];
+            if self.filled == block_size {
+                self.compress();
+            }
+        }
+    }
+
+    fn compress(&mut self) {
+        match &mut self.state {
+            State::Sha256(h) => sha256_compress(h, &self.block[..64]),
+            State::Sha512(h) => sha512_compress(h, &self.block),
+        }
+        self.filled = 0;
+    }
+
+    /// Appends the Merkle-Damgård padding with a big-endian bit length and returns the hash.
+    fn finish(&mut self) -> Digest {
+        let block_size = self.block_size();
+        let len_bytes = block_size / 8;
+        let bits = self.length * 8;
+
+        self.block[self.filled] = 0x80;
+        self.filled += 1;
+        if self.filled > block_size - len_bytes {
+            self.block[self.filled..block_size].fill(0);
+            self.compress();
+        }
+        self.block[self.filled..block_size - len_bytes].fill(0);
+        self.block[block_size - len_bytes..block_size].copy_from_slice(&bits.to_be_bytes()[16 - len_bytes..]);
+        self.compress();
+
+        let mut out = [0u8; 64];
+        let len = match self.state {
+            State::Sha256(h) => {
+                for (chunk, word) in out.chunks_exact_mut(4).zip(h) {
+                    chunk.copy_from_slice(&word.to_be_bytes());
+                }
+                32
+            }
+            State::Sha512(h) => {
+                for (chunk, word) in out.chunks_exact_mut(8).zip(h) {
+                    chunk.copy_from_slice(&word.to_be_bytes());
+                }
+                64
+            }
+        };
+        let digest = Digest::new(&out[..len]);
+        crypto::wipe(&mut out);
+        digest
+    }
+}
+
+impl Drop for Sha2 {
+    fn drop(&mut self) {
+        // the block may still hold padded key bytes
+        crypto::wipe(&mut self.block);
+    }
+}
+
+fn sha256_compress(h: &mut [u32; 8], chunk: &[u8]) {
+    let mut w = [0u32; 64];
+    for (i, word) in chunk.chunks_exact(4).enumerate() {
+        w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+    }
+    for i in 16..64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = *h;
+    for i in 0..64 {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = hh.wrapping_add(s1).wrapping_add(ch).wrapping_add(SHA256_K[i]).wrapping_add(w[i]);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0.wrapping_add(maj);
+
+        hh = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+
+    for (state, value) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+        *state = state.wrapping_add(value);
+    }
+}
+
+fn sha512_compress(h: &mut [u64; 8], chunk: &[u8]) {
+    let mut w = [0u64; 80];
+    for (i, word) in chunk.chunks_exact(8).enumerate() {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(word);
+        w[i] = u64::from_be_bytes(bytes);
+    }
+    for i in 16..80 {
+        let s0 = w[i - 15].rotate_right(1) ^ w[i - 15].rotate_right(8) ^ (w[i - 15] >> 7);
+        let s1 = w[i - 2].rotate_right(19) ^ w[i - 2].rotate_right(61) ^ (w[i - 2] >> 6);
+        w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = *h;
+    for i in 0..80 {
+        let s1 = e.rotate_right(14) ^ e.rotate_right(18) ^ e.rotate_right(41);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = hh.wrapping_add(s1).wrapping_add(ch).wrapping_add(SHA512_K[i]).wrapping_add(w[i]);
+        let s0 = a.rotate_right(28) ^ a.rotate_right(34) ^ a.rotate_right(39);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0.wrapping_add(maj);
+
+        hh = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+
+    for (state, value) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+        *state = state.wrapping_add(value);
+    }
+}
+
+const SHA256_H: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a,
+    0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5,
+    0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3,
+    0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc,
+    0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7,
+    0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13,
+    0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3,
+    0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5,
+    0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208,
+    0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const SHA512_H: [u64; 8] = [
+    0x6a09e667f3bcc908, 0xbb67ae8584caa73b, 0x3c6ef372fe94f82b, 0xa54ff53a5f1d36f1,
+    0x510e527fade682d1, 0x9b05688c2b3e6c1f, 0x1f83d9abfb41bd6b, 0x5be0cd19137e2179,
+];
+
+const SHA512_K: [u64; 80] = [
+    0x428a2f98d728ae22, 0x7137449123ef65cd, 0xb5c0fbcfec4d3b2f, 0xe9b5dba58189dbbc,
+    0x3956c25bf348b538, 0x59f111f1b605d019, 0x923f82a4af194f9b, 0xab1c5ed5da6d8118,
+    0xd807aa98a3030242, 0x12835b0145706fbe, 0x243185be4ee4b28c, 0x550c7dc3d5ffb4e2,
+    0x72be5d74f27b896f, 0x80deb1fe3b1696b1, 0x9bdc06a725c71235, 0xc19bf174cf692694,
+    0xe49b69c19ef14ad2, 0xefbe4786384f25e3, 0x0fc19dc68b8cd5b5, 0x240ca1cc77ac9c65,
+    0x2de92c6f592b0275, 0x4a7484aa6ea6e483, 0x5cb0a9dcbd41fbd4, 0x76f988da831153b5,
+    0x983e5152ee66dfab, 0xa831c66d2db43210, 0xb00327c898fb213f, 0xbf597fc7beef0ee4,
+    0xc6e00bf33da88fc2, 0xd5a79147930aa725, 0x06ca6351e003826f, 0x142929670a0e6e70,
+    0x27b70a8546d22ffc, 0x2e1b21385c26c926, 0x4d2c6dfc5ac42aed, 0x53380d139d95b3df,
+    0x650a73548baf63de, 0x766a0abb3c77b2a8, 0x81c2c92e47edaee6, 0x92722c851482353b,
+    0xa2bfe8a14cf10364, 0xa81a664bbc423001, 0xc24b8b70d0f89791, 0xc76c51a30654be30,
+    0xd192e819d6ef5218, 0xd69906245565a910, 0xf40e35855771202a, 0x106aa07032bbd1b8,
+    0x19a4c116b8d2d0c8, 0x1e376c085141ab53, 0x2748774cdf8eeb99, 0x34b0bcb5e19b48a8,
+    0x391c0cb3c5c95a63, 0x4ed8aa4ae3418acb, 0x5b9cca4f7763e373, 0x682e6ff3d6b2b8a3,
+    0x748f82ee5defb2fc, 0x78a5636f43172f60, 0x84c87814a1f0ab72, 0x8cc702081a6439ec,
+    0x90befffa23631e28, 0xa4506cebde82bde9, 0xbef9a3f7b2c67915, 0xc67178f2e372532b,
+    0xca273eceea26619c, 0xd186b8c721c0c207, 0xeada7dd6cde0eb1e, 0xf57d4f7fee6ed178,
+    0x06f067aa72176fba, 0x0a637dc5a2c898a6, 0x113f9804bef90dae, 0x1b710b35131c471b,
+    0x28db77f523047d84, 0x32caab7b40c72493, 0x3c9ebe0a15c9bebc, 0x431d67c49c100d4c,
+    0x4cc5d4becb3e42b6, 0x597f299cfc657e2a, 0x5fcb6fab3ad6faec, 0x6c44198c4a475817,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn test_sha256_abc() {
+        assert_eq!(
+            hex(&hash(Sha2::sha256, b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_sha512_abc() {
+        assert_eq!(
+            hex(&hash(Sha2::sha512, b"abc")),
+            "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+             2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"
+        );
+    }
+
+    #[test]
+    fn test_sha2_multiblock() {
+        // 56 and 112 byte messages need an extra block for the length
+        assert_eq!(
+            hex(&hash(Sha2::sha256, b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        let mut split = Sha2::sha512();
+        split.update(b"abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmn");
+        split.update(b"hijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu");
+        assert_eq!(
+            hex(&split.finish()),
+            "8e959b75dae313da8cf4f72814fc143f8f7779c6eb9f7fa17299aeadb6889018\
+             501d289e4900f7e4331b99dec4b5433ac7d329eeb6dd26545e96e55b874be909"
+        );
+    }
+
+    #[test]
+    fn test_hmac_long_key() {
+        // RFC 4231 test case 6
+        let key = [0xaa; 131];
+        let message = b"Test Using Larger Than Block-Size Key - Hash Key First";
+        assert_eq!(
+            hex(&hmac(Algorithm::Sha256, &key, message)),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+}
@@ -0,0 +1,129 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use base32::Alphabet;
+
+use super::{crypto, hotp_truncated_key, Algorithm};
+
+/// Iterator over the TOTP codes of consecutive time steps, see `totp_iter`.
+pub struct TotpIter {
+    key: Vec<u8>,
+    algorithm: Algorithm,
+    digits: u32,
+    step: u64,
+    t0: u64,
+    next: u64,                       // counter of the next step
+    last: u64,                       // counter of the last step
+    done: bool,
+}
+
+/// Iterates over the 6-digit SHA1 codes of every time step that overlaps `from..=to`, for
+/// test fixtures, auditing old logs or precomputing codes for an offline device.
+///
+/// # Arguments
+/// * `secret_base32` - A base32-encoded secret key (without padding).
+/// * `step` - Time step in seconds (usually 30 seconds).
+/// * `t0` - Unix epoch start time (usually 0).
+/// * `from` - Start of the range; the first step is the one containing it.
+/// * `to` - End of the range, inclusive.
+///
+/// # Returns
+/// An iterator of `(window_start, code)`, where `window_start` is the Unix time at which the
+/// step begins, or `None` if the secret is invalid or `step` is 0. The range is empty if `to` is
+/// before `from` or before `t0`.
+///
+/// # Example
+/// ```rust
+/// use datp::{totp_iter, totp_raw};
+///
+/// let secret = "JBSWY3DPEHPK3PXP";
+/// let codes: Vec<(u64, u32)> = totp_iter(secret, 30, 0, 1_000_010, 1_000_060).unwrap().collect();
+/// assert_eq!(codes.len(), 3);
+/// assert_eq!(codes[0], (999_990, totp_raw(secret, 30, 0, 999_990).unwrap()));
+/// assert_eq!(codes[2].0, 1_000_050);
+/// ```
+pub fn totp_iter(secret_base32: &str, step: u64, t0: u64, from: u64, to: u64) -> Option<TotpIter> {
+    totp_iter_with(secret_base32, Algorithm::Sha1, 6, step, t0, from, to)
+}
+
+/// `totp_iter` with a custom algorithm and digit count; `None` for digit counts outside 1 to 9.
+pub fn totp_iter_with(
+    secret_base32: &str,
+    algorithm: Algorithm,
+    digits: u32,
+    step: u64,
+    t0: u64,
+    from: u64,
+    to: u64,
+) -> Option<TotpIter> {
+    if step == 0 || !(1..=9).contains(&digits) {
+        return None;
+    }
+    let key = base32::decode(Alphabet::Rfc4648 { padding: false }, secret_base32)?;
+    let next = from.saturating_sub(t0) / step;
+    let (last, done) = match to.checked_sub(t0) {
+        Some(elapsed) => (elapsed / step, to < from),
+        None => (0, true),
+    };
+    Some(TotpIter { key, algorithm, digits, step, t0, next, last, done })
+}
+
+impl Iterator for TotpIter {
+    type Item = (u64, u32);
+
+    fn next(&mut self) -> Option<(u64, u32)> {
+        if self.done {
+            return None;
+        }
+        let counter = self.next;
+        let window_start = counter.checked_mul(self.step).and_then(|offset| offset.checked_add(self.t0))?;
+        let code = hotp_truncated_key(&self.key, self.algorithm, counter) % 10u32.pow(self.digits);
+        if counter >= self.last {
+            self.done = true;
+        } else {
+            self.next += 1;
+        }
+        Some((window_start, code))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.done {
+            true => (0, Some(0)),
+            false => match usize::try_from(self.last - self.next + 1) {
+                Ok(len) => (len, Some(len)),
+                Err(_) => (usize::MAX, None),
+            },
+        }
+    }
+}
+
+impl Drop for TotpIter {
+    fn drop(&mut self) {
+        crypto::wipe(&mut self.key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::totp_raw_with;
+
+    #[test]
+    fn test_totp_iter_range() {
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        let codes: Vec<_> = totp_iter_with(secret, Algorithm::Sha1, 8, 30, 0, 59, 59).unwrap().collect();
+        assert_eq!(codes, [(30, 94287082)]);
+
+        let iter = totp_iter(secret, 30, 100, 160, 249).unwrap();
+        assert_eq!(iter.size_hint(), (3, Some(3)));
+        for (window_start, code) in iter {
+            assert_eq!(Some(code), totp_raw_with(secret, Algorithm::Sha1, 6, 30, 100, window_start));
+        }
+
+        assert_eq!(totp_iter(secret, 30, 0, 100, 99).unwrap().count(), 0);
+        assert_eq!(totp_iter(secret, 30, 100, 0, 99).unwrap().count(), 0);
+        assert_eq!(totp_iter(secret, 30, 100, 0, 100).unwrap().count(), 1);
+        assert!(totp_iter(secret, 0, 0, 0, 100).is_none());
+        assert!(totp_iter("not base32!", 30, 0, 0, 100).is_none());
+        assert_eq!(totp_iter(secret, 1, 0, u64::MAX - 1, u64::MAX).unwrap().count(), 2);
+    }
+}
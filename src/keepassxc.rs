@@ -0,0 +1,183 @@
+// KeePassXC keeps TOTP settings in entry attributes:
+// - `otp`: an otpauth URI (or a KeeOTP style `key=...&step=30&size=6` query)
+// - legacy `TOTP Seed` + `TOTP Settings` ("30;6"), written by older versions
+
+use super::{build_otpauth_uri, uri, Account, Algorithm, DatpError, OtpKind};
+
+/// TOTP attributes of a KeePassXC entry.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct KeePassXcOtp {
+    pub otp: Option<String>,            // `otp` attribute
+    pub totp_seed: Option<String>,      // legacy `TOTP Seed` attribute
+    pub totp_settings: Option<String>,  // legacy `TOTP Settings` attribute, e.g. "30;6"
+}
+
+/// Which attributes [`to_keepassxc`] writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeePassXcFormat {
+    /// The `otp` attribute holding an otpauth URI (current KeePassXC).
+    #[default]
+    Otp,
+    /// The `TOTP Seed` / `TOTP Settings` pair (KeePassXC before 2.6).
+    Legacy,
+}
+
+/// Reads the TOTP configuration of a KeePassXC entry.
+///
+/// The `otp` attribute wins when present. The entry title and username fill
+/// in the issuer and account name where the stored format does not carry them.
+///
+/// # Example
+/// ```rust
+/// use datp::{parse_keepassxc, KeePassXcOtp};
+///
+/// let fields = KeePassXcOtp {
+///     totp_seed: Some("JBSW Y3DP EHPK 3PXP".to_string()),
+///     totp_settings: Some("60;8".to_string()),
+///     ..Default::default()
+/// };
+/// let account = parse_keepassxc(&fields, "GitHub", "octocat").unwrap();
+/// assert_eq!(account.issuer.as_deref(), Some("GitHub"));
+/// assert_eq!((account.period, account.digits), (60, 8));
+/// ```
+pub fn parse_keepassxc(fields: &KeePassXcOtp, title: &str, username: &str) -> Result<Account, DatpError> {
+    let mut account = match (fields.otp.as_deref().map(str::trim), fields.totp_seed.as_deref()) {
+        (Some(otp), _) if uri::strip_prefix_ignore_case(otp, "otpauth://").is_some() => Account::from_uri(otp)?,
+        (Some(otp), _) if !otp.is_empty() => parse_keeotp(otp)?,
+        (_, Some(seed)) => parse_legacy(seed, fields.totp_settings.as_deref())?,
+        _ => return Err(DatpError::MissingSecret),
+    };
+
+    if account.issuer.is_none() && !title.is_empty() {
+        account.issuer = Some(title.to_string());
+    }
+    if account.account_name.is_empty() {
+        account.account_name = if username.is_empty() { title.to_string() } else { username.to_string() };
+    }
+    Ok(account)
+}
+
+/// Writes an account in KeePassXC's attribute format.
+///
+/// The legacy format has no room for the algorithm or HOTP counters, so only
+/// SHA-1 TOTP and Steam accounts can be written that way.
+pub fn to_keepassxc(account: &Account, format: KeePassXcFormat) -> Result<KeePassXcOtp, DatpError> {
+    match format {
+        KeePassXcFormat::Otp => Ok(KeePassXcOtp { otp: Some(build_otpauth_uri(account)), ..Default::default() }),
+        KeePassXcFormat::Legacy => {
+            if account.kind == OtpKind::Hotp {
+                return Err(DatpError::UnsupportedType(account.kind.as_str().to_string()));
+            }
+            if account.algorithm != Algorithm::Sha1 {
+                return Err(DatpError::UnknownAlgorithm(account.algorithm.as_str().to_string()));
+            }
+            let digits = if account.kind == OtpKind::Steam { "S".to_string() } else { account.digits.to_string() };
+            Ok(KeePassXcOtp {
+                otp: None,
+                totp_seed: Some(account.secret.clone()),
+                totp_settings: Some(format!("{};{}", account.period, digits)),
+            })
+        }
+    }
+}
+
+fn parse_legacy(seed: &str, settings: Option<&str>) -> Result<Account, DatpError> {
+    let mut account = Account::new(String::new(), uri::normalize_secret(seed)?);
+
+    // "[step];[digits]", either part may be missing
+    let mut parts = settings.unwrap_or("").split(';').map(str::trim);
+    if let Some(step) = parts.next().filter(|step| !step.is_empty()) {
+        account.period = match step.parse::<u64>() {
+            Ok(period) if period > 0 => period,
+            _ => return Err(DatpError::InvalidPeriod(step.to_string())),
+        };
+    }
+    if let Some(digits) = parts.next().filter(|digits| !digits.is_empty()) {
+        if digits.eq_ignore_ascii_case("S") {
+            account.kind = OtpKind::Steam;
+            account.digits = 5;
+        } else {
+            account.digits = uri::parse_digits(digits)?;
+        }
+    }
+    Ok(account)
+}
+
+fn parse_keeotp(otp: &str) -> Result<Account, DatpError> {
+    let mut account = Account::new(String::new(), String::new());
+    let mut secret = None;
+
+    for pair in otp.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = uri::percent_decode(value, true)?;
+        match key {
+            "key" => secret = Some(value),
+            "step" => {
+                account.period = match value.parse::<u64>() {
+                    Ok(period) if period > 0 => period,
+                    _ => return Err(DatpError::InvalidPeriod(value)),
+                }
+            }
+            "size" => account.digits = uri::parse_digits(&value)?,
+            "otpHashMode" => account.algorithm = uri::parse_algorithm(&value)?,
+            _ => {}
+        }
+    }
+
+    account.secret = uri::normalize_secret(&secret.ok_or(DatpError::MissingSecret)?)?;
+    Ok(account)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_otp_attribute() {
+        let fields = KeePassXcOtp {
+            otp: Some("otpauth://totp/GitHub:octocat?secret=JBSWY3DPEHPK3PXP&period=30&digits=6&issuer=GitHub".to_string()),
+            ..Default::default()
+        };
+        let account = parse_keepassxc(&fields, "ignored", "ignored").unwrap();
+        assert_eq!(account.issuer.as_deref(), Some("GitHub"));
+        assert_eq!(account.account_name, "octocat");
+    }
+
+    #[test]
+    fn test_parse_keeotp_attribute() {
+        let fields = KeePassXcOtp { otp: Some("key=JBSWY3DPEHPK3PXP&step=60&size=8&otpHashMode=Sha256".to_string()), ..Default::default() };
+        let account = parse_keepassxc(&fields, "Site", "me").unwrap();
+        assert_eq!(account.algorithm, Algorithm::Sha256);
+        assert_eq!((account.period, account.digits), (60, 8));
+        assert_eq!(account.account_name, "me");
+    }
+
+    #[test]
+    fn test_legacy_roundtrip() {
+        let mut account = Account::new("octocat", "JBSWY3DPEHPK3PXP");
+        account.issuer = Some("GitHub".to_string());
+        let fields = to_keepassxc(&account, KeePassXcFormat::Legacy).unwrap();
+        assert_eq!(fields.totp_settings.as_deref(), Some("30;6"));
+        assert_eq!(parse_keepassxc(&fields, "GitHub", "octocat").unwrap(), account);
+
+        let fields = to_keepassxc(&account, KeePassXcFormat::Otp).unwrap();
+        assert_eq!(parse_keepassxc(&fields, "", "").unwrap(), account);
+    }
+
+    #[test]
+    fn test_legacy_steam() {
+        let fields = KeePassXcOtp { totp_seed: Some("JBSWY3DPEHPK3PXP".to_string()), totp_settings: Some("30;S".to_string()), otp: None };
+        let account = parse_keepassxc(&fields, "Steam", "gaben").unwrap();
+        assert_eq!(account, Account::new_steam("gaben", "JBSWY3DPEHPK3PXP"));
+        assert_eq!(to_keepassxc(&account, KeePassXcFormat::Legacy).unwrap(), fields);
+    }
+
+    #[test]
+    fn test_legacy_errors() {
+        assert_eq!(parse_keepassxc(&KeePassXcOtp::default(), "", ""), Err(DatpError::MissingSecret));
+
+        let mut account = Account::new("a", "JBSWY3DPEHPK3PXP");
+        account.algorithm = Algorithm::Sha512;
+        assert!(to_keepassxc(&account, KeePassXcFormat::Legacy).is_err());
+    }
+}
@@ -1,26 +1,419 @@
+//! HOTP/TOTP codes and the provisioning around them: otpauth URIs, QR codes, authenticator
+//! backups and a C API.
+//!
+//! Everything but code generation and verification needs the default `std` feature; without it
+//! the crate is `no_std` and only needs `alloc`, and callers pass the current time explicitly.
+//! Without `alloc` as well, only the allocation-free `totp_raw_into` and `hotp_raw_into` remain.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+mod account;
+#[cfg(feature = "std")]
+mod aegis;
+#[cfg(feature = "std")]
+mod andotp;
+#[cfg(feature = "std")]
+mod async_verifier;
+#[cfg(feature = "authy")]
+mod authy;
+#[cfg(feature = "std")]
+mod base64;
+#[cfg(feature = "std")]
+mod bitwarden;
+#[cfg(feature = "qr")]
+mod bulk;
+#[cfg(feature = "capi")]
 mod c_api;
+mod clock;
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+mod crypto;
+#[cfg(feature = "std")]
+mod csv;
+#[cfg(feature = "flutter")]
+mod dart_api;
+#[cfg(feature = "std")]
+mod enrollment;
+#[cfg(feature = "qr")]
+mod eps;
+#[cfg(feature = "std")]
+mod error;
+#[cfg(feature = "std")]
+mod freeotp;
+mod hash;
+#[cfg(feature = "alloc")]
+mod iter;
+#[cfg(feature = "std")]
+mod json;
+#[cfg(feature = "std")]
+mod keepassxc;
+#[cfg(feature = "std")]
+mod limiter;
+#[cfg(feature = "std")]
+mod lockout;
+#[cfg(feature = "qr")]
+mod logo;
+#[cfg(feature = "std")]
+mod migration;
+#[cfg(feature = "ntp")]
+mod ntp;
+#[cfg(feature = "std")]
+mod observer;
+#[cfg(feature = "pdf")]
+mod pdf;
+#[cfg(feature = "image")]
+mod png;
+#[cfg(feature = "std")]
+mod precompute;
+#[cfg(feature = "std")]
+mod secret;
+#[cfg(feature = "qr")]
+mod sheet;
+#[cfg(feature = "qr")]
+mod svg;
+#[cfg(feature = "qr")]
+mod terminal;
+#[cfg(feature = "std")]
+mod timing;
+#[cfg(feature = "std")]
+mod twofas;
+#[cfg(feature = "std")]
+mod uri;
+#[cfg(feature = "alloc")]
+mod verify;
+#[cfg(feature = "std")]
+mod verifier;
+#[cfg(feature = "std")]
+mod watcher;
+#[cfg(feature = "std")]
+pub use account::*;
+#[cfg(feature = "std")]
+pub use aegis::*;
+#[cfg(feature = "std")]
+pub use andotp::*;
+#[cfg(feature = "std")]
+pub use async_verifier::*;
+#[cfg(feature = "authy")]
+pub use authy::*;
+#[cfg(feature = "std")]
+pub use bitwarden::*;
+#[cfg(feature = "qr")]
+pub use bulk::*;
+#[cfg(feature = "capi")]
 pub use c_api::*;
+pub use clock::*;
+pub use crypto::ct_eq_codes;
+#[cfg(feature = "test-rng")]
+pub use crypto::{seed_rng, unseed_rng};
+#[cfg(feature = "std")]
+pub use csv::*;
+#[cfg(feature = "flutter")]
+pub use dart_api::*;
+#[cfg(feature = "std")]
+pub use enrollment::*;
+#[cfg(feature = "qr")]
+pub use eps::*;
+#[cfg(feature = "std")]
+pub use error::*;
+#[cfg(feature = "std")]
+pub use freeotp::*;
+#[cfg(feature = "alloc")]
+pub use iter::*;
+#[cfg(feature = "std")]
+pub use keepassxc::*;
+#[cfg(feature = "std")]
+pub use limiter::*;
+#[cfg(feature = "std")]
+pub use lockout::*;
+#[cfg(feature = "qr")]
+pub use logo::*;
+#[cfg(feature = "std")]
+pub use migration::*;
+#[cfg(feature = "ntp")]
+pub use ntp::*;
+#[cfg(feature = "std")]
+pub use observer::*;
+#[cfg(feature = "pdf")]
+pub use pdf::*;
+#[cfg(feature = "image")]
+pub use png::*;
+#[cfg(feature = "std")]
+pub use precompute::*;
+#[cfg(feature = "std")]
+pub use secret::*;
+#[cfg(feature = "qr")]
+pub use sheet::*;
+#[cfg(feature = "qr")]
+pub use svg::{GradientDirection, ModuleShape, QrGradient};
+#[cfg(feature = "qr")]
+pub use terminal::*;
+#[cfg(feature = "std")]
+pub use timing::*;
+#[cfg(feature = "std")]
+pub use twofas::*;
+#[cfg(feature = "std")]
+pub use uri::*;
+#[cfg(feature = "alloc")]
+pub use verify::*;
+#[cfg(feature = "std")]
+pub use verifier::*;
+#[cfg(feature = "std")]
+pub use watcher::*;
 
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+use alloc::string::String;
+#[cfg(feature = "alloc")]
 use base32::decode;
+#[cfg(feature = "alloc")]
 use base32::Alphabet;
-use hmac::{Hmac, KeyInit, Mac};
-use qrcode::render::svg;
+#[cfg(feature = "qr")]
 use qrcode::{EcLevel, QrCode, Version};
-use rand::Rng;
-use sha1::Sha1;
+#[cfg(feature = "std")]
 use std::time::{SystemTime, UNIX_EPOCH};
 
 
-type HmacSha1 = Hmac<Sha1>;
+/// HMAC hash function used to derive codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Algorithm {
+    #[default]
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl Algorithm {
+    /// Name of the algorithm as used in otpauth URIs, e.g. `"SHA1"`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Algorithm::Sha1 => "SHA1",
+            Algorithm::Sha256 => "SHA256",
+            Algorithm::Sha512 => "SHA512",
+        }
+    }
+}
+
+impl core::fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
 
+/// Account, styling and code parameters for the QR code functions.
+///
+/// Start from `TotpQrConfig::default()` or `TotpQrConfig::builder()` and set only what differs:
+/// black on white, at least 200 px, the smallest QR version that fits, error correction level
+/// `M`, and SHA1 with 6 digits every 30 seconds.
+#[cfg(feature = "qr")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct TotpQrConfig<'a> {
     pub account_name: &'a str,
     pub issuer: &'a str,
-    pub dark_color: &'a str,        // e.g. "#000000"
-    pub light_color: &'a str,       // e.g. "#ffffff"
+    pub dark_color: &'a str,        // "#rrggbb" or "#rgb", e.g. "#000000"
+    pub light_color: &'a str,       // "#rrggbb" or "#rgb", e.g. "#ffffff"
     pub min_dimension: u32,         // minimum width/height in px
     pub version: Version,           // QR code version
     pub ec_level: EcLevel,          // error correction level
+    pub module_shape: ModuleShape,  // shape of the dark modules
+    pub gradient: Option<QrGradient<'a>>, // fills dark modules instead of dark_color
+    pub quiet_zone: u32,            // light border in modules; scanners expect at least 4
+    pub margin: u32,                // extra light border in px outside the quiet zone
+    pub module_size: Option<u32>,   // exact px per module, overrides min_dimension
+    pub exact_size: Option<(u32, u32)>, // final width and height in px; the code is scaled and centered
+    pub aria_label: Option<&'a str>, // accessible name, defaults to the <title> text
+    pub algorithm: Algorithm,       // HMAC algorithm the codes are generated with
+    pub digits: u32,                // number of digits in a code
+    pub period: u64,                // time step in seconds
+}
+
+#[cfg(feature = "qr")]
+impl Default for TotpQrConfig<'_> {
+    fn default() -> Self {
+        TotpQrConfig {
+            account_name: "",
+            issuer: "",
+            dark_color: "#000000",
+            light_color: "#ffffff",
+            min_dimension: 200,
+            version: Version::Normal(1),
+            ec_level: EcLevel::M,
+            module_shape: ModuleShape::Square,
+            gradient: None,
+            quiet_zone: 4,
+            margin: 0,
+            module_size: None,
+            exact_size: None,
+            aria_label: None,
+            algorithm: Algorithm::Sha1,
+            digits: 6,
+            period: 30,
+        }
+    }
+}
+
+#[cfg(feature = "qr")]
+impl<'a> TotpQrConfig<'a> {
+    /// Starts a builder from the default configuration.
+    ///
+    /// # Example
+    /// ```rust
+    /// use datp::TotpQrConfig;
+    ///
+    /// let config = TotpQrConfig::builder().account("a@b.c").issuer("App").build();
+    /// assert_eq!(config.digits, 6);
+    /// ```
+    pub fn builder() -> TotpQrConfigBuilder<'a> {
+        TotpQrConfigBuilder { config: TotpQrConfig::default() }
+    }
+}
+
+/// Builder for `TotpQrConfig`, see `TotpQrConfig::builder`.
+#[cfg(feature = "qr")]
+#[derive(Debug, Clone, Copy)]
+pub struct TotpQrConfigBuilder<'a> {
+    config: TotpQrConfig<'a>,
+}
+
+#[cfg(feature = "qr")]
+impl<'a> TotpQrConfigBuilder<'a> {
+    pub fn account(mut self, account_name: &'a str) -> Self {
+        self.config.account_name = account_name;
+        self
+    }
+
+    pub fn issuer(mut self, issuer: &'a str) -> Self {
+        self.config.issuer = issuer;
+        self
+    }
+
+    pub fn dark_color(mut self, color: &'a str) -> Self {
+        self.config.dark_color = color;
+        self
+    }
+
+    pub fn light_color(mut self, color: &'a str) -> Self {
+        self.config.light_color = color;
+        self
+    }
+
+    pub fn min_dimension(mut self, pixels: u32) -> Self {
+        self.config.min_dimension = pixels;
+        self
+    }
+
+    pub fn version(mut self, version: Version) -> Self {
+        self.config.version = version;
+        self
+    }
+
+    pub fn ec_level(mut self, ec_level: EcLevel) -> Self {
+        self.config.ec_level = ec_level;
+        self
+    }
+
+    pub fn module_shape(mut self, shape: ModuleShape) -> Self {
+        self.config.module_shape = shape;
+        self
+    }
+
+    pub fn gradient(mut self, gradient: QrGradient<'a>) -> Self {
+        self.config.gradient = Some(gradient);
+        self
+    }
+
+    pub fn quiet_zone(mut self, modules: u32) -> Self {
+        self.config.quiet_zone = modules;
+        self
+    }
+
+    pub fn margin(mut self, pixels: u32) -> Self {
+        self.config.margin = pixels;
+        self
+    }
+
+    pub fn module_size(mut self, pixels: u32) -> Self {
+        self.config.module_size = Some(pixels);
+        self
+    }
+
+    pub fn exact_size(mut self, width: u32, height: u32) -> Self {
+        self.config.exact_size = Some((width, height));
+        self
+    }
+
+    pub fn aria_label(mut self, label: &'a str) -> Self {
+        self.config.aria_label = Some(label);
+        self
+    }
+
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.config.algorithm = algorithm;
+        self
+    }
+
+    pub fn digits(mut self, digits: u32) -> Self {
+        self.config.digits = digits;
+        self
+    }
+
+    pub fn period(mut self, period: u64) -> Self {
+        self.config.period = period;
+        self
+    }
+
+    pub fn build(self) -> TotpQrConfig<'a> {
+        self.config
+    }
+}
+
+/// How a QR code looks, independent of what it encodes; see `qr_svg_for`.
+///
+/// The fields mean the same as in `TotpQrConfig`, and so do the defaults.
+#[cfg(feature = "qr")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QrStyle<'a> {
+    pub dark_color: &'a str,
+    pub light_color: &'a str,
+    pub min_dimension: u32,
+    pub version: Version,
+    pub ec_level: EcLevel,
+    pub module_shape: ModuleShape,
+    pub gradient: Option<QrGradient<'a>>,
+    pub quiet_zone: u32,
+    pub margin: u32,
+    pub module_size: Option<u32>,
+    pub exact_size: Option<(u32, u32)>,
+    pub aria_label: Option<&'a str>,
+}
+
+#[cfg(feature = "qr")]
+impl Default for QrStyle<'_> {
+    fn default() -> Self {
+        TotpQrConfig::default().style()
+    }
+}
+
+#[cfg(feature = "qr")]
+impl<'a> TotpQrConfig<'a> {
+    /// The styling part of this configuration.
+    pub fn style(&self) -> QrStyle<'a> {
+        QrStyle {
+            dark_color: self.dark_color,
+            light_color: self.light_color,
+            min_dimension: self.min_dimension,
+            version: self.version,
+            ec_level: self.ec_level,
+            module_shape: self.module_shape,
+            gradient: self.gradient,
+            quiet_zone: self.quiet_zone,
+            margin: self.margin,
+            module_size: self.module_size,
+            exact_size: self.exact_size,
+            aria_label: self.aria_label,
+        }
+    }
 }
 
 /// Generates a random secret key for TOTP in base32 format.
@@ -37,12 +430,12 @@ pub struct TotpQrConfig<'a> {
 /// let secret = generate_totp_secret(10);
 /// println!("TOTP secret: {}", secret);
 /// ```
+#[cfg(feature = "std")]
 pub fn generate_totp_secret(length: usize) -> String {
-    let mut rng = rand::rng();
-    let mut bytes = vec![0u8; length];
-    rng.fill(&mut bytes);
-
-    base32::encode(Alphabet::Rfc4648 { padding: false }, &bytes)
+    let mut bytes = crypto::random_bytes(length);
+    let secret = base32::encode(Alphabet::Rfc4648 { padding: false }, &bytes);
+    crypto::wipe(&mut bytes);
+    secret
 }
 
 /// Generates a TOTP (Time-based One-Time Password) code for the current time.
@@ -63,6 +456,7 @@ pub fn generate_totp_secret(length: usize) -> String {
 /// let code = totp_raw_now(secret, 30, 0).unwrap();
 /// println!("Current TOTP code: {}", code);
 /// ```
+#[cfg(feature = "std")]
 pub fn totp_raw_now(secret_base32: &str, step: u64, t0: u64) -> Option<u32> {
     totp_raw(secret_base32, step, t0, SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs())
 }
@@ -76,7 +470,8 @@ pub fn totp_raw_now(secret_base32: &str, step: u64, t0: u64) -> Option<u32> {
 /// * `unix_time` - Specific unix time
 ///
 /// # Returns
-/// `Option<u32>` - A 6-digit TOTP code if successful, or `None` if the secret is invalid.
+/// `Option<u32>` - A 6-digit TOTP code if successful, or `None` if the secret is invalid, `step`
+/// is 0 or `unix_time` is before `t0`.
 ///
 /// # Example
 /// ```rust
@@ -86,25 +481,284 @@ pub fn totp_raw_now(secret_base32: &str, step: u64, t0: u64) -> Option<u32> {
 /// let code = totp_raw(secret, 30, 0, 1388865600).unwrap(); // 2014 year, 5 january, 0 hours, 0 minutes, 0 seconds
 /// println!("Current TOTP code: {}", code);
 /// ```
+#[cfg(feature = "alloc")]
 pub fn totp_raw(secret_base32: &str, step: u64, t0: u64, unix_time: u64) -> Option<u32> {
-    let secret = decode(Alphabet::Rfc4648 { padding: false }, secret_base32)?;
+    totp_raw_with(secret_base32, Algorithm::Sha1, 6, step, t0, unix_time)
+}
+
+/// Generates a TOTP code for the specific time with a custom algorithm and digit count.
+///
+/// # Arguments
+/// * `secret_base32` - A base32-encoded secret key (without padding).
+/// * `algorithm` - HMAC algorithm (`Algorithm::Sha1` for most authenticator apps).
+/// * `digits` - Number of digits in the code, from 1 to 9.
+/// * `step` - Time step in seconds (usually 30 seconds).
+/// * `t0` - Unix epoch start time (usually 0).
+/// * `unix_time` - Specific unix time
+///
+/// # Returns
+/// `Option<u32>` - The TOTP code if successful, or `None` if the secret or digit count is
+/// invalid, `step` is 0 or `unix_time` is before `t0`.
+///
+/// # Example
+/// ```rust
+/// use datp::{totp_raw_with, Algorithm};
+///
+/// let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZA";
+/// let code = totp_raw_with(secret, Algorithm::Sha256, 8, 30, 0, 59).unwrap();
+/// assert_eq!(code, 46119246);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn totp_raw_with(
+    secret_base32: &str,
+    algorithm: Algorithm,
+    digits: u32,
+    step: u64,
+    t0: u64,
+    unix_time: u64,
+) -> Option<u32> {
+    hotp_raw_with(secret_base32, algorithm, digits, unix_time.checked_sub(t0)?.checked_div(step)?)
+}
 
-    let counter = (unix_time - t0) / step;
-    let counter_bytes = counter.to_be_bytes();
+/// Returns how many seconds the TOTP code for `unix_time` stays valid, from `step` right after
+/// a rollover down to 1 just before the next one.
+///
+/// # Arguments
+/// * `step` - Time step in seconds (usually 30 seconds).
+/// * `t0` - Unix epoch start time (usually 0).
+/// * `unix_time` - Specific unix time
+///
+/// # Returns
+/// `Option<u64>` - The seconds left, or `None` if `step` is 0 or `unix_time` is before `t0`.
+///
+/// # Example
+/// ```rust
+/// use datp::seconds_remaining;
+///
+/// assert_eq!(seconds_remaining(30, 0, 59), Some(1));
+/// assert_eq!(seconds_remaining(30, 0, 60), Some(30));
+/// ```
+pub fn seconds_remaining(step: u64, t0: u64, unix_time: u64) -> Option<u64> {
+    Some(step - unix_time.checked_sub(t0)?.checked_rem(step)?)
+}
 
-    let mut mac = HmacSha1::new_from_slice(&secret).ok()?;
-    mac.update(&counter_bytes);
-    let hash = mac.finalize().into_bytes();
+/// Generates an HOTP (counter-based One-Time Password) code.
+///
+/// # Arguments
+/// * `secret_base32` - A base32-encoded secret key (without padding).
+/// * `counter` - Moving factor shared with the token.
+///
+/// # Returns
+/// `Option<u32>` - A 6-digit HOTP code if successful, or `None` if the secret is invalid.
+///
+/// # Example
+/// ```rust
+/// use datp::hotp_raw;
+///
+/// let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ"; // RFC 4226 test secret
+/// assert_eq!(hotp_raw(secret, 0), Some(755224));
+/// ```
+#[cfg(feature = "alloc")]
+pub fn hotp_raw(secret_base32: &str, counter: u64) -> Option<u32> {
+    hotp_raw_with(secret_base32, Algorithm::Sha1, 6, counter)
+}
+
+/// Generates an HOTP code with a custom algorithm and digit count.
+///
+/// # Arguments
+/// * `secret_base32` - A base32-encoded secret key (without padding).
+/// * `algorithm` - HMAC algorithm (`Algorithm::Sha1` for most authenticator apps).
+/// * `digits` - Number of digits in the code, from 1 to 9.
+/// * `counter` - Moving factor shared with the token.
+///
+/// # Returns
+/// `Option<u32>` - The HOTP code if successful, or `None` if the secret or digit count is invalid.
+#[cfg(feature = "alloc")]
+pub fn hotp_raw_with(secret_base32: &str, algorithm: Algorithm, digits: u32, counter: u64) -> Option<u32> {
+    if !(1..=9).contains(&digits) {
+        return None;
+    }
+    Some(hotp_truncated(secret_base32, algorithm, counter)? % 10u32.pow(digits))
+}
+
+/// Generates a Steam Guard code for the specific time.
+///
+/// Steam uses TOTP with SHA-1 and a 30 second step, but renders the code as
+/// five characters from its own alphabet instead of decimal digits.
+///
+/// # Arguments
+/// * `secret_base32` - A base32-encoded secret key (without padding).
+/// * `unix_time` - Specific unix time
+///
+/// # Returns
+/// `Option<String>` - A 5-character Steam code if successful, or `None` if the secret is invalid.
+///
+/// # Example
+/// ```rust
+/// use datp::steam_raw;
+///
+/// assert_eq!(steam_raw("JBSWY3DPEHPK3PXP", 59).as_deref(), Some("2YXGV"));
+/// ```
+#[cfg(feature = "alloc")]
+pub fn steam_raw(secret_base32: &str, unix_time: u64) -> Option<String> {
+    const STEAM_ALPHABET: &[u8; 26] = b"23456789BCDFGHJKMNPQRTVWXY";
 
-    let offset = (hash[19] & 0xf) as usize;
+    let mut code = hotp_truncated(secret_base32, Algorithm::Sha1, unix_time / 30)?;
+    let mut out = String::with_capacity(5);
+    for _ in 0..5 {
+        out.push(STEAM_ALPHABET[(code % 26) as usize] as char);
+        code /= 26;
+    }
+    Some(out)
+}
+
+/// Largest decoded secret, in bytes, accepted by `totp_raw_into` and `hotp_raw_into`; that is
+/// up to 206 base32 characters.
+pub const MAX_SECRET_BYTES: usize = 128;
+
+/// Writes the TOTP code for the specific time into `out`, without allocating.
+///
+/// The secret is decoded into a stack buffer of `MAX_SECRET_BYTES` and wiped afterwards, so this
+/// works on targets without an allocator and in interrupt handlers.
+///
+/// # Arguments
+/// * `secret_base32` - A base32-encoded secret key (without padding).
+/// * `algorithm` - HMAC algorithm (`Algorithm::Sha1` for most authenticator apps).
+/// * `digits` - Number of digits in the code, from 1 to 9.
+/// * `step` - Time step in seconds (usually 30 seconds).
+/// * `t0` - Unix epoch start time (usually 0).
+/// * `unix_time` - Specific unix time
+/// * `out` - Buffer for the ASCII digits, at least `digits` bytes long.
+///
+/// # Returns
+/// `Option<&str>` - The zero-padded code in the first `digits` bytes of `out`, or `None` if the
+/// secret is invalid or longer than `MAX_SECRET_BYTES`, the digit count is invalid, `out` is too
+/// short, `step` is 0 or `unix_time` is before `t0`.
+///
+/// # Example
+/// ```rust
+/// use datp::{totp_raw_into, Algorithm};
+///
+/// let mut buf = [0u8; 8];
+/// let code = totp_raw_into("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", Algorithm::Sha1, 8, 30, 0, 59, &mut buf);
+/// assert_eq!(code, Some("94287082"));
+/// ```
+#[allow(clippy::too_many_arguments)]
+pub fn totp_raw_into<'a>(
+    secret_base32: &str,
+    algorithm: Algorithm,
+    digits: u32,
+    step: u64,
+    t0: u64,
+    unix_time: u64,
+    out: &'a mut [u8],
+) -> Option<&'a str> {
+    let counter = unix_time.checked_sub(t0)?.checked_div(step)?;
+    hotp_raw_into(secret_base32, algorithm, digits, counter, out)
+}
+
+/// Writes the HOTP code for `counter` into `out`, without allocating; see `totp_raw_into`.
+///
+/// # Example
+/// ```rust
+/// use datp::{hotp_raw_into, Algorithm};
+///
+/// let mut buf = [0u8; 6];
+/// let code = hotp_raw_into("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", Algorithm::Sha1, 6, 0, &mut buf);
+/// assert_eq!(code, Some("755224"));
+/// ```
+pub fn hotp_raw_into<'a>(
+    secret_base32: &str,
+    algorithm: Algorithm,
+    digits: u32,
+    counter: u64,
+    out: &'a mut [u8],
+) -> Option<&'a str> {
+    if !(1..=9).contains(&digits) || out.len() < digits as usize {
+        return None;
+    }
+    let mut key = [0u8; MAX_SECRET_BYTES];
+    let code = decode_secret_into(secret_base32, &mut key)
+        .map(|len| hotp_truncated_key(&key[..len], algorithm, counter) % 10u32.pow(digits));
+    crypto::wipe(&mut key);
+
+    let out = &mut out[..digits as usize];
+    write_digits(code?, out);
+    core::str::from_utf8(out).ok()
+}
+
+/// Writes `code` as zero-padded ASCII digits filling `out`.
+pub(crate) fn write_digits(mut code: u32, out: &mut [u8]) {
+    for digit in out.iter_mut().rev() {
+        *digit = b'0' + (code % 10) as u8;
+        code /= 10;
+    }
+}
+
+/// Decodes unpadded RFC 4648 base32 into `out` and returns the number of bytes, accepting the
+/// same input as `base32::decode`.
+fn decode_secret_into(secret_base32: &str, out: &mut [u8]) -> Option<usize> {
+    let len = secret_base32.len() * 5 / 8;
+    if len > out.len() {
+        return None;
+    }
+    let (mut buffer, mut bits, mut written) = (0u32, 0, 0);
+    for c in secret_base32.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'2'..=b'7' => c - b'2' + 26,
+            _ => return None,
+        };
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out[written] = (buffer >> bits) as u8;
+            written += 1;
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(written)
+}
+
+/// HMAC plus RFC 4226 dynamic truncation, before reducing to digits.
+#[cfg(feature = "alloc")]
+fn hotp_truncated(secret_base32: &str, algorithm: Algorithm, counter: u64) -> Option<u32> {
+    with_key(secret_base32, |key| hotp_truncated_key(key, algorithm, counter))
+}
+
+/// Runs `f` on the decoded secret and wipes the copy afterwards. Secrets of up to
+/// `MAX_SECRET_BYTES` are decoded on the stack, so generating or checking a code does not
+/// allocate; only longer ones go through a heap buffer.
+#[cfg(feature = "alloc")]
+pub(crate) fn with_key<T>(secret_base32: &str, f: impl FnOnce(&[u8]) -> T) -> Option<T> {
+    if secret_base32.len() * 5 / 8 > MAX_SECRET_BYTES {
+        let mut key = decode(Alphabet::Rfc4648 { padding: false }, secret_base32)?;
+        let result = f(&key);
+        crypto::wipe(&mut key);
+        return Some(result);
+    }
+    let mut key = [0u8; MAX_SECRET_BYTES];
+    let result = decode_secret_into(secret_base32, &mut key).map(|len| f(&key[..len]));
+    crypto::wipe(&mut key);
+    result
+}
+
+/// `hotp_truncated` for an already decoded key.
+pub(crate) fn hotp_truncated_key(key: &[u8], algorithm: Algorithm, counter: u64) -> u32 {
+    hotp_truncated_mac(&hash::HmacKey::new(algorithm, key), counter)
+}
+
+/// `hotp_truncated` with the HMAC state already keyed, for computing several counters.
+pub(crate) fn hotp_truncated_mac(key: &hash::HmacKey, counter: u64) -> u32 {
+    let hash = key.mac(&counter.to_be_bytes());
+
+    let offset = (hash[hash.len() - 1] & 0xf) as usize;
     let code_bytes = &hash[offset..offset + 4];
-    let mut code = ((code_bytes[0] as u32 & 0x7f) << 24)
+    ((code_bytes[0] as u32 & 0x7f) << 24)
         | ((code_bytes[1] as u32) << 16)
         | ((code_bytes[2] as u32) << 8)
-        | (code_bytes[3] as u32);
-
-    code %= 1_000_000;
-    Some(code)
+        | (code_bytes[3] as u32)
 }
 
 
@@ -115,47 +769,163 @@ pub fn totp_raw(secret_base32: &str, step: u64, t0: u64, unix_time: u64) -> Opti
 /// * `config` - TotpQrConfig struct with customization options.
 ///
 /// # Returns
-/// `String` - SVG image of the QR code.
+/// `Ok(String)` - SVG image of the QR code, `DatpError::InvalidColor` for colors that are not
+/// `#rrggbb` or `#rgb`, or `DatpError::QrCode` when the URI does not fit into a QR code at the
+/// configured error correction level.
 ///
 /// # Example
 /// ```rust
 /// use datp::{totp_qr_svg, TotpQrConfig};
 ///
 /// let secret = "JBSWY3DPEHPK3PXP";
-/// let config = TotpQrConfig {
-///     account_name: "user@example.com",
-///     issuer: "MyApp",
-///     dark_color: "#000080",
-///     light_color: "#ffffcc",
-///     min_dimension: 250,
-///     version: qrcode::Version::Normal(5),
-///     ec_level: qrcode::EcLevel::M,
-/// };
-/// let svg = totp_qr_svg(secret, &config);
+/// let config = TotpQrConfig::builder()
+///     .account("user@example.com")
+///     .issuer("MyApp")
+///     .dark_color("#000080")
+///     .light_color("#ffffcc")
+///     .min_dimension(250)
+///     .build();
+/// let svg = totp_qr_svg(secret, &config).unwrap();
 /// std::fs::write("totp.svg", svg).unwrap();
 /// ```
-pub fn totp_qr_svg(secret_base32: &str, config: &TotpQrConfig) -> String {
-    // build the otpauth URL
-    let url = format!(
-        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits=6&period=30",
-        config.issuer,
-        config.account_name,
-        secret_base32,
-        config.issuer
-    );
-
-    // dynamically create QR code (auto version)
-    let code = QrCode::new(url.as_bytes()).expect("Failed to create QR code");
-
-    // render SVG with custom colors and size
-    code.render()
-        .min_dimensions(config.min_dimension, config.min_dimension)
-        .dark_color(svg::Color(config.dark_color))
-        .light_color(svg::Color(config.light_color))
-        .build()
-}
-
-#[cfg(test)]
+#[cfg(feature = "qr")]
+pub fn totp_qr_svg(secret_base32: &str, config: &TotpQrConfig) -> Result<String, DatpError> {
+    let url = totp_url(secret_base32, config);
+    render_svg(&url, &config.style(), &svg::account_title(config.issuer, config.account_name), svg::ACCOUNT_DESCRIPTION)
+}
+
+/// Generates an HOTP QR code as an SVG string using custom configuration.
+///
+/// `config.period` is ignored; the URI carries `counter` instead.
+///
+/// # Arguments
+/// * `secret_base32` - Base32-encoded HOTP secret.
+/// * `counter` - Initial counter value of the token.
+/// * `config` - TotpQrConfig struct with customization options.
+///
+/// # Returns
+/// `Ok(String)` - SVG image of the QR code, `DatpError::InvalidColor` for colors that are not
+/// hex, or `DatpError::QrCode` when the URI is too long.
+#[cfg(feature = "qr")]
+pub fn hotp_qr_svg(secret_base32: &str, counter: u64, config: &TotpQrConfig) -> Result<String, DatpError> {
+    let url = build_otpauth_uri(&config_account(secret_base32, config, OtpKind::Hotp, Some(counter)));
+    render_svg(&url, &config.style(), &svg::account_title(config.issuer, config.account_name), svg::ACCOUNT_DESCRIPTION)
+}
+
+/// Generates a TOTP QR code as a `data:image/svg+xml;base64,...` URI.
+///
+/// The result can be used directly as the `src` of an `<img>` element.
+///
+/// # Arguments
+/// * `secret_base32` - Base32-encoded TOTP secret.
+/// * `config` - TotpQrConfig struct with customization options.
+///
+/// # Returns
+/// `Ok(String)` - The data URI, or `DatpError::QrCode` when the URI does not fit into a QR code.
+#[cfg(feature = "qr")]
+pub fn totp_qr_data_uri(secret_base32: &str, config: &TotpQrConfig) -> Result<String, DatpError> {
+    let svg = totp_qr_svg(secret_base32, config)?;
+    Ok(format!("data:image/svg+xml;base64,{}", base64::encode(svg.as_bytes())))
+}
+
+/// Returns the QR code version `totp_qr_svg` renders for the given secret and configuration.
+///
+/// `config.version` is treated as the preferred version: when the URI does not fit into it at
+/// `config.ec_level`, the smallest version that does fit is used instead.
+///
+/// # Example
+/// ```rust
+/// use datp::{totp_qr_version, TotpQrConfig};
+/// use qrcode::{EcLevel, Version};
+///
+/// let config = TotpQrConfig::builder().account("user@example.com").issuer("MyApp").ec_level(EcLevel::H).build();
+/// // far too much data for version 1, so a larger one is picked
+/// assert!(matches!(totp_qr_version("JBSWY3DPEHPK3PXP", &config), Ok(Version::Normal(v)) if v > 1));
+/// ```
+#[cfg(feature = "qr")]
+pub fn totp_qr_version(secret_base32: &str, config: &TotpQrConfig) -> Result<Version, DatpError> {
+    Ok(encode_qr(&totp_url(secret_base32, config), &config.style())?.version())
+}
+
+/// Renders the otpauth URI of any account as an SVG QR code.
+///
+/// Unlike `totp_qr_svg`, all parameters come from the account, so HOTP counters, Steam
+/// accounts and non-default algorithms, digits and periods are kept.
+///
+/// # Arguments
+/// * `account` - Account to provision.
+/// * `style` - Colors, size and shape of the code.
+///
+/// # Returns
+/// `Ok(String)` - SVG image of the QR code, or `DatpError::QrCode` when the URI is too long.
+///
+/// # Example
+/// ```rust
+/// use datp::{qr_svg_for, Account, Algorithm, QrStyle};
+///
+/// let mut account = Account::new_hotp("alice", "JBSWY3DPEHPK3PXP", 0);
+/// account.algorithm = Algorithm::Sha256;
+/// let svg = qr_svg_for(&account, &QrStyle { dark_color: "#000080", ..Default::default() }).unwrap();
+/// ```
+#[cfg(feature = "qr")]
+pub fn qr_svg_for(account: &Account, style: &QrStyle) -> Result<String, DatpError> {
+    let title = svg::account_title(account.issuer.as_deref().unwrap_or(""), &account.account_name);
+    render_svg(&build_otpauth_uri(account), style, &title, svg::ACCOUNT_DESCRIPTION)
+}
+
+/// Renders an existing URI, such as a pre-built `otpauth://` link, as an SVG QR code.
+///
+/// The URI is encoded as given. When it parses as an otpauth URI, the SVG title names the account.
+///
+/// # Returns
+/// `Ok(String)` - SVG image of the QR code, or `DatpError::QrCode` when the URI is too long.
+#[cfg(feature = "qr")]
+pub fn qr_svg_for_uri(uri: &str, style: &QrStyle) -> Result<String, DatpError> {
+    match Account::from_uri(uri) {
+        Ok(account) => {
+            let title = svg::account_title(account.issuer.as_deref().unwrap_or(""), &account.account_name);
+            render_svg(uri, style, &title, svg::ACCOUNT_DESCRIPTION)
+        }
+        Err(_) => render_svg(uri, style, "QR code", ""),
+    }
+}
+
+/// Encodes `url` with the configured version and error correction level, growing the version if needed.
+#[cfg(feature = "qr")]
+fn encode_qr(url: &str, style: &QrStyle) -> Result<QrCode, DatpError> {
+    QrCode::with_version(url.as_bytes(), style.version, style.ec_level)
+        .or_else(|_| QrCode::with_error_correction_level(url.as_bytes(), style.ec_level))
+        .map_err(|e| DatpError::QrCode(e.to_string()))
+}
+
+#[cfg(feature = "qr")]
+pub(crate) fn render_svg(url: &str, style: &QrStyle, title: &str, description: &str) -> Result<String, DatpError> {
+    let code = encode_qr(url, style)?;
+    svg::render(&code, style, title, description)
+}
+
+/// Builds the otpauth URL in the canonical `issuer:account` label form.
+#[cfg(feature = "qr")]
+fn totp_url(secret_base32: &str, config: &TotpQrConfig) -> String {
+    build_otpauth_uri(&config_account(secret_base32, config, OtpKind::Totp, None))
+}
+
+#[cfg(feature = "qr")]
+fn config_account(secret_base32: &str, config: &TotpQrConfig, kind: OtpKind, counter: Option<u64>) -> Account {
+    Account {
+        kind,
+        issuer: Some(config.issuer.to_string()),
+        account_name: config.account_name.to_string(),
+        secret: secret_base32.to_string(),
+        algorithm: config.algorithm,
+        digits: config.digits,
+        period: config.period,
+        counter,
+        image: None,
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use super::*;
 
@@ -184,6 +954,196 @@ mod tests {
         assert!(code.is_none());
     }
 
+    #[cfg(feature = "qr")]
+    #[test]
+    fn test_totp_url_encoding() {
+        let config = TotpQrConfig {
+            account_name: "user name+test@example.com",
+            issuer: "My App",
+            ..Default::default()
+        };
+        let url = totp_url("JBSWY3DPEHPK3PXP", &config);
+        assert_eq!(
+            url,
+            "otpauth://totp/My%20App:user%20name%2Btest%40example.com?secret=JBSWY3DPEHPK3PXP&issuer=My%20App&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+
+    #[cfg(feature = "qr")]
+    #[test]
+    fn test_totp_url_without_issuer() {
+        let config = TotpQrConfig {
+            account_name: "alice",
+            ..Default::default()
+        };
+        let url = totp_url("JBSWY3DPEHPK3PXP", &config);
+        assert_eq!(url, "otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&algorithm=SHA1&digits=6&period=30");
+    }
+
+    #[cfg(feature = "qr")]
+    #[test]
+    fn test_totp_url_reflects_parameters() {
+        let config = TotpQrConfig {
+            account_name: "alice",
+            issuer: "Corp",
+            algorithm: Algorithm::Sha512,
+            digits: 8,
+            period: 60,
+            ..Default::default()
+        };
+        let url = totp_url("JBSWY3DPEHPK3PXP", &config);
+        assert!(url.ends_with("&algorithm=SHA512&digits=8&period=60"));
+    }
+
+    #[test]
+    fn test_totp_rfc6238_vectors() {
+        let sha1 = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        let sha256 = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZA";
+        let sha512 = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNA";
+        let vectors = [
+            (59, 94287082, 46119246, 90693936),
+            (1111111109, 7081804, 68084774, 25091201),
+            (1234567890, 89005924, 91819424, 93441116),
+            (2000000000, 69279037, 90698825, 38618901),
+            (20000000000, 65353130, 77737706, 47863826),
+        ];
+        for (time, code1, code256, code512) in vectors {
+            assert_eq!(totp_raw_with(sha1, Algorithm::Sha1, 8, 30, 0, time), Some(code1));
+            assert_eq!(totp_raw_with(sha256, Algorithm::Sha256, 8, 30, 0, time), Some(code256));
+            assert_eq!(totp_raw_with(sha512, Algorithm::Sha512, 8, 30, 0, time), Some(code512));
+        }
+    }
+
+    #[test]
+    fn test_hotp_rfc4226_vectors() {
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        let expected = [755224, 287082, 359152, 969429, 338314, 254676, 287922, 162583, 399871, 520489];
+        for (counter, code) in expected.into_iter().enumerate() {
+            assert_eq!(hotp_raw(secret, counter as u64), Some(code));
+        }
+    }
+
+    #[test]
+    fn test_steam_code() {
+        assert_eq!(steam_raw("JBSWY3DPEHPK3PXP", 1_700_000_000).as_deref(), Some("2KM2P"));
+        assert!(steam_raw("invalid!!secret", 0).is_none());
+    }
+
+    #[test]
+    fn test_raw_into() {
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZA";
+        let mut buf = [0u8; 9];
+        for counter in 0..50 {
+            let expected = format!("{:08}", hotp_raw_with(secret, Algorithm::Sha256, 8, counter).unwrap());
+            assert_eq!(hotp_raw_into(secret, Algorithm::Sha256, 8, counter, &mut buf), Some(expected.as_str()));
+        }
+        assert_eq!(totp_raw_into(secret, Algorithm::Sha256, 8, 30, 0, 59, &mut buf), Some("46119246"));
+        assert_eq!(totp_raw_into(secret, Algorithm::Sha256, 8, 0, 0, 59, &mut buf), None);
+        assert_eq!(totp_raw_into(secret, Algorithm::Sha256, 8, 30, 60, 59, &mut buf), None);
+        assert_eq!(hotp_raw_into(secret, Algorithm::Sha1, 10, 0, &mut buf), None);
+        assert_eq!(hotp_raw_into(secret, Algorithm::Sha1, 6, 0, &mut buf[..5]), None);
+        assert_eq!(hotp_raw_into(&"A".repeat(207), Algorithm::Sha1, 6, 0, &mut buf), None);
+        assert!(hotp_raw_into(&"A".repeat(206), Algorithm::Sha1, 6, 0, &mut buf).is_some());
+    }
+
+    #[test]
+    fn test_decode_secret_into_matches_base32() {
+        let mut key = [0u8; MAX_SECRET_BYTES];
+        for secret in ["", "A", "JBSWY3DPEHPK3PXP", "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", "MZXW6YTBOI", "MZXW6", "ab", "A=", "AB C", "ÄBCD", "89"] {
+            let expected = decode(Alphabet::Rfc4648 { padding: false }, secret);
+            let len = decode_secret_into(secret, &mut key);
+            assert_eq!(len.map(|len| key[..len].to_vec()), expected, "{}", secret);
+        }
+    }
+
+    #[test]
+    fn test_totp_invalid_digits() {
+        assert!(totp_raw_with("JBSWY3DPEHPK3PXP", Algorithm::Sha1, 0, 30, 0, 59).is_none());
+        assert!(totp_raw_with("JBSWY3DPEHPK3PXP", Algorithm::Sha1, 10, 30, 0, 59).is_none());
+    }
+
+    #[test]
+    fn test_totp_invalid_step_and_t0() {
+        assert_eq!(totp_raw_with("JBSWY3DPEHPK3PXP", Algorithm::Sha1, 6, 0, 0, 59), None);
+        assert_eq!(totp_raw_with("JBSWY3DPEHPK3PXP", Algorithm::Sha1, 6, 30, 60, 59), None);
+        assert_eq!(totp_raw("JBSWY3DPEHPK3PXP", 30, 60, 59), None);
+        assert_eq!(totp_raw("JBSWY3DPEHPK3PXP", 30, 60, 60), totp_raw("JBSWY3DPEHPK3PXP", 30, 0, 0));
+    }
+
+    #[cfg(feature = "qr")]
+    #[test]
+    fn test_qr_honors_version_and_ec_level() {
+        let mut config = TotpQrConfig {
+            account_name: "user@example.com",
+            issuer: "MyApp",
+            version: Version::Normal(12),
+            ec_level: EcLevel::Q,
+            ..Default::default()
+        };
+        let code = encode_qr(&totp_url("JBSWY3DPEHPK3PXP", &config), &config.style()).unwrap();
+        assert_eq!(code.version(), Version::Normal(12));
+        assert_eq!(code.error_correction_level(), EcLevel::Q);
+
+        // version 1 cannot hold the URI, so the smallest fitting version is used
+        config.version = Version::Normal(1);
+        let low = totp_qr_version("JBSWY3DPEHPK3PXP", &config).unwrap();
+        config.ec_level = EcLevel::L;
+        let smaller = totp_qr_version("JBSWY3DPEHPK3PXP", &config).unwrap();
+        assert!(matches!((low, smaller), (Version::Normal(q), Version::Normal(l)) if l <= q && l > 1));
+    }
+
+    #[cfg(feature = "qr")]
+    #[test]
+    fn test_totp_qr_data_uri() {
+        let config = TotpQrConfig {
+            account_name: "user@example.com",
+            issuer: "MyApp",
+            ..Default::default()
+        };
+        let uri = totp_qr_data_uri("JBSWY3DPEHPK3PXP", &config).unwrap();
+        let payload = uri.strip_prefix("data:image/svg+xml;base64,").unwrap();
+        let svg = base64::decode(payload).unwrap();
+        assert_eq!(svg, totp_qr_svg("JBSWY3DPEHPK3PXP", &config).unwrap().into_bytes());
+    }
+
+    #[cfg(feature = "qr")]
+    #[test]
+    fn test_config_builder() {
+        let config = TotpQrConfig::builder().account("a@b.c").issuer("App").digits(8).ec_level(EcLevel::H).build();
+        assert_eq!(config, TotpQrConfig { account_name: "a@b.c", issuer: "App", digits: 8, ec_level: EcLevel::H, ..Default::default() });
+        assert_eq!(totp_url("JBSWY3DPEHPK3PXP", &config), "otpauth://totp/App:a%40b.c?secret=JBSWY3DPEHPK3PXP&issuer=App&algorithm=SHA1&digits=8&period=30");
+    }
+
+    #[cfg(feature = "qr")]
+    #[test]
+    fn test_qr_svg_for() {
+        let config = TotpQrConfig::builder().account("alice").issuer("Corp").build();
+        let account = config_account("JBSWY3DPEHPK3PXP", &config, OtpKind::Totp, None);
+        let svg = qr_svg_for(&account, &config.style()).unwrap();
+        assert_eq!(svg, totp_qr_svg("JBSWY3DPEHPK3PXP", &config).unwrap());
+        assert_eq!(qr_svg_for_uri(&build_otpauth_uri(&account), &config.style()).unwrap(), svg);
+
+        let hotp = Account::new_hotp("alice", "JBSWY3DPEHPK3PXP", 42);
+        let config = TotpQrConfig { account_name: "alice", ..Default::default() };
+        assert_eq!(qr_svg_for(&hotp, &QrStyle::default()).unwrap(), hotp_qr_svg("JBSWY3DPEHPK3PXP", 42, &config).unwrap());
+
+        let other = qr_svg_for_uri("https://example.com/enroll", &QrStyle::default()).unwrap();
+        assert!(other.contains("<title>QR code</title><rect"));
+    }
+
+    #[cfg(feature = "qr")]
+    #[test]
+    fn test_qr_too_long() {
+        let name = "x".repeat(4000);
+        let config = TotpQrConfig {
+            account_name: &name,
+            issuer: "MyApp",
+            ec_level: EcLevel::H,
+            ..Default::default()
+        };
+        assert!(matches!(totp_qr_svg("JBSWY3DPEHPK3PXP", &config), Err(DatpError::QrCode(_))));
+    }
+
     #[test]
     fn test_generate_totp_secret() {
         let secret = generate_totp_secret(10);
@@ -0,0 +1,121 @@
+use super::{AttemptStore, Throttle};
+
+/// Limit for one kind of key, see `AttemptLimiter::limit`.
+struct Limit {
+    dimension: String,
+    throttle: Throttle,
+    reset_on_success: bool,
+}
+
+/// Throttles attempts by several keys at once, e.g. the user, the client IP and the device,
+/// each with its own limit and window. Failures are counted in an `AttemptStore` under
+/// `dimension:key`, so one store serves every dimension.
+///
+/// # Example
+/// ```rust
+/// use std::collections::HashMap;
+/// use std::sync::Mutex;
+/// use datp::{AttemptLimiter, Throttle};
+///
+/// let limiter = AttemptLimiter::new(Mutex::new(HashMap::new()))
+///     .limit("user", Throttle { max_failures: 5, window: 300 }, true)
+///     .limit("ip", Throttle { max_failures: 2, window: 3600 }, false);
+///
+/// // one IP trying codes for several users
+/// limiter.record_failure(&[("user", "alice"), ("ip", "203.0.113.7")], 1_000);
+/// limiter.record_failure(&[("user", "bob"), ("ip", "203.0.113.7")], 1_010);
+/// assert_eq!(limiter.limited_by(&[("user", "carol"), ("ip", "203.0.113.7")], 1_020), Some("ip"));
+/// assert_eq!(limiter.limited_by(&[("user", "carol"), ("ip", "198.51.100.1")], 1_020), None);
+/// ```
+pub struct AttemptLimiter {
+    store: Box<dyn AttemptStore + Send + Sync>,
+    limits: Vec<Limit>,
+}
+
+impl AttemptLimiter {
+    /// Creates a limiter without limits; add them with `limit`.
+    pub fn new(store: impl AttemptStore + Send + Sync + 'static) -> Self {
+        AttemptLimiter { store: Box::new(store), limits: Vec::new() }
+    }
+
+    /// Limits keys of `dimension` to `throttle.max_failures` failures within `throttle.window`
+    /// seconds. With `reset_on_success`, a successful attempt forgets the failures of the key;
+    /// leave it off for keys like IP addresses, where an attacker could reset the count by
+    /// logging into an account of their own.
+    pub fn limit(mut self, dimension: &str, throttle: Throttle, reset_on_success: bool) -> Self {
+        self.limits.retain(|limit| limit.dimension != dimension);
+        self.limits.push(Limit { dimension: dimension.to_string(), throttle, reset_on_success });
+        self
+    }
+
+    /// The first dimension whose key is over its limit at `now`, or `None` if the attempt may
+    /// go ahead. `keys` are `(dimension, key)` pairs; dimensions without a limit are ignored.
+    pub fn limited_by<'a>(&self, keys: &[(&'a str, &str)], now: u64) -> Option<&'a str> {
+        keys.iter().find_map(|&(dimension, key)| {
+            let limit = self.find(dimension)?;
+            let failures = self.store.failures(&store_key(dimension, key), now.saturating_sub(limit.throttle.window));
+            (failures >= limit.throttle.max_failures).then_some(dimension)
+        })
+    }
+
+    /// Whether any of `keys` is over its limit, see `limited_by`.
+    pub fn is_limited(&self, keys: &[(&str, &str)], now: u64) -> bool {
+        self.limited_by(keys, now).is_some()
+    }
+
+    /// Records a failed attempt for every key.
+    pub fn record_failure(&self, keys: &[(&str, &str)], now: u64) {
+        for &(dimension, key) in keys {
+            if let Some(limit) = self.find(dimension) {
+                self.store.record_failure(&store_key(dimension, key), now, now.saturating_sub(limit.throttle.window));
+            }
+        }
+    }
+
+    /// Records a successful attempt: forgets the failures of the keys whose limit resets on
+    /// success.
+    pub fn record_success(&self, keys: &[(&str, &str)]) {
+        for &(dimension, key) in keys {
+            if self.find(dimension).is_some_and(|limit| limit.reset_on_success) {
+                self.store.clear(&store_key(dimension, key));
+            }
+        }
+    }
+
+    fn find(&self, dimension: &str) -> Option<&Limit> {
+        self.limits.iter().find(|limit| limit.dimension == dimension)
+    }
+}
+
+fn store_key(dimension: &str, key: &str) -> String {
+    format!("{}:{}", dimension, key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_attempt_limiter() {
+        let store = Arc::new(Mutex::new(HashMap::<String, Vec<u64>>::new()));
+        let limiter = AttemptLimiter::new(store.clone())
+            .limit("user", Throttle { max_failures: 2, window: 60 }, true)
+            .limit("device", Throttle { max_failures: 3, window: 600 }, false);
+        let keys = [("user", "alice"), ("device", "d1"), ("ip", "ignored")];
+
+        limiter.record_failure(&keys, 0);
+        limiter.record_failure(&keys, 10);
+        assert_eq!(limiter.limited_by(&keys, 20), Some("user"));
+        // the user window has passed, the device window has not
+        assert_eq!(limiter.limited_by(&keys, 100), None);
+        limiter.record_failure(&keys, 100);
+        assert_eq!(limiter.limited_by(&keys, 110), Some("device"));
+
+        limiter.record_success(&keys);
+        assert!(!store.lock().unwrap().contains_key("user:alice"));
+        assert_eq!(store.lock().unwrap()["device:d1"].len(), 3);
+        assert!(!store.lock().unwrap().contains_key("ip:ignored"));
+    }
+}
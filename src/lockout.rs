@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use super::AttemptStore;
+
+/// Locks a key after `max_failures` failed attempts within `window` seconds, for `lock_for`
+/// seconds or, with `None`, until `Lockout::unlock` is called (e.g. by the helpdesk).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockoutPolicy {
+    pub max_failures: u32,
+    pub window: u64,                 // seconds in which the failures are counted
+    pub lock_for: Option<u64>,       // seconds; None locks until unlocked manually
+}
+
+impl Default for LockoutPolicy {
+    fn default() -> Self {
+        LockoutPolicy { max_failures: 10, window: 900, lock_for: Some(900) }
+    }
+}
+
+/// Locked keys and when their lock ends, for `Lockout`.
+///
+/// Implemented for `Mutex<HashMap<String, u64>>`.
+pub trait LockStore {
+    /// Unix time until which `key` is locked, `u64::MAX` for a manual lock.
+    fn locked_until(&self, key: &str) -> Option<u64>;
+    fn lock(&self, key: &str, until: u64);
+    fn unlock(&self, key: &str);
+}
+
+impl LockStore for Mutex<HashMap<String, u64>> {
+    fn locked_until(&self, key: &str) -> Option<u64> {
+        self.lock().unwrap_or_else(|e| e.into_inner()).get(key).copied()
+    }
+
+    fn lock(&self, key: &str, until: u64) {
+        self.lock().unwrap_or_else(|e| e.into_inner()).insert(key.to_string(), until);
+    }
+
+    fn unlock(&self, key: &str) {
+        self.lock().unwrap_or_else(|e| e.into_inner()).remove(key);
+    }
+}
+
+/// Account lockout on top of an `AttemptStore`: failures are counted there, and once a key
+/// reaches the limit of the policy it is locked in the `LockStore`.
+///
+/// Share one `Lockout` (in an `Arc`) between `TotpVerifier::lockout` and the password check of
+/// the login, so both count towards the same lock.
+///
+/// # Example
+/// ```rust
+/// use std::collections::HashMap;
+/// use std::sync::Mutex;
+/// use datp::{Lockout, LockoutPolicy};
+///
+/// let policy = LockoutPolicy { max_failures: 3, window: 60, lock_for: None };
+/// let lockout = Lockout::new(Mutex::new(HashMap::new()), Mutex::new(HashMap::new()), policy);
+/// for time in [100, 110, 120] {
+///     lockout.record_failure("alice", time);
+/// }
+/// assert!(lockout.is_locked("alice", 1_000_000));
+/// lockout.unlock("alice");
+/// assert!(!lockout.is_locked("alice", 1_000_000));
+/// ```
+pub struct Lockout {
+    attempts: Box<dyn AttemptStore + Send + Sync>,
+    locks: Box<dyn LockStore + Send + Sync>,
+    policy: LockoutPolicy,
+}
+
+impl Lockout {
+    pub fn new(
+        attempts: impl AttemptStore + Send + Sync + 'static,
+        locks: impl LockStore + Send + Sync + 'static,
+        policy: LockoutPolicy,
+    ) -> Self {
+        Lockout { attempts: Box::new(attempts), locks: Box::new(locks), policy }
+    }
+
+    pub fn policy(&self) -> &LockoutPolicy {
+        &self.policy
+    }
+
+    /// Whether `key` is locked at `now`.
+    pub fn is_locked(&self, key: &str, now: u64) -> bool {
+        self.locks.locked_until(key).is_some_and(|until| now < until)
+    }
+
+    /// Records a failed attempt and locks `key` if that reaches the limit. Returns whether
+    /// `key` is locked now.
+    pub fn record_failure(&self, key: &str, now: u64) -> bool {
+        if self.is_locked(key, now) {
+            return true;
+        }
+        self.attempts.record_failure(key, now, now.saturating_sub(self.policy.window));
+        self.lock_if_over(key, now)
+    }
+
+    /// Counts an attempt of `key` as a failure before its code or password is checked, in one
+    /// atomic step of the `AttemptStore`. Returns `false`, without counting it, if `key` is
+    /// locked or other attempts already fill the limit. Follow up with `record_success` or
+    /// `fail_attempt` once the result is known.
+    pub fn begin_attempt(&self, key: &str, now: u64) -> bool {
+        if self.is_locked(key, now) {
+            return false;
+        }
+        self.attempts.try_reserve(key, now, now.saturating_sub(self.policy.window), self.policy.max_failures)
+    }
+
+    /// Ends an attempt started with `begin_attempt` that failed, locking `key` if its failures
+    /// reach the limit. Returns whether `key` is locked now.
+    pub fn fail_attempt(&self, key: &str, now: u64) -> bool {
+        self.is_locked(key, now) || self.lock_if_over(key, now)
+    }
+
+    fn lock_if_over(&self, key: &str, now: u64) -> bool {
+        if self.attempts.failures(key, now.saturating_sub(self.policy.window)) < self.policy.max_failures {
+            return false;
+        }
+        let until = self.policy.lock_for.map_or(u64::MAX, |seconds| now.saturating_add(seconds));
+        self.locks.lock(key, until);
+        self.attempts.clear(key);
+        true
+    }
+
+    /// Forgets the failures of `key` after a successful attempt. A lock stays in place.
+    pub fn record_success(&self, key: &str) {
+        self.attempts.clear(key);
+    }
+
+    /// Lifts the lock of `key` and forgets its failures.
+    pub fn unlock(&self, key: &str) {
+        self.locks.unlock(key);
+        self.attempts.clear(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timed_lockout() {
+        let policy = LockoutPolicy { max_failures: 2, window: 60, lock_for: Some(300) };
+        let lockout = Lockout::new(Mutex::new(HashMap::new()), Mutex::new(HashMap::new()), policy);
+        // failures further apart than the window don't add up
+        assert!(!lockout.record_failure("alice", 0));
+        assert!(!lockout.record_failure("alice", 100));
+        assert!(lockout.record_failure("alice", 150));
+        assert!(lockout.is_locked("alice", 449));
+        assert!(!lockout.is_locked("alice", 450));
+        assert!(!lockout.is_locked("bob", 200));
+
+        // the counter starts over after the lock
+        assert!(!lockout.record_failure("alice", 460));
+        lockout.record_success("alice");
+        assert!(!lockout.record_failure("alice", 470));
+    }
+
+    #[test]
+    fn test_begin_attempt() {
+        let policy = LockoutPolicy { max_failures: 2, window: 60, lock_for: None };
+        let lockout = Lockout::new(Mutex::new(HashMap::new()), Mutex::new(HashMap::new()), policy);
+        // two attempts in flight fill the limit before either is checked
+        assert!(lockout.begin_attempt("alice", 0));
+        assert!(lockout.begin_attempt("alice", 1));
+        assert!(!lockout.begin_attempt("alice", 2));
+        assert!(lockout.fail_attempt("alice", 3));
+        assert!(!lockout.begin_attempt("alice", 4));
+
+        assert!(lockout.begin_attempt("bob", 0));
+        lockout.record_success("bob");
+        assert!(lockout.begin_attempt("bob", 1));
+        assert!(!lockout.fail_attempt("bob", 2));
+    }
+}
@@ -0,0 +1,123 @@
+use qrcode::EcLevel;
+
+use super::svg::{self, SvgLayout};
+use super::{base64, encode_qr, totp_url, DatpError, TotpQrConfig};
+
+/// Image placed in the center of a QR code by `totp_qr_svg_with_logo`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QrLogo<'a> {
+    /// A complete `<svg>` element; give it a `viewBox` so it scales into the logo area.
+    Svg(&'a str),
+    /// Raster image bytes, embedded as a data URI.
+    Image { mime_type: &'a str, data: &'a [u8] },
+}
+
+/// Generates a TOTP QR code as an SVG string with a logo in the center.
+///
+/// The code is always encoded with error correction level `H`, whatever `config.ec_level`
+/// says, so that it still scans with the center covered. The logo is scaled into a square
+/// about a fifth of the code's width, on a background of `config.light_color`.
+///
+/// # Arguments
+/// * `secret_base32` - Base32-encoded TOTP secret.
+/// * `config` - TotpQrConfig struct with customization options.
+/// * `logo` - The image to composite.
+///
+/// # Returns
+/// `Ok(String)` - SVG image of the QR code, or `DatpError::QrCode` when the URI does not fit into a QR code.
+///
+/// # Example
+/// ```rust
+/// use datp::{totp_qr_svg_with_logo, QrLogo, TotpQrConfig};
+///
+/// let config = TotpQrConfig::builder().account("user@example.com").issuer("MyApp").min_dimension(250).build();
+/// let logo = QrLogo::Svg(r##"<svg viewBox="0 0 10 10"><circle cx="5" cy="5" r="5" fill="#e24"/></svg>"##);
+/// let svg = totp_qr_svg_with_logo("JBSWY3DPEHPK3PXP", &config, &logo).unwrap();
+/// ```
+pub fn totp_qr_svg_with_logo(secret_base32: &str, config: &TotpQrConfig, logo: &QrLogo) -> Result<String, DatpError> {
+    let config = TotpQrConfig { ec_level: EcLevel::H, ..*config };
+    let style = config.style();
+    let code = encode_qr(&totp_url(secret_base32, &config), &style)?;
+    let mut svg = svg::render(&code, &style, &svg::account_title(config.issuer, config.account_name), svg::ACCOUNT_DESCRIPTION)?;
+
+    let SvgLayout { unit, origin, .. } = SvgLayout::new(&code, &style);
+    // QR widths are odd, so an odd logo size keeps it on the module grid
+    let side = (code.width() as u32 / 5) | 1;
+    let start = (code.width() as u32 - side) / 2;
+    let (x, size) = (origin + start * unit, side * unit);
+    let (inner_x, inner_size) = (x + unit, (side - 2) * unit);
+
+    let mut overlay = format!(
+        r#"<rect x="{x}" y="{x}" width="{size}" height="{size}" fill="{}"/>"#,
+        config.light_color
+    );
+    match logo {
+        QrLogo::Svg(markup) => {
+            let markup = match markup.trim_start().strip_prefix("<?xml") {
+                Some(rest) => rest.split_once("?>").map_or("", |(_, body)| body),
+                None => markup,
+            };
+            overlay.push_str(&format!(
+                r#"<svg x="{inner_x}" y="{inner_x}" width="{inner_size}" height="{inner_size}">{}</svg>"#,
+                markup.trim()
+            ));
+        }
+        QrLogo::Image { mime_type, data } => {
+            overlay.push_str(&format!(
+                concat!(
+                    r#"<image x="{x}" y="{x}" width="{s}" height="{s}" preserveAspectRatio="xMidYMid meet""#,
+                    r#" xmlns:xlink="http://www.w3.org/1999/xlink" href="{uri}" xlink:href="{uri}"/>"#,
+                ),
+                x = inner_x,
+                s = inner_size,
+                // like the colors, caller input must not break out of the attribute
+                uri = format_args!("data:{};base64,{}", svg::escape_xml(mime_type), base64::encode(data)),
+            ));
+        }
+    }
+    svg.insert_str(svg.len() - "</svg>".len(), &overlay);
+    Ok(svg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use qrcode::QrCode;
+
+    fn config() -> TotpQrConfig<'static> {
+        TotpQrConfig {
+            account_name: "user@example.com",
+            issuer: "MyApp",
+            light_color: "#fffff0",
+            ec_level: EcLevel::L,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_logo_forces_ec_level_h() {
+        let config = config();
+        let logo = QrLogo::Svg("<?xml version=\"1.0\"?>\n<svg viewBox=\"0 0 1 1\"><rect width=\"1\" height=\"1\"/></svg>");
+        let svg = totp_qr_svg_with_logo("JBSWY3DPEHPK3PXP", &config, &logo).unwrap();
+        let code = QrCode::with_error_correction_level(totp_url("JBSWY3DPEHPK3PXP", &config).as_bytes(), EcLevel::H).unwrap();
+        let modules = code.width() as u32 + 8;
+        let unit = 200u32.div_ceil(modules);
+        assert!(svg.starts_with(&format!(r#"<?xml version="1.0" standalone="yes"?><svg xmlns="http://www.w3.org/2000/svg" version="1.1" width="{0}" height="{0}""#, modules * unit)));
+        assert!(svg.ends_with(r#"<svg viewBox="0 0 1 1"><rect width="1" height="1"/></svg></svg></svg>"#));
+        assert!(!svg[10..].contains("<?xml"));
+        assert!(svg.contains(r##"fill="#fffff0"/><svg x=""##));
+    }
+
+    #[test]
+    fn test_logo_raster() {
+        let logo = QrLogo::Image { mime_type: "image/png", data: b"\x89PNG" };
+        let svg = totp_qr_svg_with_logo("JBSWY3DPEHPK3PXP", &config(), &logo).unwrap();
+        assert!(svg.contains(r#"href="data:image/png;base64,iVBORw==""#));
+        assert!(svg.ends_with("/></svg>"));
+
+        let logo = QrLogo::Image { mime_type: r#"image/png"/><script>alert(1)</script><x y=""#, data: b"\x89PNG" };
+        let svg = totp_qr_svg_with_logo("JBSWY3DPEHPK3PXP", &config(), &logo).unwrap();
+        assert!(!svg.contains("<script"));
+        assert!(svg.contains(r#"href="data:image/png&quot;/&gt;&lt;script&gt;"#));
+    }
+}
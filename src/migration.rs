@@ -0,0 +1,371 @@
+// Google Authenticator "Transfer accounts" export format:
+// otpauth-migration://offline?data=<base64 MigrationPayload protobuf>
+
+use base32::Alphabet;
+
+use super::{base64, crypto, uri, Account, Algorithm, DatpError, OtpKind};
+#[cfg(feature = "qr")]
+use super::{render_svg, TotpQrConfig};
+
+/// Number of accounts Google Authenticator puts in a single export QR code.
+pub const MIGRATION_ACCOUNTS_PER_QR: usize = 10;
+
+/// Decodes an `otpauth-migration://offline?data=...` URI from a Google
+/// Authenticator "Transfer accounts" QR code into the accounts it carries.
+///
+/// Google's format has no period field, so every TOTP account gets the
+/// standard 30 second period.
+///
+/// # Example
+/// ```rust
+/// use datp::decode_migration_uri;
+///
+/// let uri = "otpauth-migration://offline?data=CiIKCkhlbGxvId6tvu8SBWFsaWNlGgdFeGFtcGxlIAEoATACEAE%3D";
+/// let accounts = decode_migration_uri(uri).unwrap();
+/// assert_eq!(accounts[0].account_name, "alice");
+/// assert_eq!(accounts[0].issuer.as_deref(), Some("Example"));
+/// ```
+pub fn decode_migration_uri(uri: &str) -> Result<Vec<Account>, DatpError> {
+    let rest = uri::strip_prefix_ignore_case(uri.trim(), "otpauth-migration://").ok_or(DatpError::InvalidScheme)?;
+    let (_, query) = rest.split_once('?').ok_or(DatpError::InvalidMigrationPayload)?;
+
+    let data = query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("data="))
+        .ok_or(DatpError::InvalidMigrationPayload)?;
+    // base64 uses '+', so the value must not go through form decoding
+    let data = uri::percent_decode(data, false)?;
+    let payload = base64::decode(&data).ok_or(DatpError::InvalidMigrationPayload)?;
+
+    decode_migration_payload(&payload)
+}
+
+/// Decodes the raw protobuf `MigrationPayload` carried in the `data` parameter.
+pub fn decode_migration_payload(payload: &[u8]) -> Result<Vec<Account>, DatpError> {
+    let mut accounts = Vec::new();
+    let mut reader = ProtoReader::new(payload);
+    while let Some((field, value)) = reader.next_field()? {
+        if let (1, ProtoValue::Bytes(otp)) = (field, value) {
+            accounts.push(decode_otp_parameters(otp)?);
+        }
+    }
+    Ok(accounts)
+}
+
+fn decode_otp_parameters(message: &[u8]) -> Result<Account, DatpError> {
+    let mut account = Account::new(String::new(), String::new());
+    let mut secret: &[u8] = &[];
+    let mut issuer = String::new();
+
+    let mut reader = ProtoReader::new(message);
+    while let Some((field, value)) = reader.next_field()? {
+        match (field, value) {
+            (1, ProtoValue::Bytes(bytes)) => secret = bytes,
+            (2, ProtoValue::Bytes(bytes)) => account.account_name = utf8(bytes)?,
+            (3, ProtoValue::Bytes(bytes)) => issuer = utf8(bytes)?,
+            (4, ProtoValue::Varint(value)) => {
+                account.algorithm = match value {
+                    0 | 1 => Algorithm::Sha1,
+                    2 => Algorithm::Sha256,
+                    3 => Algorithm::Sha512,
+                    4 => return Err(DatpError::UnknownAlgorithm("MD5".to_string())),
+                    other => return Err(DatpError::UnknownAlgorithm(other.to_string())),
+                }
+            }
+            (5, ProtoValue::Varint(value)) => {
+                account.digits = match value {
+                    0 | 1 => 6,
+                    2 => 8,
+                    other => return Err(DatpError::InvalidDigits(other.to_string())),
+                }
+            }
+            (6, ProtoValue::Varint(value)) => {
+                account.kind = if value == 1 { OtpKind::Hotp } else { OtpKind::Totp }
+            }
+            (7, ProtoValue::Varint(value)) => account.counter = Some(value),
+            _ => {}
+        }
+    }
+
+    if secret.is_empty() {
+        return Err(DatpError::MissingSecret);
+    }
+    account.secret = base32::encode(Alphabet::Rfc4648 { padding: false }, secret);
+
+    // the name usually repeats the issuer as an "Issuer:account" prefix
+    if let Some((prefix, name)) = account.account_name.split_once(':')
+        && (issuer.is_empty() || prefix == issuer)
+    {
+        issuer = prefix.trim().to_string();
+        account.account_name = name.trim().to_string();
+    }
+    account.issuer = Some(issuer).filter(|issuer| !issuer.is_empty());
+
+    if account.kind == OtpKind::Hotp && account.counter.is_none() {
+        account.counter = Some(0);
+    }
+    Ok(account)
+}
+
+/// Encodes accounts as `otpauth-migration://offline?data=...` URIs that
+/// Google Authenticator can import.
+///
+/// Accounts are split into batches of at most `accounts_per_qr` (see
+/// [`MIGRATION_ACCOUNTS_PER_QR`]); every URI carries the batch size, its index
+/// and a shared random batch id so the app can tell when the transfer is
+/// complete. Google's format only knows 6 or 8 digits and a 30 second period,
+/// other accounts are rejected.
+///
+/// # Example
+/// ```rust
+/// use datp::{decode_migration_uri, encode_migration_uris, Account};
+///
+/// let accounts = vec![Account::new("alice", "JBSWY3DPEHPK3PXP")];
+/// let uris = encode_migration_uris(&accounts, 10).unwrap();
+/// assert_eq!(decode_migration_uri(&uris[0]).unwrap(), accounts);
+/// ```
+pub fn encode_migration_uris(accounts: &[Account], accounts_per_qr: usize) -> Result<Vec<String>, DatpError> {
+    let random = crypto::random_bytes(4);
+    let batch_id = i32::from_le_bytes([random[0], random[1], random[2], random[3]]) & i32::MAX;
+    let batches: Vec<&[Account]> = accounts.chunks(accounts_per_qr.max(1)).collect();
+
+    let mut uris = Vec::with_capacity(batches.len());
+    for (index, batch) in batches.iter().enumerate() {
+        let payload = encode_migration_payload(batch, index as u32, batches.len() as u32, batch_id)?;
+        uris.push(format!(
+            "otpauth-migration://offline?data={}",
+            uri::percent_encode(&base64::encode(&payload))
+        ));
+    }
+    Ok(uris)
+}
+
+/// Renders the URIs of [`encode_migration_uris`] as SVG QR codes, one per batch.
+///
+/// Only the styling fields of `config` are used; the accounts carry their own
+/// names and parameters.
+#[cfg(feature = "qr")]
+pub fn migration_qr_svgs(
+    accounts: &[Account],
+    accounts_per_qr: usize,
+    config: &TotpQrConfig,
+) -> Result<Vec<String>, DatpError> {
+    let uris = encode_migration_uris(accounts, accounts_per_qr)?;
+    let style = config.style();
+    uris.iter()
+        .enumerate()
+        .map(|(i, uri)| {
+            let title = format!("Authenticator export QR code {} of {}", i + 1, uris.len());
+            render_svg(uri, &style, &title, "Scan with Google Authenticator to import the accounts.")
+        })
+        .collect()
+}
+
+/// Encodes one batch of accounts as a raw protobuf `MigrationPayload`.
+pub fn encode_migration_payload(
+    accounts: &[Account],
+    batch_index: u32,
+    batch_size: u32,
+    batch_id: i32,
+) -> Result<Vec<u8>, DatpError> {
+    let mut payload = ProtoWriter::default();
+    for account in accounts {
+        payload.bytes(1, &encode_otp_parameters(account)?);
+    }
+    payload.varint(2, 1);
+    payload.varint(3, batch_size as u64);
+    payload.varint(4, batch_index as u64);
+    payload.varint(5, batch_id as u64);
+    Ok(payload.data)
+}
+
+fn encode_otp_parameters(account: &Account) -> Result<Vec<u8>, DatpError> {
+    if account.kind == OtpKind::Steam {
+        return Err(DatpError::UnsupportedType("steam".to_string()));
+    }
+    let secret = base32::decode(Alphabet::Rfc4648 { padding: false }, &account.secret).ok_or(DatpError::InvalidSecret)?;
+    let digits = match account.digits {
+        6 => 1,
+        8 => 2,
+        other => return Err(DatpError::InvalidDigits(other.to_string())),
+    };
+    if account.kind == OtpKind::Totp && account.period != 30 {
+        return Err(DatpError::InvalidPeriod(account.period.to_string()));
+    }
+    let algorithm = match account.algorithm {
+        Algorithm::Sha1 => 1,
+        Algorithm::Sha256 => 2,
+        Algorithm::Sha512 => 3,
+    };
+
+    let mut message = ProtoWriter::default();
+    message.bytes(1, &secret);
+    match account.issuer.as_deref().filter(|issuer| !issuer.is_empty()) {
+        Some(issuer) => {
+            message.bytes(2, format!("{}:{}", issuer, account.account_name).as_bytes());
+            message.bytes(3, issuer.as_bytes());
+        }
+        None => message.bytes(2, account.account_name.as_bytes()),
+    }
+    message.varint(4, algorithm);
+    message.varint(5, digits);
+    match account.kind {
+        OtpKind::Hotp => {
+            message.varint(6, 1);
+            message.varint(7, account.counter.unwrap_or(0));
+        }
+        OtpKind::Totp | OtpKind::Steam => message.varint(6, 2),
+    }
+    Ok(message.data)
+}
+
+fn utf8(bytes: &[u8]) -> Result<String, DatpError> {
+    String::from_utf8(bytes.to_vec()).map_err(|_| DatpError::InvalidMigrationPayload)
+}
+
+enum ProtoValue<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+/// Reader for the subset of the protobuf wire format used by the payload.
+struct ProtoReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ProtoReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        ProtoReader { data, pos: 0 }
+    }
+
+    fn varint(&mut self) -> Result<u64, DatpError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self.data.get(self.pos).ok_or(DatpError::InvalidMigrationPayload)?;
+            self.pos += 1;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(DatpError::InvalidMigrationPayload)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DatpError> {
+        let end = self.pos.checked_add(len).filter(|end| *end <= self.data.len());
+        let end = end.ok_or(DatpError::InvalidMigrationPayload)?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn next_field(&mut self) -> Result<Option<(u64, ProtoValue<'a>)>, DatpError> {
+        if self.pos >= self.data.len() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let value = match key & 0x7 {
+            0 => ProtoValue::Varint(self.varint()?),
+            1 => {
+                self.take(8)?;
+                ProtoValue::Fixed
+            }
+            2 => {
+                let len = self.varint()? as usize;
+                ProtoValue::Bytes(self.take(len)?)
+            }
+            5 => {
+                self.take(4)?;
+                ProtoValue::Fixed
+            }
+            _ => return Err(DatpError::InvalidMigrationPayload),
+        };
+        Ok(Some((key >> 3, value)))
+    }
+}
+
+/// Writer for the same subset of the wire format.
+#[derive(Default)]
+struct ProtoWriter {
+    data: Vec<u8>,
+}
+
+impl ProtoWriter {
+    fn raw_varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.data.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        self.data.push(value as u8);
+    }
+
+    fn varint(&mut self, field: u64, value: u64) {
+        self.raw_varint(field << 3);
+        self.raw_varint(value);
+    }
+
+    fn bytes(&mut self, field: u64, value: &[u8]) {
+        self.raw_varint((field << 3) | 2);
+        self.raw_varint(value.len() as u64);
+        self.data.extend_from_slice(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "otpauth-migration://offline?data=CjYKCkhlbGxvId6tvu8SGUV4YW1wbGU6YWxpY2VAZXhhbXBsZS5jb20aB0V4YW1wbGUgASgBMAIKJQoUMTIzNDU2Nzg5MDEyMzQ1Njc4OTASA2JvYhoAIAIoAjABOAUQARgBIAAoew%3D%3D";
+
+    #[test]
+    fn test_decode_migration_uri() {
+        let accounts = decode_migration_uri(SAMPLE).unwrap();
+        assert_eq!(accounts.len(), 2);
+
+        assert_eq!(accounts[0].kind, OtpKind::Totp);
+        assert_eq!(accounts[0].issuer.as_deref(), Some("Example"));
+        assert_eq!(accounts[0].account_name, "alice@example.com");
+        assert_eq!(accounts[0].secret, "JBSWY3DPEHPK3PXP");
+        assert_eq!(accounts[0].algorithm, Algorithm::Sha1);
+        assert_eq!(accounts[0].digits, 6);
+
+        assert_eq!(accounts[1].kind, OtpKind::Hotp);
+        assert_eq!(accounts[1].issuer, None);
+        assert_eq!(accounts[1].account_name, "bob");
+        assert_eq!(accounts[1].secret, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(accounts[1].algorithm, Algorithm::Sha256);
+        assert_eq!(accounts[1].digits, 8);
+        assert_eq!(accounts[1].counter, Some(5));
+    }
+
+    #[test]
+    fn test_encode_migration_batches() {
+        let accounts = decode_migration_uri(SAMPLE).unwrap();
+        let mut many = Vec::new();
+        for _ in 0..3 {
+            many.extend(accounts.iter().cloned());
+        }
+
+        let uris = encode_migration_uris(&many, 4).unwrap();
+        assert_eq!(uris.len(), 2);
+        let mut decoded = decode_migration_uri(&uris[0]).unwrap();
+        decoded.extend(decode_migration_uri(&uris[1]).unwrap());
+        assert_eq!(decoded, many);
+    }
+
+    #[test]
+    fn test_encode_migration_rejects_unsupported() {
+        let mut account = Account::new("alice", "JBSWY3DPEHPK3PXP");
+        account.digits = 7;
+        assert_eq!(encode_migration_payload(&[account], 0, 1, 1), Err(DatpError::InvalidDigits("7".to_string())));
+    }
+
+    #[test]
+    fn test_decode_migration_errors() {
+        assert_eq!(decode_migration_uri("otpauth://totp/a?secret=A"), Err(DatpError::InvalidScheme));
+        assert_eq!(decode_migration_uri("otpauth-migration://offline?x=1"), Err(DatpError::InvalidMigrationPayload));
+        assert_eq!(decode_migration_payload(&[0x0a, 0x05, 0x01]), Err(DatpError::InvalidMigrationPayload));
+    }
+}
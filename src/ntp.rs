@@ -0,0 +1,176 @@
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::{OffsetClock, SystemClock};
+
+/// Seconds from the NTP epoch (1900) to the Unix epoch.
+const NTP_UNIX_OFFSET: i128 = 2_208_988_800;
+
+/// Result of one SNTP query, see `ntp_query`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NtpSample {
+    pub offset_ms: i64,              // server time minus local time
+    pub round_trip_ms: i64,          // network delay, excluding the server's processing
+}
+
+/// Asks one NTP server for the time (SNTP, RFC 4330) and measures how far the local clock is
+/// off. `server` is a host name or address, with or without a port (default 123).
+///
+/// # Errors
+/// I/O errors of the socket, `TimedOut`/`WouldBlock` without an answer within `timeout`, and
+/// `InvalidData` for answers that are not a valid reply to this request.
+pub fn ntp_query(server: &str, timeout: Duration) -> io::Result<NtpSample> {
+    let address = resolve(server)?;
+    let bind: SocketAddr = if address.is_ipv4() { ([0, 0, 0, 0], 0).into() } else { ([0u16; 8], 0).into() };
+    let socket = UdpSocket::bind(bind)?;
+    socket.set_read_timeout(Some(timeout))?;
+    socket.connect(address)?;
+
+    let mut request = [0u8; 48];
+    request[0] = 0x23; // no leap warning, version 4, client mode
+    let sent = now_ntp();
+    request[40..48].copy_from_slice(&sent.to_be_bytes());
+    socket.send(&request)?;
+
+    let mut reply = [0u8; 48];
+    let received = loop {
+        let len = socket.recv(&mut reply)?;
+        if len >= 48 {
+            break now_ntp();
+        }
+    };
+    parse_reply(&reply, sent, received)
+}
+
+/// Queries several servers and returns the median offset in milliseconds, which is robust
+/// against one server with a wrong clock. Servers that fail are skipped.
+///
+/// # Errors
+/// The error of the last server if none of them answered.
+///
+/// # Example
+/// ```rust,no_run
+/// use std::time::Duration;
+/// use datp::ntp_offset;
+///
+/// let offset = ntp_offset(&["time.google.com", "pool.ntp.org"], Duration::from_secs(2)).unwrap();
+/// if offset.abs() > 10_000 {
+///     eprintln!("the clock is {} s off, TOTP codes will be rejected", offset / 1000);
+/// }
+/// ```
+pub fn ntp_offset(servers: &[&str], timeout: Duration) -> io::Result<i64> {
+    let mut offsets = Vec::new();
+    let mut error = io::Error::new(io::ErrorKind::InvalidInput, "no NTP servers given");
+    for server in servers {
+        match ntp_query(server, timeout) {
+            Ok(sample) => offsets.push(sample.offset_ms),
+            Err(e) => error = e,
+        }
+    }
+    if offsets.is_empty() {
+        return Err(error);
+    }
+    offsets.sort_unstable();
+    Ok(offsets[offsets.len() / 2])
+}
+
+/// The system clock corrected by `ntp_offset`, rounded to whole seconds.
+pub fn ntp_clock(servers: &[&str], timeout: Duration) -> io::Result<OffsetClock<SystemClock>> {
+    let offset_ms = ntp_offset(servers, timeout)?;
+    Ok(OffsetClock { clock: SystemClock, offset: (offset_ms + offset_ms.signum() * 500) / 1000 })
+}
+
+fn resolve(server: &str) -> io::Result<SocketAddr> {
+    let mut addresses = match server.to_socket_addrs() {
+        Ok(addresses) => addresses,
+        Err(_) => (server.trim_start_matches('[').trim_end_matches(']'), 123).to_socket_addrs()?,
+    };
+    addresses.next().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("cannot resolve {}", server)))
+}
+
+/// The time as a 64-bit NTP timestamp: seconds since 1900 and a 32-bit fraction.
+fn now_ntp() -> u64 {
+    let since_unix = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = (since_unix.as_secs() + NTP_UNIX_OFFSET as u64) & 0xffff_ffff;
+    let fraction = (u64::from(since_unix.subsec_nanos()) << 32) / 1_000_000_000;
+    (seconds << 32) | fraction
+}
+
+/// Nanoseconds since the Unix epoch of an NTP timestamp. Seconds values with the top bit clear
+/// belong to the era starting in 2036 (RFC 4330, section 3).
+fn ntp_to_unix_nanos(timestamp: u64) -> i128 {
+    let mut seconds = i128::from(timestamp >> 32);
+    if seconds < 0x8000_0000 {
+        seconds += 1 << 32;
+    }
+    let nanos = (i128::from(timestamp & 0xffff_ffff) * 1_000_000_000) >> 32;
+    (seconds - NTP_UNIX_OFFSET) * 1_000_000_000 + nanos
+}
+
+/// Computes offset and delay from a server reply to the request sent at `sent` and received
+/// at `received` (both local NTP timestamps).
+fn parse_reply(reply: &[u8; 48], sent: u64, received: u64) -> io::Result<NtpSample> {
+    let invalid = |reason: &str| io::Error::new(io::ErrorKind::InvalidData, format!("invalid NTP reply: {}", reason));
+    let timestamp = |at: usize| u64::from_be_bytes(reply[at..at + 8].try_into().expect("8 bytes"));
+    if reply[0] & 0x07 != 4 {
+        return Err(invalid("not a server reply"));
+    }
+    if reply[1] == 0 {
+        return Err(invalid("the server refused the request"));
+    }
+    if reply[0] >> 6 == 3 {
+        return Err(invalid("the server clock is not synchronized"));
+    }
+    if timestamp(24) != sent {
+        return Err(invalid("the reply does not belong to this request"));
+    }
+    let (t1, t4) = (ntp_to_unix_nanos(sent), ntp_to_unix_nanos(received));
+    let (t2, t3) = (ntp_to_unix_nanos(timestamp(32)), ntp_to_unix_nanos(timestamp(40)));
+    let offset = ((t2 - t1) + (t3 - t4)) / 2;
+    let delay = (t4 - t1) - (t3 - t2);
+    Ok(NtpSample { offset_ms: (offset / 1_000_000) as i64, round_trip_ms: (delay / 1_000_000) as i64 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ntp(unix_millis: i128) -> u64 {
+        let seconds = (unix_millis.div_euclid(1000) + NTP_UNIX_OFFSET) as u64 & 0xffff_ffff;
+        let fraction = ((unix_millis.rem_euclid(1000) as u64) << 32) / 1000;
+        (seconds << 32) | fraction
+    }
+
+    fn reply(originate: u64, receive: u64, transmit: u64) -> [u8; 48] {
+        let mut reply = [0u8; 48];
+        reply[0] = 0x24; // version 4, server mode
+        reply[1] = 2;
+        reply[24..32].copy_from_slice(&originate.to_be_bytes());
+        reply[32..40].copy_from_slice(&receive.to_be_bytes());
+        reply[40..48].copy_from_slice(&transmit.to_be_bytes());
+        reply
+    }
+
+    #[test]
+    fn test_parse_reply() {
+        // the local clock is 5 s behind; 40 ms each way and 20 ms in the server
+        let (sent, received) = (ntp(1_700_000_000_000), ntp(1_700_000_000_100));
+        let packet = reply(sent, ntp(1_700_000_005_040), ntp(1_700_000_005_060));
+        let sample = parse_reply(&packet, sent, received).unwrap();
+        assert!((4_999..=5_001).contains(&sample.offset_ms), "{:?}", sample);
+        assert!((79..=81).contains(&sample.round_trip_ms), "{:?}", sample);
+
+        assert!(parse_reply(&packet, sent + 1, received).is_err());
+        let mut refused = packet;
+        refused[1] = 0;
+        assert!(parse_reply(&refused, sent, received).is_err());
+    }
+
+    #[test]
+    fn test_ntp_era() {
+        // 2036-02-07T06:28:16Z wraps the 32-bit seconds field to 0
+        assert_eq!(ntp_to_unix_nanos(ntp(2_085_978_496_000)), 2_085_978_496_000_000_000);
+        assert_eq!(ntp_to_unix_nanos(ntp(1_700_000_000_500)), 1_700_000_000_500_000_000);
+    }
+}
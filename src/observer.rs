@@ -0,0 +1,145 @@
+use super::{hotp_verify_with, totp_verify_with, Account, OtpKind};
+
+/// Receives the outcome of verification attempts, e.g. to write them to an audit log.
+///
+/// `user` is the key the caller verified the code for and `timestamp` the Unix time of the
+/// attempt. Every method does nothing by default, so an observer only implements the events it
+/// records. The code and the secret are never passed on.
+pub trait VerificationObserver {
+    /// The code matched.
+    fn on_success(&self, user: &str, timestamp: u64) {
+        let _ = (user, timestamp);
+    }
+
+    /// The code did not match.
+    fn on_failure(&self, user: &str, timestamp: u64) {
+        let _ = (user, timestamp);
+    }
+
+    /// The code matched but was already used.
+    fn on_replay(&self, user: &str, timestamp: u64) {
+        let _ = (user, timestamp);
+    }
+
+    /// The attempt was rejected without checking the code because of too many failures.
+    fn on_throttled(&self, user: &str, timestamp: u64) {
+        let _ = (user, timestamp);
+    }
+}
+
+/// Checks a TOTP code against `account` and reports the outcome to `observer`.
+///
+/// # Arguments
+/// * `user` - Key of the user, passed on to the observer.
+/// * `account` - The account; its algorithm, digits and period are used.
+/// * `code` - The code entered by the user, leading zeros included.
+/// * `unix_time` - Time to verify at, usually now.
+/// * `skew` - Number of steps of clock drift to tolerate in each direction (usually 1).
+/// * `observer` - Receives `on_success` or `on_failure`.
+///
+/// # Returns
+/// `Some(offset)` - The step offset of the matching code, as in `totp_verify_with`, or `None`.
+/// HOTP and Steam accounts never match.
+///
+/// # Example
+/// ```rust
+/// use std::cell::Cell;
+/// use datp::{totp_raw, totp_verify_observed, Account, VerificationObserver};
+///
+/// struct Failures(Cell<u32>);
+///
+/// impl VerificationObserver for Failures {
+///     fn on_failure(&self, _user: &str, _timestamp: u64) {
+///         self.0.set(self.0.get() + 1);
+///     }
+/// }
+///
+/// let account = Account::new("alice", "JBSWY3DPEHPK3PXP");
+/// let failures = Failures(Cell::new(0));
+/// let code = format!("{:06}", totp_raw(&account.secret, 30, 0, 1_000_000).unwrap());
+/// assert_eq!(totp_verify_observed("alice", &account, &code, 1_000_000, 1, &failures), Some(0));
+/// assert_eq!(totp_verify_observed("alice", &account, "000000", 1_000_000, 1, &failures), None);
+/// assert_eq!(failures.0.get(), 1);
+/// ```
+pub fn totp_verify_observed(
+    user: &str,
+    account: &Account,
+    code: &str,
+    unix_time: u64,
+    skew: u64,
+    observer: &dyn VerificationObserver,
+) -> Option<i64> {
+    let matched = match account.kind {
+        OtpKind::Totp => {
+            totp_verify_with(&account.secret, code, account.algorithm, account.digits, account.period, 0, unix_time, skew)
+        }
+        OtpKind::Hotp | OtpKind::Steam => None,
+    };
+    report(observer, user, unix_time, matched.is_some());
+    matched
+}
+
+/// Checks an HOTP code against `account`, looking up to `look_ahead` counters past
+/// `account.counter`, and reports the outcome to `observer`.
+///
+/// # Returns
+/// `Some(counter)` - The counter of the matching code, as in `hotp_verify_with`, or `None`.
+/// Accounts that are not HOTP never match. Store the counter plus one in the account.
+pub fn hotp_verify_observed(
+    user: &str,
+    account: &Account,
+    code: &str,
+    look_ahead: u64,
+    unix_time: u64,
+    observer: &dyn VerificationObserver,
+) -> Option<u64> {
+    let matched = match (account.kind, account.counter) {
+        (OtpKind::Hotp, Some(counter)) => {
+            hotp_verify_with(&account.secret, code, account.algorithm, account.digits, counter, look_ahead)
+        }
+        _ => None,
+    };
+    report(observer, user, unix_time, matched.is_some());
+    matched
+}
+
+fn report(observer: &dyn VerificationObserver, user: &str, timestamp: u64, success: bool) {
+    if success {
+        observer.on_success(user, timestamp);
+    } else {
+        observer.on_failure(user, timestamp);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hotp_raw;
+    use std::cell::RefCell;
+
+    #[derive(Default)]
+    struct Log(RefCell<Vec<String>>);
+
+    impl VerificationObserver for Log {
+        fn on_success(&self, user: &str, timestamp: u64) {
+            self.0.borrow_mut().push(format!("success {} {}", user, timestamp));
+        }
+
+        fn on_failure(&self, user: &str, timestamp: u64) {
+            self.0.borrow_mut().push(format!("failure {} {}", user, timestamp));
+        }
+    }
+
+    #[test]
+    fn test_observed_verification() {
+        let log = Log::default();
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        let account = Account::new_hotp("bob", secret, 0);
+        let code = format!("{:06}", hotp_raw(secret, 2).unwrap());
+        assert_eq!(hotp_verify_observed("bob", &account, &code, 5, 100, &log), Some(2));
+        assert_eq!(hotp_verify_observed("bob", &account, &code, 1, 101, &log), None);
+        // an HOTP account is not checked as TOTP
+        assert_eq!(totp_verify_observed("bob", &account, &code, 102, 1, &log), None);
+        assert_eq!(*log.0.borrow(), ["success bob 100", "failure bob 101", "failure bob 102"]);
+    }
+}
@@ -0,0 +1,249 @@
+use std::fmt::Write;
+
+use qrcode::{Color, QrCode};
+
+use super::eps::{parse_hex_color, rgb};
+use super::sheet::{sheet_layout, Element, Font, QUIET_ZONE};
+use super::svg::{self, SvgLayout};
+use super::{encode_qr, totp_url, Account, DatpError, EnrollmentSheet, TotpQrConfig};
+
+/// Points per millimeter.
+const PT_PER_MM: f64 = 72.0 / 25.4;
+
+/// Generates a TOTP QR code as a one-page PDF document with the code as vector graphics.
+///
+/// Uses the same configuration as `totp_qr_eps`: sizes in points, colors `#rrggbb` or `#rgb`,
+/// square modules in `dark_color`.
+///
+/// # Arguments
+/// * `secret_base32` - Base32-encoded TOTP secret.
+/// * `config` - TotpQrConfig struct with customization options.
+///
+/// # Returns
+/// `Ok(Vec<u8>)` - The PDF document, `DatpError::InvalidColor` for colors that are not hex, or
+/// `DatpError::QrCode` when the URI does not fit into a QR code.
+///
+/// # Example
+/// ```rust
+/// use datp::{totp_qr_pdf, TotpQrConfig};
+///
+/// let config = TotpQrConfig { account_name: "user@example.com", issuer: "MyApp", ..Default::default() };
+/// let pdf = totp_qr_pdf("JBSWY3DPEHPK3PXP", &config).unwrap();
+/// assert!(pdf.starts_with(b"%PDF-1.4"));
+/// ```
+pub fn totp_qr_pdf(secret_base32: &str, config: &TotpQrConfig) -> Result<Vec<u8>, DatpError> {
+    let dark = parse_hex_color(config.dark_color)?;
+    let light = parse_hex_color(config.light_color)?;
+    let style = config.style();
+    let code = encode_qr(&totp_url(secret_base32, config), &style)?;
+    let layout = SvgLayout::new(&code, &style);
+    let SvgLayout { size, unit, origin } = layout;
+    let (page_width, page_height) = config.exact_size.unwrap_or((size, size));
+
+    let mut content = String::new();
+    writeln!(content, "{} rg 0 0 {page_width} {page_height} re f", rgb(light)).unwrap();
+    if config.exact_size.is_some() {
+        // same centering and scaling as the SVG viewBox
+        let (vx, vy, vw, _) = layout.view_box(page_width, page_height);
+        let scale = page_width as f64 / vw;
+        writeln!(content, "{scale} 0 0 {scale} {} {} cm", -vx * scale, -vy * scale).unwrap();
+    }
+    writeln!(content, "{} rg", rgb(dark)).unwrap();
+    // PDF's origin is the bottom left corner
+    let module = |x: usize, y: usize| (origin as f64 + (x as u32 * unit) as f64, (size - origin - (y as u32 + 1) * unit) as f64);
+    modules(&mut content, &code, unit as f64, module);
+    content.push_str("f\n");
+
+    let title = svg::account_title(config.issuer, config.account_name);
+    Ok(document(page_width as f64, page_height as f64, &content, &title))
+}
+
+/// Renders the enrollment sheet of `enrollment_sheet_svg` as a one-page PDF document, with the
+/// QR code as vector graphics and the text in the standard Helvetica and Courier fonts.
+///
+/// # Returns
+/// `Ok(Vec<u8>)` - The PDF document, or `DatpError::QrCode` when the URI does not fit into a QR
+/// code.
+///
+/// # Example
+/// ```rust
+/// use datp::{enrollment_sheet_pdf, Account, EnrollmentSheet};
+///
+/// let account = Account::new("alice@example.com", "JBSWY3DPEHPK3PXP");
+/// let pdf = enrollment_sheet_pdf(&account, &EnrollmentSheet::default()).unwrap();
+/// assert!(pdf.ends_with(b"%%EOF\n"));
+/// ```
+pub fn enrollment_sheet_pdf(account: &Account, sheet: &EnrollmentSheet) -> Result<Vec<u8>, DatpError> {
+    let elements = sheet_layout(account, sheet)?;
+    let (width, height) = sheet.paper.size_mm();
+    let (width, height) = (width * PT_PER_MM, height * PT_PER_MM);
+
+    let mut content = String::new();
+    for element in &elements {
+        match element {
+            Element::Text { x, y, size, font, text } => {
+                let font = match font {
+                    Font::Regular => "F1",
+                    Font::Bold => "F2",
+                    Font::Mono => "F3",
+                };
+                writeln!(
+                    content,
+                    "BT /{font} {} Tf {} {} Td {} Tj ET",
+                    number(size * PT_PER_MM),
+                    number(x * PT_PER_MM),
+                    number(height - y * PT_PER_MM),
+                    string(text)
+                )
+                .unwrap();
+            }
+            Element::Qr { x, y, size, code } => {
+                let unit = size / (code.width() + 2 * QUIET_ZONE) as f64;
+                let module = |column: usize, row: usize| {
+                    let left = x + (column + QUIET_ZONE) as f64 * unit;
+                    let bottom = y + (row + QUIET_ZONE + 1) as f64 * unit;
+                    (left * PT_PER_MM, height - bottom * PT_PER_MM)
+                };
+                modules(&mut content, code, unit * PT_PER_MM, module);
+                content.push_str("f\n");
+            }
+        }
+    }
+    Ok(document(width, height, &content, sheet.title))
+}
+
+/// Appends one rectangle per horizontal run of dark modules; `position` maps a module to the
+/// bottom left corner of its square in points.
+fn modules(content: &mut String, code: &QrCode, unit: f64, position: impl Fn(usize, usize) -> (f64, f64)) {
+    let width = code.width();
+    let colors = code.to_colors();
+    for y in 0..width {
+        let mut x = 0;
+        while x < width {
+            let run = colors[y * width + x..(y + 1) * width].iter().take_while(|&&c| c == Color::Dark).count();
+            if run > 0 {
+                let (left, bottom) = position(x, y);
+                writeln!(content, "{} {} {} {} re", number(left), number(bottom), number(run as f64 * unit), number(unit)).unwrap();
+            }
+            x += run.max(1);
+        }
+    }
+}
+
+/// A PDF 1.4 file with a single page of `width` x `height` points drawing `content`.
+fn document(width: f64, height: f64, content: &str, title: &str) -> Vec<u8> {
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+        format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 5 0 R /F2 6 0 R /F3 7 0 R >> >> /Contents 4 0 R >>",
+            number(width),
+            number(height)
+        ),
+        format!("<< /Length {} >>\nstream\n{}endstream", content.len(), content),
+        font("Helvetica"),
+        font("Helvetica-Bold"),
+        font("Courier"),
+        format!("<< /Title {} /Creator (datp) >>", string(title)),
+    ];
+
+    let mut pdf = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        writeln!(pdf, "{} 0 obj\n{}\nendobj", index + 1, object).unwrap();
+    }
+    let xref = pdf.len();
+    writeln!(pdf, "xref\n0 {}\n0000000000 65535 f ", objects.len() + 1).unwrap();
+    for offset in offsets {
+        writeln!(pdf, "{offset:010} 00000 n ").unwrap();
+    }
+    writeln!(pdf, "trailer\n<< /Size {} /Root 1 0 R /Info 8 0 R >>\nstartxref\n{xref}\n%%EOF", objects.len() + 1).unwrap();
+    pdf.into_bytes()
+}
+
+fn font(name: &str) -> String {
+    format!("<< /Type /Font /Subtype /Type1 /BaseFont /{name} /Encoding /WinAnsiEncoding >>")
+}
+
+/// A PDF string literal. Latin-1 characters are written as octal escapes, which WinAnsi maps
+/// to the same characters; anything else becomes `?`.
+fn string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('(');
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            ' '..='~' => out.push(c),
+            '\u{a0}'..='\u{ff}' => write!(out, "\\{:03o}", c as u32).unwrap(),
+            _ => out.push('?'),
+        }
+    }
+    out.push(')');
+    out
+}
+
+/// `value` with at most three decimals, without trailing zeros.
+fn number(value: f64) -> String {
+    let value = format!("{:.3}", value);
+    value.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PaperSize;
+    use qrcode::Version;
+
+    /// Checks that every xref entry points at its object, as readers look objects up by offset.
+    fn assert_xref(pdf: &str) {
+        let start: usize = pdf.rsplit("startxref\n").next().unwrap().trim_end_matches("\n%%EOF\n").parse().unwrap();
+        assert!(pdf[start..].starts_with("xref\n0 9\n"));
+        for (index, entry) in pdf[start..].lines().skip(3).take(8).enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(pdf[offset..].starts_with(&format!("{} 0 obj\n", index + 1)));
+        }
+    }
+
+    #[test]
+    fn test_totp_qr_pdf() {
+        let config = TotpQrConfig {
+            account_name: "user@example.com",
+            issuer: "MyApp",
+            dark_color: "#000080",
+            light_color: "#fff",
+            min_dimension: 228,
+            version: Version::Normal(8),
+            ..Default::default()
+        };
+        let pdf = String::from_utf8(totp_qr_pdf("JBSWY3DPEHPK3PXP", &config).unwrap()).unwrap();
+        assert!(pdf.starts_with("%PDF-1.4\n"));
+        assert!(pdf.contains("/MediaBox [0 0 228 228]"));
+        assert!(pdf.contains("/Title (QR code for MyApp: user@example.com)"));
+        assert!(pdf.contains("\n1 1 1 rg 0 0 228 228 re f\n0 0 0.502 rg\n"));
+        // top edge of the top left finder pattern, as in the EPS output
+        assert!(pdf.contains(" rg\n16 208 28 4 re\n"));
+        assert_xref(&pdf);
+
+        let config = TotpQrConfig { light_color: "white", ..config };
+        assert_eq!(totp_qr_pdf("JBSWY3DPEHPK3PXP", &config), Err(DatpError::InvalidColor("white".to_string())));
+    }
+
+    #[test]
+    fn test_enrollment_sheet_pdf() {
+        let mut account = Account::new("zoë (admin)", "JBSWY3DPEHPK3PXP");
+        account.issuer = Some("ACME".to_string());
+        let sheet = EnrollmentSheet { paper: PaperSize::Letter, recovery_codes: &["one", "two"], ..Default::default() };
+        let pdf = String::from_utf8(enrollment_sheet_pdf(&account, &sheet).unwrap()).unwrap();
+        assert!(pdf.contains("/MediaBox [0 0 612 792]"));
+        assert!(pdf.contains(r"(ACME: zo\353 \(admin\)) Tj"));
+        assert!(pdf.contains("/F3 15.591 Tf 70.866 "));
+        assert!(pdf.contains("(JBSW Y3DP EHPK 3PXP) Tj"));
+        assert!(pdf.contains("(two) Tj"));
+        assert!(pdf.ends_with("%%EOF\n"));
+        assert_xref(&pdf);
+    }
+}
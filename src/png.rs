@@ -0,0 +1,256 @@
+// PNG output for QR codes. The encoder is self-contained: QR codes only need a
+// two-colour palette, and their long runs of identical pixels and rows are
+// handled well by deflate with fixed Huffman codes, so no image or zlib crate
+// is pulled in.
+
+use qrcode::{EcLevel, QrCode};
+
+use super::{base64, build_otpauth_uri, Account, DatpError};
+
+/// Largest `QrPngConfig::module_size`; the biggest code then is a 11840 px square.
+pub const MAX_PNG_MODULE_SIZE: u32 = 64;
+
+/// Options for `totp_qr_png`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QrPngConfig {
+    pub module_size: u32,           // width/height of one QR module in px, at most MAX_PNG_MODULE_SIZE
+    pub dark_color: [u8; 3],        // RGB of dark modules
+    pub light_color: [u8; 3],       // RGB of the background
+    pub ec_level: EcLevel,          // error correction level
+    pub quiet_zone: bool,           // 4-module light border around the code
+}
+
+impl Default for QrPngConfig {
+    fn default() -> Self {
+        QrPngConfig {
+            module_size: 8,
+            dark_color: [0, 0, 0],
+            light_color: [255, 255, 255],
+            ec_level: EcLevel::M,
+            quiet_zone: true,
+        }
+    }
+}
+
+/// Renders the otpauth URI of an account as a PNG image.
+///
+/// # Arguments
+/// * `account` - Account to provision; TOTP, HOTP and Steam accounts are supported.
+/// * `config` - Pixel size, colors and error correction level.
+///
+/// # Returns
+/// `Ok(Vec<u8>)` - PNG file contents, `DatpError::InvalidModuleSize` when `module_size` is above
+/// `MAX_PNG_MODULE_SIZE`, or `DatpError::QrCode` when the URI does not fit into a QR code.
+///
+/// # Example
+/// ```rust
+/// use datp::{totp_qr_png, Account, QrPngConfig};
+///
+/// let account = Account::new("user@example.com", "JBSWY3DPEHPK3PXP");
+/// let config = QrPngConfig { module_size: 4, dark_color: [0, 0, 128], ..Default::default() };
+/// let png = totp_qr_png(&account, &config).unwrap();
+/// assert_eq!(&png[1..4], b"PNG");
+/// ```
+pub fn totp_qr_png(account: &Account, config: &QrPngConfig) -> Result<Vec<u8>, DatpError> {
+    let too_large = || DatpError::InvalidModuleSize(config.module_size.to_string());
+    if config.module_size > MAX_PNG_MODULE_SIZE {
+        return Err(too_large());
+    }
+    let code = QrCode::with_error_correction_level(build_otpauth_uri(account).as_bytes(), config.ec_level)
+        .map_err(|e| DatpError::QrCode(e.to_string()))?;
+    let border = if config.quiet_zone { 4 } else { 0 };
+    let modules = code.width() + 2 * border;
+    let scale = config.module_size.max(1) as usize;
+    let size = modules.checked_mul(scale).ok_or_else(too_large)?;
+    let colors = code.to_colors();
+    let is_dark = |x: usize, y: usize| {
+        let (x, y) = (x / scale, y / scale);
+        x >= border && y >= border && x < modules - border && y < modules - border
+            && colors[(y - border) * code.width() + x - border] == qrcode::Color::Dark
+    };
+
+    // 1-bit indexed rows, each prefixed with filter type 0; palette entry 1 is dark
+    let stride = size.div_ceil(8) + 1;
+    let mut raw = vec![0u8; stride.checked_mul(size).ok_or_else(too_large)?];
+    for y in 0..size {
+        for x in 0..size {
+            if is_dark(x, y) {
+                raw[y * stride + 1 + x / 8] |= 0x80 >> (x % 8);
+            }
+        }
+    }
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&(size as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(size as u32).to_be_bytes());
+    ihdr.extend_from_slice(&[1, 3, 0, 0, 0]); // bit depth 1, indexed colour
+    let mut plte = config.light_color.to_vec();
+    plte.extend_from_slice(&config.dark_color);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    write_chunk(&mut png, b"IHDR", &ihdr);
+    write_chunk(&mut png, b"PLTE", &plte);
+    write_chunk(&mut png, b"IDAT", &zlib_compress(&raw, stride));
+    write_chunk(&mut png, b"IEND", &[]);
+    Ok(png)
+}
+
+/// Renders an account as a `data:image/png;base64,...` URI for an `<img src>` attribute.
+///
+/// See `totp_qr_png` for the arguments.
+pub fn totp_qr_png_data_uri(account: &Account, config: &QrPngConfig) -> Result<String, DatpError> {
+    Ok(format!("data:image/png;base64,{}", base64::encode(&totp_qr_png(account, config)?)))
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+
+/// LSB-first bit writer as required by deflate.
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u32,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, count: u32) {
+        self.acc |= value << self.bits;
+        self.bits += count;
+        while self.bits >= 8 {
+            self.out.push(self.acc as u8);
+            self.acc >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    // Huffman codes are defined MSB-first
+    fn write_code(&mut self, code: u32, count: u32) {
+        self.write(code.reverse_bits() >> (32 - count), count);
+    }
+
+    fn literal(&mut self, symbol: u32) {
+        match symbol {
+            0..=143 => self.write_code(0x30 + symbol, 8),
+            144..=255 => self.write_code(0x190 + symbol - 144, 9),
+            256..=279 => self.write_code(symbol - 256, 7),
+            _ => self.write_code(0xc0 + symbol - 280, 8),
+        }
+    }
+
+    fn matched(&mut self, length: usize, distance: usize) {
+        let l = LENGTH_BASE.iter().rposition(|&base| base as usize <= length).unwrap();
+        self.literal(257 + l as u32);
+        self.write((length - LENGTH_BASE[l] as usize) as u32, LENGTH_EXTRA[l] as u32);
+        let d = DIST_BASE.iter().rposition(|&base| base as usize <= distance).unwrap();
+        self.write_code(d as u32, 5);
+        self.write((distance - DIST_BASE[d] as usize) as u32, DIST_EXTRA[d] as u32);
+    }
+}
+
+/// Compresses `data` as a zlib stream with a single fixed-Huffman block. Matches are only
+/// searched at distance 1 (runs) and `row` (the previous scanline).
+fn zlib_compress(data: &[u8], row: usize) -> Vec<u8> {
+    let mut writer = BitWriter { out: vec![0x78, 0x01], acc: 0, bits: 0 };
+    writer.write(1, 1); // final block
+    writer.write(1, 2); // fixed Huffman codes
+    let mut i = 0;
+    while i < data.len() {
+        let mut best = (0, 0);
+        for distance in [row, 1] {
+            if distance == 0 || distance > i || distance > 32768 {
+                continue;
+            }
+            let length = (0..258.min(data.len() - i)).take_while(|&k| data[i + k] == data[i + k - distance]).count();
+            if length > best.0 {
+                best = (length, distance);
+            }
+        }
+        if best.0 >= 3 {
+            writer.matched(best.0, best.1);
+            i += best.0;
+        } else {
+            writer.literal(data[i] as u32);
+            i += 1;
+        }
+    }
+    writer.literal(256);
+    let mut out = writer.out;
+    if writer.bits > 0 {
+        out.push(writer.acc as u8);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32_and_adler32() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn test_totp_qr_png_layout() {
+        let account = Account::new("user@example.com", "JBSWY3DPEHPK3PXP");
+        let config = QrPngConfig { module_size: 3, dark_color: [0, 0, 128], ..Default::default() };
+        let png = totp_qr_png(&account, &config).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[12..16], b"IHDR");
+        let modules = QrCode::with_error_correction_level(build_otpauth_uri(&account).as_bytes(), EcLevel::M)
+            .unwrap()
+            .width() as u32
+            + 8;
+        assert_eq!(u32::from_be_bytes(png[16..20].try_into().unwrap()), modules * 3);
+        assert_eq!(&png[37..41], b"PLTE");
+        assert_eq!(&png[41..47], &[255, 255, 255, 0, 0, 128]);
+        assert!(png.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]));
+
+        let config = QrPngConfig { module_size: MAX_PNG_MODULE_SIZE + 1, ..Default::default() };
+        assert_eq!(totp_qr_png(&account, &config), Err(DatpError::InvalidModuleSize("65".to_string())));
+        let config = QrPngConfig { module_size: u32::MAX, ..Default::default() };
+        assert!(matches!(totp_qr_png(&account, &config), Err(DatpError::InvalidModuleSize(_))));
+    }
+}
@@ -0,0 +1,108 @@
+use std::thread;
+
+use super::hash::HmacKey;
+use super::{hotp_truncated_mac, with_key, Algorithm};
+
+/// Options of `precompute_codes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Precompute {
+    pub algorithm: Algorithm,
+    pub digits: u32,
+    pub step: u64,                      // time step in seconds
+    pub t0: u64,
+    pub window: u64,                    // steps before and after the current one
+    pub threads: usize,                 // 0 uses the available parallelism
+}
+
+impl Default for Precompute {
+    fn default() -> Self {
+        Precompute { algorithm: Algorithm::Sha1, digits: 6, step: 30, t0: 0, window: 1, threads: 0 }
+    }
+}
+
+/// Users per worker below which `precompute_codes` does not start threads.
+const MIN_USERS_PER_THREAD: usize = 64;
+
+/// Computes the codes of many users at `unix_time` in parallel, e.g. to pre-stage the expected
+/// codes of a notification batch.
+///
+/// For every `(user, secret)` pair the result holds the user and its codes for the steps from
+/// `window` before to `window` after the current one, oldest first, so the current code is at
+/// index `window`. Each secret is decoded and keyed once for all its steps. The results are in
+/// input order; a secret that is not valid base32 gives `None`, as do all users when `step` is
+/// 0, `unix_time` is before `t0` or `digits` is not between 1 and 9.
+///
+/// # Example
+/// ```rust
+/// use datp::{precompute_codes, totp_raw, Precompute};
+///
+/// let users = vec![(1, "JBSWY3DPEHPK3PXP"), (2, "GEZDGNBVGY3TQOJQ"), (3, "not base32")];
+/// let codes = precompute_codes(users, 1_000_000, &Precompute::default());
+/// assert_eq!(codes[0].1.as_ref().unwrap()[1], totp_raw("JBSWY3DPEHPK3PXP", 30, 0, 1_000_000).unwrap());
+/// assert_eq!(codes[1].1.as_ref().unwrap().len(), 3);
+/// assert_eq!(codes[2], (3, None));
+/// ```
+pub fn precompute_codes<K, S>(users: impl IntoIterator<Item = (K, S)>, unix_time: u64, options: &Precompute) -> Vec<(K, Option<Vec<u32>>)>
+where
+    S: AsRef<str> + Sync,
+{
+    let (users, secrets): (Vec<K>, Vec<S>) = users.into_iter().unzip();
+    let current = unix_time.checked_sub(options.t0).and_then(|time| time.checked_div(options.step));
+    let (Some(current), true) = (current, (1..=9).contains(&options.digits)) else {
+        return users.into_iter().map(|user| (user, None)).collect();
+    };
+    let (first, last) = (current.saturating_sub(options.window), current.saturating_add(options.window));
+    let modulus = 10u32.pow(options.digits);
+    let compute = |secret: &S| {
+        with_key(secret.as_ref(), |key| {
+            let key = HmacKey::new(options.algorithm, key);
+            (first..=last).map(|counter| hotp_truncated_mac(&key, counter) % modulus).collect::<Vec<_>>()
+        })
+    };
+
+    let threads = match options.threads {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    };
+    let threads = threads.min(secrets.len() / MIN_USERS_PER_THREAD).max(1);
+    let codes: Vec<Option<Vec<u32>>> = if threads == 1 {
+        secrets.iter().map(compute).collect()
+    } else {
+        let chunk = secrets.len().div_ceil(threads);
+        thread::scope(|scope| {
+            let workers: Vec<_> =
+                secrets.chunks(chunk).map(|batch| scope.spawn(move || batch.iter().map(compute).collect::<Vec<_>>())).collect();
+            workers.into_iter().flat_map(|worker| worker.join().expect("precompute worker panicked")).collect()
+        })
+    };
+    users.into_iter().zip(codes).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generate_totp_secret, totp_raw_with};
+
+    #[test]
+    fn test_precompute_codes() {
+        let mut users: Vec<(usize, String)> = (0..500).map(|i| (i, generate_totp_secret(20))).collect();
+        users[123].1 = "1nvalid".to_string();
+        let options = Precompute { algorithm: Algorithm::Sha256, digits: 8, window: 2, threads: 4, ..Default::default() };
+        let parallel = precompute_codes(users.iter().map(|(i, secret)| (*i, secret.as_str())), 1_000_000, &options);
+        let serial = precompute_codes(users.iter().map(|(i, secret)| (*i, secret.as_str())), 1_000_000, &Precompute { threads: 1, ..options });
+        assert_eq!(parallel, serial);
+
+        for ((user, codes), (_, secret)) in parallel.iter().zip(&users) {
+            if *user == 123 {
+                assert_eq!(codes, &None);
+                continue;
+            }
+            let expected: Vec<u32> =
+                (-2i64..=2).map(|offset| totp_raw_with(secret, Algorithm::Sha256, 8, 30, 0, (1_000_000 + offset * 30) as u64).unwrap()).collect();
+            assert_eq!(codes.as_deref(), Some(&expected[..]));
+        }
+
+        let none = precompute_codes([("a", "JBSWY3DPEHPK3PXP")], 10, &Precompute { t0: 20, ..Default::default() });
+        assert_eq!(none, [("a", None)]);
+    }
+}
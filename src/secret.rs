@@ -0,0 +1,74 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::{crypto, generate_totp_secret, uri, DatpError};
+
+/// A base32 secret, normalized to uppercase without whitespace, hyphens or padding.
+///
+/// `Display` prints the base32 text, while `Debug` is redacted so that secrets inside logged
+/// structs don't leak. Comparison is constant-time and the text is wiped when dropped.
+///
+/// # Example
+/// ```rust
+/// use datp::Secret;
+///
+/// let secret: Secret = "jbsw y3dp ehpk 3pxp".parse().unwrap();
+/// assert_eq!(secret.to_string(), "JBSWY3DPEHPK3PXP");
+/// assert_eq!(format!("{:?}", secret), "Secret(<redacted>)");
+/// assert!("not base32!".parse::<Secret>().is_err());
+/// ```
+#[derive(Clone, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    /// Generates a random secret of `bytes` bytes, see `generate_totp_secret`.
+    pub fn generate(bytes: usize) -> Self {
+        Secret(generate_totp_secret(bytes))
+    }
+
+    /// The base32 text.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for Secret {
+    type Err = DatpError;
+
+    /// Normalizes and validates base32 text; `MissingSecret` if it is empty and
+    /// `InvalidSecret` if it does not decode.
+    fn from_str(secret: &str) -> Result<Self, DatpError> {
+        uri::normalize_secret(secret).map(Secret)
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(<redacted>)")
+    }
+}
+
+impl PartialEq for Secret {
+    fn eq(&self, other: &Secret) -> bool {
+        crypto::ct_eq(self.0.as_bytes(), other.0.as_bytes())
+    }
+}
+
+impl AsRef<str> for Secret {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        // zero bytes are valid UTF-8, so the string stays valid
+        crypto::wipe(unsafe { self.0.as_bytes_mut() });
+    }
+}
@@ -0,0 +1,216 @@
+use std::fmt::Write;
+
+use qrcode::{Color, EcLevel, QrCode};
+
+use super::svg::escape_xml;
+use super::{build_otpauth_uri, encode_qr, Account, DatpError, OtpKind, QrStyle};
+
+/// Paper size of an enrollment sheet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PaperSize {
+    #[default]
+    A4,             // 210 x 297 mm
+    Letter,         // 8.5 x 11 in
+}
+
+impl PaperSize {
+    /// Width and height in millimeters.
+    pub fn size_mm(&self) -> (f64, f64) {
+        match self {
+            PaperSize::A4 => (210.0, 297.0),
+            PaperSize::Letter => (215.9, 279.4),
+        }
+    }
+}
+
+/// Options of `enrollment_sheet_svg`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EnrollmentSheet<'a> {
+    pub paper: PaperSize,
+    pub title: &'a str,
+    pub recovery_codes: &'a [&'a str], // printed below the key when not empty
+}
+
+impl Default for EnrollmentSheet<'_> {
+    fn default() -> Self {
+        EnrollmentSheet { paper: PaperSize::A4, title: "Set up two-factor authentication", recovery_codes: &[] }
+    }
+}
+
+/// Font of a line of text on the sheet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Font {
+    Regular,
+    Bold,
+    Mono,
+}
+
+/// Something drawn on the sheet; coordinates in millimeters from the top left corner, text
+/// positioned by its left end and baseline.
+pub(crate) enum Element {
+    Text { x: f64, y: f64, size: f64, font: Font, text: String },
+    Qr { x: f64, y: f64, size: f64, code: QrCode },
+}
+
+const MARGIN: f64 = 25.0;
+const QR_SIZE: f64 = 70.0;
+/// Modules of light border around the QR code.
+pub(crate) const QUIET_ZONE: usize = 4;
+/// Groups of four characters per line of the manual-entry key.
+const GROUPS_PER_LINE: usize = 8;
+
+/// Lays out the sheet: heading, caption, steps, QR code, the key in groups of four and the
+/// recovery codes.
+pub(crate) fn sheet_layout(account: &Account, sheet: &EnrollmentSheet) -> Result<Vec<Element>, DatpError> {
+    let style = QrStyle { ec_level: EcLevel::M, ..QrStyle::default() };
+    let code = encode_qr(&build_otpauth_uri(account), &style)?;
+    let (width, _) = sheet.paper.size_mm();
+    let text = |y: f64, size: f64, font: Font, text: &str| Element::Text { x: MARGIN, y, size, font, text: text.to_string() };
+
+    let caption = match account.issuer.as_deref().filter(|issuer| !issuer.is_empty()) {
+        Some(issuer) => format!("{}: {}", issuer, account.account_name),
+        None => account.account_name.clone(),
+    };
+    let mut elements = vec![
+        text(32.0, 7.0, Font::Bold, sheet.title),
+        text(42.0, 5.0, Font::Regular, &caption),
+        text(54.0, 4.0, Font::Regular, "1. Open your authenticator app and scan this code:"),
+        Element::Qr { x: (width - QR_SIZE) / 2.0, y: 60.0, size: QR_SIZE, code },
+        text(142.0, 4.0, Font::Regular, "2. Can't scan it? Add the account by entering this key:"),
+    ];
+
+    let groups: Vec<&str> = account.secret.as_bytes().chunks(4).map(|group| std::str::from_utf8(group).unwrap_or("")).collect();
+    let mut y = 152.0;
+    for line in groups.chunks(GROUPS_PER_LINE) {
+        elements.push(text(y, 5.5, Font::Mono, &line.join(" ")));
+        y += 8.0;
+    }
+    elements.push(text(y, 3.5, Font::Regular, &parameters(account)));
+    y += 12.0;
+    elements.push(text(y, 4.0, Font::Regular, "3. Enter the code shown in the app to finish the setup."));
+
+    if !sheet.recovery_codes.is_empty() {
+        y += 16.0;
+        elements.push(text(y, 5.0, Font::Bold, "Recovery codes"));
+        y += 7.0;
+        elements.push(text(y, 3.5, Font::Regular, "Each code signs you in once if you lose your device. Keep this sheet safe."));
+        y += 10.0;
+        for pair in sheet.recovery_codes.chunks(2) {
+            for (column, recovery_code) in pair.iter().enumerate() {
+                let x = if column == 0 { MARGIN } else { width / 2.0 };
+                elements.push(Element::Text { x, y, size: 4.5, font: Font::Mono, text: recovery_code.to_string() });
+            }
+            y += 7.0;
+        }
+    }
+    Ok(elements)
+}
+
+/// The line describing how the codes are generated, for entering the key by hand.
+fn parameters(account: &Account) -> String {
+    match account.kind {
+        OtpKind::Totp => format!(
+            "Time-based, {}, {} digits, a new code every {} seconds",
+            account.algorithm, account.digits, account.period
+        ),
+        OtpKind::Hotp => format!("Counter-based, {}, {} digits", account.algorithm, account.digits),
+        OtpKind::Steam => "Steam Guard".to_string(),
+    }
+}
+
+/// The dark modules of `code` as SVG path data, one unit per module, starting after the
+/// quiet zone.
+fn module_path(code: &QrCode) -> String {
+    let width = code.width();
+    let mut path = String::new();
+    for (index, color) in code.to_colors().iter().enumerate() {
+        if *color == Color::Dark {
+            write!(path, "M{} {}h1v1h-1z", index % width + QUIET_ZONE, index / width + QUIET_ZONE).unwrap();
+        }
+    }
+    path
+}
+
+/// Composes a printable enrollment document as one SVG page sized for A4 or Letter: the QR
+/// code, the issuer and account, the key in groups of four for manual entry, how the codes are
+/// generated, and optionally recovery codes. Meant for helpdesk-driven onboarding where users
+/// get their setup instructions on paper.
+///
+/// # Returns
+/// `Ok(String)` - The SVG document, or `DatpError::QrCode` when the URI does not fit into a QR
+/// code.
+///
+/// # Example
+/// ```rust
+/// use datp::{enrollment_sheet_svg, Account, EnrollmentSheet, PaperSize};
+///
+/// let mut account = Account::new("alice@example.com", "JBSWY3DPEHPK3PXP");
+/// account.issuer = Some("ACME".to_string());
+/// let sheet = EnrollmentSheet { paper: PaperSize::Letter, recovery_codes: &["4f7k-2m9q", "x3pd-81ta"], ..Default::default() };
+/// let svg = enrollment_sheet_svg(&account, &sheet).unwrap();
+/// assert!(svg.contains("JBSW Y3DP EHPK 3PXP"));
+/// ```
+pub fn enrollment_sheet_svg(account: &Account, sheet: &EnrollmentSheet) -> Result<String, DatpError> {
+    let elements = sheet_layout(account, sheet)?;
+    let (width, height) = sheet.paper.size_mm();
+
+    let mut svg = String::new();
+    svg.push_str(r#"<?xml version="1.0" standalone="yes"?>"#);
+    write!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" version="1.1" width="{width}mm" height="{height}mm" viewBox="0 0 {width} {height}" role="img" aria-label="{}"><title>{}</title>"#,
+        escape_xml(sheet.title),
+        escape_xml(sheet.title)
+    )
+    .unwrap();
+    svg.push_str(r##"<rect width="100%" height="100%" fill="#ffffff"/>"##);
+    for element in &elements {
+        match element {
+            Element::Text { x, y, size, font, text } => {
+                let font = match font {
+                    Font::Regular => r#"font-family="Helvetica, Arial, sans-serif""#,
+                    Font::Bold => r#"font-family="Helvetica, Arial, sans-serif" font-weight="bold""#,
+                    Font::Mono => r#"font-family="Courier New, Courier, monospace""#,
+                };
+                write!(svg, r##"<text x="{x}" y="{y}" font-size="{size}" {font} fill="#000000">{}</text>"##, escape_xml(text))
+                    .unwrap();
+            }
+            Element::Qr { x, y, size, code } => {
+                let unit = size / (code.width() + 2 * QUIET_ZONE) as f64;
+                write!(
+                    svg,
+                    r##"<path transform="translate({x} {y}) scale({unit})" shape-rendering="crispEdges" fill="#000000" d="{}"/>"##,
+                    module_path(code)
+                )
+                .unwrap();
+            }
+        }
+    }
+    svg.push_str("</svg>");
+    Ok(svg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enrollment_sheet() {
+        let mut account = Account::new("alice & bob", "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        account.issuer = Some("ACME".to_string());
+        let svg = enrollment_sheet_svg(&account, &EnrollmentSheet::default()).unwrap();
+        assert!(svg.contains(r#"width="210mm" height="297mm" viewBox="0 0 210 297""#));
+        assert!(svg.contains(">ACME: alice &amp; bob</text>"));
+        // 48 characters: 8 groups on the first line, 4 on the second
+        assert!(svg.contains(">GEZD GNBV GY3T QOJQ GEZD GNBV GY3T QOJQ</text>"));
+        assert!(svg.contains(">GEZD GNBV GY3T QOJQ</text>"));
+        assert!(svg.contains("a new code every 30 seconds"));
+        assert!(!svg.contains("Recovery codes"));
+
+        let sheet = EnrollmentSheet { paper: PaperSize::Letter, recovery_codes: &["one", "two", "three"], ..Default::default() };
+        let svg = enrollment_sheet_svg(&Account::new_hotp("carol", "JBSWY3DPEHPK3PXP", 0), &sheet).unwrap();
+        assert!(svg.contains(r#"width="215.9mm""#));
+        assert!(svg.contains("Counter-based, SHA1, 6 digits"));
+        assert_eq!(svg.matches("font-size=\"4.5\"").count(), 3);
+    }
+}
@@ -0,0 +1,272 @@
+use std::fmt::Write;
+
+use qrcode::{Color, QrCode};
+
+use super::eps::parse_hex_color;
+use super::{DatpError, QrStyle};
+
+/// Shape used to draw the dark modules of an SVG QR code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ModuleShape {
+    #[default]
+    Square,
+    /// Corners are rounded where a module has no dark neighbour, so connected modules flow into each other.
+    Rounded,
+    /// Every module is a separate circle.
+    Dot,
+}
+
+/// Direction of a `QrGradient`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum GradientDirection {
+    #[default]
+    Horizontal,     // left to right
+    Vertical,       // top to bottom
+    Diagonal,       // top left to bottom right
+}
+
+/// Linear gradient used instead of `dark_color` to fill the dark modules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct QrGradient<'a> {
+    pub start_color: &'a str,           // e.g. "#4f46e5"
+    pub end_color: &'a str,             // e.g. "#db2777"
+    pub direction: GradientDirection,
+}
+
+pub(crate) const ACCOUNT_DESCRIPTION: &str = "Scan with an authenticator app to add this account.";
+
+/// `<title>` of a code provisioning an account.
+pub(crate) fn account_title(issuer: &str, account_name: &str) -> String {
+    if issuer.is_empty() {
+        format!("QR code for {}", account_name)
+    } else {
+        format!("QR code for {}: {}", issuer, account_name)
+    }
+}
+
+pub(crate) fn escape_xml(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Geometry of a rendered code, in pixels before any `exact_size` scaling.
+pub(crate) struct SvgLayout {
+    pub size: u32,      // width and height of the code, quiet zone and margin included
+    pub unit: u32,      // width and height of one module
+    pub origin: u32,    // offset of the first module from the top left corner
+}
+
+impl SvgLayout {
+    pub(crate) fn new(code: &QrCode, style: &QrStyle) -> SvgLayout {
+        let modules = code.width() as u32 + 2 * style.quiet_zone;
+        let natural = style.min_dimension.saturating_sub(2 * style.margin);
+        let unit = style.module_size.unwrap_or_else(|| natural.div_ceil(modules)).max(1);
+        SvgLayout {
+            size: modules * unit + 2 * style.margin,
+            unit,
+            origin: style.margin + style.quiet_zone * unit,
+        }
+    }
+
+    /// The `viewBox` (x, y, width, height) that centers the code in an image of `width` x `height`.
+    pub(crate) fn view_box(&self, width: u32, height: u32) -> (f64, f64, f64, f64) {
+        let size = self.size as f64;
+        let (width, height) = (width.max(1) as f64, height.max(1) as f64);
+        if width >= height {
+            let view_width = size * width / height;
+            ((size - view_width) / 2.0, 0.0, view_width, size)
+        } else {
+            let view_height = size * height / width;
+            (0.0, (size - view_height) / 2.0, size, view_height)
+        }
+    }
+}
+
+/// Renders `code` with the colors, module shape, gradient and quiet zone of `style`,
+/// labelled with `title` and `description` for screen readers. Colors must be `#rrggbb` or
+/// `#rgb`, anything else is `DatpError::InvalidColor` so it can't break out of the attribute.
+pub(crate) fn render(code: &QrCode, style: &QrStyle, title: &str, description: &str) -> Result<String, DatpError> {
+    parse_hex_color(style.dark_color)?;
+    parse_hex_color(style.light_color)?;
+    if let Some(gradient) = style.gradient {
+        parse_hex_color(gradient.start_color)?;
+        parse_hex_color(gradient.end_color)?;
+    }
+
+    let layout = SvgLayout::new(code, style);
+    let (size, unit) = (layout.size, layout.unit);
+    let (image_width, image_height) = style.exact_size.unwrap_or((size, size));
+    let (vx, vy, vw, vh) = layout.view_box(image_width, image_height);
+    let width = code.width();
+    let colors = code.to_colors();
+    let dark = |x: isize, y: isize| {
+        x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < width && colors[y as usize * width + x as usize] == Color::Dark
+    };
+
+    let mut svg = String::new();
+    svg.push_str(r#"<?xml version="1.0" standalone="yes"?>"#);
+    write!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" version="1.1" width="{image_width}" height="{image_height}" viewBox="{vx} {vy} {vw} {vh}""#
+    )
+    .unwrap();
+    if style.module_shape == ModuleShape::Square {
+        svg.push_str(r#" shape-rendering="crispEdges""#);
+    }
+    write!(
+        svg,
+        r#" role="img" aria-label="{}"><title>{}</title>"#,
+        escape_xml(style.aria_label.unwrap_or(title)),
+        escape_xml(title)
+    )
+    .unwrap();
+    if !description.is_empty() {
+        write!(svg, "<desc>{}</desc>", escape_xml(description)).unwrap();
+    }
+
+    let fill = match style.gradient {
+        Some(gradient) => {
+            let end = match gradient.direction {
+                GradientDirection::Horizontal => r#"x2="1" y2="0""#,
+                GradientDirection::Vertical => r#"x2="0" y2="1""#,
+                GradientDirection::Diagonal => r#"x2="1" y2="1""#,
+            };
+            write!(
+                svg,
+                r#"<defs><linearGradient id="datp-gradient" x1="0" y1="0" {end}><stop offset="0" stop-color="{}"/><stop offset="1" stop-color="{}"/></linearGradient></defs>"#,
+                gradient.start_color, gradient.end_color
+            )
+            .unwrap();
+            "url(#datp-gradient)".to_string()
+        }
+        None => style.dark_color.to_string(),
+    };
+    write!(svg, r#"<rect x="{vx}" y="{vy}" width="{vw}" height="{vh}" fill="{}"/>"#, style.light_color).unwrap();
+    write!(svg, r#"<path fill="{fill}" d=""#).unwrap();
+
+    let origin = layout.origin;
+    for y in 0..width as isize {
+        let top = origin + y as u32 * unit;
+        let mut x = 0;
+        while x < width as isize {
+            if !dark(x, y) {
+                x += 1;
+                continue;
+            }
+            let left = origin + x as u32 * unit;
+            match style.module_shape {
+                ModuleShape::Square => {
+                    // merge horizontal runs into one rectangle
+                    let run = (x..width as isize).take_while(|&x| dark(x, y)).count() as u32;
+                    write!(svg, "M{left} {top}h{}v{unit}H{left}z", run * unit).unwrap();
+                    x += run as isize;
+                    continue;
+                }
+                ModuleShape::Dot => {
+                    let r = unit as f64 / 2.0;
+                    write!(svg, "M{left} {}a{r} {r} 0 1 0 {unit} 0a{r} {r} 0 1 0 -{unit} 0z", top as f64 + r).unwrap();
+                }
+                ModuleShape::Rounded => {
+                    let r = unit as f64 / 2.0;
+                    let corner = |dx: isize, dy: isize| if dark(x + dx, y) || dark(x, y + dy) { 0.0 } else { r };
+                    let (tl, tr, br, bl) = (corner(-1, -1), corner(1, -1), corner(1, 1), corner(-1, 1));
+                    let (l, t, u) = (left as f64, top as f64, unit as f64);
+                    write!(svg, "M{} {t}H{}", l + tl, l + u - tr).unwrap();
+                    if tr > 0.0 { write!(svg, "a{tr} {tr} 0 0 1 {tr} {tr}").unwrap(); }
+                    write!(svg, "V{}", t + u - br).unwrap();
+                    if br > 0.0 { write!(svg, "a{br} {br} 0 0 1 -{br} {br}").unwrap(); }
+                    write!(svg, "H{}", l + bl).unwrap();
+                    if bl > 0.0 { write!(svg, "a{bl} {bl} 0 0 1 -{bl} -{bl}").unwrap(); }
+                    write!(svg, "V{}", t + tl).unwrap();
+                    if tl > 0.0 { write!(svg, "a{tl} {tl} 0 0 1 {tl} -{tl}").unwrap(); }
+                    svg.push('z');
+                }
+            }
+            x += 1;
+        }
+    }
+    svg.push_str(r#""/></svg>"#);
+    Ok(svg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use qrcode::{EcLevel, Version};
+
+    fn style() -> QrStyle<'static> {
+        QrStyle { min_dimension: 100, ..Default::default() }
+    }
+
+    #[test]
+    fn test_square_modules() {
+        let code = QrCode::with_version(b"hello", Version::Normal(1), EcLevel::M).unwrap();
+        let svg = render(&code, &style(), "QR code", "").unwrap();
+        // 21 modules + 2 * 4 quiet zone, 4px each
+        assert!(svg.contains(r#"width="116" height="116" viewBox="0 0 116 116" shape-rendering="crispEdges" role="img""#));
+        // top edge of the top left finder pattern is one 7 module run
+        assert!(svg.contains(r#"d="M16 16h28v4H16z"#));
+    }
+
+    #[test]
+    fn test_accessibility() {
+        let code = QrCode::with_version(b"hello", Version::Normal(1), EcLevel::M).unwrap();
+        let svg = render(&code, &style(), &account_title("A&B", "<alice>"), ACCOUNT_DESCRIPTION).unwrap();
+        assert!(svg.contains(concat!(
+            r#" role="img" aria-label="QR code for A&amp;B: &lt;alice&gt;"><title>QR code for A&amp;B: &lt;alice&gt;</title>"#,
+            r#"<desc>Scan with an authenticator app to add this account.</desc><rect"#
+        )));
+
+        let svg = render(&code, &QrStyle { aria_label: Some("Enrollment \"code\""), ..style() }, "t", "d").unwrap();
+        assert!(svg.contains(r#"aria-label="Enrollment &quot;code&quot;"><title>t</title><desc>d</desc>"#));
+    }
+
+    #[test]
+    fn test_dimensions() {
+        let code = QrCode::with_version(b"hello", Version::Normal(1), EcLevel::M).unwrap();
+        let svg = render(&code, &QrStyle { module_size: Some(3), margin: 5, quiet_zone: 2, ..style() }, "t", "").unwrap();
+        // 25 modules of 3px plus 5px on each side
+        assert!(svg.contains(r#"width="85" height="85" viewBox="0 0 85 85""#));
+        assert!(svg.contains(r#"d="M11 11h21v3H11z"#));
+
+        let svg = render(&code, &QrStyle { module_size: Some(4), exact_size: Some((232, 116)), ..style() }, "t", "").unwrap();
+        assert!(svg.contains(r#"width="232" height="116" viewBox="-58 0 232 116""#));
+        assert!(svg.contains(r##"<rect x="-58" y="0" width="232" height="116" fill="#ffffff"/>"##));
+    }
+
+    #[test]
+    fn test_styles() {
+        let code = QrCode::with_version(b"hello", Version::Normal(1), EcLevel::M).unwrap();
+        let gradient = QrGradient { start_color: "#111111", end_color: "#222222", direction: GradientDirection::Vertical };
+        let svg = render(&code, &QrStyle { module_shape: ModuleShape::Dot, gradient: Some(gradient), quiet_zone: 0, min_dimension: 84, ..style() }, "QR code", "").unwrap();
+        assert!(svg.contains(r#"width="84""#));
+        assert!(!svg.contains("crispEdges"));
+        assert!(svg.contains(r##"x1="0" y1="0" x2="0" y2="1"><stop offset="0" stop-color="#111111"/>"##));
+        assert!(svg.contains(r##"<path fill="url(#datp-gradient)" d="M0 2a2 2 0 1 0 4 0a2 2 0 1 0 -4 0z"##));
+
+        // the top left corner of a finder pattern is rounded, its neighbour to the right is not
+        let svg = render(&code, &QrStyle { module_shape: ModuleShape::Rounded, quiet_zone: 0, min_dimension: 84, ..style() }, "QR code", "").unwrap();
+        assert!(svg.contains(r#"d="M2 0H4V4H0V2a2 2 0 0 1 2 -2zM4 0H8V4H4V0z"#));
+    }
+
+    #[test]
+    fn test_invalid_colors() {
+        let code = QrCode::with_version(b"hello", Version::Normal(1), EcLevel::M).unwrap();
+        let injected = r#"#000"/><script>alert(1)</script><path d=""#;
+        let invalid = |color: &str| Err(DatpError::InvalidColor(color.to_string()));
+        assert_eq!(render(&code, &QrStyle { dark_color: injected, ..style() }, "t", ""), invalid(injected));
+        assert_eq!(render(&code, &QrStyle { light_color: "white", ..style() }, "t", ""), invalid("white"));
+        let gradient = QrGradient { start_color: "#111", end_color: "red", direction: GradientDirection::Horizontal };
+        assert_eq!(render(&code, &QrStyle { gradient: Some(gradient), ..style() }, "t", ""), invalid("red"));
+        assert!(render(&code, &QrStyle { dark_color: "#ABC", ..style() }, "t", "").is_ok());
+    }
+}
@@ -0,0 +1,78 @@
+use qrcode::render::unicode::Dense1x2;
+use qrcode::{EcLevel, QrCode};
+
+use super::{build_otpauth_uri, Account, DatpError};
+
+/// Characters used to draw a QR code in a terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TerminalQrStyle {
+    /// Upper/lower half blocks, two modules per character cell; the most compact.
+    #[default]
+    HalfBlock,
+    /// Two full blocks per module; larger, but works with fonts that render half blocks badly.
+    FullBlock,
+}
+
+/// Renders the otpauth URI of an account as a QR code made of Unicode block characters.
+///
+/// The output includes the quiet zone and ends every line with `\n`. Terminals with a dark
+/// background draw the blocks in a light colour, so pass `invert = true` there to keep dark
+/// modules dark; most scanners need that.
+///
+/// # Arguments
+/// * `account` - Account to provision.
+/// * `style` - Half or full block characters.
+/// * `invert` - Draw light modules instead of dark ones.
+///
+/// # Returns
+/// `Ok(String)` - The rendered code, or `DatpError::QrCode` when the URI does not fit into a QR code.
+///
+/// # Example
+/// ```rust
+/// use datp::{totp_qr_terminal, Account, TerminalQrStyle};
+///
+/// let account = Account::new("user@example.com", "JBSWY3DPEHPK3PXP");
+/// print!("{}", totp_qr_terminal(&account, TerminalQrStyle::HalfBlock, true).unwrap());
+/// ```
+pub fn totp_qr_terminal(account: &Account, style: TerminalQrStyle, invert: bool) -> Result<String, DatpError> {
+    let code = QrCode::with_error_correction_level(build_otpauth_uri(account).as_bytes(), EcLevel::M)
+        .map_err(|e| DatpError::QrCode(e.to_string()))?;
+
+    let mut out = match style {
+        TerminalQrStyle::HalfBlock => {
+            let (dark, light) = if invert { (Dense1x2::Light, Dense1x2::Dark) } else { (Dense1x2::Dark, Dense1x2::Light) };
+            code.render::<Dense1x2>().dark_color(dark).light_color(light).build()
+        }
+        TerminalQrStyle::FullBlock => {
+            let (dark, light) = if invert { (' ', '█') } else { ('█', ' ') };
+            code.render::<char>().module_dimensions(2, 1).dark_color(dark).light_color(light).build()
+        }
+    };
+    out.push('\n');
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_totp_qr_terminal() {
+        let account = Account::new("user@example.com", "JBSWY3DPEHPK3PXP");
+        let width = QrCode::new(build_otpauth_uri(&account).as_bytes()).unwrap().width() + 8;
+
+        let full = totp_qr_terminal(&account, TerminalQrStyle::FullBlock, false).unwrap();
+        assert_eq!(full.lines().count(), width);
+        assert!(full.lines().all(|line| line.chars().count() == width * 2));
+        // quiet zone, then the top edge of the left finder pattern
+        assert_eq!(full.lines().nth(4).unwrap().chars().skip(8).take(14).collect::<String>(), "█".repeat(14));
+
+        let half = totp_qr_terminal(&account, TerminalQrStyle::HalfBlock, false).unwrap();
+        assert_eq!(half.lines().count(), width.div_ceil(2));
+        assert!(half.lines().all(|line| line.chars().count() == width));
+
+        let inverted = totp_qr_terminal(&account, TerminalQrStyle::FullBlock, true).unwrap();
+        assert!(inverted.starts_with('█'));
+        assert!(full.starts_with(' '));
+    }
+}
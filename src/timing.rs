@@ -0,0 +1,86 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Where a TOTP time step stands, with sub-second precision, for countdown rings and progress
+/// bars in authenticator UIs.
+///
+/// # Example
+/// ```rust
+/// use datp::WindowTiming;
+///
+/// let timing = WindowTiming::now(30, 0).unwrap();
+/// assert!((0.0..1.0).contains(&timing.progress));
+/// println!("{:.0}% through, next code at {}", timing.progress * 100.0, timing.next_rollover);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WindowTiming {
+    pub counter: u64,                   // current time step
+    pub progress: f64,                  // part of the step elapsed, from 0.0 at a rollover to just below 1.0
+    pub remaining: Duration,            // time left until the next rollover, never zero
+    pub next_rollover: u64,             // Unix time of the next rollover
+    pub next_rollover_at: Instant,      // the same moment on the monotonic clock, for animation timers
+}
+
+impl WindowTiming {
+    /// The timing of the current step by the system clock.
+    ///
+    /// # Arguments
+    /// * `step` - Time step in seconds (usually 30 seconds).
+    /// * `t0` - Unix epoch start time (usually 0).
+    ///
+    /// # Returns
+    /// `Option<WindowTiming>` - The timing, or `None` if `step` is 0 or the clock is before `t0`.
+    pub fn now(step: u64, t0: u64) -> Option<WindowTiming> {
+        WindowTiming::at(step, t0, SystemTime::now().duration_since(UNIX_EPOCH).ok()?)
+    }
+
+    /// The timing of the step containing `since_epoch`, the time since the Unix epoch.
+    /// `next_rollover_at` is counted from the moment of the call, as if that were `since_epoch`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use std::time::Duration;
+    /// use datp::WindowTiming;
+    ///
+    /// let timing = WindowTiming::at(30, 0, Duration::from_millis(67_500)).unwrap();
+    /// assert_eq!((timing.counter, timing.progress, timing.next_rollover), (2, 0.25, 90));
+    /// assert_eq!(timing.remaining, Duration::from_millis(22_500));
+    /// ```
+    pub fn at(step: u64, t0: u64, since_epoch: Duration) -> Option<WindowTiming> {
+        let elapsed = since_epoch.checked_sub(Duration::from_secs(t0))?;
+        let counter = elapsed.as_secs().checked_div(step)?;
+        let into_step = elapsed - Duration::from_secs(counter * step);
+        let remaining = Duration::from_secs(step) - into_step;
+        Some(WindowTiming {
+            counter,
+            progress: into_step.as_secs_f64() / step as f64,
+            remaining,
+            next_rollover: t0.checked_add(counter.checked_add(1)?.checked_mul(step)?)?,
+            next_rollover_at: Instant::now().checked_add(remaining)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seconds_remaining;
+
+    #[test]
+    fn test_window_timing() {
+        let before = Instant::now();
+        let timing = WindowTiming::at(30, 10, Duration::from_secs(40)).unwrap();
+        assert_eq!((timing.counter, timing.progress, timing.next_rollover), (1, 0.0, 70));
+        assert_eq!(timing.remaining, Duration::from_secs(30));
+        assert!(timing.next_rollover_at >= before + timing.remaining);
+
+        let timing = WindowTiming::at(10, 0, Duration::from_millis(1_999_999)).unwrap();
+        assert!(timing.progress > 0.99 && timing.progress < 1.0);
+        assert_eq!(timing.remaining, Duration::from_millis(1));
+        assert_eq!(timing.remaining.as_secs() + 1, seconds_remaining(10, 0, 1_999).unwrap());
+
+        assert_eq!(WindowTiming::at(0, 0, Duration::from_secs(40)), None);
+        assert_eq!(WindowTiming::at(30, 50, Duration::from_secs(40)), None);
+        assert_eq!(WindowTiming::at(30, 0, Duration::MAX), None);
+        assert_eq!(WindowTiming::at(1, 0, Duration::MAX), None);
+    }
+}
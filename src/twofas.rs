@@ -0,0 +1,170 @@
+// 2FAS Auth backups (`.2fas`): JSON with a `services` array. Password
+// protected backups leave `services` empty and carry the same array in
+// `servicesEncrypted` as "base64(ciphertext+tag):base64(salt):base64(iv)",
+// encrypted with AES-256-GCM under PBKDF2-HMAC-SHA256(password, salt, 10000).
+
+use super::json::{self, Json};
+use super::{base64, crypto, uri, Account, DatpError, OtpKind};
+
+const PBKDF2_ITERATIONS: u32 = 10_000;
+
+/// A 2FAS service: the account plus the metadata 2FAS keeps alongside it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TwoFasService {
+    pub account: Account,
+    pub service_name: String,                     // name shown in the 2FAS list
+    pub service_type_id: Option<String>,          // 2FAS service catalogue id
+    pub icon_collection_id: Option<String>,       // icon from the 2FAS icon collection
+    pub icon_label: Option<String>,               // text of the label icon, e.g. "GI"
+    pub icon_background_color: Option<String>,    // label icon color name, e.g. "Orange"
+}
+
+/// Reads a 2FAS Auth `.2fas` backup.
+///
+/// # Arguments
+/// * `backup` - Contents of the `.2fas` file.
+/// * `password` - Backup password, only needed for password-protected backups.
+///
+/// # Returns
+/// `Result<Vec<TwoFasService>, DatpError>` - The services in the backup, with their
+/// names and icons so they survive the migration.
+pub fn import_2fas(backup: &str, password: Option<&str>) -> Result<Vec<TwoFasService>, DatpError> {
+    let root = json::parse(backup).ok_or(DatpError::InvalidJson)?;
+
+    let decrypted;
+    let services = match root.str_field("servicesEncrypted") {
+        Some(encrypted) => {
+            let password = password.ok_or(DatpError::PasswordRequired)?;
+            decrypted = decrypt_services(encrypted, password)?;
+            &decrypted
+        }
+        None => root.get("services").ok_or_else(|| invalid("missing 'services' array"))?,
+    };
+
+    services
+        .as_array()
+        .ok_or_else(|| invalid("'services' is not an array"))?
+        .iter()
+        .map(parse_service)
+        .collect()
+}
+
+fn decrypt_services(encrypted: &str, password: &str) -> Result<Json, DatpError> {
+    let parts: Vec<Vec<u8>> = encrypted
+        .split(':')
+        .map(base64::decode)
+        .collect::<Option<_>>()
+        .ok_or_else(|| invalid("'servicesEncrypted' is not valid base64"))?;
+    let [data, salt, iv] = parts.as_slice() else {
+        return Err(invalid("'servicesEncrypted' must have three parts"));
+    };
+    if data.len() < 16 {
+        return Err(invalid("'servicesEncrypted' is too short"));
+    }
+
+    let key = crypto::pbkdf2_sha256(password.as_bytes(), salt, PBKDF2_ITERATIONS, 32);
+    let (ciphertext, tag) = data.split_at(data.len() - 16);
+    let plaintext = crypto::aes256_gcm_decrypt(&key, iv, ciphertext, tag, &[]).ok_or(DatpError::WrongPassword)?;
+    let plaintext = String::from_utf8(plaintext).map_err(|_| DatpError::InvalidJson)?;
+    json::parse(&plaintext).ok_or(DatpError::InvalidJson)
+}
+
+fn parse_service(service: &Json) -> Result<TwoFasService, DatpError> {
+    let secret = service.str_field("secret").ok_or(DatpError::MissingSecret)?;
+    let service_name = service.str_field("name").unwrap_or("").to_string();
+    let otp = service.get("otp").unwrap_or(&Json::Null);
+
+    let account_name = otp
+        .str_field("account")
+        .filter(|name| !name.is_empty())
+        .or_else(|| otp.str_field("label").filter(|label| !label.is_empty()))
+        .unwrap_or(&service_name);
+    let mut account = Account::new(account_name, uri::normalize_secret(secret)?);
+    account.issuer = otp
+        .str_field("issuer")
+        .filter(|issuer| !issuer.is_empty())
+        .or(Some(service_name.as_str()).filter(|name| !name.is_empty()))
+        .map(str::to_string);
+
+    if let Some(algorithm) = otp.str_field("algorithm") {
+        account.algorithm = uri::parse_algorithm(algorithm)?;
+    }
+    if let Some(digits) = otp.get("digits").and_then(Json::as_u64) {
+        account.digits = uri::parse_digits(&digits.to_string())?;
+    }
+    if let Some(period) = otp.get("period").and_then(Json::as_u64) {
+        if period == 0 {
+            return Err(DatpError::InvalidPeriod(period.to_string()));
+        }
+        account.period = period;
+    }
+    match otp.str_field("tokenType").map(str::to_ascii_uppercase).as_deref() {
+        None | Some("TOTP") => {}
+        Some("HOTP") => {
+            account.kind = OtpKind::Hotp;
+            account.counter = Some(otp.get("counter").and_then(Json::as_u64).unwrap_or(0));
+        }
+        Some("STEAM") => {
+            account.kind = OtpKind::Steam;
+            account.digits = 5;
+        }
+        Some(other) => return Err(DatpError::UnsupportedType(other.to_string())),
+    }
+
+    let icon = service.get("icon").unwrap_or(&Json::Null);
+    let label = icon.get("label").unwrap_or(&Json::Null);
+    Ok(TwoFasService {
+        account,
+        service_name,
+        service_type_id: service.str_field("serviceTypeID").map(str::to_string),
+        icon_collection_id: icon.get("iconCollection").and_then(|collection| collection.str_field("id")).map(str::to_string),
+        icon_label: label.str_field("text").map(str::to_string),
+        icon_background_color: label.str_field("backgroundColor").map(str::to_string),
+    })
+}
+
+fn invalid(reason: &str) -> DatpError {
+    DatpError::InvalidBackup(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Algorithm;
+
+    // produced with the cryptography package, password "test"
+    const ENCRYPTED: &str = r#"{"services":[],"groups":[],"schemaVersion":4,"appOrigin":"android","servicesEncrypted":"MGqMe4ji1aaCiI0GKf8w0GAHW2tRhBvZ66dLDK1EwcSdfVIiRdvMlFSijnmbrDJMcPCVFtedAFpBDpa2mcJ3ShVpTUYXpNJa9Cd4SP03I4XbU1LiIKBU4fTpbRDITMUorKXySUwrZElp6NWX+QafC+rtlCQInZ5PMp4D6KSdE5Bs9cfG9LCz5cLVQ8uwJq5+nL418hhQe58+7VJkBv41QtkRvPnxhsVB+Bxv/qX8wCKXpGGPVR1fxkFmApU0wmzFOY7+usYni5PEAi/Cak5M+caJ1BmVypCNiPRFCd2FHzQF08QxaUxLhonBxKfDt/wa+A3Q0GKbrNBaSDDz5htFmCkiZRsINWovkHxZLVqgkHyBM7o0hwNvTSQ85CBaLbGtPQv/LAb505qqnAkzi6KpMCQH2IsBDy3JljlmoIUbjTEJeXJs5VWzoXZCZFDTNg0X/XzpjtpMpoSCMYWphf2cVthr1Lmw/YypFi4EJtnEF7hepBEN/6urynsYnM/P8ZMOW18hPj6OSydOnI0q2VXxWq2RK+niKGM7BjGhp96C/K4Jnos6C47r632+psWIxjkrltmEPNMQY2vAm432bKgsx/itGFEFZ4qOdK1iZw+Xg1trpCfo1Rr0Fdk07Q5SqompJEwKn9n8PS1MKnt5KmQnzn+jiUf47rK7EBg9ZP1kRQVd+QoUxHAHTLzkJ0j5orOFAU1zs9xGYwSLxdYmLJ132VBe+XXj2mzBKumI2sNN4cHxq4vinHJRN/5gQDWx1BZnYWCollK5iFHQ8YU1ELTQh59TBIffntTw4HMm7ydZ33q94V5SIhuuB7GmFrp1mFW4nN2QgWAxXbtVOQ59753dW6BM6bi2ZXVnsEyFnYPgldE0zqUrNjGilIAlPvTA6CdqZt9H9Jadwu5a3eYtk0k=:AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=:AAECAwQFBgcICQoL"}"#;
+
+    #[test]
+    fn test_import_encrypted() {
+        let services = import_2fas(ENCRYPTED, Some("test")).unwrap();
+        assert_eq!(services.len(), 2);
+
+        let github = &services[0];
+        assert_eq!(github.account.issuer.as_deref(), Some("GitHub"));
+        assert_eq!(github.account.account_name, "octocat");
+        assert_eq!(github.icon_collection_id.as_deref(), Some("a5b3fb65-4ec5-43e6-8ec1-49e24ca9e7ad"));
+        assert_eq!(github.icon_label.as_deref(), Some("GI"));
+
+        let counter = &services[1].account;
+        assert_eq!(counter.kind, OtpKind::Hotp);
+        assert_eq!(counter.counter, Some(4));
+        assert_eq!(counter.algorithm, Algorithm::Sha256);
+        assert_eq!(counter.digits, 8);
+        assert_eq!(counter.account_name, "Counter");
+
+        assert_eq!(import_2fas(ENCRYPTED, Some("nope")), Err(DatpError::WrongPassword));
+        assert_eq!(import_2fas(ENCRYPTED, None), Err(DatpError::PasswordRequired));
+    }
+
+    #[test]
+    fn test_import_plain() {
+        let backup = r#"{"services":[{"name":"Steam","secret":"JBSWY3DPEHPK3PXP","otp":{"account":"gaben","tokenType":"STEAM"}}],"schemaVersion":4}"#;
+        let services = import_2fas(backup, None).unwrap();
+        assert_eq!(services[0].account, Account::new_steam("gaben", "JBSWY3DPEHPK3PXP"));
+        assert_eq!(services[0].service_name, "Steam");
+
+        let zero = r#"{"services":[{"name":"x","secret":"JBSWY3DPEHPK3PXP","otp":{"period":0}}],"schemaVersion":4}"#;
+        assert_eq!(import_2fas(zero, None), Err(DatpError::InvalidPeriod("0".to_string())));
+    }
+}
@@ -0,0 +1,500 @@
+use std::fmt;
+use std::str::FromStr;
+
+use base32::Alphabet;
+
+use super::{Account, Algorithm, DatpError, OtpKind};
+
+impl Account {
+    /// Parses an `otpauth://totp/...` or `otpauth://hotp/...` URI, e.g. one
+    /// scanned from a QR code. HOTP URIs must carry a `counter` parameter.
+    ///
+    /// The issuer is taken from the `issuer` parameter or, if absent, from the
+    /// `Issuer:account` label prefix. Missing optional parameters fall back to
+    /// the defaults of [`Account::new`]. Secrets are normalized to uppercase
+    /// base32 without padding or whitespace.
+    ///
+    /// # Example
+    /// ```rust
+    /// use datp::{Account, Algorithm};
+    ///
+    /// let account = Account::from_uri(
+    ///     "otpauth://totp/ACME%20Co:john@example.com?secret=JBSWY3DPEHPK3PXP&algorithm=SHA256&digits=8",
+    /// ).unwrap();
+    /// assert_eq!(account.issuer.as_deref(), Some("ACME Co"));
+    /// assert_eq!(account.account_name, "john@example.com");
+    /// assert_eq!(account.algorithm, Algorithm::Sha256);
+    /// assert_eq!(account.digits, 8);
+    /// ```
+    pub fn from_uri(uri: &str) -> Result<Account, DatpError> {
+        parse_uri(uri, false).map_err(|mut problems| problems.remove(0))
+    }
+
+    /// Parses an otpauth URI in strict mode, reporting every problem found.
+    ///
+    /// On top of the checks done by [`Account::from_uri`], strict mode rejects
+    /// an issuer label prefix that disagrees with the `issuer` parameter,
+    /// repeated parameters, and parameters it does not know. All problems are
+    /// collected instead of stopping at the first one, so import UIs can show
+    /// the user everything that is wrong with a URI at once.
+    ///
+    /// # Example
+    /// ```rust
+    /// use datp::{Account, DatpError};
+    ///
+    /// let problems = Account::from_uri_strict(
+    ///     "otpauth://totp/Foo:alice?issuer=Bar&algorithm=MD5",
+    /// ).unwrap_err();
+    /// assert!(problems.contains(&DatpError::MissingSecret));
+    /// assert!(problems.contains(&DatpError::UnknownAlgorithm("MD5".to_string())));
+    /// assert!(problems.contains(&DatpError::ConflictingIssuer {
+    ///     label: "Foo".to_string(),
+    ///     parameter: "Bar".to_string(),
+    /// }));
+    /// ```
+    pub fn from_uri_strict(uri: &str) -> Result<Account, Vec<DatpError>> {
+        parse_uri(uri, true)
+    }
+}
+
+fn parse_uri(uri: &str, strict: bool) -> Result<Account, Vec<DatpError>> {
+    let rest = strip_prefix_ignore_case(uri.trim(), "otpauth://").ok_or_else(|| vec![DatpError::InvalidScheme])?;
+
+    let (kind, rest) = rest.split_once('/').ok_or_else(|| vec![DatpError::InvalidLabel])?;
+    let kind = match kind.to_ascii_lowercase().as_str() {
+        "totp" => OtpKind::Totp,
+        "hotp" => OtpKind::Hotp,
+        "steam" => OtpKind::Steam,
+        _ => return Err(vec![DatpError::UnsupportedType(kind.to_string())]),
+    };
+
+    let mut problems = Vec::new();
+    let (label, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let label = percent_decode(label, false).unwrap_or_else(|err| {
+        problems.push(err);
+        String::new()
+    });
+    let (label_issuer, account_name) = match label.split_once(':') {
+        Some((issuer, name)) => (Some(issuer.trim().to_string()), name.trim().to_string()),
+        None => (None, label.trim().to_string()),
+    };
+    if account_name.is_empty() && problems.is_empty() {
+        problems.push(DatpError::InvalidLabel);
+    }
+
+    let mut account = Account::new(account_name, String::new());
+    account.kind = kind;
+    let label_issuer = label_issuer.filter(|issuer| !issuer.is_empty());
+    let mut param_issuer = None;
+    let mut secret = None;
+    let mut seen: Vec<String> = Vec::new();
+
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let key = key.to_ascii_lowercase();
+        let value = match percent_decode(value, true) {
+            Ok(value) => value,
+            Err(err) => {
+                problems.push(err);
+                continue;
+            }
+        };
+        if strict && seen.contains(&key) {
+            problems.push(DatpError::DuplicateParameter(key.clone()));
+        }
+        seen.push(key.clone());
+
+        let result = match key.as_str() {
+            "secret" => {
+                secret = Some(value);
+                Ok(())
+            }
+            "issuer" => {
+                param_issuer = Some(value).filter(|issuer| !issuer.is_empty());
+                Ok(())
+            }
+            "algorithm" => parse_algorithm(&value).map(|algorithm| account.algorithm = algorithm),
+            "digits" => parse_digits(&value).map(|digits| account.digits = digits),
+            "period" => match value.parse::<u64>() {
+                Ok(period) if period > 0 => {
+                    account.period = period;
+                    Ok(())
+                }
+                _ => Err(DatpError::InvalidPeriod(value)),
+            },
+            // KeePassXC marks Steam Guard accounts this way
+            "encoder" if value.eq_ignore_ascii_case("steam") => {
+                account.kind = OtpKind::Steam;
+                Ok(())
+            }
+            "image" => {
+                account.image = Some(value).filter(|image| !image.is_empty());
+                Ok(())
+            }
+            "counter" => match value.parse() {
+                Ok(counter) => {
+                    account.counter = Some(counter);
+                    Ok(())
+                }
+                Err(_) => Err(DatpError::InvalidCounter(value)),
+            },
+            // unknown parameters are ignored so newer URIs still import
+            _ if strict => Err(DatpError::UnknownParameter(key)),
+            _ => Ok(()),
+        };
+        if let Err(err) = result {
+            problems.push(err);
+        }
+    }
+
+    if let (true, Some(label), Some(parameter)) = (strict, &label_issuer, &param_issuer)
+        && label != parameter
+    {
+        problems.push(DatpError::ConflictingIssuer { label: label.clone(), parameter: parameter.clone() });
+    }
+    account.issuer = param_issuer.or(label_issuer);
+    if account.kind == OtpKind::Steam {
+        account.digits = 5;
+    }
+    if kind == OtpKind::Hotp && account.counter.is_none() && !seen.iter().any(|key| key == "counter") {
+        problems.push(DatpError::MissingCounter);
+    }
+
+    match secret.as_deref().map(normalize_secret) {
+        Some(Ok(secret)) => account.secret = secret,
+        Some(Err(err)) => problems.push(err),
+        None => problems.push(DatpError::MissingSecret),
+    }
+
+    if problems.is_empty() { Ok(account) } else { Err(problems) }
+}
+
+/// Builds the otpauth provisioning URI for an account.
+///
+/// The label uses the canonical `issuer:account` form, every component is
+/// percent-encoded, and the algorithm, digits and period (or counter for HOTP)
+/// of the account are emitted so enrolled apps generate matching codes.
+///
+/// # Arguments
+/// * `account` - Account to provision.
+///
+/// # Returns
+/// `String` - The `otpauth://` URI, e.g. for an `<a href>` or a native app handoff.
+///
+/// # Example
+/// ```rust
+/// use datp::{build_otpauth_uri, Account};
+///
+/// let mut account = Account::new("user@example.com", "JBSWY3DPEHPK3PXP");
+/// account.issuer = Some("MyApp".to_string());
+/// assert_eq!(
+///     build_otpauth_uri(&account),
+///     "otpauth://totp/MyApp:user%40example.com?secret=JBSWY3DPEHPK3PXP&issuer=MyApp&algorithm=SHA1&digits=6&period=30"
+/// );
+/// ```
+pub fn build_otpauth_uri(account: &Account) -> String {
+    let issuer = account.issuer.as_deref().filter(|issuer| !issuer.is_empty());
+
+    let kind = if account.kind == OtpKind::Hotp { "hotp" } else { "totp" };
+    let mut url = format!("otpauth://{}/", kind);
+    if let Some(issuer) = issuer {
+        url.push_str(&percent_encode(issuer));
+        url.push(':');
+    }
+    url.push_str(&percent_encode(&account.account_name));
+
+    url.push_str("?secret=");
+    url.push_str(&percent_encode(&account.secret));
+    if let Some(issuer) = issuer {
+        url.push_str("&issuer=");
+        url.push_str(&percent_encode(issuer));
+    }
+    url.push_str(&format!("&algorithm={}&digits={}", account.algorithm.as_str(), account.digits));
+    match account.kind {
+        OtpKind::Totp => url.push_str(&format!("&period={}", account.period)),
+        OtpKind::Hotp => url.push_str(&format!("&counter={}", account.counter.unwrap_or(0))),
+        OtpKind::Steam => url.push_str(&format!("&period={}&encoder=steam", account.period)),
+    }
+    if let Some(image) = account.image.as_deref().filter(|image| !image.is_empty()) {
+        url.push_str("&image=");
+        url.push_str(&percent_encode(image));
+    }
+    url
+}
+
+/// Normalizes a user-supplied base32 secret and checks that it decodes.
+pub(crate) fn normalize_secret(secret: &str) -> Result<String, DatpError> {
+    let normalized: String = secret
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-' && *c != '=')
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    if normalized.is_empty() {
+        return Err(DatpError::MissingSecret);
+    }
+    match base32::decode(Alphabet::Rfc4648 { padding: false }, &normalized) {
+        Some(_) => Ok(normalized),
+        None => Err(DatpError::InvalidSecret),
+    }
+}
+
+pub(crate) fn parse_algorithm(value: &str) -> Result<Algorithm, DatpError> {
+    match value.to_ascii_uppercase().replace('-', "").as_str() {
+        "SHA1" => Ok(Algorithm::Sha1),
+        "SHA256" => Ok(Algorithm::Sha256),
+        "SHA512" => Ok(Algorithm::Sha512),
+        _ => Err(DatpError::UnknownAlgorithm(value.to_string())),
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = DatpError;
+
+    /// Parses `SHA1`, `SHA256` or `SHA512`, ignoring case and hyphens (`sha-256`).
+    fn from_str(value: &str) -> Result<Self, DatpError> {
+        parse_algorithm(value)
+    }
+}
+
+impl fmt::Display for Account {
+    /// Writes the otpauth URI, see `build_otpauth_uri`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&build_otpauth_uri(self))
+    }
+}
+
+impl FromStr for Account {
+    type Err = DatpError;
+
+    /// Parses an otpauth URI, see `Account::from_uri`.
+    fn from_str(uri: &str) -> Result<Self, DatpError> {
+        Account::from_uri(uri)
+    }
+}
+
+/// Parses an otpauth URI, so `Account::try_from(uri)` and value parsers that expect
+/// `TryFrom<&str>` or `TryFrom<String>` work as well as `uri.parse::<Account>()`.
+///
+/// # Example
+/// ```rust
+/// use datp::Account;
+///
+/// let account = Account::try_from("otpauth://totp/MyApp:alice?secret=JBSWY3DPEHPK3PXP").unwrap();
+/// assert_eq!(account.issuer.as_deref(), Some("MyApp"));
+/// let account: Account = "otpauth://hotp/bob?secret=JBSWY3DPEHPK3PXP&counter=3".parse().unwrap();
+/// assert_eq!(account.counter, Some(3));
+/// ```
+impl TryFrom<&str> for Account {
+    type Error = DatpError;
+
+    fn try_from(uri: &str) -> Result<Self, DatpError> {
+        Account::from_uri(uri)
+    }
+}
+
+impl TryFrom<String> for Account {
+    type Error = DatpError;
+
+    fn try_from(uri: String) -> Result<Self, DatpError> {
+        Account::from_uri(&uri)
+    }
+}
+
+pub(crate) fn parse_digits(value: &str) -> Result<u32, DatpError> {
+    match value.parse::<u32>() {
+        Ok(digits) if (1..=9).contains(&digits) => Ok(digits),
+        _ => Err(DatpError::InvalidDigits(value.to_string())),
+    }
+}
+
+pub(crate) fn strip_prefix_ignore_case<'a>(input: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = input.get(..prefix.len())?;
+    if head.eq_ignore_ascii_case(prefix) { Some(&input[prefix.len()..]) } else { None }
+}
+
+/// Percent-encodes everything except the RFC 3986 unreserved characters.
+pub(crate) fn percent_encode(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for byte in input.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => out.push(byte as char),
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+/// Decodes `%XX` escapes; in query values `+` is also accepted as a space.
+pub(crate) fn percent_decode(input: &str, plus_as_space: bool) -> Result<String, DatpError> {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = input.get(i + 1..i + 3).ok_or(DatpError::InvalidPercentEncoding)?;
+                out.push(u8::from_str_radix(hex, 16).map_err(|_| DatpError::InvalidPercentEncoding)?);
+                i += 3;
+            }
+            b'+' if plus_as_space => {
+                out.push(b' ');
+                i += 1;
+            }
+            byte => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(out).map_err(|_| DatpError::InvalidPercentEncoding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_full_uri() {
+        let account = Account::from_uri(
+            "otpauth://totp/Example:alice@google.com?secret=JBSWY3DPEHPK3PXP&issuer=Example&algorithm=SHA512&digits=8&period=60",
+        )
+        .unwrap();
+        assert_eq!(account.issuer.as_deref(), Some("Example"));
+        assert_eq!(account.account_name, "alice@google.com");
+        assert_eq!(account.secret, "JBSWY3DPEHPK3PXP");
+        assert_eq!(account.algorithm, Algorithm::Sha512);
+        assert_eq!(account.digits, 8);
+        assert_eq!(account.period, 60);
+    }
+
+    #[test]
+    fn test_parse_defaults_and_normalization() {
+        let account = Account::from_uri("otpauth://totp/bob?secret=jbsw%20y3dp%20ehpk%203pxp").unwrap();
+        assert_eq!(account.issuer, None);
+        assert_eq!(account.account_name, "bob");
+        assert_eq!(account.secret, "JBSWY3DPEHPK3PXP");
+        assert_eq!(account.algorithm, Algorithm::Sha1);
+        assert_eq!(account.digits, 6);
+        assert_eq!(account.period, 30);
+    }
+
+    #[test]
+    fn test_parse_issuer_conventions() {
+        let from_label = Account::from_uri("otpauth://totp/My%20Bank%3A%20carol?secret=JBSWY3DPEHPK3PXP").unwrap();
+        assert_eq!(from_label.issuer.as_deref(), Some("My Bank"));
+        assert_eq!(from_label.account_name, "carol");
+
+        let from_param = Account::from_uri("otpauth://totp/carol?secret=JBSWY3DPEHPK3PXP&issuer=My+Bank").unwrap();
+        assert_eq!(from_param.issuer.as_deref(), Some("My Bank"));
+    }
+
+    #[test]
+    fn test_display_and_from_str() {
+        for algorithm in [Algorithm::Sha1, Algorithm::Sha256, Algorithm::Sha512] {
+            assert_eq!(algorithm.to_string().parse::<Algorithm>(), Ok(algorithm));
+        }
+        assert_eq!("sha-512".parse::<Algorithm>(), Ok(Algorithm::Sha512));
+        assert_eq!("MD5".parse::<Algorithm>(), Err(DatpError::UnknownAlgorithm("MD5".to_string())));
+
+        let mut account = Account::new_hotp("alice@example.com", "JBSWY3DPEHPK3PXP", 7);
+        account.issuer = Some("ACME".to_string());
+        assert_eq!(account.to_string(), build_otpauth_uri(&account));
+        assert_eq!(account.to_string().parse::<Account>(), Ok(account.clone()));
+        assert_eq!(Account::try_from(account.to_string()), Ok(account));
+        assert_eq!(Account::try_from("https://example.com"), Err(DatpError::InvalidScheme));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Account::from_uri("https://example.com"), Err(DatpError::InvalidScheme));
+        assert_eq!(Account::from_uri("otpauth://totp/alice"), Err(DatpError::MissingSecret));
+        assert_eq!(Account::from_uri("otpauth://totp/?secret=JBSWY3DPEHPK3PXP"), Err(DatpError::InvalidLabel));
+        assert_eq!(Account::from_uri("otpauth://totp/a?secret=!!!"), Err(DatpError::InvalidSecret));
+        assert_eq!(
+            Account::from_uri("otpauth://totp/a?secret=JBSWY3DPEHPK3PXP&algorithm=MD5"),
+            Err(DatpError::UnknownAlgorithm("MD5".to_string()))
+        );
+        assert_eq!(
+            Account::from_uri("otpauth://totp/a?secret=JBSWY3DPEHPK3PXP&digits=12"),
+            Err(DatpError::InvalidDigits("12".to_string()))
+        );
+        assert_eq!(
+            Account::from_uri("otpauth://totp/a?secret=JBSWY3DPEHPK3PXP&period=0"),
+            Err(DatpError::InvalidPeriod("0".to_string()))
+        );
+        assert_eq!(Account::from_uri("otpauth://totp/a%ZZ?secret=JBSWY3DPEHPK3PXP"), Err(DatpError::InvalidPercentEncoding));
+    }
+
+    #[test]
+    fn test_strict_reports_all_problems() {
+        let problems =
+            Account::from_uri_strict("otpauth://totp/Foo:a?secret=!!&digits=0&digits=6&issuer=Bar&foo=1").unwrap_err();
+        assert_eq!(
+            problems,
+            vec![
+                DatpError::InvalidDigits("0".to_string()),
+                DatpError::DuplicateParameter("digits".to_string()),
+                DatpError::UnknownParameter("foo".to_string()),
+                DatpError::ConflictingIssuer { label: "Foo".to_string(), parameter: "Bar".to_string() },
+                DatpError::InvalidSecret,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strict_accepts_clean_uri() {
+        let uri = "otpauth://totp/Example:alice?secret=JBSWY3DPEHPK3PXP&issuer=Example&algorithm=SHA1&digits=6&period=30";
+        assert_eq!(Account::from_uri_strict(uri).unwrap(), Account::from_uri(uri).unwrap());
+        // the lenient parser lets the parameter win over the label
+        let account = Account::from_uri("otpauth://totp/Foo:a?secret=JBSWY3DPEHPK3PXP&issuer=Bar").unwrap();
+        assert_eq!(account.issuer.as_deref(), Some("Bar"));
+    }
+
+    #[test]
+    fn test_parse_hotp() {
+        let account = Account::from_uri("otpauth://hotp/Example:alice?secret=JBSWY3DPEHPK3PXP&counter=42").unwrap();
+        assert_eq!(account.kind, OtpKind::Hotp);
+        assert_eq!(account.counter, Some(42));
+        assert_eq!(
+            Account::from_uri("otpauth://hotp/alice?secret=JBSWY3DPEHPK3PXP"),
+            Err(DatpError::MissingCounter)
+        );
+    }
+
+    #[test]
+    fn test_hotp_uri_roundtrip() {
+        let mut account = Account::new_hotp("alice@example.com", "JBSWY3DPEHPK3PXP", 7);
+        account.issuer = Some("Example Co".to_string());
+        let uri = build_otpauth_uri(&account);
+        assert_eq!(
+            uri,
+            "otpauth://hotp/Example%20Co:alice%40example.com?secret=JBSWY3DPEHPK3PXP&issuer=Example%20Co&algorithm=SHA1&digits=6&counter=7"
+        );
+        assert_eq!(Account::from_uri(&uri).unwrap(), account);
+    }
+
+    #[test]
+    fn test_steam_uri_roundtrip() {
+        let account = Account::new_steam("gaben", "JBSWY3DPEHPK3PXP");
+        let uri = build_otpauth_uri(&account);
+        assert!(uri.starts_with("otpauth://totp/Steam:gaben?"));
+        assert!(uri.ends_with("&digits=5&period=30&encoder=steam"));
+        assert_eq!(Account::from_uri(&uri).unwrap(), account);
+    }
+
+    #[test]
+    fn test_image_parameter() {
+        let mut account = Account::new("alice", "JBSWY3DPEHPK3PXP");
+        account.image = Some("https://example.com/logo.png?size=64".to_string());
+        let uri = build_otpauth_uri(&account);
+        assert!(uri.ends_with("&image=https%3A%2F%2Fexample.com%2Flogo.png%3Fsize%3D64"));
+        assert_eq!(Account::from_uri(&uri).unwrap(), account);
+        assert!(Account::from_uri_strict(&uri).is_ok());
+    }
+
+    #[test]
+    fn test_percent_roundtrip() {
+        let input = "user name+test@example.com";
+        assert_eq!(percent_decode(&percent_encode(input), false).unwrap(), input);
+    }
+}
@@ -0,0 +1,767 @@
+use std::borrow::Borrow;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use super::{totp_verify_with, Account, Clock, DatpError, Lockout, OtpKind, SystemClock, VerificationObserver};
+
+/// Looks up the account of a user for `TotpVerifier`.
+///
+/// Implemented for closures, e.g. `move |user: &str| accounts.get(user).cloned()`.
+pub trait SecretResolver {
+    fn resolve(&self, user: &str) -> Option<Account>;
+}
+
+impl<F: Fn(&str) -> Option<Account>> SecretResolver for F {
+    fn resolve(&self, user: &str) -> Option<Account> {
+        self(user)
+    }
+}
+
+/// Remembers which codes were used, so a code can't be replayed while it is still valid.
+pub trait UsedCodeStore {
+    /// Records that `user` used the code of time step `counter`. Returns `false` if it was
+    /// recorded before. The entry may be dropped after `expires_at` (Unix time), when the code
+    /// is no longer accepted anyway.
+    fn mark_used(&self, user: &str, counter: u64, expires_at: u64) -> bool;
+}
+
+/// A thread-safe `UsedCodeStore` in memory, for services running as a single instance.
+///
+/// Entries are keyed on `(user, counter)` and evicted once `expires_at` has passed on the
+/// store's clock. At most `capacity` entries are kept: when it is full, the least recently
+/// recorded entry is evicted even if it has not expired, which would let that code be replayed,
+/// so size it above the number of logins expected within a code's lifetime.
+///
+/// # Example
+/// ```rust
+/// use datp::{MemoryReplayStore, UsedCodeStore};
+///
+/// let store = MemoryReplayStore::with_clock(10_000, || 1_000);
+/// assert!(store.mark_used("alice", 33, 1_060));
+/// assert!(!store.mark_used("alice", 33, 1_060));
+/// assert!(store.mark_used("bob", 33, 1_060));
+/// ```
+pub struct MemoryReplayStore {
+    capacity: usize,
+    clock: Box<dyn Clock + Send + Sync>,
+    entries: Mutex<ReplayEntries>,
+}
+
+#[derive(Default)]
+struct ReplayEntries {
+    expiry: HashMap<(String, u64), (u64, u64)>,     // (expires_at, sequence number)
+    order: VecDeque<((String, u64), u64)>,          // keys with their sequence number, oldest first
+    next: u64,
+}
+
+impl MemoryReplayStore {
+    /// Creates a store holding up to `capacity` entries, using the system clock.
+    pub fn new(capacity: usize) -> Self {
+        MemoryReplayStore::with_clock(capacity, SystemClock)
+    }
+
+    /// Creates a store that evicts expired entries by `clock`; use the clock of the
+    /// `TotpVerifier`.
+    pub fn with_clock(capacity: usize, clock: impl Clock + Send + Sync + 'static) -> Self {
+        MemoryReplayStore { capacity: capacity.max(1), clock: Box::new(clock), entries: Mutex::new(ReplayEntries::default()) }
+    }
+
+    /// Number of entries currently kept, expired ones not yet evicted included.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).expiry.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl UsedCodeStore for MemoryReplayStore {
+    fn mark_used(&self, user: &str, counter: u64, expires_at: u64) -> bool {
+        let now = self.clock.now();
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let ReplayEntries { expiry, order, next } = &mut *entries;
+        let key = (user.to_string(), counter);
+        if expiry.get(&key).is_some_and(|&(expires, _)| expires >= now) {
+            return false;
+        }
+
+        // drop expired entries from the front, and the oldest ones while full
+        while let Some((oldest, sequence)) = order.front() {
+            match expiry.get(oldest) {
+                Some(&(expires, current)) if current == *sequence => {
+                    if expires >= now && expiry.len() < self.capacity {
+                        break;
+                    }
+                    expiry.remove(oldest);
+                }
+                // superseded by a later record of the same key
+                _ => {}
+            }
+            order.pop_front();
+        }
+
+        *next += 1;
+        expiry.insert(key.clone(), (expires_at, *next));
+        order.push_back((key, *next));
+        true
+    }
+}
+
+/// Failed attempts per key, for throttling.
+///
+/// Implemented for `Mutex<HashMap<String, Vec<u64>>>`, which keeps the failure times in memory,
+/// and for `Arc`s of stores, so a `TotpVerifier` and an `AttemptLimiter` can share one.
+pub trait AttemptStore {
+    /// Number of failures of `key` at or after `since` (Unix time).
+    fn failures(&self, key: &str, since: u64) -> u32;
+
+    /// Records a failure of `key` at `at`. Failures older than `since` may be pruned.
+    fn record_failure(&self, key: &str, at: u64, since: u64);
+
+    /// Forgets the failures of `key`, after a successful attempt.
+    fn clear(&self, key: &str);
+
+    /// Records a failure of `key` at `at` unless it already has `max_failures` failures at or
+    /// after `since`, and returns whether it did. The check and the record must happen as one
+    /// atomic step: `TotpVerifier` counts every attempt as a failure this way before checking
+    /// the code and clears the key if it was right, so concurrent attempts can't all get past
+    /// the limit.
+    fn try_reserve(&self, key: &str, at: u64, since: u64, max_failures: u32) -> bool;
+}
+
+impl AttemptStore for Mutex<HashMap<String, Vec<u64>>> {
+    fn failures(&self, key: &str, since: u64) -> u32 {
+        let attempts = self.lock().unwrap_or_else(|e| e.into_inner());
+        attempts.get(key).map_or(0, |times| times.iter().filter(|&&at| at >= since).count() as u32)
+    }
+
+    fn record_failure(&self, key: &str, at: u64, since: u64) {
+        let mut attempts = self.lock().unwrap_or_else(|e| e.into_inner());
+        let times = attempts.entry(key.to_string()).or_default();
+        times.retain(|&time| time >= since);
+        times.push(at);
+    }
+
+    fn clear(&self, key: &str) {
+        self.lock().unwrap_or_else(|e| e.into_inner()).remove(key);
+    }
+
+    fn try_reserve(&self, key: &str, at: u64, since: u64, max_failures: u32) -> bool {
+        let mut attempts = self.lock().unwrap_or_else(|e| e.into_inner());
+        let times = attempts.entry(key.to_string()).or_default();
+        times.retain(|&time| time >= since);
+        if times.len() >= max_failures as usize {
+            return false;
+        }
+        times.push(at);
+        true
+    }
+}
+
+impl<T: AttemptStore + ?Sized> AttemptStore for Arc<T> {
+    fn failures(&self, key: &str, since: u64) -> u32 {
+        (**self).failures(key, since)
+    }
+
+    fn record_failure(&self, key: &str, at: u64, since: u64) {
+        (**self).record_failure(key, at, since)
+    }
+
+    fn clear(&self, key: &str) {
+        (**self).clear(key)
+    }
+
+    fn try_reserve(&self, key: &str, at: u64, since: u64, max_failures: u32) -> bool {
+        (**self).try_reserve(key, at, since, max_failures)
+    }
+}
+
+/// Clock drift of each user's device in time steps, learned from their accepted codes.
+///
+/// Implemented for `Mutex<HashMap<String, i64>>`.
+pub trait DriftStore {
+    /// The last drift of `user`, 0 if unknown.
+    fn drift(&self, user: &str) -> i64;
+    fn set_drift(&self, user: &str, steps: i64);
+}
+
+impl DriftStore for Mutex<HashMap<String, i64>> {
+    fn drift(&self, user: &str) -> i64 {
+        self.lock().unwrap_or_else(|e| e.into_inner()).get(user).copied().unwrap_or(0)
+    }
+
+    fn set_drift(&self, user: &str, steps: i64) {
+        self.lock().unwrap_or_else(|e| e.into_inner()).insert(user.to_string(), steps);
+    }
+}
+
+/// At most `max_failures` failed attempts per user within `window` seconds; further attempts
+/// are rejected without checking the code until the oldest failure leaves the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Throttle {
+    pub max_failures: u32,
+    pub window: u64,                 // seconds
+}
+
+impl Default for Throttle {
+    fn default() -> Self {
+        Throttle { max_failures: 5, window: 300 }
+    }
+}
+
+/// How a verification ended, see `VerificationReport`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerificationOutcome {
+    /// The code matched and was not used before.
+    Accepted,
+    /// The code did not match, or the user is unknown or has no TOTP account.
+    Rejected,
+    /// The code matched but was already used.
+    Replayed,
+    /// Too many failures; the code was not checked.
+    Throttled,
+    /// The user is locked out by the `Lockout`; the code was not checked.
+    Locked,
+}
+
+/// Everything `TotpVerifier::verify_report` found out about an attempt, for logging and for
+/// adapting the policy (e.g. asking for a resync when `matched_offset` grows).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerificationReport {
+    pub outcome: VerificationOutcome,
+    pub matched_offset: Option<i64>, // steps between the matching code and the clock, drift included
+    pub window_used: u64,            // steps accepted on either side of the user's drift
+    pub throttled: bool,             // rejected by the throttle or a lockout without checking
+    pub replay_detected: bool,
+    pub counter: Option<u64>,        // time step of the matching code
+}
+
+impl VerificationReport {
+    pub(crate) fn new(outcome: VerificationOutcome, window_used: u64) -> Self {
+        VerificationReport {
+            outcome,
+            matched_offset: None,
+            window_used,
+            throttled: matches!(outcome, VerificationOutcome::Throttled | VerificationOutcome::Locked),
+            replay_detected: outcome == VerificationOutcome::Replayed,
+            counter: None,
+        }
+    }
+
+    /// The report of a code that matched `found`.
+    pub(crate) fn matched(outcome: VerificationOutcome, window_used: u64, found: &CodeMatch) -> Self {
+        VerificationReport { matched_offset: Some(found.offset), counter: Some(found.step), ..VerificationReport::new(outcome, window_used) }
+    }
+
+    /// Whether the code was accepted.
+    pub fn is_accepted(&self) -> bool {
+        self.outcome == VerificationOutcome::Accepted
+    }
+}
+
+/// Where a code matched, for the replay and drift stores.
+pub(crate) struct CodeMatch {
+    pub(crate) offset: i64,         // steps from the clock, drift included
+    pub(crate) step: u64,
+    pub(crate) expires_at: u64,     // Unix time after which the code can't match any more
+}
+
+/// Looks for `code` within `window` steps around the user's `drift`.
+pub(crate) fn match_code(account: &Account, code: &str, drift: i64, now: u64, window: u64) -> Option<CodeMatch> {
+    let period = account.period.max(1);
+    let centre = now.saturating_add_signed(drift.saturating_mul(period as i64));
+    let offset = totp_verify_with(&account.secret, code, account.algorithm, account.digits, period, 0, centre, window)?;
+    let step = (centre / period).saturating_add_signed(offset);
+    let reach = window.saturating_add(drift.unsigned_abs()).saturating_add(1);
+    Some(CodeMatch { offset: drift + offset, step, expires_at: step.saturating_add(reach).saturating_mul(period) })
+}
+
+/// TOTP verification for a server with many users: resolves each user's account, reads the
+/// clock, and applies the window, replay protection, throttling and drift tracking that the
+/// loose `totp_verify*` functions leave to the caller.
+///
+/// Only the resolver is required. Without a replay store a code can be used more than once
+/// within its window; without a throttle, attempts are unlimited.
+///
+/// # Example
+/// ```rust
+/// use std::collections::HashMap;
+/// use std::sync::Mutex;
+/// use datp::{totp_raw, Account, Throttle, TotpVerifier};
+///
+/// let accounts = HashMap::from([("alice".to_string(), Account::new("alice", "JBSWY3DPEHPK3PXP"))]);
+/// let verifier = TotpVerifier::new(move |user: &str| accounts.get(user).cloned())
+///     .clock(|| 1_000_000)
+///     .throttle(Mutex::new(HashMap::new()), Throttle::default());
+///
+/// let code = format!("{:06}", totp_raw("JBSWY3DPEHPK3PXP", 30, 0, 1_000_000).unwrap());
+/// assert!(verifier.verify("alice", &code));
+/// assert!(!verifier.verify("bob", &code));
+/// ```
+pub struct TotpVerifier {
+    resolver: Box<dyn SecretResolver + Send + Sync>,
+    clock: Box<dyn Clock + Send + Sync>,
+    window: u64,
+    used_codes: Option<Box<dyn UsedCodeStore + Send + Sync>>,
+    throttle: Option<(Box<dyn AttemptStore + Send + Sync>, Throttle)>,
+    drift: Option<Box<dyn DriftStore + Send + Sync>>,
+    lockout: Option<Arc<Lockout>>,
+    observer: Option<Box<dyn VerificationObserver + Send + Sync>>,
+}
+
+impl TotpVerifier {
+    /// Creates a verifier using the system clock and a window of one step either way.
+    pub fn new(resolver: impl SecretResolver + Send + Sync + 'static) -> Self {
+        TotpVerifier {
+            resolver: Box::new(resolver),
+            clock: Box::new(SystemClock),
+            window: 1,
+            used_codes: None,
+            throttle: None,
+            drift: None,
+            lockout: None,
+            observer: None,
+        }
+    }
+
+    pub fn clock(mut self, clock: impl Clock + Send + Sync + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Steps accepted before and after the current one (or the user's learned drift).
+    pub fn window(mut self, steps: u64) -> Self {
+        self.window = steps;
+        self
+    }
+
+    pub fn replay_store(mut self, store: impl UsedCodeStore + Send + Sync + 'static) -> Self {
+        self.used_codes = Some(Box::new(store));
+        self
+    }
+
+    pub fn throttle(mut self, store: impl AttemptStore + Send + Sync + 'static, throttle: Throttle) -> Self {
+        self.throttle = Some((Box::new(store), throttle));
+        self
+    }
+
+    /// Centers the window of each user on the offset of their last accepted code, so a device
+    /// whose clock runs off keeps working with a small window.
+    pub fn drift_store(mut self, store: impl DriftStore + Send + Sync + 'static) -> Self {
+        self.drift = Some(Box::new(store));
+        self
+    }
+
+    /// Locks users out according to the lockout's policy. Failed codes count towards the lock,
+    /// and locked users are rejected before their code is checked.
+    pub fn lockout(mut self, lockout: Arc<Lockout>) -> Self {
+        self.lockout = Some(lockout);
+        self
+    }
+
+    pub fn observer(mut self, observer: impl VerificationObserver + Send + Sync + 'static) -> Self {
+        self.observer = Some(Box::new(observer));
+        self
+    }
+
+    /// Checks `code` for `user`. Unknown users, non-TOTP accounts, throttled users and
+    /// replayed codes are all rejected.
+    pub fn verify(&self, user: &str, code: &str) -> bool {
+        self.verify_report(user, code).is_accepted()
+    }
+
+    /// Checks `code` for `user` like `verify`, and reports how it went.
+    ///
+    /// # Example
+    /// ```rust
+    /// use datp::{totp_raw, Account, TotpVerifier, VerificationOutcome};
+    ///
+    /// let verifier = TotpVerifier::new(|_: &str| Some(Account::new("alice", "JBSWY3DPEHPK3PXP")))
+    ///     .clock(|| 1_000_000);
+    /// let code = format!("{:06}", totp_raw("JBSWY3DPEHPK3PXP", 30, 0, 1_000_030).unwrap());
+    /// let report = verifier.verify_report("alice", &code);
+    /// assert_eq!(report.outcome, VerificationOutcome::Accepted);
+    /// assert_eq!(report.matched_offset, Some(1));
+    /// ```
+    pub fn verify_report(&self, user: &str, code: &str) -> VerificationReport {
+        self.attempt(user, || self.resolver.resolve(user), code, self.clock.now())
+    }
+
+    /// Checks a batch of `(user, account, code)` submissions, such as queued codes from an
+    /// offline kiosk, and returns a report for each in order.
+    ///
+    /// The clock is read once for the whole batch and the accounts are given with the
+    /// submissions instead of going through the resolver, so their algorithm, digits and period
+    /// are used as they are; replay protection, throttling, lockout, drift and the observer
+    /// apply as in `verify_report`. With `threads` other than 1 the batch is split across that
+    /// many threads (0 uses the available parallelism), in which case submissions of the same
+    /// user are not necessarily checked in order.
+    ///
+    /// # Returns
+    /// `Result<Vec<VerificationReport>, DatpError>` - One report per submission, or
+    /// `DatpError::Thread` if a worker thread could not be started or panicked; submissions
+    /// checked by the other threads still count towards replay protection and throttling.
+    ///
+    /// # Example
+    /// ```rust
+    /// use datp::{totp_raw_with, Account, Algorithm, TotpVerifier};
+    ///
+    /// let verifier = TotpVerifier::new(|_: &str| None::<Account>).clock(|| 1_000_000);
+    /// let mut alice = Account::new("alice", "JBSWY3DPEHPK3PXP");
+    /// alice.algorithm = Algorithm::Sha256;
+    /// alice.digits = 8;
+    /// let code = format!("{:08}", totp_raw_with(&alice.secret, Algorithm::Sha256, 8, 30, 0, 1_000_000).unwrap());
+    /// let bob = Account::new("bob", "GEZDGNBVGY3TQOJQ");
+    /// let reports = verifier.verify_many([("alice", &alice, code.as_str()), ("bob", &bob, "000000")], 1).unwrap();
+    /// assert!(reports[0].is_accepted());
+    /// assert!(!reports[1].is_accepted());
+    /// ```
+    pub fn verify_many<U, A, C>(
+        &self,
+        submissions: impl IntoIterator<Item = (U, A, C)>,
+        threads: usize,
+    ) -> Result<Vec<VerificationReport>, DatpError>
+    where
+        U: AsRef<str> + Sync,
+        A: Borrow<Account> + Sync,
+        C: AsRef<str> + Sync,
+    {
+        let submissions: Vec<(U, A, C)> = submissions.into_iter().collect();
+        let now = self.clock.now();
+        let verify = |(user, account, code): &(U, A, C)| {
+            self.attempt(user.as_ref(), || Some(account.borrow().clone()), code.as_ref(), now)
+        };
+
+        let threads = match threads {
+            0 => thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        };
+        if threads == 1 || submissions.len() < 2 {
+            return Ok(submissions.iter().map(verify).collect());
+        }
+        let chunk = submissions.len().div_ceil(threads);
+        thread::scope(|scope| {
+            let mut workers = Vec::new();
+            for batch in submissions.chunks(chunk) {
+                let worker = thread::Builder::new()
+                    .name("datp-verifier".to_string())
+                    .spawn_scoped(scope, move || batch.iter().map(verify).collect::<Vec<_>>());
+                // the workers already started are joined when the scope ends
+                workers.push(worker.map_err(|e| DatpError::Thread(e.to_string()))?);
+            }
+            // join every worker before looking at the results, or the scope would panic on
+            // the panicked workers left unjoined
+            let batches: Vec<_> = workers.into_iter().map(|worker| worker.join()).collect();
+            let mut reports = Vec::with_capacity(submissions.len());
+            for batch in batches {
+                reports.extend(batch.map_err(|_| DatpError::Thread("a verification worker panicked".to_string()))?);
+            }
+            Ok(reports)
+        })
+    }
+
+    /// Runs the checks of `verify_report` at `now`, with the account from `resolve`.
+    ///
+    /// The attempt is recorded as a failure in the throttle store and the lockout before the
+    /// code is checked, each under the store's own lock, and cleared again if the code is
+    /// accepted; checking first and recording afterwards would let concurrent attempts all pass
+    /// the limit.
+    fn attempt(&self, user: &str, resolve: impl FnOnce() -> Option<Account>, code: &str, now: u64) -> VerificationReport {
+        if self.lockout.as_ref().is_some_and(|lockout| lockout.is_locked(user, now)) {
+            self.notify(|observer| observer.on_throttled(user, now));
+            return VerificationReport::new(VerificationOutcome::Locked, self.window);
+        }
+        let reserved = self.throttle.as_ref().is_none_or(|(store, throttle)| {
+            store.try_reserve(user, now, now.saturating_sub(throttle.window), throttle.max_failures)
+        });
+        if !reserved {
+            self.notify(|observer| observer.on_throttled(user, now));
+            return VerificationReport::new(VerificationOutcome::Throttled, self.window);
+        }
+        if self.lockout.as_ref().is_some_and(|lockout| !lockout.begin_attempt(user, now)) {
+            self.notify(|observer| observer.on_throttled(user, now));
+            return VerificationReport::new(VerificationOutcome::Locked, self.window);
+        }
+
+        let report = match resolve() {
+            Some(account) if account.kind == OtpKind::Totp => self.check(user, &account, code, now),
+            _ => VerificationReport::new(VerificationOutcome::Rejected, self.window),
+        };
+        match report.outcome {
+            VerificationOutcome::Accepted => {
+                if let Some((store, _)) = &self.throttle {
+                    store.clear(user);
+                }
+                if let Some(lockout) = &self.lockout {
+                    lockout.record_success(user);
+                }
+                self.notify(|observer| observer.on_success(user, now));
+                return report;
+            }
+            VerificationOutcome::Replayed => self.notify(|observer| observer.on_replay(user, now)),
+            VerificationOutcome::Rejected | VerificationOutcome::Throttled | VerificationOutcome::Locked => {
+                self.notify(|observer| observer.on_failure(user, now))
+            }
+        }
+        // the failure was recorded when the attempt began
+        if let Some(lockout) = &self.lockout {
+            lockout.fail_attempt(user, now);
+        }
+        report
+    }
+
+    /// Checks the code against the window around the user's drift and marks it as used.
+    fn check(&self, user: &str, account: &Account, code: &str, now: u64) -> VerificationReport {
+        let drift = self.drift.as_ref().map_or(0, |store| store.drift(user));
+        let Some(found) = match_code(account, code, drift, now, self.window) else {
+            return VerificationReport::new(VerificationOutcome::Rejected, self.window);
+        };
+        if let Some(store) = &self.used_codes
+            && !store.mark_used(user, found.step, found.expires_at)
+        {
+            return VerificationReport::matched(VerificationOutcome::Replayed, self.window, &found);
+        }
+        if let Some(store) = &self.drift {
+            store.set_drift(user, found.offset);
+        }
+        VerificationReport::matched(VerificationOutcome::Accepted, self.window, &found)
+    }
+
+    fn notify(&self, event: impl FnOnce(&dyn VerificationObserver)) {
+        if let Some(observer) = &self.observer {
+            event(observer.as_ref());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{totp_raw, totp_raw_with, Algorithm, LockoutPolicy};
+    use std::collections::HashSet;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    const SECRET: &str = "JBSWY3DPEHPK3PXP";
+
+    fn code(unix_time: u64) -> String {
+        format!("{:06}", totp_raw(SECRET, 30, 0, unix_time).unwrap())
+    }
+
+    fn resolver(user: &str) -> Option<Account> {
+        match user {
+            "alice" => Some(Account::new("alice", SECRET)),
+            "hotp" => Some(Account::new_hotp("hotp", SECRET, 0)),
+            _ => None,
+        }
+    }
+
+    impl UsedCodeStore for Mutex<HashSet<(String, u64)>> {
+        fn mark_used(&self, user: &str, counter: u64, _expires_at: u64) -> bool {
+            self.lock().unwrap().insert((user.to_string(), counter))
+        }
+    }
+
+    #[derive(Default)]
+    struct Events(Mutex<Vec<&'static str>>);
+
+    impl VerificationObserver for Arc<Events> {
+        fn on_success(&self, _user: &str, _timestamp: u64) {
+            self.0.lock().unwrap().push("success");
+        }
+
+        fn on_failure(&self, _user: &str, _timestamp: u64) {
+            self.0.lock().unwrap().push("failure");
+        }
+
+        fn on_replay(&self, _user: &str, _timestamp: u64) {
+            self.0.lock().unwrap().push("replay");
+        }
+
+        fn on_throttled(&self, _user: &str, _timestamp: u64) {
+            self.0.lock().unwrap().push("throttled");
+        }
+    }
+
+    #[test]
+    fn test_replay_and_throttle() {
+        let events = Arc::new(Events::default());
+        let verifier = TotpVerifier::new(resolver)
+            .clock(|| 1_000_000)
+            .replay_store(Mutex::new(HashSet::new()))
+            .throttle(Mutex::new(HashMap::new()), Throttle { max_failures: 2, window: 60 })
+            .observer(events.clone());
+
+        assert!(!verifier.verify("hotp", &code(1_000_000)));
+        assert!(verifier.verify("alice", &code(1_000_000)));
+        assert!(!verifier.verify("alice", &code(1_000_000)));
+        assert!(verifier.verify("alice", &code(999_970)));
+        assert!(!verifier.verify("alice", "000000"));
+        assert!(!verifier.verify("alice", "000001"));
+        // throttled: even the right code is rejected
+        assert!(!verifier.verify("alice", &code(1_000_030)));
+        assert_eq!(
+            *events.0.lock().unwrap(),
+            ["failure", "success", "replay", "success", "failure", "failure", "throttled"]
+        );
+    }
+
+    #[test]
+    fn test_verify_report() {
+        let verifier = TotpVerifier::new(resolver)
+            .clock(|| 1_000_000)
+            .window(2)
+            .replay_store(Mutex::new(HashSet::new()))
+            .throttle(Mutex::new(HashMap::new()), Throttle { max_failures: 1, window: 60 });
+
+        let accepted = verifier.verify_report("alice", &code(999_940));
+        assert_eq!(accepted.outcome, VerificationOutcome::Accepted);
+        assert_eq!((accepted.matched_offset, accepted.counter, accepted.window_used), (Some(-2), Some(33_331), 2));
+
+        let replayed = verifier.verify_report("alice", &code(999_940));
+        assert_eq!(replayed.outcome, VerificationOutcome::Replayed);
+        assert!(replayed.replay_detected && !replayed.throttled && !replayed.is_accepted());
+        assert_eq!(replayed.counter, Some(33_331));
+
+        let throttled = verifier.verify_report("alice", &code(1_000_000));
+        assert_eq!(throttled.outcome, VerificationOutcome::Throttled);
+        assert!(throttled.throttled);
+        assert_eq!(throttled.matched_offset, None);
+
+        let rejected = verifier.verify_report("bob", "123456");
+        assert_eq!((rejected.outcome, rejected.matched_offset), (VerificationOutcome::Rejected, None));
+    }
+
+    #[test]
+    fn test_lockout() {
+        let policy = LockoutPolicy { max_failures: 3, window: 600, lock_for: None };
+        let lockout = Arc::new(Lockout::new(Mutex::new(HashMap::new()), Mutex::new(HashMap::new()), policy));
+        let verifier = TotpVerifier::new(resolver).clock(|| 1_000_000).lockout(lockout.clone());
+
+        // a wrong password counts as well
+        lockout.record_failure("alice", 1_000_000);
+        assert!(!verifier.verify("alice", "000000"));
+        assert!(!verifier.verify("alice", "000001"));
+        let report = verifier.verify_report("alice", &code(1_000_000));
+        assert_eq!(report.outcome, VerificationOutcome::Locked);
+        assert!(report.throttled);
+
+        lockout.unlock("alice");
+        assert!(verifier.verify("alice", &code(1_000_000)));
+    }
+
+    #[test]
+    fn test_drift_tracking() {
+        let now = Arc::new(AtomicU64::new(1_000_000));
+        let clock = now.clone();
+        let verifier = TotpVerifier::new(resolver)
+            .clock(move || clock.load(Ordering::Relaxed))
+            .window(1)
+            .drift_store(Mutex::new(HashMap::new()));
+
+        // the device runs one step ahead, then two
+        assert!(verifier.verify("alice", &code(1_000_030)));
+        now.store(1_000_300, Ordering::Relaxed);
+        assert!(verifier.verify("alice", &code(1_000_360)));
+        // three steps off is outside a window of one around the current time, but not around
+        // the learned drift of two
+        now.store(1_000_600, Ordering::Relaxed);
+        assert!(verifier.verify("alice", &code(1_000_690)));
+        assert!(!verifier.verify("alice", &code(1_000_600)));
+    }
+
+    #[test]
+    fn test_attempt_store() {
+        let store = Mutex::new(HashMap::new());
+        store.record_failure("alice", 10, 0);
+        store.record_failure("alice", 20, 0);
+        assert_eq!(store.failures("alice", 15), 1);
+        store.record_failure("alice", 30, 15);
+        assert_eq!(store.lock().unwrap()["alice"], [20, 30]);
+        store.clear("alice");
+        assert_eq!(store.failures("alice", 0), 0);
+    }
+
+    #[test]
+    fn test_memory_replay_store() {
+        let now = Arc::new(AtomicU64::new(1_000));
+        let clock = now.clone();
+        let store = MemoryReplayStore::with_clock(3, move || clock.load(Ordering::Relaxed));
+        assert!(store.mark_used("alice", 1, 1_030));
+        assert!(!store.mark_used("alice", 1, 1_030));
+        assert!(store.mark_used("alice", 2, 1_060));
+
+        // expired entries are evicted and may be recorded again
+        now.store(1_031, Ordering::Relaxed);
+        assert!(store.mark_used("alice", 1, 1_090));
+        assert_eq!(store.len(), 2);
+        assert!(!store.mark_used("alice", 1, 1_090));
+
+        // at capacity, the oldest entry goes
+        assert!(store.mark_used("bob", 1, 1_090));
+        assert!(store.mark_used("carol", 1, 1_090));
+        assert_eq!(store.len(), 3);
+        assert!(store.mark_used("alice", 2, 1_060));
+        assert!(!store.mark_used("carol", 1, 1_090));
+    }
+
+    #[test]
+    fn test_verify_many() {
+        let reads = Arc::new(AtomicU64::new(0));
+        let counter = reads.clone();
+        let verifier = TotpVerifier::new(|_: &str| None)
+            .clock(move || {
+                counter.fetch_add(1, Ordering::Relaxed);
+                1_000_000
+            })
+            .replay_store(Mutex::new(HashSet::new()))
+            .throttle(Mutex::new(HashMap::new()), Throttle { max_failures: 1, window: 300 });
+        let code = |secret| format!("{:06}", totp_raw(secret, 30, 0, 1_000_000).unwrap());
+        let mut submissions: Vec<(String, Account, String)> =
+            (0..50).map(|i| (format!("user{i}"), Account::new("", SECRET), code(SECRET))).collect();
+        submissions[7].2 = "000000".to_string();
+        // the parameters of each account are used, not SHA1, 6 digits and 30 seconds
+        submissions[9].1.algorithm = Algorithm::Sha512;
+        submissions[9].1.digits = 8;
+        submissions[9].1.period = 60;
+        submissions[9].2 = format!("{:08}", totp_raw_with(SECRET, Algorithm::Sha512, 8, 60, 0, 1_000_000).unwrap());
+
+        let reports = verifier.verify_many(submissions.clone(), 4).unwrap();
+        assert_eq!(reads.load(Ordering::Relaxed), 1);
+        assert_eq!(reports.len(), 50);
+        assert!(reports.iter().enumerate().all(|(i, report)| report.is_accepted() == (i != 7)));
+
+        // the second run finds replays, and user7 throttled
+        let outcomes: Vec<_> = verifier.verify_many(submissions, 1).unwrap().iter().map(|report| report.outcome).collect();
+        assert_eq!(outcomes[0], VerificationOutcome::Replayed);
+        assert_eq!(outcomes[7], VerificationOutcome::Throttled);
+
+        // a resolver or observer that panics on a worker thread comes back as an error
+        struct Panics;
+        impl VerificationObserver for Panics {
+            fn on_failure(&self, _user: &str, _timestamp: u64) {
+                panic!("observer failed");
+            }
+        }
+        let verifier = TotpVerifier::new(|_: &str| None).clock(|| 1_000_000).observer(Panics);
+        let account = Account::new("", SECRET);
+        let result = verifier.verify_many([("alice", &account, "000000"), ("bob", &account, "000000")], 2);
+        assert!(matches!(result, Err(DatpError::Thread(_))));
+    }
+
+    #[test]
+    fn test_concurrent_attempts() {
+        // every attempt counts against the throttle before its code is checked, so parallel
+        // guesses can't get past the limit
+        let verifier = TotpVerifier::new(|_: &str| None)
+            .clock(|| 1_000_000)
+            .throttle(Mutex::new(HashMap::new()), Throttle { max_failures: 3, window: 300 });
+        let account = Account::new("alice", SECRET);
+        let guesses: Vec<_> = (0..64).map(|i| ("alice", &account, format!("{:06}", i))).collect();
+        let reports = verifier.verify_many(guesses, 8).unwrap();
+        let checked = reports.iter().filter(|report| report.outcome == VerificationOutcome::Rejected).count();
+        assert_eq!(checked, 3);
+        assert!(reports.iter().all(|report| report.outcome != VerificationOutcome::Accepted));
+    }
+}
@@ -0,0 +1,184 @@
+use super::hash::HmacKey;
+use super::{crypto, hotp_truncated_mac, with_key, write_digits, Algorithm};
+
+/// Checks a 6-digit SHA1 TOTP code, accepting up to `skew` time steps before or after `unix_time`.
+///
+/// # Arguments
+/// * `secret_base32` - A base32-encoded secret key (without padding).
+/// * `code` - The code entered by the user, leading zeros included.
+/// * `step` - Time step in seconds (usually 30 seconds).
+/// * `t0` - Unix epoch start time (usually 0).
+/// * `unix_time` - Time to verify at, usually now.
+/// * `skew` - Number of steps of clock drift to tolerate in each direction (usually 1).
+///
+/// # Returns
+/// `true` if the code matches one of the steps in the window.
+///
+/// # Example
+/// ```rust
+/// use datp::{totp_raw, totp_verify};
+///
+/// let secret = "JBSWY3DPEHPK3PXP";
+/// let code = format!("{:06}", totp_raw(secret, 30, 0, 1_000_000).unwrap());
+/// assert!(totp_verify(secret, &code, 30, 0, 1_000_030, 1));
+/// assert!(!totp_verify(secret, &code, 30, 0, 1_000_090, 1));
+/// ```
+pub fn totp_verify(secret_base32: &str, code: &str, step: u64, t0: u64, unix_time: u64, skew: u64) -> bool {
+    totp_verify_with(secret_base32, code, Algorithm::Sha1, 6, step, t0, unix_time, skew).is_some()
+}
+
+/// Checks a TOTP code with a custom algorithm and digit count.
+///
+/// Every step in the window is compared in constant time, so the time taken does not reveal
+/// which step matched or how much of the code was right.
+///
+/// # Returns
+/// `Some(offset)` - The offset in steps of the matching code from the step of `unix_time`
+/// (negative for a client behind), or `None` if nothing matches, the secret is invalid or the
+/// code does not have `digits` digits. A `step` of 0 or a `unix_time` before `t0` never matches.
+#[allow(clippy::too_many_arguments)]
+pub fn totp_verify_with(
+    secret_base32: &str,
+    code: &str,
+    algorithm: Algorithm,
+    digits: u32,
+    step: u64,
+    t0: u64,
+    unix_time: u64,
+    skew: u64,
+) -> Option<i64> {
+    let current = unix_time.checked_sub(t0)?.checked_div(step)?;
+    let first = current.saturating_sub(skew);
+    let last = current.saturating_add(skew);
+    let matched = with_key(secret_base32, |key| find_match(key, code, algorithm, digits, first, last))??;
+    Some(matched as i64 - current as i64)
+}
+
+/// Checks an HOTP code against the counters from `counter` to `counter + look_ahead`.
+///
+/// The server must store the returned counter plus one, so a code can't be used twice.
+///
+/// # Returns
+/// `Some(counter)` - The counter of the matching code, or `None`.
+///
+/// # Example
+/// ```rust
+/// use datp::hotp_verify;
+///
+/// let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ"; // RFC 4226 test secret
+/// assert_eq!(hotp_verify(secret, "359152", 0, 5), Some(2));
+/// assert_eq!(hotp_verify(secret, "359152", 3, 5), None);
+/// ```
+pub fn hotp_verify(secret_base32: &str, code: &str, counter: u64, look_ahead: u64) -> Option<u64> {
+    hotp_verify_with(secret_base32, code, Algorithm::Sha1, 6, counter, look_ahead)
+}
+
+/// Checks an HOTP code with a custom algorithm and digit count, see `hotp_verify`.
+pub fn hotp_verify_with(
+    secret_base32: &str,
+    code: &str,
+    algorithm: Algorithm,
+    digits: u32,
+    counter: u64,
+    look_ahead: u64,
+) -> Option<u64> {
+    with_key(secret_base32, |key| find_match(key, code, algorithm, digits, counter, counter.saturating_add(look_ahead)))?
+}
+
+/// Compares `code` with the codes of all counters in `first..=last` and returns the first match.
+///
+/// The HMAC is keyed once for the whole window and the expected codes are formatted on the
+/// stack, so this does not allocate.
+pub(crate) fn find_match(key: &[u8], code: &str, algorithm: Algorithm, digits: u32, first: u64, last: u64) -> Option<u64> {
+    if !(1..=9).contains(&digits) || code.len() != digits as usize {
+        return None;
+    }
+    let key = HmacKey::new(algorithm, key);
+    let mut expected = [0u8; 9];
+    let expected = &mut expected[..digits as usize];
+    let mut matched = None;
+    for counter in first..=last {
+        write_digits(hotp_truncated_mac(&key, counter) % 10u32.pow(digits), expected);
+        if crypto::ct_eq(expected, code.as_bytes()) && matched.is_none() {
+            matched = Some(counter);
+        }
+    }
+    matched
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hotp_raw_with;
+
+    #[test]
+    fn test_totp_verify_window() {
+        // RFC 6238 SHA1 vector, 8 digits at T = 59
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        assert_eq!(totp_verify_with(secret, "94287082", Algorithm::Sha1, 8, 30, 0, 59, 0), Some(0));
+        assert_eq!(totp_verify_with(secret, "94287082", Algorithm::Sha1, 8, 30, 0, 89, 1), Some(-1));
+        assert_eq!(totp_verify_with(secret, "94287082", Algorithm::Sha1, 8, 30, 0, 29, 1), Some(1));
+        assert_eq!(totp_verify_with(secret, "94287082", Algorithm::Sha1, 8, 30, 0, 119, 1), None);
+        assert_eq!(totp_verify_with(secret, "9428708", Algorithm::Sha1, 8, 30, 0, 59, 0), None);
+        assert_eq!(totp_verify_with(secret, "94287082", Algorithm::Sha1, 8, 0, 0, 59, 0), None);
+        assert_eq!(totp_verify_with(secret, "94287082", Algorithm::Sha1, 8, 30, 100, 59, 0), None);
+    }
+
+    #[test]
+    fn test_leading_zeros() {
+        // find a counter whose code starts with 0
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        let (counter, code) = (0..1000).map(|c| (c, hotp_raw_with(secret, Algorithm::Sha1, 6, c).unwrap())).find(|(_, code)| *code < 100_000).unwrap();
+        assert_eq!(hotp_verify(secret, &format!("{:06}", code), counter, 0), Some(counter));
+        assert_eq!(hotp_verify(secret, &code.to_string(), counter, 0), None);
+    }
+
+    #[cfg(feature = "std")]
+    mod counting {
+        use std::alloc::{GlobalAlloc, Layout, System};
+        use std::cell::Cell;
+
+        thread_local! {
+            pub static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+        }
+
+        /// Counts the allocations of each thread, so that tests running in parallel don't
+        /// disturb each other.
+        pub struct CountingAllocator;
+
+        unsafe impl GlobalAlloc for CountingAllocator {
+            unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+                let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+                unsafe { System.alloc(layout) }
+            }
+
+            unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+                unsafe { System.dealloc(ptr, layout) }
+            }
+        }
+
+        #[global_allocator]
+        static ALLOCATOR: CountingAllocator = CountingAllocator;
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_hot_path_does_not_allocate() {
+        let secret = "GEZDGNBVGY3TQOJQ".repeat(12); // 120 bytes, longer than a SHA-512 block
+        let secret = secret.as_str();
+        let allocations = || counting::ALLOCATIONS.with(|count| count.get());
+        let before = allocations();
+        for algorithm in [Algorithm::Sha1, Algorithm::Sha256, Algorithm::Sha512] {
+            assert!(crate::totp_raw_with(secret, algorithm, 8, 30, 0, 59).is_some());
+            assert!(totp_verify_with(secret, "00000000", algorithm, 8, 30, 0, 1_000_000, 2).is_none());
+            assert!(hotp_verify_with(secret, "000000", algorithm, 6, 0, 10).is_none());
+        }
+        assert!(!totp_verify("JBSWY3DPEHPK3PXP", "000000", 30, 0, 1_000_000, 1));
+        assert_eq!(allocations(), before);
+
+        // secrets beyond MAX_SECRET_BYTES still work, through a heap buffer
+        let long = "A".repeat(300);
+        let code = hotp_raw_with(&long, Algorithm::Sha1, 6, 3).unwrap();
+        assert_eq!(hotp_verify_with(&long, &format!("{:06}", code), Algorithm::Sha1, 6, 0, 5), Some(3));
+    }
+}
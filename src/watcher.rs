@@ -0,0 +1,152 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::{hotp_raw_with, steam_raw, Account, DatpError, OtpKind};
+
+/// A code sent by `CodeWatcher` when it becomes current.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TotpCode {
+    pub code: String,                   // zero-padded digits, or five characters for Steam
+    pub counter: u64,                   // time step of the code
+    pub valid_from: u64,                // Unix time of the rollover to this code
+    pub valid_until: u64,               // Unix time of the next rollover
+}
+
+impl TotpCode {
+    fn new(account: &Account, counter: u64) -> Option<TotpCode> {
+        let valid_from = counter.checked_mul(account.period)?;
+        let code = match account.kind {
+            OtpKind::Steam => steam_raw(&account.secret, valid_from)?,
+            _ => {
+                let code = hotp_raw_with(&account.secret, account.algorithm, account.digits, counter)?;
+                format!("{:0width$}", code, width = account.digits as usize)
+            }
+        };
+        Some(TotpCode { code, counter, valid_from, valid_until: valid_from.saturating_add(account.period) })
+    }
+}
+
+/// Sends the code of a TOTP or Steam account from a background thread every time it rolls over,
+/// for GUI toolkits that integrate with channels rather than async streams.
+///
+/// The current code is sent right away. The thread stops when the watcher is dropped (or
+/// `stop` is called) and when the receiving side goes away.
+///
+/// # Example
+/// ```rust
+/// use datp::{Account, CodeWatcher};
+///
+/// let (watcher, codes) = CodeWatcher::channel(&Account::new("alice", "JBSWY3DPEHPK3PXP")).unwrap();
+/// let current = codes.recv().unwrap();
+/// assert_eq!(current.code.len(), 6);
+/// println!("{} until {}", current.code, current.valid_until);
+/// watcher.stop();
+/// ```
+pub struct CodeWatcher {
+    stopped: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl CodeWatcher {
+    /// Starts watching `account` and returns the receiving end of a new channel.
+    pub fn channel(account: &Account) -> Result<(CodeWatcher, Receiver<TotpCode>), DatpError> {
+        let (sender, receiver) = mpsc::channel();
+        Ok((CodeWatcher::start(account, sender)?, receiver))
+    }
+
+    /// Starts watching `account`, sending its codes to `sender`.
+    pub fn start(account: &Account, sender: Sender<TotpCode>) -> Result<CodeWatcher, DatpError> {
+        CodeWatcher::start_with(account, move |code| sender.send(code).is_ok())
+    }
+
+    /// Starts watching `account`, calling `send` with every new code on the watcher thread; the
+    /// watcher stops once `send` returns `false`. Use it for other channels, e.g.
+    /// `move |code| sender.send(code).is_ok()` with a crossbeam or glib sender.
+    ///
+    /// # Returns
+    /// `Ok(CodeWatcher)`, `DatpError::UnsupportedType` for HOTP accounts, `InvalidPeriod` for a
+    /// period of 0, `InvalidDigits` or `InvalidSecret`, and `DatpError::Thread` if the system
+    /// cannot start the watcher thread.
+    pub fn start_with(account: &Account, mut send: impl FnMut(TotpCode) -> bool + Send + 'static) -> Result<CodeWatcher, DatpError> {
+        if account.kind == OtpKind::Hotp {
+            return Err(DatpError::UnsupportedType(account.kind.as_str().to_string()));
+        }
+        if account.period == 0 {
+            return Err(DatpError::InvalidPeriod(account.period.to_string()));
+        }
+        if !(1..=9).contains(&account.digits) {
+            return Err(DatpError::InvalidDigits(account.digits.to_string()));
+        }
+        TotpCode::new(account, 0).ok_or(DatpError::InvalidSecret)?;
+
+        let account = account.clone();
+        let stopped = Arc::new((Mutex::new(false), Condvar::new()));
+        let signal = Arc::clone(&stopped);
+        let thread = thread::Builder::new().name("datp-code-watcher".to_string()).spawn(move || {
+            let (stopped, wake) = &*signal;
+            let mut last = None;
+            loop {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+                let counter = now.as_secs() / account.period;
+                if last != Some(counter) {
+                    last = Some(counter);
+                    let Some(code) = TotpCode::new(&account, counter) else { break };
+                    if !send(code) {
+                        break;
+                    }
+                }
+                let next = Duration::from_secs((counter + 1).saturating_mul(account.period));
+                let wait = next.saturating_sub(now);
+                // wakes up early for stop; spurious wakeups just go round the loop again
+                let state = stopped.lock().unwrap_or_else(|e| e.into_inner());
+                let (state, _) = wake.wait_timeout_while(state, wait, |stopped| !*stopped).unwrap_or_else(|e| e.into_inner());
+                if *state {
+                    break;
+                }
+            }
+        });
+        let thread = thread.map_err(|e| DatpError::Thread(e.to_string()))?;
+        Ok(CodeWatcher { stopped, thread: Some(thread) })
+    }
+
+    /// Stops the thread and waits for it to finish.
+    pub fn stop(self) {}
+}
+
+impl Drop for CodeWatcher {
+    fn drop(&mut self) {
+        let (stopped, wake) = &*self.stopped;
+        *stopped.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        wake.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::totp_raw;
+
+    #[test]
+    fn test_code_watcher() {
+        let account = Account { period: 1, ..Account::new("alice", "JBSWY3DPEHPK3PXP") };
+        let (watcher, codes) = CodeWatcher::channel(&account).unwrap();
+        let first = codes.recv_timeout(Duration::from_secs(5)).unwrap();
+        let second = codes.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(second.counter, first.counter + 1);
+        assert_eq!((second.valid_from, second.valid_until), (first.valid_until, first.valid_until + 1));
+        assert_eq!(second.code, format!("{:06}", totp_raw("JBSWY3DPEHPK3PXP", 1, 0, second.valid_from).unwrap()));
+        watcher.stop();
+        // the sender went away with the thread
+        while codes.recv().is_ok() {}
+
+        let steam = TotpCode::new(&Account::new_steam("bob", "JBSWY3DPEHPK3PXP"), 1).unwrap();
+        assert_eq!((steam.code.as_str(), steam.valid_from, steam.valid_until), ("2YXGV", 30, 60));
+        assert!(matches!(CodeWatcher::channel(&Account::new_hotp("carol", "JBSWY3DPEHPK3PXP", 0)), Err(DatpError::UnsupportedType(_))));
+        assert_eq!(CodeWatcher::channel(&Account::new("dave", "1nvalid")).err(), Some(DatpError::InvalidSecret));
+    }
+}